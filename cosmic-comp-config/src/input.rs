@@ -4,6 +4,7 @@
 
 pub use input::{AccelProfile, ClickMethod, ScrollMethod, TapButtonMap};
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
 
 #[derive(Clone, Debug, Default, Deserialize, Serialize)]
 pub struct InputConfig {
@@ -11,14 +12,26 @@ pub struct InputConfig {
     #[serde(skip_serializing_if = "Option::is_none", default)]
     pub acceleration: Option<AccelConfig>,
     #[serde(skip_serializing_if = "Option::is_none", default)]
+    #[serde(alias = "calibration_matrix")]
     pub calibration: Option<[f32; 6]>,
+    /// Translates raw button codes (as in `linux/input-event-codes.h`) to other codes.
+    /// Buttons without an entry are forwarded unchanged.
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub button_map: Option<BTreeMap<u32, u32>>,
     #[serde(with = "ClickMethodDef")]
     #[serde(skip_serializing_if = "Option::is_none", default)]
     pub click_method: Option<ClickMethod>,
     #[serde(skip_serializing_if = "Option::is_none", default)]
     pub disable_while_typing: Option<bool>,
+    /// Milliseconds touchpad input is ignored after typing, if `disable_while_typing` is enabled.
+    /// Unlike libinput's own detection, this covers every keyboard of the seat.
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub disable_while_typing_timeout: Option<u32>,
     #[serde(skip_serializing_if = "Option::is_none", default)]
     pub left_handed: Option<bool>,
+    /// Connector name of the output absolute positioned devices (e.g. touchscreens) are confined to
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub map_to_output: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none", default)]
     pub middle_button_emulation: Option<bool>,
     #[serde(skip_serializing_if = "Option::is_none", default)]
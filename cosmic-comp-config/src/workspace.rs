@@ -12,6 +12,9 @@ pub struct WorkspaceConfig {
     pub workspace_amount: WorkspaceAmount,
     #[serde(default = "default_workspace_layout")]
     pub workspace_layout: WorkspaceLayout,
+    /// Layouts new workspaces start with, later matching entries override earlier ones
+    #[serde(default)]
+    pub default_layouts: Vec<DefaultLayout>,
 }
 
 impl Default for WorkspaceConfig {
@@ -20,6 +23,7 @@ impl Default for WorkspaceConfig {
             workspace_mode: WorkspaceMode::OutputBound,
             workspace_amount: WorkspaceAmount::Dynamic,
             workspace_layout: WorkspaceLayout::Vertical,
+            default_layouts: Vec::new(),
         }
     }
 }
@@ -41,3 +45,26 @@ pub enum WorkspaceLayout {
     Vertical,
     Horizontal,
 }
+
+/// Starting layout of the workspaces matching `output` and `workspace`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DefaultLayout {
+    /// Connector name of the output, `None` matches any output
+    #[serde(default)]
+    pub output: Option<String>,
+    /// Workspace number starting at 1, `None` matches any workspace
+    #[serde(default)]
+    pub workspace: Option<u8>,
+    /// Start tiled or floating, `None` keeps the global default
+    #[serde(default)]
+    pub tiling: Option<bool>,
+    /// Orientation of new splits, `None` picks it from the aspect ratio
+    #[serde(default)]
+    pub orientation: Option<TilingOrientation>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum TilingOrientation {
+    Horizontal,
+    Vertical,
+}
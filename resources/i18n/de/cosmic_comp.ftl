@@ -2,4 +2,9 @@ grow-window = Vergrößern
 shrink-window = Verkleinern
 swap-windows = Fenster tauschen
 stack-windows = Fenster stapeln
-unknown-keybinding = <nicht zugewiesen>
\ No newline at end of file
+unknown-keybinding = <nicht zugewiesen>
+compositor-recovered = Der Compositor hat sich von wiederholten Fehlern erholt. Einige Fenster wurden ausgeblendet.
+not-responding = {$title} (Reagiert nicht)
+confirm-kill = Erneut klicken, um {$title} zu beenden
+osd-workspace = Arbeitsfläche {$name}
+osd-brightness = Helligkeit {$percent}%
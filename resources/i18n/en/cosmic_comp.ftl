@@ -2,4 +2,9 @@ grow-window = Grow
 shrink-window = Shrink
 swap-windows = Swap Windows
 stack-windows = Stack Windows
-unknown-keybinding = <unset>
\ No newline at end of file
+unknown-keybinding = <unset>
+compositor-recovered = The compositor recovered from repeated errors. Some windows were moved out of view.
+not-responding = {$title} (Not Responding)
+confirm-kill = Click again to force quit {$title}
+osd-workspace = Workspace {$name}
+osd-brightness = Brightness {$percent}%
@@ -6,8 +6,8 @@ use smithay::reexports::drm::control::{
     connector::{self, State as ConnectorState},
     crtc,
     dumbbuffer::DumbBuffer,
-    property, AtomicCommitFlags, Device as ControlDevice, Mode, ModeFlags, PlaneType,
-    ResourceHandle,
+    property, AtomicCommitFlags, Device as ControlDevice, Mode, ModeFlags, ModeTypeFlags,
+    PlaneType, ResourceHandle,
 };
 use std::{collections::HashMap, ops::Range};
 
@@ -327,6 +327,131 @@ pub fn calculate_refresh_rate(mode: Mode) -> u32 {
     refresh as u32
 }
 
+/// Builds a user defined mode from its timings, `clock` is in kHz.
+fn custom_mode(clock: u32, h: [u16; 4], v: [u16; 4], flags: ModeFlags) -> Mode {
+    let mut name = [0; 32];
+    for (dst, src) in name
+        .iter_mut()
+        .take(31)
+        .zip(format!("{}x{}", h[0], v[0]).bytes())
+    {
+        *dst = src as _;
+    }
+    Mode::from(drm_ffi::drm_mode_modeinfo {
+        clock,
+        hdisplay: h[0],
+        hsync_start: h[1],
+        hsync_end: h[2],
+        htotal: h[3],
+        hskew: 0,
+        vdisplay: v[0],
+        vsync_start: v[1],
+        vsync_end: v[2],
+        vtotal: v[3],
+        vscan: 0,
+        vrefresh: (clock as u64 * 1000 / (h[3] as u64 * v[3] as u64)) as u32,
+        flags: flags.bits(),
+        type_: ModeTypeFlags::USERDEF.bits(),
+        name,
+    })
+}
+
+/// Parses an X11 style modeline, e.g.
+/// `"241.50 2560 2608 2640 2720 1440 1443 1448 1481 +hsync -vsync"`.
+///
+/// The pixel clock is in MHz, a leading `Modeline` keyword and mode name are ignored.
+pub fn parse_modeline(modeline: &str) -> Result<Mode> {
+    let mut words = modeline
+        .split_whitespace()
+        .skip_while(|word| word.parse::<f64>().is_err());
+    let clock = words
+        .next()
+        .and_then(|word| word.parse::<f64>().ok())
+        .ok_or(anyhow!("Modeline is missing the pixel clock"))?;
+    let mut timings = [0u16; 8];
+    for timing in timings.iter_mut() {
+        *timing = words
+            .next()
+            .and_then(|word| word.parse().ok())
+            .ok_or(anyhow!("Modeline is missing timings"))?;
+    }
+    let mut flags = ModeFlags::empty();
+    for word in words {
+        flags |= match word.to_lowercase().as_str() {
+            "+hsync" => ModeFlags::PHSYNC,
+            "-hsync" => ModeFlags::NHSYNC,
+            "+vsync" => ModeFlags::PVSYNC,
+            "-vsync" => ModeFlags::NVSYNC,
+            "interlace" => ModeFlags::INTERLACE,
+            "doublescan" => ModeFlags::DBLSCAN,
+            x => return Err(anyhow!("Unknown modeline flag: {}", x)),
+        };
+    }
+
+    let [hdisplay, hsync_start, hsync_end, htotal, vdisplay, vsync_start, vsync_end, vtotal] =
+        timings;
+    if clock <= 0.0
+        || hdisplay == 0
+        || vdisplay == 0
+        || !(hdisplay <= hsync_start && hsync_start <= hsync_end && hsync_end <= htotal)
+        || !(vdisplay <= vsync_start && vsync_start <= vsync_end && vsync_end <= vtotal)
+    {
+        return Err(anyhow!("Invalid modeline timings"));
+    }
+
+    Ok(custom_mode(
+        (clock * 1000.0).round() as u32,
+        [hdisplay, hsync_start, hsync_end, htotal],
+        [vdisplay, vsync_start, vsync_end, vtotal],
+        flags,
+    ))
+}
+
+/// Computes a mode with CVT reduced blanking timings (v1), `refresh` is in mHz.
+pub fn cvt_rb_mode(width: u16, height: u16, refresh: u32) -> Mode {
+    // all in pixels, lines or microseconds
+    const H_BLANK: u16 = 160;
+    const H_SYNC: u16 = 32;
+    const MIN_V_BLANK: f64 = 460.0;
+    const V_FRONT_PORCH: u16 = 3;
+    const MIN_V_BACK_PORCH: u16 = 6;
+    // in MHz
+    const CLOCK_STEP: f64 = 0.25;
+
+    let width = width / 8 * 8;
+    let refresh = refresh as f64 / 1000.0;
+    // the vsync width encodes the aspect ratio
+    let aspect = |w: u32, h: u32| width as u32 * h == height as u32 * w;
+    let v_sync = if aspect(4, 3) {
+        4
+    } else if aspect(16, 9) {
+        5
+    } else if aspect(16, 10) {
+        6
+    } else if aspect(5, 4) || aspect(15, 9) {
+        7
+    } else {
+        10
+    };
+
+    let h_period = (1_000_000.0 / refresh - MIN_V_BLANK) / height as f64;
+    let v_blank =
+        ((MIN_V_BLANK / h_period) as u16 + 1).max(V_FRONT_PORCH + v_sync + MIN_V_BACK_PORCH);
+    let vtotal = height + v_blank;
+    let htotal = width + H_BLANK;
+    let clock =
+        CLOCK_STEP * (refresh * vtotal as f64 * htotal as f64 / 1_000_000.0 / CLOCK_STEP).floor();
+
+    let hsync_end = width + H_BLANK / 2;
+    let vsync_start = height + V_FRONT_PORCH;
+    custom_mode(
+        (clock * 1000.0) as u32,
+        [width, hsync_end - H_SYNC, hsync_end, htotal],
+        [height, vsync_start, vsync_start + v_sync, vtotal],
+        ModeFlags::PHSYNC | ModeFlags::NVSYNC,
+    )
+}
+
 pub fn supports_vrr(dev: &impl ControlDevice, conn: connector::Handle) -> Result<bool> {
     get_property_val(dev, conn, "vrr_capable").map(|(val_type, val)| {
         match val_type.convert_value(val) {
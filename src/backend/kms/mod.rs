@@ -3,8 +3,9 @@
 #[cfg(feature = "debug")]
 use crate::backend::render::element::AsGlowRenderer;
 use crate::{
-    backend::render::{workspace_elements, CLEAR_COLOR},
-    config::OutputConfig,
+    backend::render::{background::clear_color, workspace_elements},
+    config::{Config, OutputConfig, RenderLatency},
+    recovery::CodePath,
     shell::Shell,
     state::{BackendData, ClientState, Common, Fps, SurfaceDmabufFeedback},
     utils::prelude::*,
@@ -55,7 +56,7 @@ use smithay::{
             Dispatcher, EventLoop, InsertError, LoopHandle, RegistrationToken,
         },
         drm::{
-            control::{connector, crtc, Device as ControlDevice, ModeTypeFlags},
+            control::{connector, crtc, Device as ControlDevice, Mode, ModeTypeFlags},
             Device as _,
         },
         input::{self, Libinput},
@@ -76,7 +77,7 @@ use smithay::{
     },
     xwayland::XWaylandClientData,
 };
-use tracing::{error, info, trace, warn};
+use tracing::{error, info, trace, trace_span, warn};
 
 use std::{
     cell::RefCell,
@@ -84,7 +85,7 @@ use std::{
     ffi::CStr,
     fmt,
     path::PathBuf,
-    time::Duration,
+    time::{Duration, Instant},
 };
 
 mod drm_helpers;
@@ -94,11 +95,22 @@ use socket::*;
 use super::render::{init_shaders, CursorMode, GlMultiRenderer};
 // for now we assume we need at least 3ms
 const MIN_RENDER_TIME: Duration = Duration::from_millis(3);
+// headroom added on top of the render time estimate
+const SAFE_RENDER_MARGIN: Duration = Duration::from_millis(4);
+const LOW_LATENCY_RENDER_MARGIN: Duration = Duration::from_millis(1);
+// amount of recent frames considered for estimating the render time
+const RENDER_TIME_WINDOW: usize = 30;
+// factors of the red, green and blue ramps with the night light enabled, roughly 4500K
+const NIGHT_LIGHT: [f64; 3] = [1.0, 0.84, 0.67];
 
 #[derive(Debug)]
 pub struct KmsState {
     pub devices: HashMap<DrmNode, Device>,
     pub input_devices: HashMap<String, input::Device>,
+    /// Lock leds shown on all keyboards
+    keyboard_leds: input::Led,
+    /// Whether the night light curve is applied on top of the gamma ramps
+    night_light: bool,
     pub api: GpuManager<GbmGlesBackend<GlowRenderer>>,
     pub primary: DrmNode,
     session: LibSeatSession,
@@ -144,6 +156,8 @@ pub struct Surface {
     surface: Option<GbmDrmCompositor>,
     connector: connector::Handle,
     output: Output,
+    /// The mode actually in use, which may differ from the configured one
+    mode: Option<OutputMode>,
     refresh_rate: u32,
     vrr: bool,
     scheduled: bool,
@@ -151,8 +165,11 @@ pub struct Surface {
     dirty: bool,
     last_animation_state: bool,
     render_timer_token: Option<RegistrationToken>,
+    expected_vblank: Option<Instant>,
     fps: Fps,
     feedback: HashMap<DrmNode, SurfaceDmabufFeedback>,
+    /// Gamma ramp set by a client, kept across mode changes
+    gamma: Option<Vec<u16>>,
 }
 
 pub type GbmDrmCompositor = DrmCompositor<
@@ -181,16 +198,31 @@ pub fn init_backend(
         .handle()
         .insert_source(libinput_backend, move |mut event, _, state| {
             if let InputEvent::DeviceAdded { ref mut device } = &mut event {
-                state.common.config.read_device(device);
-                state
-                    .backend
-                    .kms()
-                    .input_devices
+                let kms = state.backend.kms();
+                // also covers devices re-added after a VT switch
+                if device.has_capability(input::DeviceCapability::Keyboard) {
+                    device.led_update(kms.keyboard_leds);
+                }
+                kms.input_devices
                     .insert(device.name().into(), device.clone());
+                if Config::is_external_mouse(device) {
+                    kms.update_external_mouse(&state.common.config);
+                } else {
+                    state
+                        .common
+                        .config
+                        .read_device(device, kms.has_external_mouse());
+                }
             } else if let InputEvent::DeviceRemoved { device } = &event {
-                state.backend.kms().input_devices.remove(device.name());
+                let kms = state.backend.kms();
+                kms.input_devices.remove(device.name());
+                if Config::is_external_mouse(device) {
+                    kms.update_external_mouse(&state.common.config);
+                }
             }
-            state.process_input_event(event, true);
+            state.catch_panic(CodePath::Input, |state| {
+                state.process_input_event(event, true)
+            });
             for output in state.common.shell.outputs() {
                 if let Err(err) = state.backend.kms().schedule_render(
                     &state.common.event_loop_handle,
@@ -378,6 +410,8 @@ pub fn init_backend(
         session,
         devices: HashMap::new(),
         input_devices: HashMap::new(),
+        keyboard_leds: input::Led::empty(),
+        night_light: false,
     });
 
     // Create relative pointer global
@@ -472,26 +506,36 @@ impl State {
                 notifier,
                 move |event, metadata, state: &mut State| match event {
                     DrmEvent::VBlank(crtc) => {
+                        let latency = state.common.config.static_conf.latency;
                         let rescheduled = if let Some(device) =
                             state.backend.kms().devices.get_mut(&drm_node)
                         {
                             if let Some(surface) = device.surfaces.get_mut(&crtc) {
                                 trace!(?crtc, "VBlank");
-                                #[cfg(feature = "debug")]
                                 surface.fps.displayed();
+                                if let Some(expected) = surface.expected_vblank.take() {
+                                    // we are more than half a frame late, we missed the vblank we aimed for
+                                    let half_frame = Duration::from_secs_f64(
+                                        500.0 / surface.refresh_rate as f64,
+                                    );
+                                    if Instant::now() > expected + half_frame {
+                                        surface.fps.missed_deadline();
+                                    }
+                                }
 
                                 match surface.surface.as_mut().map(|x| x.frame_submitted()) {
                                     Some(Ok(feedback)) => {
                                         if let Some(mut feedback) = feedback.flatten() {
+                                            // read the sequence before the metadata is consumed
+                                            let seq = metadata
+                                                .as_ref()
+                                                .map(|metadata| metadata.sequence)
+                                                .unwrap_or(0);
                                             let submit_time =
                                                 match metadata.take().map(|data| data.time) {
                                                     Some(DrmEventTime::Monotonic(tp)) => Some(tp),
                                                     _ => None,
                                                 };
-                                            let seq = metadata
-                                                .as_ref()
-                                                .map(|metadata| metadata.sequence)
-                                                .unwrap_or(0);
 
                                             let (clock, flags) = if let Some(tp) = submit_time {
                                                 (
@@ -532,10 +576,25 @@ impl State {
                                         ) != animations_going;
                                         (surface.dirty || animations_going || animation_diff).then(
                                             || {
-                                                (
-                                                    surface.output.clone(),
-                                                    surface.fps.avg_rendertime(5),
-                                                )
+                                                let estimated_rendertime = match latency {
+                                                    RenderLatency::Safe => {
+                                                        surface
+                                                            .fps
+                                                            .max_rendertime(RENDER_TIME_WINDOW)
+                                                            + SAFE_RENDER_MARGIN
+                                                    }
+                                                    RenderLatency::Low => {
+                                                        surface.fps.avg_rendertime(5)
+                                                            + LOW_LATENCY_RENDER_MARGIN
+                                                    }
+                                                };
+                                                let estimated_rendertime = std::cmp::max(
+                                                    estimated_rendertime,
+                                                    MIN_RENDER_TIME,
+                                                );
+                                                surface.fps.estimated_rendertime =
+                                                    estimated_rendertime;
+                                                (surface.output.clone(), estimated_rendertime)
                                             },
                                         )
                                     }
@@ -552,7 +611,7 @@ impl State {
                             None
                         };
 
-                        if let Some((output, avg_rendertime)) = rescheduled {
+                        if let Some((output, estimated_rendertime)) = rescheduled {
                             let mut scheduled_sessions =
                                 state.workspace_session_for_output(&output);
                             let mut output_sessions = output.pending_buffers().peekable();
@@ -562,8 +621,6 @@ impl State {
                                     .extend(output_sessions);
                             }
 
-                            let estimated_rendertime =
-                                std::cmp::max(avg_rendertime, MIN_RENDER_TIME);
                             if let Err(err) = state.backend.kms().schedule_render(
                                 &state.common.event_loop_handle,
                                 &output,
@@ -711,6 +768,9 @@ impl State {
             .output_configuration_state
             .add_heads(wl_outputs.iter());
         let seats = self.common.seats().cloned().collect::<Vec<_>>();
+        self.common
+            .config
+            .store_window_positions(&self.common.shell);
         self.common.config.read_outputs(
             &mut self.common.output_configuration_state,
             &mut self.backend,
@@ -871,7 +931,13 @@ impl State {
         self.common
             .output_configuration_state
             .add_heads(outputs_added.iter());
+        for output in &outputs_removed {
+            self.common.gamma_control_state.output_removed(output);
+        }
         let seats = self.common.seats().cloned().collect::<Vec<_>>();
+        self.common
+            .config
+            .store_window_positions(&self.common.shell);
         self.common.config.read_outputs(
             &mut self.common.output_configuration_state,
             &mut self.backend,
@@ -921,9 +987,16 @@ impl State {
         self.common
             .output_configuration_state
             .remove_heads(outputs_removed.iter());
+        for output in &outputs_removed {
+            self.common.gamma_control_state.output_removed(output);
+        }
 
         let seats = self.common.seats().cloned().collect::<Vec<_>>();
         if self.backend.kms().session.is_active() {
+            // remember the window positions, before the outputs are gone
+            self.common
+                .config
+                .store_window_positions(&self.common.shell);
             for output in outputs_removed {
                 self.common
                     .shell
@@ -1060,6 +1133,7 @@ impl Device {
             output: output.clone(),
             surface: None,
             connector: conn,
+            mode: None,
             vrr,
             refresh_rate,
             scheduled: false,
@@ -1067,8 +1141,10 @@ impl Device {
             dirty: false,
             last_animation_state: false,
             render_timer_token: None,
+            expected_vblank: None,
             fps: Fps::new(renderer.as_mut()),
             feedback: HashMap::new(),
+            gamma: None,
         };
         self.surfaces.insert(crtc, data);
 
@@ -1089,6 +1165,54 @@ pub fn source_node_for_surface(w: &WlSurface, dh: &DisplayHandle) -> Option<DrmN
     None
 }
 
+/// Picks the mode for `config` out of the `modes` advertised by a connector.
+///
+/// Advertised modes are used if their refresh rate is within 1Hz of the requested one. Modelines
+/// and any other size or refresh rate are turned into custom (CVT-RB) modes, returned together
+/// with the closest advertised mode as a fallback in case the modeset fails.
+fn select_mode(modes: &[Mode], config: &OutputConfig) -> Result<(Mode, Option<Mode>)> {
+    let refresh_diff = |mode: &Mode| {
+        (config.mode_refresh() as i32 - drm_helpers::calculate_refresh_rate(*mode) as i32).abs()
+    };
+    let matching_size = |mode: &Mode| {
+        let (x, y) = mode.size();
+        Size::from((x as i32, y as i32)) == config.mode_size()
+    };
+    let closest = modes
+        .iter()
+        // match the size
+        .filter(|mode| matching_size(mode))
+        // and then select the closest refresh rate (e.g. to match 59.98 as 60)
+        .min_by_key(|mode| refresh_diff(mode))
+        .or_else(|| {
+            modes
+                .iter()
+                .find(|mode| mode.mode_type().contains(ModeTypeFlags::PREFERRED))
+        })
+        .or(modes.first())
+        .copied();
+
+    if let Some(modeline) = config.modeline.as_deref() {
+        let mode = drm_helpers::parse_modeline(modeline).context("Invalid modeline")?;
+        return Ok((mode, closest));
+    }
+
+    let (width, height) = config.mode.0;
+    match closest {
+        Some(mode)
+            if matching_size(&mode) && (config.mode.1.is_none() || refresh_diff(&mode) <= 1000) =>
+        {
+            Ok((mode, None))
+        }
+        _ if width > 0 && height > 0 => Ok((
+            drm_helpers::cvt_rb_mode(width as u16, height as u16, config.mode_refresh()),
+            closest,
+        )),
+        Some(mode) => Ok((mode, None)),
+        None => Err(anyhow::anyhow!("Unknown mode")),
+    }
+}
+
 fn render_node_for_output(
     dh: &DisplayHandle,
     output: &Output,
@@ -1190,10 +1314,11 @@ impl Surface {
         state: &mut Common,
         screencopy: Option<&[(ScreencopySession, BufferParams)]>,
     ) -> Result<()> {
-        #[cfg(feature = "debug")]
-        puffin::profile_function!();
+        #[cfg(feature = "puffin")]
+        puffin::profile_function!(self.output.name());
+        let _span = trace_span!("render_output", output = %self.output.name()).entered();
 
-        if self.surface.is_none() {
+        if self.surface.is_none() || self.output.is_powered_off() {
             return Ok(());
         }
 
@@ -1242,7 +1367,7 @@ impl Surface {
         let res = compositor.render_frame::<_, _, GlesTexture>(
             &mut renderer,
             &elements,
-            CLEAR_COLOR, // TODO use a theme neutral color
+            clear_color(state, &self.output),
         );
         self.fps.render();
 
@@ -1383,6 +1508,33 @@ impl KmsState {
         self.session.change_vt(num).map_err(Into::into)
     }
 
+    pub fn has_external_mouse(&self) -> bool {
+        self.input_devices.values().any(Config::is_external_mouse)
+    }
+
+    /// Re-apply the device configuration after a mouse was plugged in or removed,
+    /// so devices disabled on external mice get toggled.
+    pub fn update_external_mouse(&mut self, config: &Config) {
+        let external_mouse = self.has_external_mouse();
+        for device in self.input_devices.values_mut() {
+            config.read_device(device, external_mouse);
+        }
+    }
+
+    pub fn update_keyboard_leds(&mut self, leds: input::Led) {
+        if self.keyboard_leds == leds {
+            return;
+        }
+        self.keyboard_leds = leds;
+        for device in self
+            .input_devices
+            .values_mut()
+            .filter(|device| device.has_capability(input::DeviceCapability::Keyboard))
+        {
+            device.led_update(leds);
+        }
+    }
+
     pub fn apply_config_for_output(
         &mut self,
         output: &Output,
@@ -1405,13 +1557,15 @@ impl KmsState {
                 .user_data()
                 .get::<RefCell<OutputConfig>>()
                 .unwrap()
-                .borrow();
+                .borrow()
+                .clone();
 
             if !output_config.enabled {
                 if !test_only {
                     shell.remove_output(output, seats);
                     if surface.surface.take().is_some() {
                         // just drop it
+                        surface.mode = None;
                         surface.pending = false;
                         surface.dirty = false;
                     }
@@ -1421,20 +1575,7 @@ impl KmsState {
                 let drm = &mut device.drm;
                 let conn = surface.connector;
                 let conn_info = drm.get_connector(conn, false)?;
-                let mode = conn_info
-                    .modes()
-                    .iter()
-                    // match the size
-                    .filter(|mode| {
-                        let (x, y) = mode.size();
-                        Size::from((x as i32, y as i32)) == output_config.mode_size()
-                    })
-                    // and then select the closest refresh rate (e.g. to match 59.98 as 60)
-                    .min_by_key(|mode| {
-                        let refresh_rate = drm_helpers::calculate_refresh_rate(**mode);
-                        (output_config.mode.1.unwrap() as i32 - refresh_rate as i32).abs()
-                    })
-                    .ok_or(anyhow::anyhow!("Unknown mode"))?;
+                let (mut mode, fallback) = select_mode(conn_info.modes(), &output_config)?;
 
                 if !test_only {
                     let res = if let Some(compositor) = surface.surface.as_mut() {
@@ -1446,9 +1587,22 @@ impl KmsState {
                                 output_config.vrr,
                             )?;
                         }
-                        compositor
-                            .use_mode(*mode)
-                            .context("Failed to apply new mode")?;
+                        // the previous mode stays active, if the connector rejects the new one
+                        if let Err(err) = compositor.use_mode(mode) {
+                            let Some(fallback) = fallback else {
+                                return Err(err).context("Failed to apply new mode");
+                            };
+                            warn!(
+                                ?err,
+                                ?mode,
+                                "Custom mode rejected by {}, falling back to the closest advertised mode.",
+                                output.name()
+                            );
+                            compositor
+                                .use_mode(fallback)
+                                .context("Failed to apply new mode")?;
+                            mode = fallback;
+                        }
                         false
                     } else {
                         surface.vrr = drm_helpers::set_vrr(drm, *crtc, conn, output_config.vrr)
@@ -1463,9 +1617,21 @@ impl KmsState {
                                 );
                             }
                         }
-                        surface.refresh_rate = drm_helpers::calculate_refresh_rate(*mode);
-
-                        let drm_surface = drm.create_surface(*crtc, *mode, &[conn])?;
+                        let drm_surface = match (drm.create_surface(*crtc, mode, &[conn]), fallback)
+                        {
+                            (Ok(drm_surface), _) => drm_surface,
+                            (Err(err), Some(fallback)) => {
+                                warn!(
+                                    ?err,
+                                    ?mode,
+                                    "Custom mode rejected by {}, falling back to the closest advertised mode.",
+                                    output.name()
+                                );
+                                mode = fallback;
+                                drm.create_surface(*crtc, mode, &[conn])?
+                            }
+                            (Err(err), None) => return Err(err.into()),
+                        };
                         let driver = drm
                             .get_driver()
                             .with_context(|| "Failed to query drm driver")?;
@@ -1509,6 +1675,20 @@ impl KmsState {
                         surface.surface = Some(target);
                         true
                     };
+
+                    // remember the mode actually in use, so it gets announced to clients,
+                    // without touching the configured one
+                    let refresh = drm_helpers::calculate_refresh_rate(mode);
+                    let output_mode = OutputMode {
+                        size: (mode.size().0 as i32, mode.size().1 as i32).into(),
+                        refresh: refresh as i32,
+                    };
+                    if mode.mode_type().contains(ModeTypeFlags::USERDEF) {
+                        output.add_mode(output_mode);
+                    }
+                    surface.mode = Some(output_mode);
+                    surface.refresh_rate = refresh;
+
                     shell.add_output(output);
                     res
                 } else {
@@ -1519,6 +1699,13 @@ impl KmsState {
             false
         };
 
+        if !test_only {
+            // modesets and newly enabled crtcs don't keep the previous ramp
+            if let Err(err) = self.apply_gamma(output) {
+                warn!(?err, output = output.name(), "Failed to apply gamma ramp.");
+            }
+        }
+
         if recreated {
             let sessions = output.pending_buffers().collect::<Vec<_>>();
             if let Err(err) = self.schedule_render(
@@ -1595,6 +1782,129 @@ impl KmsState {
         unreachable!()
     }
 
+    pub fn set_output_power(
+        &mut self,
+        output: &Output,
+        on: bool,
+        loop_handle: &LoopHandle<'_, State>,
+    ) -> Result<()> {
+        let surface = self
+            .devices
+            .values_mut()
+            .flat_map(|device| device.surfaces.values_mut())
+            .find(|surface| surface.output == *output)
+            .with_context(|| format!("No surface for output {}", output.name()))?;
+        let compositor = surface
+            .surface
+            .as_mut()
+            .with_context(|| format!("Output {} is disabled", output.name()))?;
+
+        if on {
+            compositor
+                .reset_state()
+                .with_context(|| "Failed to reset crtc state")?;
+        } else {
+            compositor
+                .clear()
+                .with_context(|| "Failed to disable crtc")?;
+            if let Some(token) = surface.render_timer_token.take() {
+                loop_handle.remove(token);
+            }
+        }
+        // no vblank is going to arrive for the last frame anymore
+        surface.scheduled = false;
+        surface.pending = false;
+        Ok(())
+    }
+
+    /// Number of entries per color channel of the gamma ramp of `output`
+    pub fn gamma_size(&self, output: &Output) -> Result<u32> {
+        let (drm, crtc) = self
+            .devices
+            .values()
+            .flat_map(|device| {
+                device
+                    .surfaces
+                    .iter()
+                    .map(move |(crtc, surface)| (&device.drm, *crtc, surface))
+            })
+            .find(|(_, _, surface)| surface.output == *output)
+            .map(|(drm, crtc, _)| (drm, crtc))
+            .with_context(|| format!("No surface for output {}", output.name()))?;
+        Ok(drm.get_crtc(crtc)?.gamma_length())
+    }
+
+    /// Sets the gamma ramp of a client for `output`, `None` restores the default one.
+    pub fn set_gamma(&mut self, output: &Output, ramp: Option<Vec<u16>>) -> Result<()> {
+        let surface = self
+            .devices
+            .values_mut()
+            .flat_map(|device| device.surfaces.values_mut())
+            .find(|surface| surface.output == *output)
+            .with_context(|| format!("No surface for output {}", output.name()))?;
+        surface.gamma = ramp;
+        self.apply_gamma(output)
+    }
+
+    pub fn night_light(&self) -> bool {
+        self.night_light
+    }
+
+    pub fn set_night_light(&mut self, enabled: bool) {
+        self.night_light = enabled;
+        let outputs = self
+            .devices
+            .values()
+            .flat_map(|device| device.surfaces.values())
+            .map(|surface| surface.output.clone())
+            .collect::<Vec<_>>();
+        for output in outputs {
+            if let Err(err) = self.apply_gamma(&output) {
+                warn!(?err, output = output.name(), "Failed to apply night light.");
+            }
+        }
+    }
+
+    /// The mode currently driving `output`, if it is enabled.
+    pub fn applied_mode(&self, output: &Output) -> Option<OutputMode> {
+        self.devices
+            .values()
+            .flat_map(|device| device.surfaces.values())
+            .find(|surface| surface.output == *output)
+            .and_then(|surface| surface.mode)
+    }
+
+    /// Writes the client ramp of `output` or the default one to its crtc,
+    /// with the night light curve on top, if enabled.
+    fn apply_gamma(&self, output: &Output) -> Result<()> {
+        let night_light = self.night_light;
+        let (drm, crtc, surface) = self
+            .devices
+            .values()
+            .flat_map(|device| {
+                device
+                    .surfaces
+                    .iter()
+                    .map(move |(crtc, surface)| (&device.drm, *crtc, surface))
+            })
+            .find(|(_, _, surface)| surface.output == *output)
+            .with_context(|| format!("No surface for output {}", output.name()))?;
+        if surface.surface.is_none() {
+            // applied once the output gets enabled
+            return Ok(());
+        }
+
+        let size = drm.get_crtc(crtc)?.gamma_length() as usize;
+        if size == 0 {
+            return Ok(());
+        }
+        let ramp = gamma_ramp(surface.gamma.as_deref(), size, night_light)?;
+        let (red, rest) = ramp.split_at(size);
+        let (green, blue) = rest.split_at(size);
+        drm.set_gamma(crtc, red, green, blue)
+            .with_context(|| "Failed to set gamma ramp")
+    }
+
     pub fn schedule_render(
         &mut self,
         loop_handle: &LoopHandle<'_, State>,
@@ -1608,7 +1918,7 @@ impl KmsState {
             .flat_map(|(node, d)| d.surfaces.iter_mut().map(move |(c, s)| (node, c, s)))
             .find(|(_, _, s)| s.output == *output)
         {
-            if surface.surface.is_none() {
+            if surface.surface.is_none() || output.is_powered_off() {
                 if let Some(sessions) = screencopy_sessions {
                     loop_handle.insert_idle(move |state| {
                         for (session, params) in sessions.into_iter() {
@@ -1624,87 +1934,38 @@ impl KmsState {
                 if let Some(token) = surface.render_timer_token.take() {
                     loop_handle.remove(token);
                 }
-                surface.render_timer_token = Some(loop_handle.insert_source(
-                    if surface.vrr || estimated_rendertime.is_none() {
+
+                let frame_duration = Duration::from_secs_f64(1000.0 / surface.refresh_rate as f64);
+                let timer = match estimated_rendertime {
+                    Some(estimated_rendertime) if !surface.vrr => {
+                        surface.expected_vblank = Some(Instant::now() + frame_duration);
+                        Timer::from_duration(frame_duration.saturating_sub(estimated_rendertime))
+                    }
+                    _ => {
+                        surface.expected_vblank = None;
                         Timer::immediate()
-                    } else {
-                        Timer::from_duration(
-                            Duration::from_secs_f64(1000.0 / surface.refresh_rate as f64)
-                                .saturating_sub(estimated_rendertime.unwrap()),
-                        )
-                    },
-                    move |_time, _, state| {
-                        let backend = state.backend.kms();
-                        let (mut device, mut other) = backend
+                    }
+                };
+                surface.render_timer_token =
+                    Some(loop_handle.insert_source(timer, move |_time, _, state| {
+                        // Defer the actual rendering to an idle callback, so any input events,
+                        // that became ready in the same event loop iteration, are processed first
+                        // and the frame reflects the freshest pointer position.
+                        let sessions = screencopy_sessions.take();
+                        if let Some(surface) = state
+                            .backend
+                            .kms()
                             .devices
-                            .iter_mut()
-                            .partition::<Vec<_>, _>(|(key, _val)| *key == &device);
-                        let target_device = &mut device[0].1;
-
-                        if let Some(surface) = target_device.surfaces.get_mut(&crtc) {
-                            let target_node = target_device.render_node;
-                            let render_node = render_node_for_output(
-                                &state.common.display_handle,
-                                &surface.output,
-                                target_node,
-                                &state.common.shell,
-                            );
-                            let common = &mut state.common;
-
-                            let result = if render_node != target_node {
-                                let render_device = &mut other
-                                    .iter_mut()
-                                    .find(|(_, val)| val.render_node == render_node)
-                                    .unwrap()
-                                    .1;
-                                surface.render_output(
-                                    &mut backend.api,
-                                    Some((
-                                        &render_device.render_node,
-                                        render_device.allocator.as_mut(),
-                                    )),
-                                    &target_node,
-                                    common,
-                                    screencopy_sessions.as_deref(),
-                                )
-                            } else {
-                                surface.render_output(
-                                    &mut backend.api,
-                                    None,
-                                    &target_node,
-                                    common,
-                                    screencopy_sessions.as_deref(),
-                                )
-                            };
-
-                            match result {
-                                Ok(_) => {
-                                    trace!(?crtc, "Frame pending");
-                                    surface.dirty = false;
-                                    surface.pending = true;
-                                    surface.scheduled = false;
-                                    surface.render_timer_token = None;
-                                    return TimeoutAction::Drop;
-                                }
-                                Err(err) => {
-                                    if backend.session.is_active() {
-                                        error!(?err, "Error rendering.");
-                                        return TimeoutAction::ToDuration(Duration::from_secs_f64(
-                                            (1000.0 / surface.refresh_rate as f64) - 0.003,
-                                        ));
-                                    }
-                                }
-                            };
-                        }
-
-                        if let Some(sessions) = screencopy_sessions.as_mut() {
-                            for (session, params) in sessions.drain(..) {
-                                state.common.still_pending(session, params);
-                            }
+                            .get_mut(&device)
+                            .and_then(|device| device.surfaces.get_mut(&crtc))
+                        {
+                            surface.render_timer_token = None;
                         }
+                        state.common.event_loop_handle.insert_idle(move |state| {
+                            render_surface_guarded(state, device, crtc, sessions)
+                        });
                         TimeoutAction::Drop
-                    },
-                )?);
+                    })?);
                 trace!(?surface.render_timer_token, ?crtc, "Frame scheduled");
                 surface.scheduled = true;
             } else {
@@ -1721,3 +1982,248 @@ impl KmsState {
         Ok(())
     }
 }
+
+/// Renders a surface, skipping the frame if rendering panics.
+fn render_surface_guarded(
+    state: &mut State,
+    device: DrmNode,
+    crtc: crtc::Handle,
+    screencopy_sessions: Option<Vec<(ScreencopySession, BufferParams)>>,
+) {
+    if state
+        .catch_panic(CodePath::Render, |state| {
+            render_surface(state, device, crtc, screencopy_sessions)
+        })
+        .is_some()
+    {
+        return;
+    }
+
+    // the panic left the surface in an unknown state, start over with a full frame
+    let backend = state.backend.kms();
+    let Some(surface) = backend
+        .devices
+        .get_mut(&device)
+        .and_then(|device| device.surfaces.get_mut(&crtc))
+    else {
+        return;
+    };
+    surface.scheduled = false;
+    surface.pending = false;
+    surface.dirty = true;
+    surface.expected_vblank = None;
+    let output = surface.output.clone();
+    if !state.common.should_stop {
+        if let Err(err) =
+            backend.schedule_render(&state.common.event_loop_handle, &output, None, None)
+        {
+            warn!(?err, "Failed to reschedule render.");
+        }
+    }
+}
+
+/// Red, green and blue ramps of `size` entries each, back to back.
+/// Uses the ramp of a client or a linear one, with the night light curve on top, if enabled.
+fn gamma_ramp(client: Option<&[u16]>, size: usize, night_light: bool) -> Result<Vec<u16>> {
+    let mut ramp = match client {
+        Some(ramp) if ramp.len() == size * 3 => ramp.to_vec(),
+        Some(_) => anyhow::bail!("Gamma ramp doesn't match the size of the crtc"),
+        None => (0..3)
+            .flat_map(|_| {
+                (0..size).map(move |i| (i * u16::MAX as usize / (size - 1).max(1)) as u16)
+            })
+            .collect(),
+    };
+    if night_light {
+        for (channel, factor) in ramp.chunks_exact_mut(size).zip(NIGHT_LIGHT) {
+            for value in channel {
+                *value = (*value as f64 * factor).round() as u16;
+            }
+        }
+    }
+    Ok(ramp)
+}
+
+fn render_surface(
+    state: &mut State,
+    device: DrmNode,
+    crtc: crtc::Handle,
+    mut screencopy_sessions: Option<Vec<(ScreencopySession, BufferParams)>>,
+) {
+    let backend = state.backend.kms();
+    let (mut device, mut other) = backend
+        .devices
+        .iter_mut()
+        .partition::<Vec<_>, _>(|(key, _val)| *key == &device);
+    let Some((&device_node, target_device)) = device.pop() else {
+        return;
+    };
+
+    if let Some(surface) = target_device.surfaces.get_mut(&crtc) {
+        let target_node = target_device.render_node;
+        let render_node = render_node_for_output(
+            &state.common.display_handle,
+            &surface.output,
+            target_node,
+            &state.common.shell,
+        );
+        let common = &mut state.common;
+
+        let result = if render_node != target_node {
+            let render_device = &mut other
+                .iter_mut()
+                .find(|(_, val)| val.render_node == render_node)
+                .unwrap()
+                .1;
+            surface.render_output(
+                &mut backend.api,
+                Some((&render_device.render_node, render_device.allocator.as_mut())),
+                &target_node,
+                common,
+                screencopy_sessions.as_deref(),
+            )
+        } else {
+            surface.render_output(
+                &mut backend.api,
+                None,
+                &target_node,
+                common,
+                screencopy_sessions.as_deref(),
+            )
+        };
+
+        match result {
+            Ok(_) => {
+                trace!(?crtc, "Frame pending");
+                surface.dirty = false;
+                surface.pending = true;
+                surface.scheduled = false;
+                return;
+            }
+            Err(err) => {
+                if backend.session.is_active() {
+                    error!(?err, "Error rendering.");
+                    let retry =
+                        Duration::from_secs_f64((1000.0 / surface.refresh_rate as f64) - 0.003);
+                    surface.expected_vblank = None;
+                    match common.event_loop_handle.insert_source(
+                        Timer::from_duration(retry),
+                        move |_time, _, state| {
+                            render_surface_guarded(
+                                state,
+                                device_node,
+                                crtc,
+                                screencopy_sessions.take(),
+                            );
+                            TimeoutAction::Drop
+                        },
+                    ) {
+                        Ok(token) => {
+                            surface.render_timer_token = Some(token);
+                            return;
+                        }
+                        Err(err) => {
+                            warn!(?err, "Failed to reschedule render.");
+                            surface.scheduled = false;
+                            return;
+                        }
+                    }
+                }
+            }
+        };
+    }
+
+    if let Some(sessions) = screencopy_sessions.as_mut() {
+        for (session, params) in sessions.drain(..) {
+            state.common.still_pending(session, params);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{drm_helpers, gamma_ramp, select_mode};
+    use crate::config::OutputConfig;
+
+    #[test]
+    fn default_ramp_is_linear() {
+        let ramp = gamma_ramp(None, 256, false).unwrap();
+        assert_eq!(ramp.len(), 256 * 3);
+        for channel in ramp.chunks_exact(256) {
+            assert_eq!(channel[0], 0);
+            assert_eq!(channel[255], u16::MAX);
+            assert!(channel.windows(2).all(|w| w[0] < w[1]));
+        }
+    }
+
+    #[test]
+    fn single_entry_ramp() {
+        assert_eq!(gamma_ramp(None, 1, false).unwrap(), vec![0, 0, 0]);
+    }
+
+    #[test]
+    fn client_ramp_is_used_as_is() {
+        let client = (0..12).collect::<Vec<u16>>();
+        assert_eq!(gamma_ramp(Some(&client), 4, false).unwrap(), client);
+    }
+
+    #[test]
+    fn client_ramp_of_wrong_size_is_rejected() {
+        let client = vec![0u16; 4 * 3 - 1];
+        assert!(gamma_ramp(Some(&client), 4, false).is_err());
+        let client = vec![0u16; 8 * 3];
+        assert!(gamma_ramp(Some(&client), 4, false).is_err());
+    }
+
+    #[test]
+    fn night_light_keeps_red_and_dims_blue() {
+        let client = vec![u16::MAX; 4 * 3];
+        let ramp = gamma_ramp(Some(&client), 4, true).unwrap();
+        let (red, rest) = ramp.split_at(4);
+        let (green, blue) = rest.split_at(4);
+        assert!(red.iter().all(|v| *v == u16::MAX));
+        assert!(green.iter().zip(blue).all(|(g, b)| g > b && *g < u16::MAX));
+    }
+
+    fn output_config(size: (i32, i32), refresh: Option<u32>) -> OutputConfig {
+        OutputConfig {
+            mode: (size, refresh),
+            ..OutputConfig::default()
+        }
+    }
+
+    #[test]
+    fn advertised_mode_within_tolerance() {
+        let modes = [
+            drm_helpers::cvt_rb_mode(2560, 1440, 59_951),
+            drm_helpers::cvt_rb_mode(1920, 1080, 60_000),
+        ];
+        let (mode, fallback) =
+            select_mode(&modes, &output_config((2560, 1440), Some(60_000))).unwrap();
+        assert_eq!(mode, modes[0]);
+        assert!(fallback.is_none());
+
+        let (mode, fallback) = select_mode(&modes, &output_config((1920, 1080), None)).unwrap();
+        assert_eq!(mode, modes[1]);
+        assert!(fallback.is_none());
+    }
+
+    #[test]
+    fn custom_refresh_rate_falls_back_to_advertised() {
+        let modes = [drm_helpers::cvt_rb_mode(2560, 1440, 60_000)];
+        let (mode, fallback) =
+            select_mode(&modes, &output_config((2560, 1440), Some(75_000))).unwrap();
+        assert_eq!(mode.size(), (2560, 1440));
+        assert!(drm_helpers::calculate_refresh_rate(mode).abs_diff(75_000) <= 1000);
+        assert_eq!(fallback, Some(modes[0]));
+    }
+
+    #[test]
+    fn custom_size_falls_back_to_advertised() {
+        let modes = [drm_helpers::cvt_rb_mode(1920, 1080, 60_000)];
+        let (mode, fallback) =
+            select_mode(&modes, &output_config((1600, 900), Some(60_000))).unwrap();
+        assert_eq!(mode.size(), (1600, 900));
+        assert_eq!(fallback, Some(modes[0]));
+    }
+}
@@ -16,7 +16,6 @@ use smithay::{
     },
     xwayland::XWaylandClientData,
 };
-use std::sync::Arc;
 use tracing::{info, warn};
 
 use crate::state::{ClientState, State};
@@ -94,10 +93,8 @@ impl State {
             .common
             .event_loop_handle
             .insert_source(listener, move |client_stream, _, state: &mut State| {
-                if let Err(err) = state.common.display_handle.insert_client(
-                    client_stream,
-                    Arc::new(state.new_client_state_with_node(render_node)),
-                ) {
+                let client_state = state.new_client_state_with_node(render_node);
+                if let Err(err) = state.insert_client(client_stream, client_state) {
                     warn!(
                         socket_name = socket_name_clone,
                         ?err,
@@ -55,7 +55,11 @@ pub fn init_backend_auto(
             &state.common.config,
             "seat-0".into(),
         );
-        state.common.add_seat(initial_seat);
+        state.common.add_seat(initial_seat.clone());
+        if state.common.config.static_conf.numlock_on_startup {
+            state.press_lock_key(&initial_seat, crate::input::KEY_NUMLOCK);
+            state.update_keyboard_leds(&initial_seat);
+        }
     }
     res
 }
@@ -0,0 +1,227 @@
+// SPDX-License-Identifier: GPL-3.0-only
+
+use std::{
+    cell::RefCell,
+    collections::HashMap,
+    path::{Path, PathBuf},
+};
+
+use anyhow::{anyhow, Context, Result};
+use image::RgbaImage;
+use smithay::{
+    backend::{
+        allocator::Fourcc,
+        renderer::{
+            element::{
+                memory::{MemoryRenderBuffer, MemoryRenderBufferRenderElement},
+                Kind,
+            },
+            ImportMem, Renderer,
+        },
+    },
+    output::Output,
+    utils::{Logical, Physical, Point, Rectangle, Size, Transform},
+};
+use tracing::warn;
+
+use crate::{
+    config::{BackgroundMode, OutputConfig, StaticConfig},
+    state::Common,
+    utils::prelude::*,
+};
+
+use super::CLEAR_COLOR;
+
+/// Color shown where no surface covers the output, e.g. before a wallpaper client is running.
+pub fn clear_color(state: &Common, output: &Output) -> [f32; 4] {
+    output
+        .user_data()
+        .get::<RefCell<OutputConfig>>()
+        .and_then(|config| config.borrow().background_color)
+        .or_else(|| {
+            state
+                .config
+                .static_conf
+                .background_color_for(&output.name())
+        })
+        .map_or(CLEAR_COLOR, |[r, g, b]| [r, g, b, 1.0])
+}
+
+struct Wallpaper {
+    image: RgbaImage,
+    buffer: MemoryRenderBuffer,
+}
+
+impl std::fmt::Debug for Wallpaper {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Wallpaper")
+            .field("size", &self.image.dimensions())
+            .finish_non_exhaustive()
+    }
+}
+
+/// Decoded wallpapers of the `background` and `background_image` config keys.
+#[derive(Debug, Default)]
+pub struct Backgrounds {
+    images: HashMap<PathBuf, Wallpaper>,
+    /// Tiled wallpapers, rendered for the current physical size of each output
+    tiled: RefCell<HashMap<String, (Size<i32, Physical>, MemoryRenderBuffer)>>,
+}
+
+impl Backgrounds {
+    pub fn load(config: &StaticConfig) -> Backgrounds {
+        let mut images = HashMap::new();
+        for path in config.background_images() {
+            if images.contains_key(path) {
+                continue;
+            }
+            match load_image(path) {
+                Ok(wallpaper) => {
+                    images.insert(path.clone(), wallpaper);
+                }
+                Err(err) => warn!(?err, "Failed to load background image."),
+            }
+        }
+
+        Backgrounds {
+            images,
+            tiled: RefCell::new(HashMap::new()),
+        }
+    }
+}
+
+impl State {
+    /// Re-reads the configured images, e.g. after a script replaced the file.
+    pub fn reload_backgrounds(&mut self) {
+        self.common.backgrounds = Backgrounds::load(&self.common.config.static_conf);
+        for output in self.common.shell.outputs().cloned().collect::<Vec<_>>() {
+            self.backend
+                .schedule_render(&self.common.event_loop_handle, &output, None);
+        }
+    }
+}
+
+/// Decodes a wallpaper image.
+fn load_image(path: &Path) -> Result<Wallpaper> {
+    let image = image::open(path)
+        .with_context(|| format!("Failed to open {}", path.display()))?
+        .into_rgba8();
+    let buffer = memory_buffer(&image)?;
+    Ok(Wallpaper { image, buffer })
+}
+
+fn memory_buffer(image: &RgbaImage) -> Result<MemoryRenderBuffer> {
+    let size = (image.width() as i32, image.height() as i32);
+    let mut buffer = MemoryRenderBuffer::new(Fourcc::Abgr8888, size, 1, Transform::Normal, None);
+    buffer
+        .render()
+        .draw(|buf| {
+            buf.copy_from_slice(image.as_raw());
+            Result::<_, ()>::Ok(vec![Rectangle::from_loc_and_size((0, 0), size)])
+        })
+        .map_err(|_| anyhow!("Failed to copy image into buffer"))?;
+    Ok(buffer)
+}
+
+/// Repeats `image` from the top left corner until `size` is covered.
+fn tile(image: &RgbaImage, size: Size<i32, Physical>) -> Result<MemoryRenderBuffer> {
+    let (width, height) = (size.w.max(1) as u32, size.h.max(1) as u32);
+    let mut tiled = RgbaImage::new(width, height);
+    for (x, y, pixel) in tiled.enumerate_pixels_mut() {
+        *pixel = *image.get_pixel(x % image.width(), y % image.height());
+    }
+    memory_buffer(&tiled)
+}
+
+/// The configured wallpaper of `output`, drawn below the background layer.
+pub fn image_element<R>(
+    renderer: &mut R,
+    state: &Common,
+    output: &Output,
+) -> Option<MemoryRenderBufferRenderElement<R>>
+where
+    R: Renderer + ImportMem,
+    <R as Renderer>::TextureId: 'static,
+{
+    let name = output.name();
+    let (path, mode) = state.config.static_conf.background_image_for(&name)?;
+    let wallpaper = state.backgrounds.images.get(path)?;
+    let output_size = output.geometry().size.as_logical();
+    let image_size = Size::<i32, Logical>::from((
+        wallpaper.image.width() as i32,
+        wallpaper.image.height() as i32,
+    ));
+
+    let tiled;
+    let (buffer, location, src, size) = match mode {
+        BackgroundMode::Stretch => (&wallpaper.buffer, (0, 0).into(), None, output_size),
+        BackgroundMode::Fill => {
+            // crop the image to the aspect ratio of the output
+            let scale = f64::max(
+                output_size.w as f64 / image_size.w as f64,
+                output_size.h as f64 / image_size.h as f64,
+            );
+            let visible = Size::<f64, Logical>::from((
+                output_size.w as f64 / scale,
+                output_size.h as f64 / scale,
+            ));
+            let src = Rectangle::from_loc_and_size(
+                (
+                    (image_size.w as f64 - visible.w) / 2.,
+                    (image_size.h as f64 - visible.h) / 2.,
+                ),
+                visible,
+            );
+            (&wallpaper.buffer, (0, 0).into(), Some(src), output_size)
+        }
+        BackgroundMode::Fit => {
+            let scale = f64::min(
+                output_size.w as f64 / image_size.w as f64,
+                output_size.h as f64 / image_size.h as f64,
+            );
+            let size = image_size.to_f64().upscale(scale).to_i32_round();
+            let location = Point::<i32, Logical>::from((
+                (output_size.w - size.w) / 2,
+                (output_size.h - size.h) / 2,
+            ));
+            (&wallpaper.buffer, location, None, size)
+        }
+        BackgroundMode::Tile => {
+            // the logical size already accounts for the transform of the output
+            let physical_size = output_size
+                .to_f64()
+                .to_physical(output.current_scale().fractional_scale())
+                .to_i32_round();
+            let mut cache = state.backgrounds.tiled.borrow_mut();
+            if cache
+                .get(&name)
+                .map_or(true, |(size, _)| *size != physical_size)
+            {
+                match tile(&wallpaper.image, physical_size) {
+                    Ok(buffer) => {
+                        cache.insert(name.clone(), (physical_size, buffer));
+                    }
+                    Err(err) => {
+                        warn!(?err, "Failed to tile background image.");
+                        return None;
+                    }
+                }
+            }
+            tiled = cache.get(&name).unwrap().1.clone();
+            (&tiled, (0, 0).into(), None, output_size)
+        }
+    };
+
+    let output_scale = output.current_scale().fractional_scale();
+    MemoryRenderBufferRenderElement::from_buffer(
+        renderer,
+        location.to_f64().to_physical(output_scale),
+        buffer,
+        None,
+        src,
+        Some(size),
+        Kind::Unspecified,
+    )
+    .map_err(|err| warn!(?err, "Failed to render background image."))
+    .ok()
+}
@@ -185,7 +185,7 @@ where
     R: Renderer + ImportAll,
     <R as Renderer>::TextureId: 'static,
 {
-    #[cfg(feature = "debug")]
+    #[cfg(feature = "puffin")]
     puffin::profile_function!();
 
     if get_role(&surface) != Some("dnd_icon") {
@@ -272,7 +272,7 @@ where
     R: Renderer + ImportMem + ImportAll,
     <R as Renderer>::TextureId: Clone + 'static,
 {
-    #[cfg(feature = "debug")]
+    #[cfg(feature = "puffin")]
     puffin::profile_function!();
     // draw the cursor as relevant
     // reset the cursor if the surface is no longer alive
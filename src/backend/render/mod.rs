@@ -11,8 +11,9 @@ use std::{
 #[cfg(feature = "debug")]
 use crate::debug::{fps_ui, profiler_ui};
 use crate::{
+    input::PointerHidden,
     shell::{
-        focus::target::WindowGroup, grabs::SeatMoveGrabState, layout::tiling::ANIMATION_DURATION,
+        element::osd::osd_for_output, focus::target::WindowGroup, grabs::SeatMoveGrabState,
         CosmicMapped, CosmicMappedRenderElement, OverviewMode, Trigger, WorkspaceRenderElement,
     },
     state::{Common, Fps, SessionLock},
@@ -44,7 +45,7 @@ use smithay::{
             element::{
                 surface::{render_elements_from_surface_tree, WaylandSurfaceRenderElement},
                 utils::{Relocate, RelocateRenderElement},
-                Element, Id, Kind, RenderElement,
+                AsRenderElements, Element, Id, Kind, RenderElement,
             },
             gles::{
                 element::PixelShaderElement, GlesError, GlesPixelProgram, GlesRenderer, Uniform,
@@ -56,9 +57,10 @@ use smithay::{
             Bind, Blit, ExportMem, ImportAll, ImportMem, Offscreen, Renderer, TextureFilter,
         },
     },
-    desktop::{layer_map_for_output, PopupManager},
+    desktop::{layer_map_for_output, utils::bbox_from_surface_tree, PopupManager},
+    input::Seat,
     output::{Output, OutputNoMode},
-    utils::{IsAlive, Logical, Point, Rectangle, Scale},
+    utils::{IsAlive, Logical, Monotonic, Point, Rectangle, Scale, Time},
     wayland::{
         dmabuf::get_dmabuf,
         shell::wlr_layer::Layer,
@@ -67,9 +69,11 @@ use smithay::{
 };
 use tracing::warn;
 
+pub mod background;
 pub mod cursor;
 use self::cursor::CursorRenderElement;
 pub mod element;
+pub mod snapshot;
 use self::element::{AsGlowRenderer, CosmicElement};
 
 pub type GlMultiRenderer<'a, 'b> =
@@ -91,6 +95,9 @@ pub enum Usage {
     MoveGrabIndicator,
     FocusIndicator,
     PotentialGroupIndicator,
+    Dim,
+    #[cfg(feature = "debug")]
+    DebugHighlight,
 }
 
 #[derive(Clone)]
@@ -374,10 +381,11 @@ where
     CosmicMappedRenderElement<R>: RenderElement<R>,
     E: From<CursorRenderElement<R>> + From<CosmicMappedRenderElement<R>>,
 {
-    #[cfg(feature = "debug")]
+    #[cfg(feature = "puffin")]
     puffin::profile_function!();
 
     let scale = output.current_scale().fractional_scale();
+    let output_geometry = output.geometry().as_logical();
     let mut elements = Vec::new();
 
     for seat in state.seats() {
@@ -386,8 +394,18 @@ where
             None => continue,
         };
         let location = pointer.current_location() - output.current_location().to_f64();
+        // every seat has its own cursor, only draw it on the outputs it is (partially) on
+        let on_output = output_geometry.overlaps(cursor_area(
+            seat,
+            pointer.current_location(),
+            state.clock.now(),
+        ));
+        let hidden = seat
+            .user_data()
+            .get::<PointerHidden>()
+            .is_some_and(|hidden| hidden.0.get());
 
-        if mode != CursorMode::None {
+        if on_output && mode != CursorMode::None && !hidden {
             elements.extend(
                 cursor::draw_cursor(
                     renderer,
@@ -402,7 +420,12 @@ where
             );
         }
 
-        if let Some(wl_surface) = get_dnd_icon(seat) {
+        if let Some(wl_surface) = get_dnd_icon(seat).filter(|icon| {
+            output_geometry.overlaps(bbox_from_surface_tree(
+                icon,
+                pointer.current_location().to_i32_round(),
+            ))
+        }) {
             elements.extend(
                 cursor::draw_dnd_icon(renderer, &wl_surface, location.to_i32_round(), scale)
                     .into_iter()
@@ -426,6 +449,22 @@ where
     elements
 }
 
+/// Area covered by the cursor image of `seat`, when its pointer is at the global `location`.
+fn cursor_area(
+    seat: &Seat<State>,
+    location: Point<f64, Logical>,
+    time: Time<Monotonic>,
+) -> Rectangle<i32, Logical> {
+    match seat.cursor_geometry((location.x, location.y), time) {
+        Some((geometry, hotspot)) => Rectangle::from_loc_and_size(
+            (geometry.loc.x - hotspot.x, geometry.loc.y - hotspot.y),
+            (geometry.size.w, geometry.size.h),
+        ),
+        // the size of other cursors isn't known, but they are drawn at the pointer location
+        None => Rectangle::from_loc_and_size(location.to_i32_round(), (1, 1)),
+    }
+}
+
 pub fn workspace_elements<R>(
     _gpu: Option<&DrmNode>,
     renderer: &mut R,
@@ -444,11 +483,26 @@ where
     CosmicMappedRenderElement<R>: RenderElement<R>,
     WorkspaceRenderElement<R>: RenderElement<R>,
 {
-    #[cfg(feature = "debug")]
+    #[cfg(feature = "puffin")]
     puffin::profile_function!();
 
     let mut elements = cursor_elements(renderer, state, output, cursor_mode);
 
+    if let Some(banner) = state.recovery.banner.as_ref() {
+        let scale = output.current_scale().fractional_scale();
+        elements.extend(
+            AsRenderElements::<R>::render_elements::<CosmicMappedRenderElement<R>>(
+                banner,
+                renderer,
+                (0, 0).into(),
+                scale.into(),
+                1.0,
+            )
+            .into_iter()
+            .map(Into::into),
+        );
+    }
+
     #[cfg(feature = "debug")]
     {
         let output_geo = output.geometry();
@@ -471,7 +525,7 @@ where
             elements.push(fps_overlay.into());
         }
 
-        if state.shell.outputs().next() == Some(output) {
+        if &state.egui_output() == output {
             if let Some(profiler_overlay) = profiler_ui(
                 state,
                 renderer.glow_renderer_mut(),
@@ -484,6 +538,34 @@ where
                 elements.push(profiler_overlay.into());
             }
         }
+
+        if let Some(mapped) = state
+            .egui
+            .highlighted
+            .as_ref()
+            .filter(|_| state.egui.active)
+        {
+            if let Some(geo) = state
+                .shell
+                .space_for(mapped)
+                .filter(|w| w.output() == output && w.handle == current.0)
+                .and_then(|w| w.element_geometry(mapped))
+            {
+                elements.push(
+                    CosmicMappedRenderElement::from(IndicatorShader::element(
+                        renderer,
+                        Key::Window(Usage::DebugHighlight, mapped.clone()),
+                        geo,
+                        4,
+                        0,
+                        1.0,
+                        scale,
+                        [1.0, 0.0, 1.0],
+                    ))
+                    .into(),
+                );
+            }
+        }
     }
 
     // If session locked, only show session lock surfaces
@@ -546,6 +628,21 @@ where
     elements.extend(overlay_popups.into_iter().map(Into::into));
     elements.extend(overlay_elements.into_iter().map(Into::into));
 
+    // the osd sits right below the overlay layer
+    if let Some((osd, location, alpha)) = osd_for_output(output) {
+        elements.extend(
+            AsRenderElements::<R>::render_elements::<CosmicMappedRenderElement<R>>(
+                &osd,
+                renderer,
+                location.to_physical_precise_round(output_scale),
+                output_scale.into(),
+                alpha,
+            )
+            .into_iter()
+            .map(Into::into),
+        );
+    }
+
     let mut window_elements = if !has_fullscreen {
         let (top_elements, top_popups) =
             split_layer_elements(renderer, output, Layer::Top, exclude_workspace_overview);
@@ -568,8 +665,12 @@ where
             let is_active_space = workspace.outputs().any(|o| o == &active_output);
 
             let percentage = {
+                let duration = state
+                    .config
+                    .static_conf
+                    .animation_duration(|d| d.workspace_switch);
                 let percentage = Instant::now().duration_since(*start).as_millis() as f32
-                    / ANIMATION_DURATION.as_millis() as f32;
+                    / duration.as_millis().max(1) as f32;
                 ease(EaseInOutCubic, 0.0, 1.0, percentage)
             };
             let offset = Point::<i32, Logical>::from(match (layout, *previous_idx < current.1) {
@@ -596,6 +697,7 @@ where
                     overview.clone(),
                     resize_indicator.clone(),
                     active_hint,
+                    state.config.static_conf.dim_unfocused,
                     theme,
                 )
                 .map_err(|_| OutputNoMode)?;
@@ -654,6 +756,7 @@ where
             overview,
             resize_indicator,
             active_hint,
+            state.config.static_conf.dim_unfocused,
             theme,
         )
         .map_err(|_| OutputNoMode)?;
@@ -695,6 +798,12 @@ where
 
     elements.extend(window_elements);
 
+    if !has_fullscreen {
+        if let Some(image) = background::image_element(renderer, state, output) {
+            elements.push(CosmicMappedRenderElement::from(image).into());
+        }
+    }
+
     Ok(elements)
 }
 
@@ -905,7 +1014,7 @@ where
     WorkspaceRenderElement<R>: RenderElement<R>,
     Source: Clone,
 {
-    #[cfg(feature = "debug")]
+    #[cfg(feature = "puffin")]
     puffin::profile_function!();
 
     if let Some(ref mut fps) = fps {
@@ -954,7 +1063,7 @@ where
         renderer,
         age,
         &elements,
-        CLEAR_COLOR, // TODO use a theme neutral color
+        background::clear_color(state, output),
     );
 
     if let Some(fps) = fps.as_mut() {
@@ -1025,9 +1134,135 @@ where
                 std::ptr::null(),
             );
         }
-
-        puffin::GlobalProfiler::lock().new_frame();
     }
 
+    #[cfg(feature = "puffin")]
+    puffin::GlobalProfiler::lock().new_frame();
+
     res
 }
+
+#[cfg(test)]
+mod tests {
+    use smithay::{
+        input::{pointer::MotionEvent, Seat},
+        output::Output,
+        utils::{Logical, Rectangle, SERIAL_COUNTER},
+    };
+    use wayland_client::protocol::{
+        wl_compositor::WlCompositor,
+        wl_pointer::{self, WlPointer},
+        wl_seat::WlSeat,
+    };
+
+    use super::cursor_area;
+    use crate::{
+        shell::{focus::target::PointerFocusTarget, CosmicSurface},
+        utils::{
+            prelude::*,
+            testing::{ClientEvent, TestClient, TestServer},
+        },
+    };
+
+    /// Moves the pointer of `seat` over `window` to the global `location`.
+    fn move_pointer(
+        server: &mut TestServer,
+        seat: &Seat<State>,
+        window: &CosmicSurface,
+        location: (f64, f64),
+    ) {
+        seat.get_pointer().unwrap().motion(
+            &mut server.state,
+            Some((
+                PointerFocusTarget::Fullscreen(window.clone()),
+                (0, 0).into(),
+            )),
+            &MotionEvent {
+                location: location.into(),
+                serial: SERIAL_COUNTER.next_serial(),
+                time: 0,
+            },
+        );
+    }
+
+    /// Sets a cursor image of `size` with `hotspot`, after `pointer` entered a surface.
+    fn set_cursor(
+        server: &mut TestServer,
+        client: &mut TestClient,
+        pointer: &WlPointer,
+        size: i32,
+        hotspot: i32,
+    ) {
+        server.roundtrip(client);
+        let serial = client
+            .take_events()
+            .into_iter()
+            .find_map(|event| match event {
+                ClientEvent::Pointer(wl_pointer::Event::Enter { serial, .. }) => Some(serial),
+                _ => None,
+            })
+            .expect("pointer didn't enter the window");
+        let surface = client
+            .bind::<WlCompositor>(5)
+            .create_surface(&client.handle(), ());
+        pointer.set_cursor(serial, Some(&surface), hotspot, hotspot);
+        surface.attach(Some(&client.create_buffer(size, size)), 0, 0);
+        surface.commit();
+        server.roundtrip(client);
+    }
+
+    fn area_of(server: &TestServer, seat: &Seat<State>) -> Rectangle<i32, Logical> {
+        let location = seat.get_pointer().unwrap().current_location();
+        cursor_area(seat, location, server.state.common.clock.now())
+    }
+
+    fn is_on(area: Rectangle<i32, Logical>, output: &Output) -> bool {
+        output.geometry().as_logical().overlaps(area)
+    }
+
+    #[test]
+    fn every_seat_has_its_own_cursor() {
+        let mut server = TestServer::new();
+        let left = server.state.common.shell.outputs().next().unwrap().clone();
+        let right = server.add_output("TEST-2", (1920, 0));
+        let seat0 = server.state.common.seats().next().unwrap().clone();
+        let seat1 = server.add_seat("seat-1");
+
+        let mut client = server.connect();
+        let pointers = client
+            .bind_all::<WlSeat>(7)
+            .iter()
+            .map(|seat| seat.get_pointer(&client.handle(), ()))
+            .collect::<Vec<_>>();
+        let (surface, _toplevel) = client.create_toplevel();
+        surface.commit();
+        server.roundtrip(&mut client);
+        let (window, _, _) = server.state.common.shell.pending_windows[0].clone();
+
+        // both seats on the same output, with differently sized cursors
+        move_pointer(&mut server, &seat0, &window, (100., 100.));
+        set_cursor(&mut server, &mut client, &pointers[0], 24, 4);
+        move_pointer(&mut server, &seat1, &window, (300., 300.));
+        set_cursor(&mut server, &mut client, &pointers[1], 32, 16);
+
+        let area0 = area_of(&server, &seat0);
+        let area1 = area_of(&server, &seat1);
+        assert_eq!(area0, Rectangle::from_loc_and_size((96, 96), (24, 24)));
+        assert_eq!(area1, Rectangle::from_loc_and_size((284, 284), (32, 32)));
+        assert!(is_on(area0, &left) && is_on(area1, &left));
+        assert!(!is_on(area0, &right) && !is_on(area1, &right));
+
+        // across the edge both outputs draw the cursor, further right only the right one
+        move_pointer(&mut server, &seat1, &window, (1910., 300.));
+        let area1 = area_of(&server, &seat1);
+        assert!(is_on(area1, &left) && is_on(area1, &right));
+        move_pointer(&mut server, &seat1, &window, (1950., 300.));
+        let area1 = area_of(&server, &seat1);
+        assert!(!is_on(area1, &left) && is_on(area1, &right));
+
+        assert_eq!(
+            area_of(&server, &seat0),
+            Rectangle::from_loc_and_size((96, 96), (24, 24))
+        );
+    }
+}
@@ -0,0 +1,122 @@
+// SPDX-License-Identifier: GPL-3.0-only
+
+use anyhow::{anyhow, bail, Result};
+use smithay::{
+    backend::{
+        allocator::Fourcc,
+        renderer::{
+            damage::{Error as DTError, OutputDamageTracker},
+            element::{memory::MemoryRenderBuffer, RenderElement},
+            gles::GlesRenderbuffer,
+            Bind, ExportMem, ImportAll, ImportMem, Offscreen, Renderer,
+        },
+    },
+    utils::{Logical, Point, Rectangle, Scale, Transform},
+};
+use tracing::warn;
+
+use crate::{
+    shell::{CosmicMapped, CosmicMappedRenderElement},
+    state::{BackendData, State},
+};
+
+use super::element::AsGlowRenderer;
+
+/// Copies the current content of `mapped` into memory,
+/// so it can still be drawn after the client destroyed its surfaces.
+pub fn snapshot(
+    state: &mut State,
+    mapped: &CosmicMapped,
+    scale: i32,
+) -> Option<MemoryRenderBuffer> {
+    let result = match &mut state.backend {
+        BackendData::Kms(kms) => {
+            let primary = kms.primary;
+            kms.api
+                .single_renderer(&primary)
+                .map_err(anyhow::Error::from)
+                .and_then(|mut renderer| render_snapshot(&mut renderer, mapped, scale))
+        }
+        BackendData::Winit(winit) => render_snapshot(winit.backend.renderer(), mapped, scale),
+        BackendData::X11(x11) => render_snapshot(&mut x11.renderer, mapped, scale),
+        _ => return None,
+    };
+
+    result
+        .map_err(|err| warn!(?err, "Failed to snapshot window."))
+        .ok()
+}
+
+fn render_snapshot<R>(
+    renderer: &mut R,
+    mapped: &CosmicMapped,
+    scale: i32,
+) -> Result<MemoryRenderBuffer>
+where
+    R: Renderer
+        + ImportAll
+        + ImportMem
+        + ExportMem
+        + Bind<GlesRenderbuffer>
+        + Offscreen<GlesRenderbuffer>
+        + AsGlowRenderer,
+    <R as Renderer>::TextureId: Clone + 'static,
+    CosmicMappedRenderElement<R>: RenderElement<R>,
+{
+    let geometry = mapped.geometry();
+    if geometry.size.w <= 0 || geometry.size.h <= 0 {
+        bail!("Window has no size");
+    }
+    let buffer_size = geometry.size.to_buffer(scale, Transform::Normal);
+
+    let render_buffer =
+        Offscreen::<GlesRenderbuffer>::create_buffer(renderer, Fourcc::Abgr8888, buffer_size)
+            .map_err(|err| anyhow!("{:?}", err))?;
+    renderer
+        .bind(render_buffer)
+        .map_err(|err| anyhow!("{:?}", err))?;
+
+    let (elements, _) = mapped.split_render_elements::<R, CosmicMappedRenderElement<R>>(
+        renderer,
+        Point::<i32, Logical>::from((-geometry.loc.x, -geometry.loc.y)).to_physical(scale),
+        Scale::from(scale as f64),
+        1.0,
+    );
+    let mut damage_tracker = OutputDamageTracker::new(
+        geometry.size.to_physical(scale),
+        scale as f64,
+        Transform::Normal,
+    );
+    damage_tracker
+        .render_output(renderer, 0, &elements, [0.0, 0.0, 0.0, 0.0])
+        .map_err(|err| match err {
+            DTError::Rendering(err) => anyhow!("{:?}", err),
+            DTError::OutputNoMode(err) => err.into(),
+        })?;
+
+    let mapping = renderer
+        .copy_framebuffer(
+            Rectangle::from_loc_and_size((0, 0), buffer_size),
+            Fourcc::Abgr8888,
+        )
+        .map_err(|err| anyhow!("{:?}", err))?;
+    let data = renderer
+        .map_texture(&mapping)
+        .map_err(|err| anyhow!("{:?}", err))?;
+
+    let mut buffer = MemoryRenderBuffer::new(
+        Fourcc::Abgr8888,
+        buffer_size,
+        scale,
+        Transform::Normal,
+        None,
+    );
+    buffer
+        .render()
+        .draw(|buf| {
+            buf.copy_from_slice(data);
+            Result::<_, ()>::Ok(vec![Rectangle::from_loc_and_size((0, 0), buffer_size)])
+        })
+        .map_err(|_| anyhow!("Failed to copy snapshot into buffer"))?;
+    Ok(buffer)
+}
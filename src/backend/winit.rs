@@ -3,7 +3,7 @@
 use crate::{
     backend::render,
     config::OutputConfig,
-    input::Devices,
+    recovery::CodePath,
     state::{BackendData, Common},
     utils::prelude::*,
     wayland::protocols::screencopy::{BufferParams, Session as ScreencopySession},
@@ -193,9 +193,17 @@ pub fn init_backend(
         event_loop
             .handle()
             .insert_source(render_source, move |_, _, state| {
-                if let Err(err) = state.backend.winit().render_output(&mut state.common) {
-                    error!(?err, "Failed to render frame.");
-                    render_ping.ping();
+                let result = state.catch_panic(CodePath::Render, |state| {
+                    state.backend.winit().render_output(&mut state.common)
+                });
+                match result {
+                    Some(Err(err)) => {
+                        error!(?err, "Failed to render frame.");
+                        render_ping.ping();
+                    }
+                    // the frame was skipped, try again
+                    None if !state.common.should_stop => render_ping.ping(),
+                    _ => {}
                 }
             })
             .map_err(|_| anyhow::anyhow!("Failed to init eventloop timer for winit"))?,
@@ -315,12 +323,8 @@ impl State {
         // here we can handle special cases for winit inputs
         match event {
             WinitEvent::Focus(true) => {
-                for seat in self.common.seats().cloned().collect::<Vec<_>>().iter() {
-                    let devices = seat.user_data().get::<Devices>().unwrap();
-                    if devices.has_device(&WinitVirtualDevice) {
-                        seat.set_active_output(&self.backend.winit().output);
-                        break;
-                    }
+                if let Some(seat) = self.common.seat_with_device(&WinitVirtualDevice) {
+                    seat.set_active_output(&self.backend.winit().output);
                 }
             }
             WinitEvent::Resized { size, .. } => {
@@ -347,7 +351,11 @@ impl State {
                 render_ping.ping();
             }
             WinitEvent::Redraw => render_ping.ping(),
-            WinitEvent::Input(event) => self.process_input_event(event, false),
+            WinitEvent::Input(event) => {
+                self.catch_panic(CodePath::Input, |state| {
+                    state.process_input_event(event, false)
+                });
+            }
             _ => {}
         };
     }
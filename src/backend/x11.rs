@@ -3,7 +3,7 @@
 use crate::{
     backend::render,
     config::OutputConfig,
-    input::Devices,
+    recovery::CodePath,
     state::{BackendData, Common},
     utils::prelude::*,
     wayland::protocols::screencopy::{BufferParams, Session as ScreencopySession},
@@ -125,19 +125,36 @@ impl X11State {
             ping::make_ping().with_context(|| "Failed to create output event loop source")?;
         let _token = handle
             .insert_source(source, move |_, _, state| {
-                let x11_state = state.backend.x11();
-                if let Some(surface) = x11_state
+                if state
+                    .backend
+                    .x11()
                     .surfaces
-                    .iter_mut()
-                    .find(|s| s.output == output_ref)
+                    .iter()
+                    .any(|s| s.output == output_ref)
                 {
-                    if let Err(err) =
+                    let result = state.catch_panic(CodePath::Render, |state| {
+                        let x11_state = state.backend.x11();
+                        let surface = x11_state
+                            .surfaces
+                            .iter_mut()
+                            .find(|s| s.output == output_ref)
+                            .unwrap();
                         surface.render_output(&mut x11_state.renderer, &mut state.common)
-                    {
+                    });
+                    if let Some(Err(err)) = &result {
                         error!(?err, "Error rendering.");
                     }
-                    surface.dirty = false;
-                    surface.pending = true;
+                    if let Some(surface) = state
+                        .backend
+                        .x11()
+                        .surfaces
+                        .iter_mut()
+                        .find(|s| s.output == output_ref)
+                    {
+                        // after a panic no frame was submitted, so don't wait for one
+                        surface.dirty = result.is_none();
+                        surface.pending = result.is_some();
+                    }
                 }
             })
             .with_context(|| "Failed to add output to event loop")?;
@@ -521,20 +538,17 @@ impl State {
                         .map(|surface| surface.output.clone())
                         .unwrap();
 
-                    let device = event.device();
-                    for seat in self.common.seats().cloned().collect::<Vec<_>>().iter() {
-                        let devices = seat.user_data().get::<Devices>().unwrap();
-                        if devices.has_device(&device) {
-                            seat.set_active_output(&output);
-                            break;
-                        }
+                    if let Some(seat) = self.common.seat_with_device(&event.device()) {
+                        seat.set_active_output(&output);
                     }
                 }
             }
             _ => {}
         };
 
-        self.process_input_event(event, false);
+        self.catch_panic(CodePath::Input, |state| {
+            state.process_input_event(event, false)
+        });
         // TODO actually figure out the output
         for output in self.common.shell.outputs() {
             self.backend.x11().schedule_render(output, None);
@@ -1,5 +1,5 @@
 use smithay::reexports::input::{
-    Device as InputDevice, DeviceConfigError, ScrollMethod, SendEventsMode,
+    Device as InputDevice, DeviceCapability, DeviceConfigError, ScrollMethod, SendEventsMode,
 };
 use tracing::warn;
 
@@ -25,17 +25,20 @@ pub fn for_device(device: &InputDevice) -> InputConfig {
             None
         },
         calibration: device.config_calibration_matrix(),
+        button_map: None,
         click_method: device.config_click_method(),
         disable_while_typing: if device.config_dwt_is_available() {
             Some(device.config_dwt_enabled())
         } else {
             None
         },
+        disable_while_typing_timeout: None,
         left_handed: if device.config_left_handed_is_available() {
             Some(device.config_left_handed())
         } else {
             None
         },
+        map_to_output: None,
         middle_button_emulation: if device.config_middle_emulation_is_available() {
             Some(device.config_middle_emulation_enabled())
         } else {
@@ -82,6 +85,14 @@ pub fn for_device(device: &InputDevice) -> InputConfig {
     }
 }
 
+const IDENTITY_MATRIX: [f32; 6] = [1.0, 0.0, 0.0, 0.0, 1.0, 0.0];
+
+// A calibration matrix needs finite values and must not collapse the input onto a line
+fn is_valid_calibration(matrix: &[f32; 6]) -> bool {
+    matrix.iter().all(|x| x.is_finite())
+        && (matrix[0] * matrix[4] - matrix[1] * matrix[3]).abs() > f32::EPSILON
+}
+
 // Get setting from `device_config` if present, then `default_config`
 // Returns `is_default` to indicate this is a default value.
 pub fn get_config<'a, T: 'a, F: Fn(&'a InputConfig) -> Option<T>>(
@@ -116,10 +127,27 @@ fn config_set_error<T: std::fmt::Debug>(
     }
 }
 
+// Mice are pointer devices, which aren't touchpads or trackpoints
+pub fn is_external_mouse(device: &InputDevice) -> bool {
+    device.has_capability(DeviceCapability::Pointer)
+        && device.config_tap_finger_count() == 0
+        && !is_pointing_stick(device)
+}
+
+// Trackpoints are built into the laptop just like its touchpad
+fn is_pointing_stick(device: &InputDevice) -> bool {
+    // SAFETY: libinput is created from the udev context of the session, see `init_backend`
+    unsafe { device.udev_device() }.is_some_and(|udev| {
+        udev.property_value("ID_INPUT_POINTINGSTICK")
+            .is_some_and(|value| value == "1")
+    })
+}
+
 pub fn update_device(
     device: &mut InputDevice,
     device_config: Option<&InputConfig>,
     default_config: &InputConfig,
+    external_mouse: bool,
 ) {
     macro_rules! config {
         ($f:expr) => {
@@ -131,9 +159,20 @@ pub fn update_device(
     if let Err(err) = match state {
         DeviceState::Enabled => device.config_send_events_set_mode(SendEventsMode::ENABLED),
         DeviceState::Disabled => device.config_send_events_set_mode(SendEventsMode::DISABLED),
-        DeviceState::DisabledOnExternalMouse => {
+        DeviceState::DisabledOnExternalMouse
+            if device
+                .config_send_events_modes()
+                .contains(SendEventsMode::DISABLED_ON_EXTERNAL_MOUSE) =>
+        {
             device.config_send_events_set_mode(SendEventsMode::DISABLED_ON_EXTERNAL_MOUSE)
         }
+        // libinput doesn't support this mode for the device, so emulate it
+        DeviceState::DisabledOnExternalMouse if external_mouse => {
+            device.config_send_events_set_mode(SendEventsMode::DISABLED)
+        }
+        DeviceState::DisabledOnExternalMouse => {
+            device.config_send_events_set_mode(SendEventsMode::ENABLED)
+        }
     } {
         warn!(
             ?err,
@@ -152,7 +191,17 @@ pub fn update_device(
             config_set_error(device, "acceleration speed", accel.speed, err, is_default);
         }
     }
-    if let Some((matrix, is_default)) = config!(|x| x.calibration) {
+    // only absolute devices (touchscreens, tablets) can be calibrated
+    let calibration = config!(|x| x.calibration).filter(|_| device.config_calibration_has_matrix());
+    if let Some((mut matrix, is_default)) = calibration {
+        if !is_valid_calibration(&matrix) {
+            warn!(
+                ?matrix,
+                "Invalid calibration matrix for device {:?}, using identity.",
+                device.name(),
+            );
+            matrix = IDENTITY_MATRIX;
+        }
         if let Err(err) = device.config_calibration_set_matrix(matrix) {
             config_set_error(device, "calibration matrix", matrix, err, is_default);
         }
@@ -1,17 +1,24 @@
 // SPDX-License-Identifier: GPL-3.0-only
 
-use crate::shell::{focus::FocusDirection, grabs::ResizeEdge, Direction, ResizeDirection};
+use crate::shell::{focus::FocusDirection, grabs::ResizeEdge, Corner, Direction, ResizeDirection};
 use cosmic_comp_config::workspace::WorkspaceLayout;
-use serde::Deserialize;
+use serde::{
+    de::{self, value::MapAccessDeserializer, MapAccess, Visitor},
+    Deserialize, Deserializer,
+};
 use smithay::{
     backend::input::KeyState,
-    input::keyboard::{xkb::keysym_get_name, ModifiersState},
+    input::keyboard::{
+        xkb::{self, keysym_get_name},
+        ModifiersState,
+    },
+    utils::Transform,
 };
-use std::collections::HashMap;
+use std::{collections::HashMap, fmt, str::FromStr};
 
 use super::types::*;
 
-#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Deserialize)]
 pub enum KeyModifier {
     Ctrl,
     Alt,
@@ -47,6 +54,18 @@ impl Into<KeyModifiers> for ModifiersState {
     }
 }
 
+impl KeyModifier {
+    /// Whether this modifier is part of `modifiers`
+    pub fn is_held(&self, modifiers: &ModifiersState) -> bool {
+        match self {
+            KeyModifier::Ctrl => modifiers.ctrl,
+            KeyModifier::Alt => modifiers.alt,
+            KeyModifier::Shift => modifiers.shift,
+            KeyModifier::Super => modifiers.logo,
+        }
+    }
+}
+
 impl std::ops::AddAssign<KeyModifier> for KeyModifiers {
     fn add_assign(&mut self, rhs: KeyModifier) {
         match rhs {
@@ -83,17 +102,161 @@ impl Into<KeyModifiers> for KeyModifier {
 
 /// Describtion of a key combination that might be
 /// handled by the compositor.
-#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Hash)]
-#[serde(deny_unknown_fields)]
+///
+/// Configured either as a string like `"Super+Shift+q"` or as `(modifiers: [Super, Shift], key: "q")`.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub struct KeyPattern {
     /// What modifiers are expected to be pressed alongside the key
-    #[serde(deserialize_with = "deserialize_KeyModifiers")]
     pub modifiers: KeyModifiers,
     /// The actual key, that was pressed
-    #[serde(deserialize_with = "deserialize_Keysym", default)]
     pub key: Option<Keysym>,
 }
 
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct KeyPatternFields {
+    #[serde(deserialize_with = "deserialize_KeyModifiers")]
+    modifiers: KeyModifiers,
+    #[serde(deserialize_with = "deserialize_Keysym", default)]
+    key: Option<Keysym>,
+}
+
+impl FromStr for KeyPattern {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        // a trailing `++` binds the plus key itself
+        let (combo, plus) = match s.strip_suffix("++") {
+            Some(combo) => (combo, true),
+            None => (s, false),
+        };
+        let mut tokens = combo.split('+').map(str::trim).collect::<Vec<_>>();
+        if plus {
+            tokens.push("plus");
+        }
+
+        let mut modifiers = KeyModifiers::default();
+        let mut key = None;
+        for (i, token) in tokens.iter().enumerate() {
+            let modifier = match token.to_lowercase().as_str() {
+                "super" | "logo" | "meta" => Some(KeyModifier::Super),
+                "ctrl" | "control" => Some(KeyModifier::Ctrl),
+                "alt" => Some(KeyModifier::Alt),
+                "shift" => Some(KeyModifier::Shift),
+                _ => None,
+            };
+            match modifier {
+                Some(modifier) => modifiers += modifier,
+                None if i + 1 == tokens.len() && !token.is_empty() => {
+                    // bindings match the unshifted keysym, e.g. `q` instead of `Q`
+                    let name = if token.chars().count() == 1 {
+                        token.to_lowercase()
+                    } else {
+                        token.to_string()
+                    };
+                    key = Some(keysym_from_name(&name).ok_or_else(|| {
+                        format!("unknown key `{}` in key binding \"{}\"", token, s)
+                    })?);
+                }
+                None => {
+                    return Err(format!(
+                        "unknown modifier `{}` in key binding \"{}\"",
+                        token, s
+                    ))
+                }
+            }
+        }
+
+        Ok(KeyPattern { modifiers, key })
+    }
+}
+
+impl<'de> Deserialize<'de> for KeyPattern {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        struct KeyPatternVisitor;
+
+        impl<'de> Visitor<'de> for KeyPatternVisitor {
+            type Value = KeyPattern;
+
+            fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+                formatter.write_str(
+                    "a key combination like \"Super+Shift+q\" or (modifiers: [..], key: \"..\")",
+                )
+            }
+
+            fn visit_str<E: de::Error>(self, value: &str) -> Result<KeyPattern, E> {
+                value.parse().map_err(E::custom)
+            }
+
+            fn visit_map<A: MapAccess<'de>>(self, map: A) -> Result<KeyPattern, A::Error> {
+                let fields = KeyPatternFields::deserialize(MapAccessDeserializer::new(map))?;
+                Ok(KeyPattern {
+                    modifiers: fields.modifiers,
+                    key: fields.key,
+                })
+            }
+        }
+
+        deserializer.deserialize_any(KeyPatternVisitor)
+    }
+}
+
+/// Checks a key binding of the config, refusing duplicates and bindings that would swallow typed
+/// letters or digits.
+fn check_config_binding(
+    bindings: &HashMap<KeyPattern, Action>,
+    pattern: &KeyPattern,
+) -> Result<(), String> {
+    let typed = pattern.key.is_some_and(|key| {
+        char::from_u32(xkb::keysym_to_utf32(key)).is_some_and(char::is_alphanumeric)
+    });
+    if typed && pattern.modifiers == KeyModifiers::default() {
+        return Err(format!(
+            "key binding \"{}\" has no modifiers and would block typing",
+            pattern.to_string()
+        ));
+    }
+    if bindings.contains_key(pattern) {
+        return Err(format!("duplicate key binding \"{}\"", pattern.to_string()));
+    }
+    Ok(())
+}
+
+/// Deserializes the key bindings of the config.
+///
+/// Invalid bindings fail the whole config, so the error points at the line of the binding.
+pub fn deserialize_key_bindings<'de, D>(
+    deserializer: D,
+) -> Result<HashMap<KeyPattern, Action>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    struct KeyBindingsVisitor;
+
+    impl<'de> Visitor<'de> for KeyBindingsVisitor {
+        type Value = HashMap<KeyPattern, Action>;
+
+        fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+            formatter.write_str("a map of key bindings to actions")
+        }
+
+        fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Self::Value, A::Error> {
+            let mut bindings = HashMap::new();
+            while let Some(pattern) = map.next_key::<KeyPattern>()? {
+                // fail before parsing the action, so the error points at the binding
+                check_config_binding(&bindings, &pattern).map_err(de::Error::custom)?;
+                bindings.insert(pattern, map.next_value()?);
+            }
+            Ok(bindings)
+        }
+    }
+
+    deserializer.deserialize_map(KeyBindingsVisitor)
+}
+
 impl KeyPattern {
     pub fn new(modifiers: impl Into<KeyModifiers>, key: Option<Keysym>) -> KeyPattern {
         KeyPattern {
@@ -128,16 +291,52 @@ impl ToString for KeyPattern {
     }
 }
 
-#[derive(Debug, Deserialize, Clone, PartialEq, Eq)]
+#[derive(Debug, Deserialize, Clone, PartialEq)]
 pub enum Action {
     Terminate,
     Debug,
+    CycleLogLevel,
+    /// Log the outputs, workspaces, windows and seat focus, to attach to bug reports
+    DumpState,
+    ToggleShortcuts,
+    ToggleKeybindingsInhibit,
+    ReloadBackground,
+    ToggleNumlock,
+    /// Drive the pointer with the numeric keypad
+    ToggleMouseKeys,
+    /// Blank the output with the given connector name, or the active one
+    ToggleOutputPower(Option<String>),
+    /// Rotate the active output further by the given transform, e.g. `_90` cycles through all rotations
+    RotateOutput(#[serde(with = "TransformDef")] Transform),
+    /// Warm up the colors of all outputs on top of any client gamma ramps
+    ToggleNightLight,
+    /// Open new windows on the active output from now on
+    SetPrimaryOutput,
+    #[serde(skip)]
+    _MouseKeys(u32, KeyState),
     Close,
+    /// Disconnect the client of the focused window, for apps that don't react to `Close`
+    Kill,
 
     Workspace(u8),
     NextWorkspace,
     PreviousWorkspace,
     LastWorkspace,
+    /// Switch to the previously active workspace of the active output,
+    /// pressing it again returns to where it started.
+    /// `LastWorkspace` keeps switching to the last workspace by index.
+    WorkspaceBackAndForth,
+    /// Focus the previously focused window on any workspace or output,
+    /// pressing it again shortly after goes further back
+    FocusPrevWindow,
+    /// Focus the most recently used window with the given app_id,
+    /// cycling through all of them if one is focused already
+    FocusAppId(String),
+    /// Focus a window with the given app_id like `FocusAppId`, or run `command` if there is none
+    FocusOrSpawn {
+        app_id: String,
+        command: String,
+    },
     MoveToWorkspace(u8),
     MoveToNextWorkspace,
     MoveToPreviousWorkspace,
@@ -146,6 +345,8 @@ pub enum Action {
     SendToNextWorkspace,
     SendToPreviousWorkspace,
     SendToLastWorkspace,
+    /// Exchange the windows of two workspaces of the active output
+    SwapWorkspaces(u8, u8),
 
     NextOutput,
     PreviousOutput,
@@ -159,17 +360,40 @@ pub enum Action {
 
     ToggleOrientation,
     Orientation(crate::shell::layout::Orientation),
+    /// Split the focused container evenly
+    BalanceWindows,
+    /// Split every container of the workspace evenly
+    BalanceWorkspace,
+    /// Re-insert all tiled windows, as if they were opened one after another
+    ResetWorkspaceLayout,
 
     ToggleStacking,
+    /// Switch the active workspace between tiling and floating-only
+    #[serde(alias = "ToggleWorkspaceLayout")]
     ToggleTiling,
     ToggleWindowFloating,
     SwapWindow,
+    /// Hide the focused window in the scratchpad
+    MoveToScratchpad,
+    /// Show the most recent scratchpad window floating above the active workspace, or hide it again
+    ShowScratchpad,
 
     Resizing(ResizeDirection),
     #[serde(skip)]
     _ResizingInternal(ResizeDirection, ResizeEdge, KeyState),
+    /// Resize the focused window with the arrow keys, until Enter or Escape is pressed
+    EnterResizeMode,
+    #[serde(skip)]
+    _ResizeModeStep(ResizeDirection, ResizeEdge),
+    #[serde(skip)]
+    _ExitResizeMode(bool),
     Maximize,
+    AdjustOpacity(f32),
+    TileHalf(Direction),
+    TileQuarter(Corner),
     Spawn(String),
+    /// Change the display backlight by the given percentage, e.g. `Brightness(-5)`
+    Brightness(i32),
 }
 
 fn insert_binding(
@@ -296,4 +520,177 @@ pub fn add_default_bindings(
         output_next.iter().copied(),
         Action::MoveToNextOutput,
     );
+
+    insert_binding(
+        key_bindings,
+        KeyModifiers::default(),
+        std::iter::once(Keysym::XF86_AudioRaiseVolume),
+        Action::Spawn("amixer sset Master 5%+".into()),
+    );
+    insert_binding(
+        key_bindings,
+        KeyModifiers::default(),
+        std::iter::once(Keysym::XF86_AudioLowerVolume),
+        Action::Spawn("amixer sset Master 5%-".into()),
+    );
+    insert_binding(
+        key_bindings,
+        KeyModifiers::default(),
+        std::iter::once(Keysym::XF86_AudioMute),
+        Action::Spawn("amixer sset Master toggle".into()),
+    );
+    insert_binding(
+        key_bindings,
+        KeyModifiers::default(),
+        std::iter::once(Keysym::XF86_MonBrightnessUp),
+        Action::Brightness(5),
+    );
+    insert_binding(
+        key_bindings,
+        KeyModifiers::default(),
+        std::iter::once(Keysym::XF86_MonBrightnessDown),
+        Action::Brightness(-5),
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pattern(logo: bool, ctrl: bool, alt: bool, shift: bool, key: Keysym) -> KeyPattern {
+        KeyPattern::new(
+            KeyModifiers {
+                ctrl,
+                alt,
+                shift,
+                logo,
+            },
+            Some(key),
+        )
+    }
+
+    fn parse_bindings(
+        config: &str,
+    ) -> Result<HashMap<KeyPattern, Action>, ron::error::SpannedError> {
+        let mut deserializer = ron::Deserializer::from_str(config).unwrap();
+        deserialize_key_bindings(&mut deserializer).map_err(|err| deserializer.span_error(err))
+    }
+
+    #[test]
+    fn parses_combinations() {
+        assert_eq!(
+            "Super+Shift+q".parse::<KeyPattern>().unwrap(),
+            pattern(true, false, false, true, Keysym::q)
+        );
+        assert_eq!(
+            "Ctrl+Alt+F2".parse::<KeyPattern>().unwrap(),
+            pattern(false, true, true, false, Keysym::F2)
+        );
+        assert_eq!(
+            "XF86AudioMute".parse::<KeyPattern>().unwrap(),
+            KeyPattern::new(KeyModifiers::default(), Some(Keysym::XF86_AudioMute))
+        );
+        assert_eq!(
+            "Super++".parse::<KeyPattern>().unwrap(),
+            pattern(true, false, false, false, Keysym::plus)
+        );
+        assert_eq!(
+            "Super".parse::<KeyPattern>().unwrap(),
+            KeyPattern::new(
+                KeyModifiers {
+                    logo: true,
+                    ..Default::default()
+                },
+                None
+            )
+        );
+    }
+
+    #[test]
+    fn modifiers_and_keys_ignore_case() {
+        assert_eq!(
+            "super+SHIFT+Q".parse::<KeyPattern>().unwrap(),
+            pattern(true, false, false, true, Keysym::q)
+        );
+        assert_eq!(
+            "control+alt+return".parse::<KeyPattern>().unwrap(),
+            pattern(false, true, true, false, Keysym::Return)
+        );
+    }
+
+    #[test]
+    fn refuses_unknown_tokens() {
+        assert!("Super+NoSuchKey".parse::<KeyPattern>().is_err());
+        assert!("Hyper+q".parse::<KeyPattern>().is_err());
+        assert!("Super+q+Shift".parse::<KeyPattern>().is_err());
+        assert!("Super+".parse::<KeyPattern>().is_err());
+    }
+
+    #[test]
+    fn accepts_struct_format() {
+        let bindings = parse_bindings(
+            r#"{
+                (modifiers: [Super, Shift], key: "q"): Close,
+                "Super+Return": Terminate,
+            }"#,
+        )
+        .unwrap();
+        assert_eq!(
+            bindings.get(&pattern(true, false, false, true, Keysym::q)),
+            Some(&Action::Close)
+        );
+        assert_eq!(
+            bindings.get(&pattern(true, false, false, false, Keysym::Return)),
+            Some(&Action::Terminate)
+        );
+    }
+
+    #[test]
+    fn refuses_duplicates() {
+        let err = parse_bindings(
+            r#"{
+                "Super+q": Close,
+                "super+Q": Terminate,
+            }"#,
+        )
+        .unwrap_err();
+        assert_eq!(err.position.line, 3);
+        assert!(err
+            .to_string()
+            .contains("duplicate key binding \"Super+q\""));
+    }
+
+    #[test]
+    fn refuses_unmodified_letters_and_digits() {
+        for (config, binding) in [
+            (r#"{ "F1": Terminate, "q": Close }"#, "q"),
+            (r#"{ "F1": Terminate, "7": Close }"#, "7"),
+        ] {
+            let err = parse_bindings(config).unwrap_err();
+            assert!(err
+                .to_string()
+                .contains(&format!("key binding \"{}\" has no modifiers", binding)));
+        }
+
+        // function keys and shifted digits are fine
+        let bindings = parse_bindings(r#"{ "F1": Terminate, "Shift+7": Close }"#).unwrap();
+        assert_eq!(bindings.len(), 2);
+    }
+
+    #[test]
+    fn reports_line_of_unknown_token() {
+        let err = parse_bindings(
+            r#"{
+                "Super+q": Close,
+                "Super+NoSuchKey": Terminate,
+            }"#,
+        )
+        .unwrap_err();
+        assert_eq!(err.position.line, 3);
+        assert!(err.to_string().contains("unknown key `NoSuchKey`"));
+
+        let err = parse_bindings(r#"{ "Hyper+q": Close }"#).unwrap_err();
+        assert_eq!(err.position.line, 1);
+        assert!(err.to_string().contains("unknown modifier `Hyper`"));
+    }
 }
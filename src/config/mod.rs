@@ -3,6 +3,7 @@
 use crate::{
     shell::Shell,
     state::{BackendData, State},
+    utils::prelude::{Global, Local, OutputExt},
     wayland::protocols::output_configuration::OutputConfigurationState,
 };
 use cosmic_config::ConfigGet;
@@ -19,9 +20,9 @@ pub use smithay::{
             TapButtonMap,
         },
     },
-    utils::{Logical, Physical, Point, Size, Transform},
+    utils::{Logical, Physical, Point, Rectangle, Size, Transform},
 };
-use std::{cell::RefCell, collections::HashMap, fs::OpenOptions, path::PathBuf};
+use std::{cell::RefCell, collections::HashMap, fs::OpenOptions, path::PathBuf, time::Duration};
 use tracing::{debug, error, info, warn};
 
 mod input_config;
@@ -29,11 +30,13 @@ mod key_bindings;
 pub use key_bindings::{Action, KeyModifier, KeyModifiers, KeyPattern};
 mod types;
 pub use self::types::*;
+mod window_rules;
 use cosmic_comp_config::{
     input::InputConfig,
     workspace::{WorkspaceConfig, WorkspaceLayout},
     XkbConfig,
 };
+pub use window_rules::{window_rule, WindowRule};
 
 #[derive(Debug)]
 pub struct Config {
@@ -49,13 +52,406 @@ pub struct Config {
 
 #[derive(Debug, Deserialize)]
 pub struct StaticConfig {
+    #[serde(deserialize_with = "key_bindings::deserialize_key_bindings")]
     pub key_bindings: HashMap<key_bindings::KeyPattern, key_bindings::Action>,
     pub tiling_enabled: bool,
+    #[serde(default)]
+    pub latency: RenderLatency,
+    #[serde(default = "default_enabled")]
+    pub persist_window_positions: bool,
+    #[serde(default)]
+    pub clipboard: ClipboardConfig,
+    /// Command to exec, if the compositor fails unrecoverably
+    #[serde(default)]
+    pub fallback_compositor: Option<String>,
+    #[serde(default)]
+    pub window_rules: Vec<WindowRule>,
+    /// Color of outputs without a background surface, as rgb in the range 0.0-1.0
+    #[serde(default)]
+    pub background_color: Option<[f32; 3]>,
+    /// Image stretched across outputs without a background surface
+    #[serde(default)]
+    pub background_image: Option<PathBuf>,
+    /// Wallpaper drawn below the background layer, takes precedence over
+    /// `background_color` and `background_image`
+    #[serde(default)]
+    pub background: BackgroundConfig,
+    /// Brightness of windows not focused by any seat, clamped to 0.1-1.0
+    #[serde(default)]
+    pub dim_unfocused: Option<f32>,
+    #[serde(default = "default_enabled")]
+    pub animations: bool,
+    #[serde(default)]
+    pub animation_durations: AnimationDurations,
+    /// Move floating dialogs along when their parent window is moved
+    #[serde(default = "default_enabled")]
+    pub dialogs_follow_parent: bool,
+    /// Enable num lock, when the compositor starts
+    #[serde(default)]
+    pub numlock_on_startup: bool,
+    /// Raise floating windows when they are clicked
+    #[serde(default = "default_enabled")]
+    pub raise_on_click: bool,
+    /// Milliseconds to wait for a client to acknowledge a maximize, fullscreen or resize
+    #[serde(default = "default_configure_timeout")]
+    pub configure_timeout: u64,
+    #[serde(default)]
+    pub accessibility: AccessibilityConfig,
+    #[serde(default)]
+    pub workspace_focus: WorkspaceFocus,
+    #[serde(default)]
+    pub hot_corners: HotCornersConfig,
+    /// Shell running `Action::Spawn` commands with `-c`,
+    /// `None` splits the command into words and executes it directly
+    #[serde(default = "default_spawn_shell")]
+    pub spawn_shell: Option<String>,
+    /// Let touchscreens move the pointer and click with the left button, instead of sending touch events
+    #[serde(default)]
+    pub touch_as_pointer: bool,
+    #[serde(default)]
+    pub data_device_focus: DataDeviceFocus,
+    /// Actions triggered by tapping a modifier on its own, e.g. `Super` to open a launcher
+    #[serde(default)]
+    pub tap_bindings: HashMap<KeyModifier, Action>,
+    /// Holding this modifier passes keys to the focused window instead of triggering key bindings,
+    /// e.g. to send `Super+T` to a virtual machine as `Super+Alt+T` with `Some(Alt)`.
+    /// The window sees the modifier as well and bindings including it can no longer be used.
+    #[serde(default)]
+    pub bypass_modifier: Option<KeyModifier>,
+    /// Scroll the window below the pointer, even if the pointer didn't move since it got there
+    #[serde(default)]
+    pub scroll_under_pointer: bool,
+    /// Milliseconds after the last wheel scroll to send an axis stop, like touchpads do when
+    /// lifting the fingers. Helps clients with kinetic scrolling, `None` never sends one.
+    #[serde(default)]
+    pub wheel_stop_delay: Option<u64>,
+    /// Export `WAYLAND_DISPLAY`, `DISPLAY` and `XDG_CURRENT_DESKTOP` to the systemd user
+    /// and D-Bus activation environments, disable it for nested sessions
+    #[serde(default = "default_enabled")]
+    pub import_environment: bool,
+    #[serde(default)]
+    pub mouse_warping: MouseWarping,
+    /// Let `Focus` actions wrap around from the outermost output to the one on the opposite side
+    #[serde(default)]
+    pub focus_wraps_outputs: bool,
+    /// Drop the gaps around a tiled window, when it is the only one on its workspace
+    #[serde(default)]
+    pub smart_gaps: bool,
+    /// Hide the focus indicator of a tiled window, when it is the only one on its workspace
+    #[serde(default)]
+    pub smart_borders: bool,
+    /// Evdev codes of the (remapped) buttons changing the keyboard focus on click,
+    /// e.g. `Some([272])` for the left button only. `None` lets any button change the focus.
+    #[serde(default)]
+    pub focus_buttons: Option<Vec<u32>>,
+    /// Hide the cursor while a touchscreen drives the pointer, until a pointer device moves it again
+    #[serde(default = "default_enabled")]
+    pub hide_pointer_on_touch: bool,
+    /// Upper limit for how often a single window is captured per second, e.g. for thumbnails.
+    /// `None` captures on every commit.
+    #[serde(default)]
+    pub toplevel_capture_fps: Option<u32>,
+    /// Briefly show brightness changes and workspace switches on the active output
+    #[serde(default = "default_enabled")]
+    pub osd: bool,
+    #[serde(default)]
+    pub fullscreen_focus_priority: FullscreenFocusPriority,
+    /// Commits per second above which windows on hidden workspaces only get
+    /// a frame callback every few seconds, `None` never throttles them
+    #[serde(default)]
+    pub commit_throttle: Option<u32>,
+}
+
+/// Length of the individual animations in milliseconds, 0 disables an animation.
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(default)]
+pub struct AnimationDurations {
+    pub window_open: u64,
+    pub window_close: u64,
+    pub workspace_switch: u64,
+}
+
+impl Default for AnimationDurations {
+    fn default() -> Self {
+        AnimationDurations {
+            window_open: 150,
+            window_close: 150,
+            workspace_switch: 200,
+        }
+    }
+}
+
+/// Keyboard accessibility features, both can be suspended by pressing the two shift keys together.
+#[derive(Debug, Default, Deserialize, Clone, PartialEq)]
+pub struct AccessibilityConfig {
+    /// A tapped modifier applies to the next key, tapping it twice locks it until tapped again
+    #[serde(default)]
+    pub sticky_keys: bool,
+    /// Milliseconds a key has to be held down, before it registers
+    #[serde(default)]
+    pub slow_keys: Option<u64>,
+    /// Allow toggling mouse keys with Shift+NumLock
+    #[serde(default)]
+    pub mouse_keys: bool,
+}
+
+impl AccessibilityConfig {
+    /// Whether any feature suspended by the shift keys is configured
+    pub fn is_enabled(&self) -> bool {
+        self.sticky_keys || self.slow_keys.is_some()
+    }
+}
+
+/// Actions triggered by moving the pointer into a corner or against an edge of an output.
+#[derive(Debug, Deserialize, Clone, PartialEq)]
+pub struct HotCornersConfig {
+    #[serde(default)]
+    pub enabled: bool,
+    /// Enable or disable hot corners by connector name, overriding `enabled`
+    #[serde(default)]
+    pub outputs: HashMap<String, bool>,
+    /// Milliseconds the pointer has to rest in a corner
+    #[serde(default = "default_hot_corner_delay")]
+    pub delay: u64,
+    /// Distance the pointer has to be pushed against an edge or corner
+    #[serde(default = "default_hot_corner_pressure")]
+    pub pressure: f64,
+    #[serde(default)]
+    pub actions: HashMap<HotCorner, Action>,
+}
+
+impl Default for HotCornersConfig {
+    fn default() -> Self {
+        HotCornersConfig {
+            enabled: false,
+            outputs: HashMap::new(),
+            delay: default_hot_corner_delay(),
+            pressure: default_hot_corner_pressure(),
+            actions: HashMap::new(),
+        }
+    }
+}
+
+impl HotCornersConfig {
+    pub fn enabled_for(&self, output: &str) -> bool {
+        self.outputs.get(output).copied().unwrap_or(self.enabled)
+    }
+}
+
+fn default_hot_corner_delay() -> u64 {
+    250
+}
+
+fn default_hot_corner_pressure() -> f64 {
+    100.0
+}
+
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum HotCorner {
+    TopLeft,
+    TopRight,
+    BottomLeft,
+    BottomRight,
+    Top,
+    Bottom,
+    Left,
+    Right,
+}
+
+impl HotCorner {
+    /// Distance `delta` pushes the pointer further into the corner or edge
+    pub fn pressure(&self, delta: Point<f64, Global>) -> f64 {
+        let (x, y) = match self {
+            HotCorner::TopLeft => (-delta.x, -delta.y),
+            HotCorner::TopRight => (delta.x, -delta.y),
+            HotCorner::BottomLeft => (-delta.x, delta.y),
+            HotCorner::BottomRight => (delta.x, delta.y),
+            HotCorner::Top => (0.0, -delta.y),
+            HotCorner::Bottom => (0.0, delta.y),
+            HotCorner::Left => (-delta.x, 0.0),
+            HotCorner::Right => (delta.x, 0.0),
+        };
+        x.max(0.0) + y.max(0.0)
+    }
+
+    pub fn is_edge(&self) -> bool {
+        matches!(
+            self,
+            HotCorner::Top | HotCorner::Bottom | HotCorner::Left | HotCorner::Right
+        )
+    }
+}
+
+#[derive(Debug, Default, Deserialize, Clone, PartialEq)]
+pub struct BackgroundConfig {
+    /// Background of outputs without an entry in `outputs`
+    #[serde(default)]
+    pub all: Option<Background>,
+    /// Backgrounds by connector name
+    #[serde(default)]
+    pub outputs: HashMap<String, Background>,
+}
+
+impl BackgroundConfig {
+    pub fn for_output(&self, name: &str) -> Option<&Background> {
+        self.outputs.get(name).or(self.all.as_ref())
+    }
+
+    pub fn images(&self) -> impl Iterator<Item = &PathBuf> {
+        self.all
+            .iter()
+            .chain(self.outputs.values())
+            .filter_map(|background| match background {
+                Background::Image { path, .. } => Some(path),
+                Background::Color(_) => None,
+            })
+    }
+}
+
+#[derive(Debug, Deserialize, Clone, PartialEq)]
+pub enum Background {
+    /// Solid color, as rgb in the range 0.0-1.0
+    Color([f32; 3]),
+    /// Png or jpeg image
+    Image {
+        path: PathBuf,
+        #[serde(default)]
+        mode: BackgroundMode,
+    },
+}
+
+/// How an image is fitted to the output.
+#[derive(Debug, Default, Deserialize, Clone, Copy, PartialEq, Eq)]
+pub enum BackgroundMode {
+    /// Scale to cover the output, cropping the overflowing edges.
+    #[default]
+    Fill,
+    /// Scale to fit into the output, keeping the aspect ratio.
+    Fit,
+    /// Scale to the size of the output, ignoring the aspect ratio.
+    Stretch,
+    /// Repeat the unscaled image from the top left corner.
+    Tile,
+}
+
+/// Which window gets focused, when switching to a workspace.
+#[derive(Debug, Default, Deserialize, Clone, Copy, PartialEq, Eq)]
+pub enum WorkspaceFocus {
+    /// The window, that was focused when leaving the workspace.
+    #[default]
+    LastFocused,
+    /// The window below the pointer, if any.
+    UnderCursor,
+    /// Nothing, until a window is clicked or focused explicitly.
+    None,
+}
+
+/// When to move the pointer along with the keyboard focus, after it was changed with a key binding.
+#[derive(Debug, Default, Deserialize, Clone, Copy, PartialEq, Eq)]
+pub enum MouseWarping {
+    /// Never move the pointer.
+    #[default]
+    Disabled,
+    /// Move the pointer to the center of the output, if the focus moved to another output.
+    Output,
+    /// Move the pointer to the center of the focused window, unless it is already inside.
+    Window,
+}
+
+/// Which focus the clipboard and primary selection of a seat follow.
+#[derive(Debug, Default, Deserialize, Clone, Copy, PartialEq, Eq)]
+pub enum DataDeviceFocus {
+    /// The client with keyboard focus.
+    #[default]
+    Keyboard,
+    /// The client below the pointer.
+    Pointer,
+    /// Whichever of both changed last.
+    Both,
+}
+
+/// Who keeps the keyboard focus, while a fullscreen window is shown and a
+/// layer surface with on-demand keyboard interactivity (e.g. a notification) wants it.
+///
+/// Layer surfaces with exclusive keyboard interactivity always take the focus.
+#[derive(Debug, Default, Deserialize, Clone, Copy, PartialEq, Eq)]
+pub enum FullscreenFocusPriority {
+    /// The layer surface gets focused when it is mapped or clicked.
+    #[default]
+    Layer,
+    /// The fullscreen window keeps the focus, the layer surface only gets pointer input.
+    Fullscreen,
+}
+
+/// How aggressively frames are delayed towards the next vblank.
+#[derive(Debug, Default, Deserialize, Clone, Copy, PartialEq, Eq)]
+pub enum RenderLatency {
+    /// Leave plenty of headroom based on the slowest recent frames.
+    #[default]
+    Safe,
+    /// Start rendering as late as the average recent frame allows.
+    Low,
+}
+
+/// Keeps copied data around after the client owning the selection exits.
+#[derive(Debug, Deserialize, Clone, PartialEq)]
+pub struct ClipboardConfig {
+    #[serde(default = "default_enabled")]
+    pub persist: bool,
+    /// Also cache the primary selection
+    #[serde(default = "default_enabled")]
+    pub persist_primary: bool,
+    /// Maximum number of bytes cached per mime type
+    #[serde(default = "default_clipboard_max_size")]
+    pub max_size: usize,
+    /// Mime types to cache, a trailing `*` matches any suffix
+    #[serde(default = "default_clipboard_mime_types")]
+    pub mime_types: Vec<String>,
+}
+
+impl Default for ClipboardConfig {
+    fn default() -> Self {
+        ClipboardConfig {
+            persist: true,
+            persist_primary: true,
+            max_size: default_clipboard_max_size(),
+            mime_types: default_clipboard_mime_types(),
+        }
+    }
+}
+
+impl ClipboardConfig {
+    pub fn caches(&self, mime_type: &str) -> bool {
+        self.mime_types
+            .iter()
+            .any(|pattern| match pattern.strip_suffix('*') {
+                Some(prefix) => mime_type.starts_with(prefix),
+                None => mime_type == pattern,
+            })
+    }
+}
+
+/// Default of `InputConfig::disable_while_typing_timeout` in milliseconds
+const DEFAULT_TYPING_TIMEOUT: u32 = 200;
+
+fn default_clipboard_max_size() -> usize {
+    4 * 1024 * 1024
+}
+
+fn default_clipboard_mime_types() -> Vec<String> {
+    vec![
+        "text/*".into(),
+        "UTF8_STRING".into(),
+        "STRING".into(),
+        "TEXT".into(),
+        "image/png".into(),
+    ]
 }
 
 #[derive(Debug)]
 pub struct DynamicConfig {
     outputs: (Option<PathBuf>, OutputsConfig),
+    windows: (Option<PathBuf>, WindowPositionsConfig),
 }
 
 #[derive(Debug, Deserialize, Serialize)]
@@ -81,13 +477,86 @@ impl From<Output> for OutputInfo {
     }
 }
 
+/// Floating window geometries, keyed by the output layout they were recorded in and the app_id.
+#[derive(Debug, Default, Deserialize, Serialize)]
+pub struct WindowPositionsConfig {
+    pub positions: HashMap<Vec<OutputLayout>, HashMap<String, Vec<WindowPosition>>>,
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct OutputLayout {
+    pub output: OutputInfo,
+    pub position: (i32, i32),
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq, Eq)]
+pub struct WindowPosition {
+    pub output: String,
+    pub position: (i32, i32),
+    pub size: (i32, i32),
+}
+
+impl WindowPosition {
+    pub fn geometry(&self) -> Rectangle<i32, Local> {
+        Rectangle::from_loc_and_size(self.position, self.size)
+    }
+}
+
+impl StaticConfig {
+    /// Duration of the animation selected by `animation`, zero if animations are disabled.
+    pub fn animation_duration(&self, animation: impl Fn(&AnimationDurations) -> u64) -> Duration {
+        if self.animations {
+            Duration::from_millis(animation(&self.animation_durations))
+        } else {
+            Duration::ZERO
+        }
+    }
+
+    /// Solid background color of the output named `output`, if any.
+    pub fn background_color_for(&self, output: &str) -> Option<[f32; 3]> {
+        match self.background.for_output(output) {
+            Some(Background::Color(color)) => Some(*color),
+            Some(Background::Image { .. }) => None,
+            None => self.background_color,
+        }
+    }
+
+    /// Wallpaper of the output named `output` and how to fit it, if any.
+    pub fn background_image_for(&self, output: &str) -> Option<(&PathBuf, BackgroundMode)> {
+        match self.background.for_output(output) {
+            Some(Background::Image { path, mode }) => Some((path, *mode)),
+            Some(Background::Color(_)) => None,
+            None => self
+                .background_image
+                .as_ref()
+                .map(|path| (path, BackgroundMode::Stretch)),
+        }
+    }
+
+    /// All wallpapers, that might be drawn.
+    pub fn background_images(&self) -> impl Iterator<Item = &PathBuf> {
+        self.background.images().chain(self.background_image.iter())
+    }
+}
+
 fn default_enabled() -> bool {
     true
 }
 
+fn default_configure_timeout() -> u64 {
+    1000
+}
+
+fn default_spawn_shell() -> Option<String> {
+    Some("/bin/sh".into())
+}
+
 #[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
 pub struct OutputConfig {
     pub mode: ((i32, i32), Option<u32>),
+    /// X11 style modeline overriding `mode`, for outputs not advertising the mode they support
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub modeline: Option<String>,
     pub vrr: bool,
     pub scale: f64,
     #[serde(with = "TransformDef")]
@@ -97,18 +566,26 @@ pub struct OutputConfig {
     pub enabled: bool,
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub max_bpc: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub background_color: Option<[f32; 3]>,
+    /// Output new windows open on, unless something else decides their placement
+    #[serde(default)]
+    pub primary: bool,
 }
 
 impl Default for OutputConfig {
     fn default() -> OutputConfig {
         OutputConfig {
             mode: ((0, 0), None),
+            modeline: None,
             vrr: false,
             scale: 1.0,
             transform: Transform::Normal,
             position: (0, 0),
             enabled: true,
             max_bpc: None,
+            background_color: None,
+            primary: false,
         }
     }
 }
@@ -179,8 +656,16 @@ impl Config {
             if path.exists() {
                 info!("Using config at {}", path.display());
                 let mut config: StaticConfig =
-                    ron::de::from_reader(OpenOptions::new().read(true).open(path).unwrap())
-                        .expect("Malformed config file");
+                    ron::de::from_reader(OpenOptions::new().read(true).open(&path).unwrap())
+                        .unwrap_or_else(|err| {
+                            panic!(
+                                "Malformed config file {} at line {}, column {}: {}",
+                                path.display(),
+                                err.position.line,
+                                err.position.col,
+                                err.code
+                            )
+                        });
 
                 key_bindings::add_default_bindings(&mut config.key_bindings, workspace_layout);
 
@@ -191,6 +676,42 @@ impl Config {
         StaticConfig {
             key_bindings: HashMap::new(),
             tiling_enabled: false,
+            latency: RenderLatency::default(),
+            persist_window_positions: true,
+            clipboard: ClipboardConfig::default(),
+            fallback_compositor: None,
+            window_rules: Vec::new(),
+            background_color: None,
+            background_image: None,
+            background: BackgroundConfig::default(),
+            dim_unfocused: None,
+            animations: true,
+            animation_durations: AnimationDurations::default(),
+            dialogs_follow_parent: true,
+            numlock_on_startup: false,
+            raise_on_click: true,
+            configure_timeout: default_configure_timeout(),
+            accessibility: AccessibilityConfig::default(),
+            workspace_focus: WorkspaceFocus::default(),
+            hot_corners: HotCornersConfig::default(),
+            spawn_shell: default_spawn_shell(),
+            touch_as_pointer: false,
+            data_device_focus: DataDeviceFocus::default(),
+            tap_bindings: HashMap::new(),
+            bypass_modifier: None,
+            scroll_under_pointer: false,
+            wheel_stop_delay: None,
+            import_environment: true,
+            mouse_warping: MouseWarping::default(),
+            focus_wraps_outputs: false,
+            smart_gaps: false,
+            smart_borders: false,
+            focus_buttons: None,
+            hide_pointer_on_touch: true,
+            toplevel_capture_fps: None,
+            osd: true,
+            fullscreen_focus_priority: FullscreenFocusPriority::default(),
+            commit_throttle: None,
         }
     }
 
@@ -198,10 +719,32 @@ impl Config {
         let output_path =
             xdg.and_then(|base| base.place_state_file("cosmic-comp/outputs.ron").ok());
         let outputs = Self::load_outputs(&output_path);
+        let windows_path =
+            xdg.and_then(|base| base.place_state_file("cosmic-comp/windows.ron").ok());
+        let windows = Self::load_window_positions(&windows_path);
 
         DynamicConfig {
             outputs: (output_path, outputs),
+            windows: (windows_path, windows),
+        }
+    }
+
+    fn load_window_positions(path: &Option<PathBuf>) -> WindowPositionsConfig {
+        if let Some(path) = path.as_ref() {
+            if path.exists() {
+                match ron::de::from_reader(OpenOptions::new().read(true).open(path).unwrap()) {
+                    Ok(config) => return config,
+                    Err(err) => {
+                        warn!(?err, "Failed to read window positions, resetting..");
+                        if let Err(err) = std::fs::remove_file(path) {
+                            error!(?err, "Failed to remove window positions.");
+                        }
+                    }
+                };
+            }
         }
+
+        WindowPositionsConfig::default()
     }
 
     fn load_outputs(path: &Option<PathBuf>) -> OutputsConfig {
@@ -350,6 +893,71 @@ impl Config {
             output_state.update();
             self.write_outputs(output_state.outputs());
         }
+
+        self.restore_window_positions(shell);
+    }
+
+    fn output_layout(shell: &Shell) -> Vec<OutputLayout> {
+        let mut layout = shell
+            .outputs()
+            .map(|output| OutputLayout {
+                output: output.clone().into(),
+                position: (output.geometry().loc.x, output.geometry().loc.y),
+            })
+            .collect::<Vec<_>>();
+        layout.sort();
+        layout
+    }
+
+    /// Remember the floating window positions for the current output layout
+    pub fn store_window_positions(&mut self, shell: &Shell) {
+        if !self.static_conf.persist_window_positions {
+            return;
+        }
+
+        let layout = Self::output_layout(shell);
+        if layout.is_empty() {
+            return;
+        }
+        let positions = shell.floating_window_positions();
+        self.dynamic_conf
+            .windows_mut()
+            .positions
+            .insert(layout, positions);
+    }
+
+    /// Move floating windows back to where they were, when we have seen the current output layout before
+    pub fn restore_window_positions(&self, shell: &mut Shell) {
+        if !self.static_conf.persist_window_positions {
+            return;
+        }
+
+        let layout = Self::output_layout(shell);
+        if let Some(positions) = self.dynamic_conf.windows().positions.get(&layout) {
+            shell.restore_floating_window_positions(positions);
+        }
+    }
+
+    /// Lookup a previously stored position for a newly mapped window
+    pub fn window_position(
+        &self,
+        shell: &Shell,
+        app_id: &str,
+        output: &Output,
+    ) -> Option<Rectangle<i32, Local>> {
+        if !self.static_conf.persist_window_positions {
+            return None;
+        }
+
+        let layout = Self::output_layout(shell);
+        self.dynamic_conf
+            .windows()
+            .positions
+            .get(&layout)?
+            .get(app_id)?
+            .iter()
+            .find(|pos| pos.output == output.name())
+            .map(WindowPosition::geometry)
     }
 
     pub fn write_outputs(
@@ -381,9 +989,13 @@ impl Config {
         self.xkb.clone()
     }
 
-    pub fn read_device(&self, device: &mut InputDevice) {
+    pub fn read_device(&self, device: &mut InputDevice, external_mouse: bool) {
         let (device_config, default_config) = self.get_device_config(device);
-        input_config::update_device(device, device_config, default_config);
+        input_config::update_device(device, device_config, default_config, external_mouse);
+    }
+
+    pub fn is_external_mouse(device: &InputDevice) -> bool {
+        input_config::is_external_mouse(device)
     }
 
     pub fn scroll_factor(&self, device: &InputDevice) -> f64 {
@@ -394,6 +1006,37 @@ impl Config {
         .map_or(1.0, |x| x.0)
     }
 
+    pub fn map_button(&self, device: &InputDevice, button: u32) -> u32 {
+        let (device_config, default_config) = self.get_device_config(device);
+        input_config::get_config(device_config, default_config, |x| {
+            x.button_map.as_ref()?.get(&button).copied()
+        })
+        .map_or(button, |x| x.0)
+    }
+
+    /// How long input of the touchpad `device` is ignored after a key press, if at all
+    pub fn typing_timeout(&self, device: &InputDevice) -> Option<Duration> {
+        if device.config_tap_finger_count() == 0 {
+            return None;
+        }
+        let (device_config, default_config) = self.get_device_config(device);
+        let enabled =
+            input_config::get_config(device_config, default_config, |x| x.disable_while_typing)
+                .map_or(true, |x| x.0);
+        let timeout = input_config::get_config(device_config, default_config, |x| {
+            x.disable_while_typing_timeout
+        })
+        .map_or(DEFAULT_TYPING_TIMEOUT, |x| x.0);
+        enabled.then(|| Duration::from_millis(timeout as u64))
+    }
+
+    /// Connector name of the output `device` is mapped to, if any
+    pub fn map_to_output(&self, device: &InputDevice) -> Option<String> {
+        let (device_config, default_config) = self.get_device_config(device);
+        input_config::get_config(device_config, default_config, |x| x.map_to_output.clone())
+            .map(|x| x.0)
+    }
+
     fn get_device_config(&self, device: &InputDevice) -> (Option<&InputConfig>, &InputConfig) {
         let default_config = if device.config_tap_finger_count() > 0 {
             &self.input_touchpad
@@ -450,6 +1093,14 @@ impl DynamicConfig {
     pub fn outputs_mut<'a>(&'a mut self) -> PersistenceGuard<'a, OutputsConfig> {
         PersistenceGuard(self.outputs.0.clone(), &mut self.outputs.1)
     }
+
+    pub fn windows(&self) -> &WindowPositionsConfig {
+        &self.windows.1
+    }
+
+    pub fn windows_mut<'a>(&'a mut self) -> PersistenceGuard<'a, WindowPositionsConfig> {
+        PersistenceGuard(self.windows.0.clone(), &mut self.windows.1)
+    }
 }
 
 fn get_config<T: Default + serde::de::DeserializeOwned>(
@@ -464,8 +1115,9 @@ fn get_config<T: Default + serde::de::DeserializeOwned>(
 
 fn update_input(state: &mut State) {
     if let BackendData::Kms(ref mut kms_state) = &mut state.backend {
+        let external_mouse = kms_state.has_external_mouse();
         for device in kms_state.input_devices.values_mut() {
-            state.common.config.read_device(device);
+            state.common.config.read_device(device, external_mouse);
         }
     }
 }
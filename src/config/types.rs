@@ -65,23 +65,31 @@ where
 
     let name = String::deserialize(deserializer)?;
     //let name = format!("KEY_{}", code);
-    match xkb::keysym_from_name(&name, xkb::KEYSYM_NO_FLAGS) {
+    match keysym_from_name(&name) {
+        Some(x) => Ok(Some(x)),
+        None => Err(<D::Error as Error>::invalid_value(
+            Unexpected::Str(&name),
+            &"One of the keysym names of xkbcommon.h without the 'KEY_' prefix",
+        )),
+    }
+}
+
+/// Looks up a keysym by its name in xkbcommon.h, falling back to a case insensitive match
+pub fn keysym_from_name(name: &str) -> Option<Keysym> {
+    match xkb::keysym_from_name(name, xkb::KEYSYM_NO_FLAGS) {
         x if x.raw() == NO_SYMBOL => {
-            match xkb::keysym_from_name(&name, xkb::KEYSYM_CASE_INSENSITIVE) {
-                x if x.raw() == NO_SYMBOL => Err(<D::Error as Error>::invalid_value(
-                    Unexpected::Str(&name),
-                    &"One of the keysym names of xkbcommon.h without the 'KEY_' prefix",
-                )),
+            match xkb::keysym_from_name(name, xkb::KEYSYM_CASE_INSENSITIVE) {
+                x if x.raw() == NO_SYMBOL => None,
                 x => {
                     warn!(
                         "Key-Binding '{}' only matched case insensitive for {:?}",
                         name,
                         xkb::keysym_get_name(x)
                     );
-                    Ok(Some(x))
+                    Some(x)
                 }
             }
         }
-        x => Ok(Some(x)),
+        x => Some(x),
     }
 }
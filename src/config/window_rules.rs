@@ -0,0 +1,61 @@
+// SPDX-License-Identifier: GPL-3.0-only
+
+use regex::Regex;
+use serde::{Deserialize, Deserializer};
+
+use crate::shell::CosmicSurface;
+
+/// Effects applied to every window matching all given patterns.
+///
+/// Patterns are regular expressions that have to match the whole `app_id` or title.
+/// If multiple rules match, later rules take precedence.
+#[derive(Debug, Clone, Deserialize)]
+pub struct WindowRule {
+    #[serde(default, deserialize_with = "deserialize_pattern")]
+    pub app_id: Option<Regex>,
+    #[serde(default, deserialize_with = "deserialize_pattern")]
+    pub title: Option<Regex>,
+    /// Forward all key bindings to the window while it is focused
+    #[serde(default)]
+    pub inhibit_keybindings: Option<bool>,
+    /// Initial opacity of the window, clamped to 0.1-1.0
+    #[serde(default)]
+    pub opacity: Option<f32>,
+    /// Let windows launched from the focused window take its place, until they close
+    #[serde(default)]
+    pub swallow: Option<bool>,
+}
+
+impl WindowRule {
+    pub fn matches(&self, window: &CosmicSurface) -> bool {
+        self.app_id
+            .as_ref()
+            .map_or(true, |pattern| pattern.is_match(&window.app_id()))
+            && self
+                .title
+                .as_ref()
+                .map_or(true, |pattern| pattern.is_match(&window.title()))
+    }
+}
+
+fn deserialize_pattern<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Option<Regex>, D::Error> {
+    Option::<String>::deserialize(deserializer)?
+        .map(|pattern| Regex::new(&format!("^(?:{})$", pattern)))
+        .transpose()
+        .map_err(serde::de::Error::custom)
+}
+
+/// Looks up the last matching rule setting a given effect.
+pub fn window_rule<T>(
+    rules: &[WindowRule],
+    window: &CosmicSurface,
+    effect: impl Fn(&WindowRule) -> Option<T>,
+) -> Option<T> {
+    rules
+        .iter()
+        .rev()
+        .filter(|rule| rule.matches(window))
+        .find_map(effect)
+}
@@ -1,13 +1,24 @@
 // SPDX-License-Identifier: GPL-3.0-only
 
-use std::collections::HashMap;
+use std::{
+    collections::HashMap,
+    sync::atomic::Ordering,
+    time::{Duration, Instant},
+};
 
 use crate::{
+    input::Accessibility,
+    logger,
     shell::{
         focus::target::{KeyboardFocusTarget, PointerFocusTarget},
-        CosmicSurface,
+        CosmicMapped, CosmicSurface, Shell,
+    },
+    state::{Common, Fps, State},
+    utils::{
+        commit_stats::{top_offenders, WindowStats},
+        prelude::SeatExt,
     },
-    state::{Common, Fps},
+    wayland::handlers::single_pixel_buffer::SAVED_UPLOAD_BYTES,
 };
 use egui::{load::SizedTexture, Color32, Vec2};
 use smithay::{
@@ -19,39 +30,343 @@ use smithay::{
             glow::GlowRenderer,
         },
     },
-    input::keyboard::xkb,
-    reexports::wayland_server::Resource,
-    utils::{Logical, Rectangle},
+    input::{keyboard::xkb, Seat},
+    reexports::{
+        calloop::timer::{TimeoutAction, Timer},
+        wayland_server::Resource,
+    },
+    utils::{IsAlive, Logical, Rectangle},
 };
+use tracing::{level_filters::LevelFilter, Level};
 
 pub const ELEMENTS_COLOR: Color32 = Color32::from_rgb(70, 198, 115);
 pub const RENDER_COLOR: Color32 = Color32::from_rgb(29, 114, 58);
 pub const SCREENCOPY_COLOR: Color32 = Color32::from_rgb(253, 178, 39);
 pub const DISPLAY_COLOR: Color32 = Color32::from_rgb(41, 184, 209);
 
+/// How long short notifications stay on screen.
+pub const TOAST_DURATION: Duration = Duration::from_secs(2);
+
 pub fn profiler_ui(
     state: &mut Common,
     renderer: &mut GlowRenderer,
     area: Rectangle<i32, Logical>,
     scale: f64,
 ) -> Result<Option<TextureRenderElement<GlesTexture>>, GlesError> {
-    if !state.egui.active {
+    let toast = state
+        .egui
+        .toast
+        .filter(|(shown, _)| shown.elapsed() < TOAST_DURATION)
+        .map(|(_, text)| text);
+    if !state.egui.active && toast.is_none() {
         return Ok(None);
     }
+    let active = state.egui.active;
 
-    state
+    if state
         .egui
-        .state
-        .render(
-            |ctx| {
+        .highlighted
+        .as_ref()
+        .is_some_and(|mapped| !mapped.alive())
+    {
+        state.egui.highlighted = None;
+    }
+
+    let seats = state.seats().cloned().collect::<Vec<_>>();
+    let inhibited = seats
+        .iter()
+        .filter(|seat| state.keybindings_inhibited(seat))
+        .cloned()
+        .collect::<Vec<_>>();
+    let client_stats = if active {
+        top_offenders(state, CLIENT_STATS_LINES)
+    } else {
+        Vec::new()
+    };
+    let mut action = None;
+    let res = state.egui.state.render(
+        |ctx| {
+            if active {
                 puffin_egui::profiler_window(ctx);
+                action = shell_inspector(
+                    ctx,
+                    &state.shell,
+                    &seats,
+                    &inhibited,
+                    state.egui.highlighted.as_ref(),
+                );
+                log_ui(ctx, &mut state.egui.logs);
+                client_stats_ui(ctx, &client_stats);
+            }
+            if let Some(toast) = toast {
+                egui::Area::new("toast")
+                    .anchor(egui::Align2::CENTER_TOP, [0.0, 40.0])
+                    .show(ctx, |ui| {
+                        egui::Frame::popup(ui.style())
+                            .show(ui, |ui| ui.label(egui::RichText::new(toast).heading()));
+                    });
+            }
+        },
+        renderer,
+        area,
+        scale,
+        0.8,
+    )?;
+
+    match action {
+        Some(InspectorAction::Highlight(mapped)) => {
+            if state.egui.highlighted.as_ref() == Some(&mapped) {
+                state.egui.highlighted = None;
+            } else {
+                state.egui.highlighted = Some(mapped);
+            }
+        }
+        Some(InspectorAction::Close(mapped)) => mapped.send_close(),
+        None => {}
+    }
+
+    Ok(Some(res))
+}
+
+impl State {
+    /// Briefly shows `text` on top of all outputs, even if the overlay is hidden.
+    pub fn show_toast(&mut self, text: &'static str) {
+        self.common.egui.toast = Some((Instant::now(), text));
+        for output in self.common.shell.outputs().cloned().collect::<Vec<_>>() {
+            self.backend
+                .schedule_render(&self.common.event_loop_handle, &output, None);
+        }
+        // render once more to hide the toast again
+        let _ = self.common.event_loop_handle.insert_source(
+            Timer::from_duration(TOAST_DURATION),
+            |_, _, state| {
+                for output in state.common.shell.outputs().cloned().collect::<Vec<_>>() {
+                    state
+                        .backend
+                        .schedule_render(&state.common.event_loop_handle, &output, None);
+                }
+                TimeoutAction::Drop
             },
-            renderer,
-            area,
-            scale,
-            0.8,
-        )
-        .map(Some)
+        );
+    }
+}
+
+/// Filter state of the log window.
+#[derive(Debug)]
+pub struct LogView {
+    pub search: String,
+    pub level: LevelFilter,
+}
+
+impl Default for LogView {
+    fn default() -> Self {
+        LogView {
+            search: String::new(),
+            level: LevelFilter::TRACE,
+        }
+    }
+}
+
+const LOG_LINES: usize = 500;
+const CLIENT_STATS_LINES: usize = 10;
+
+fn client_stats_ui(ctx: &egui::Context, stats: &[WindowStats]) {
+    egui::Window::new("Client Stats")
+        .default_pos([1080.0, 10.0])
+        .show(ctx, |ui| {
+            egui::Grid::new("client_stats")
+                .striped(true)
+                .show(ui, |ui| {
+                    ui.label(egui::RichText::new("App").strong());
+                    ui.label(egui::RichText::new("Pid").strong());
+                    ui.label(egui::RichText::new("Commits/s").strong());
+                    ui.label(egui::RichText::new("Buffers/s").strong());
+                    ui.end_row();
+                    for window in stats {
+                        ui.label(&window.app_id);
+                        ui.label(
+                            window
+                                .pid
+                                .map(|pid| pid.to_string())
+                                .unwrap_or_else(|| String::from("-")),
+                        );
+                        ui.label(format!("{:.1}", window.rate.commits_per_sec));
+                        ui.label(format!(
+                            "{:.1} MiB",
+                            window.rate.buffer_bytes_per_sec as f64 / (1024.0 * 1024.0)
+                        ));
+                        ui.end_row();
+                    }
+                });
+        });
+}
+
+fn log_ui(ctx: &egui::Context, view: &mut LogView) {
+    egui::Window::new("Logs")
+        .default_pos([10.0, 420.0])
+        .default_size([640.0, 320.0])
+        .show(ctx, |ui| {
+            ui.horizontal(|ui| {
+                ui.label(format!("Log level: {}", logger::level()));
+                if ui.button("Cycle").clicked() {
+                    logger::cycle_level();
+                }
+                ui.separator();
+                egui::ComboBox::from_label("Show")
+                    .selected_text(view.level.to_string())
+                    .show_ui(ui, |ui| {
+                        for level in [
+                            LevelFilter::ERROR,
+                            LevelFilter::WARN,
+                            LevelFilter::INFO,
+                            LevelFilter::DEBUG,
+                            LevelFilter::TRACE,
+                        ] {
+                            ui.selectable_value(&mut view.level, level, level.to_string());
+                        }
+                    });
+                ui.label("Search");
+                ui.text_edit_singleline(&mut view.search);
+            });
+            ui.separator();
+
+            let search = view.search.to_lowercase();
+            let entries = logger::LOG_BUFFER.recent(LOG_LINES, |entry| {
+                entry.level <= view.level
+                    && (search.is_empty()
+                        || entry.target.contains(&search)
+                        || entry.message.to_lowercase().contains(&search))
+            });
+            egui::ScrollArea::vertical()
+                .stick_to_bottom(true)
+                .show(ui, |ui| {
+                    for entry in entries {
+                        let color = match entry.level {
+                            Level::ERROR => Color32::LIGHT_RED,
+                            Level::WARN => Color32::YELLOW,
+                            Level::INFO => Color32::LIGHT_GREEN,
+                            _ => Color32::GRAY,
+                        };
+                        ui.label(egui::RichText::new(entry.to_string()).code().color(color));
+                    }
+                });
+        });
+}
+
+enum InspectorAction {
+    Highlight(CosmicMapped),
+    Close(CosmicMapped),
+}
+
+fn shell_inspector(
+    ctx: &egui::Context,
+    shell: &Shell,
+    seats: &[Seat<State>],
+    inhibited: &[Seat<State>],
+    highlighted: Option<&CosmicMapped>,
+) -> Option<InspectorAction> {
+    let mut action = None;
+
+    egui::Window::new("Shell Inspector")
+        .default_pos([420.0, 10.0])
+        .show(ctx, |ui| {
+            for seat in seats {
+                ui.label(egui::RichText::new(seat.name()).strong());
+                ui.label(
+                    egui::RichText::new(format!("Active Output: {}", seat.active_output().name()))
+                        .code(),
+                );
+                if let Some(ptr) = seat.get_pointer() {
+                    ui.label(
+                        egui::RichText::new(format!("Pos: {:?}", ptr.current_location())).code(),
+                    );
+                }
+                if inhibited.contains(seat) {
+                    ui.label(
+                        egui::RichText::new("Keybindings inhibited by focused window")
+                            .color(Color32::YELLOW),
+                    );
+                }
+            }
+            ui.separator();
+
+            egui::ScrollArea::vertical().show(ui, |ui| {
+                for output in shell.outputs() {
+                    egui::CollapsingHeader::new(format!("Output {}", output.name()))
+                        .default_open(true)
+                        .show(ui, |ui| {
+                            for (idx, workspace) in
+                                shell.workspaces.spaces_for_output(output).enumerate()
+                            {
+                                egui::CollapsingHeader::new(format!("Workspace {}", idx + 1))
+                                    .id_source((output.name(), idx))
+                                    .default_open(true)
+                                    .show(ui, |ui| {
+                                        for mapped in workspace.mapped() {
+                                            let window = mapped.active_window();
+                                            let layer = if workspace.is_fullscreen(mapped) {
+                                                "Fullscreen"
+                                            } else if workspace.is_floating(mapped) {
+                                                "Floating"
+                                            } else {
+                                                "Tiled"
+                                            };
+                                            ui.horizontal(|ui| {
+                                                if ui
+                                                    .selectable_label(
+                                                        highlighted == Some(mapped),
+                                                        format!(
+                                                            "{} ({})",
+                                                            window.app_id(),
+                                                            window.title()
+                                                        ),
+                                                    )
+                                                    .clicked()
+                                                {
+                                                    action = Some(InspectorAction::Highlight(
+                                                        mapped.clone(),
+                                                    ));
+                                                }
+                                                if ui.button("Close").clicked() {
+                                                    action = Some(InspectorAction::Close(
+                                                        mapped.clone(),
+                                                    ));
+                                                }
+                                            });
+                                            ui.label(
+                                                egui::RichText::new(format!(
+                                                    "{}: {:?}",
+                                                    layer,
+                                                    workspace.element_geometry(mapped)
+                                                ))
+                                                .code(),
+                                            );
+                                            for seat in seats {
+                                                let position = workspace
+                                                    .focus_stack
+                                                    .get(seat)
+                                                    .iter()
+                                                    .position(|m| m == mapped);
+                                                ui.label(
+                                                    egui::RichText::new(format!(
+                                                        "Focus Stack ({}): {}",
+                                                        seat.name(),
+                                                        position
+                                                            .map(|p| p.to_string())
+                                                            .unwrap_or_else(|| String::from("-"))
+                                                    ))
+                                                    .code(),
+                                                );
+                                            }
+                                        }
+                                    });
+                            }
+                        });
+                }
+            });
+        });
+
+    action
 }
 
 pub fn fps_ui(
@@ -62,7 +377,7 @@ pub fn fps_ui(
     area: Rectangle<i32, Logical>,
     scale: f64,
 ) -> Result<TextureRenderElement<GlesTexture>, GlesError> {
-    use egui_plot::{Bar, BarChart, Legend, Plot};
+    use egui_plot::{Bar, BarChart, Legend, Line, Plot};
 
     let (max, min, avg, avg_fps) = (
         fps.max_frametime().as_secs_f64(),
@@ -74,8 +389,19 @@ pub fn fps_ui(
         fps.max_time_to_display().as_secs_f64(),
         fps.min_time_to_display().as_secs_f64(),
     );
+    let (estimated_rendertime, missed_deadlines) =
+        (fps.estimated_rendertime.as_secs_f64(), fps.missed_deadlines);
 
     let amount = avg_fps.round() as usize * 2;
+    let frame_times = fps
+        .frames
+        .iter()
+        .rev()
+        .take(amount)
+        .rev()
+        .enumerate()
+        .map(|(i, frame)| [i as f64, frame.frame_time().as_secs_f64() * 1000.0])
+        .collect::<Vec<_>>();
     let ((bars_elements, bars_render), (bars_screencopy, bars_displayed)): (
         (Vec<Bar>, Vec<Bar>),
         (Vec<Bar>, Vec<Bar>),
@@ -186,6 +512,26 @@ pub fn fps_ui(
                         ui.label(egui::RichText::new(format!("avg: {:>7.6}", avg)).code());
                         ui.label(egui::RichText::new(format!("min: {:>7.6}", min)).code());
                         ui.label(egui::RichText::new(format!("max: {:>7.6}", max)).code());
+                        ui.label("Latency:");
+                        ui.label(
+                            egui::RichText::new(format!(
+                                "est. render time: {:>7.6}",
+                                estimated_rendertime
+                            ))
+                            .code(),
+                        );
+                        ui.label(
+                            egui::RichText::new(format!("missed deadlines: {}", missed_deadlines))
+                                .code(),
+                        );
+                        ui.label(
+                            egui::RichText::new(format!(
+                                "skipped solid color uploads: {:.2} MiB",
+                                SAVED_UPLOAD_BYTES.load(Ordering::Relaxed) as f64
+                                    / (1024.0 * 1024.0)
+                            ))
+                            .code(),
+                        );
                         let elements_chart = BarChart::new(bars_elements).vertical();
                         let render_chart = BarChart::new(bars_render)
                             .stack_on(&[&elements_chart])
@@ -212,6 +558,17 @@ pub fn fps_ui(
                                 plot_ui.bar_chart(display_chart);
                             });
 
+                        ui.label("Frame Time Graph (ms):");
+                        Plot::new("Frame Time")
+                            .view_aspect(5.0)
+                            .include_x(0.0)
+                            .include_x(amount as f64)
+                            .include_y(0.0)
+                            .show_x(false)
+                            .show(ui, |plot_ui| {
+                                plot_ui.line(Line::new(frame_times.clone()));
+                            });
+
                         ui.separator();
                         ui.label(egui::RichText::new("Input States").heading());
                         for seat in state.seats() {
@@ -272,6 +629,19 @@ pub fn fps_ui(
                                             ))
                                             .code(),
                                         );
+                                        if let Some(accessibility) =
+                                            seat.user_data().get::<Accessibility>()
+                                        {
+                                            let (latched, locked) =
+                                                accessibility.sticky_modifiers();
+                                            ui.label(
+                                                egui::RichText::new(format!(
+                                                    "Sticky: latched {:?} / locked {:?}",
+                                                    latched, locked,
+                                                ))
+                                                .code(),
+                                            );
+                                        }
 
                                         ui.label(
                                             egui::RichText::new(format!(
@@ -2,9 +2,21 @@
 
 use crate::{
     backend::render::cursor::CursorState,
-    config::{xkb_config_to_wl, Action, Config, KeyPattern},
+    config::{
+        xkb_config_to_wl, AccessibilityConfig, Action, Config, HotCorner, KeyModifier,
+        KeyModifiers, KeyPattern, MouseWarping, OutputConfig,
+    },
     shell::{
-        focus::{target::PointerFocusTarget, FocusDirection},
+        element::{
+            osd::{self, OsdContent},
+            surface::Responsiveness,
+            CosmicMapped,
+        },
+        focus::{
+            fullscreen_keeps_focus,
+            target::{KeyboardFocusTarget, PointerFocusTarget},
+            FocusDirection, FocusHistory,
+        },
         grabs::{ResizeEdge, SeatMoveGrabState},
         layout::{
             floating::ResizeGrabMarker,
@@ -13,9 +25,14 @@ use crate::{
         Direction, FocusResult, MoveResult, OverviewMode, ResizeDirection, ResizeMode, Trigger,
         Workspace,
     },
-    state::{Common, SessionLock},
+    state::{BackendData, Common, SessionLock},
     utils::prelude::*,
-    wayland::{handlers::screencopy::ScreencopySessions, protocols::screencopy::Session},
+    wayland::{
+        handlers::{
+            screencopy::ScreencopySessions, seat::PointerSelectionFocus, xdg_shell::PopupGrabData,
+        },
+        protocols::{output_configuration::OutputConfiguration, screencopy::Session},
+    },
 };
 use calloop::{timer::Timer, RegistrationToken};
 use cosmic_comp_config::workspace::WorkspaceLayout;
@@ -23,13 +40,17 @@ use cosmic_protocols::screencopy::v1::server::zcosmic_screencopy_session_v1::Inp
 #[allow(deprecated)]
 use smithay::{
     backend::input::{
-        Axis, AxisSource, Device, DeviceCapability, GestureBeginEvent, GestureEndEvent,
-        GesturePinchUpdateEvent as _, GestureSwipeUpdateEvent as _, InputBackend, InputEvent,
-        KeyState, PointerAxisEvent,
+        AbsolutePositionEvent, Axis, AxisSource, ButtonState, Device, DeviceCapability,
+        GestureBeginEvent, GestureEndEvent, GesturePinchUpdateEvent as _,
+        GestureSwipeUpdateEvent as _, InputBackend, InputEvent, KeyState, PointerAxisEvent,
+        TouchSlot,
+    },
+    desktop::{
+        find_popup_root_surface, layer_map_for_output, space::SpaceElement,
+        utils::under_from_surface_tree, PopupManager, PopupUngrabStrategy, WindowSurfaceType,
     },
-    desktop::{layer_map_for_output, space::SpaceElement, WindowSurfaceType},
     input::{
-        keyboard::{FilterResult, KeysymHandle, XkbConfig},
+        keyboard::{FilterResult, KeysymHandle, ModifiersState, XkbConfig},
         pointer::{
             AxisFrame, ButtonEvent, CursorImageStatus, GestureHoldBeginEvent, GestureHoldEndEvent,
             GesturePinchBeginEvent, GesturePinchEndEvent, GesturePinchUpdateEvent,
@@ -40,26 +61,35 @@ use smithay::{
     },
     output::Output,
     reexports::{
-        input::event::pointer::PointerAxisEvent as LibinputPointerAxisEvent,
-        wayland_server::DisplayHandle,
+        input::{
+            event::pointer::{
+                PointerAxisEvent as LibinputPointerAxisEvent,
+                PointerButtonEvent as LibinputPointerButtonEvent,
+                PointerMotionAbsoluteEvent as LibinputPointerMotionAbsoluteEvent,
+            },
+            event::touch::{
+                TouchDownEvent as LibinputTouchDownEvent,
+                TouchMotionEvent as LibinputTouchMotionEvent,
+            },
+            Device as InputDevice, Led,
+        },
+        wayland_server::{DisplayHandle, Resource},
     },
-    utils::{Point, Serial, SERIAL_COUNTER},
+    utils::{Logical, Point, Rectangle, Serial, Transform, SERIAL_COUNTER},
     wayland::{
         keyboard_shortcuts_inhibit::KeyboardShortcutsInhibitorSeat,
         pointer_constraints::{with_pointer_constraint, PointerConstraint},
         seat::WaylandFocus,
-        shell::wlr_layer::Layer as WlrLayer,
+        shell::wlr_layer::{KeyboardInteractivity, Layer as WlrLayer},
     },
     xwayland::X11Surface,
 };
-#[cfg(not(feature = "debug"))]
-use tracing::info;
-use tracing::{error, trace, warn};
-use xkbcommon::xkb::{Keycode, Keysym};
+use tracing::{error, info, warn};
+use xkbcommon::xkb::{self, Keycode, Keysym};
 
 use std::{
     any::Any,
-    cell::RefCell,
+    cell::{Cell, RefCell},
     collections::HashMap,
     time::{Duration, Instant},
 };
@@ -69,12 +99,163 @@ crate::utils::id_gen!(next_seat_id, SEAT_ID, SEAT_IDS);
 #[repr(transparent)]
 pub struct SeatId(pub usize);
 pub struct ActiveOutput(pub RefCell<Output>);
+/// Latest modifiers of the seat's keyboard, also updated for forwarded keys
+#[derive(Default, Debug)]
+pub struct CurrentModifiers(pub RefCell<ModifiersState>);
 #[derive(Default)]
 pub struct SupressedKeys(RefCell<Vec<(Keycode, Option<RegistrationToken>)>>);
 #[derive(Default, Debug)]
 pub struct ModifiersShortcutQueue(RefCell<Option<KeyPattern>>);
 #[derive(Default)]
 pub struct Devices(RefCell<HashMap<String, Vec<DeviceCapability>>>);
+/// Touchpads of a seat and the last typed key, see `InputConfig::disable_while_typing_timeout`
+#[derive(Default)]
+pub struct Typing(RefCell<TypingState>);
+
+#[derive(Default)]
+struct TypingState {
+    touchpads: HashMap<String, InputDevice>,
+    last_key: Option<Instant>,
+}
+/// Modal keyboard resizing started by `Action::EnterResizeMode`
+#[derive(Default)]
+pub struct ModalResize(RefCell<Option<ModalResizeState>>);
+
+struct ModalResizeState {
+    pattern: KeyPattern,
+    /// Steps taken so far, undone if the resize is cancelled
+    steps: Vec<(ResizeDirection, ResizeEdge)>,
+    last_activity: Instant,
+    timer: Option<RegistrationToken>,
+}
+
+/// Sticky and slow keys of a seat, see `AccessibilityConfig`
+#[derive(Default, Debug)]
+pub struct Accessibility(RefCell<AccessibilityState>);
+
+#[derive(Default, Debug)]
+struct AccessibilityState {
+    /// Toggled by pressing both shift keys
+    suspended: bool,
+    held_shifts: Vec<Keycode>,
+    /// Modifiers applying to the next key
+    latched: Vec<(Keycode, Keysym)>,
+    /// Modifiers applying until they are tapped again
+    locked: Vec<(Keycode, Keysym)>,
+    /// Modifier pressed without any other key so far
+    tap: Option<Keycode>,
+    last_tap: Option<(Keycode, Instant)>,
+    /// Physically pressed latched or locked modifiers, hidden from clients
+    intercepted: Vec<Keycode>,
+    /// Synthetic key events to send after the current one
+    queue: Vec<(Keycode, KeyState)>,
+    /// Keys waiting for the slow keys delay
+    slow_pending: Vec<(u32, RegistrationToken)>,
+}
+
+/// Pointer control from the numeric keypad, toggled by `Action::ToggleMouseKeys`
+#[derive(Debug)]
+pub struct MouseKeys(RefCell<MouseKeysState>);
+
+#[derive(Debug)]
+struct MouseKeysState {
+    active: bool,
+    /// Held down direction keys
+    held: Vec<u32>,
+    /// Button used for clicks and drags
+    button: u32,
+    /// Button held down by `KP_0`, until `KP_Decimal` is pressed
+    dragging: Option<u32>,
+    motion_start: Instant,
+    timer: Option<RegistrationToken>,
+}
+
+impl Default for MouseKeys {
+    fn default() -> MouseKeys {
+        MouseKeys(RefCell::new(MouseKeysState {
+            active: false,
+            held: Vec::new(),
+            button: BTN_LEFT,
+            dragging: None,
+            motion_start: Instant::now(),
+            timer: None,
+        }))
+    }
+}
+
+/// Hot corner or edge of an output the pointer of a seat rests in, see `HotCornersConfig`
+#[derive(Default, Debug)]
+pub struct HotCorners(RefCell<HotCornersState>);
+
+#[derive(Default, Debug)]
+struct HotCornersState {
+    current: Option<(Output, HotCorner)>,
+    /// Distance the pointer was pushed into the current corner
+    pressure: f64,
+    /// Set once the action ran, until the pointer leaves the corner again
+    triggered: bool,
+    timer: Option<RegistrationToken>,
+}
+
+/// Touch point emulating the pointer of a seat, see `StaticConfig::touch_as_pointer`
+#[derive(Default, Debug)]
+pub struct TouchPointer(Cell<Option<TouchSlot>>);
+
+/// Whether the cursor of a seat is hidden after touch input, see `StaticConfig::hide_pointer_on_touch`
+#[derive(Default, Debug)]
+pub struct PointerHidden(pub Cell<bool>);
+
+/// Modifier pressed on its own, see `StaticConfig::tap_bindings`.
+/// Any other key, button or scroll in between cancels the tap.
+#[derive(Default, Debug)]
+pub struct ModifierTap(Cell<Option<KeyModifier>>);
+
+/// Pending axis stop after wheel scrolling and the axes scrolled, see `StaticConfig::wheel_stop_delay`
+#[derive(Default, Debug)]
+pub struct WheelStop(RefCell<Option<(RegistrationToken, [bool; 2])>>);
+
+/// Buttons whose press was dropped (e.g. it only dismissed a popup grab), their release is dropped as well
+#[derive(Default, Debug)]
+pub struct SwallowedButtons(RefCell<Vec<u32>>);
+
+/// Evdev keycode of the num lock key
+pub const KEY_NUMLOCK: u32 = 69;
+
+const KEY_KPASTERISK: u32 = 55;
+const KEY_KP7: u32 = 71;
+const KEY_KP8: u32 = 72;
+const KEY_KP9: u32 = 73;
+const KEY_KPMINUS: u32 = 74;
+const KEY_KP4: u32 = 75;
+const KEY_KP5: u32 = 76;
+const KEY_KP6: u32 = 77;
+const KEY_KPPLUS: u32 = 78;
+const KEY_KP1: u32 = 79;
+const KEY_KP2: u32 = 80;
+const KEY_KP3: u32 = 81;
+const KEY_KP0: u32 = 82;
+const KEY_KPDOT: u32 = 83;
+const KEY_KPSLASH: u32 = 98;
+
+/// Evdev keycodes of the ctrl, shift, alt and super keys, which don't count as typing
+const MODIFIER_KEYS: [u32; 8] = [29, 42, 54, 56, 97, 100, 125, 126];
+
+const BTN_LEFT: u32 = 0x110;
+const BTN_RIGHT: u32 = 0x111;
+const BTN_MIDDLE: u32 = 0x112;
+
+/// Interval of pointer motion while a mouse keys direction is held
+const MOUSE_KEYS_INTERVAL: Duration = Duration::from_millis(16);
+/// Pointer speed of mouse keys in pixels per interval, accelerating over `MOUSE_KEYS_ACCEL_TIME`
+const MOUSE_KEYS_MIN_SPEED: f64 = 1.0;
+const MOUSE_KEYS_MAX_SPEED: f64 = 20.0;
+const MOUSE_KEYS_ACCEL_TIME: Duration = Duration::from_millis(1500);
+
+/// Tapping a latched modifier again within this time locks it.
+const STICKY_KEYS_LOCK_DELAY: Duration = Duration::from_millis(500);
+
+/// Resize mode is left, if no key was pressed for this long.
+const RESIZE_MODE_TIMEOUT: Duration = Duration::from_secs(5);
 
 impl Default for SeatId {
     fn default() -> SeatId {
@@ -113,6 +294,272 @@ impl SupressedKeys {
     }
 }
 
+impl ModalResize {
+    fn pattern(&self) -> Option<KeyPattern> {
+        self.0.borrow().as_ref().map(|state| state.pattern.clone())
+    }
+}
+
+impl MouseKeys {
+    pub fn is_active(&self) -> bool {
+        self.0.borrow().active
+    }
+
+    /// Pointer motion for the next interval, `None` once no direction is held anymore.
+    fn step(&self) -> Option<Point<f64, Logical>> {
+        let mut state = self.0.borrow_mut();
+        let (x, y) = state
+            .held
+            .iter()
+            .filter_map(|key| mouse_keys_direction(*key))
+            .fold((0i32, 0i32), |(x, y), (dx, dy)| (x + dx, y + dy));
+        if !state.active || (x == 0 && y == 0) {
+            state.timer = None;
+            return None;
+        }
+
+        let progress = (state.motion_start.elapsed().as_secs_f64()
+            / MOUSE_KEYS_ACCEL_TIME.as_secs_f64())
+        .min(1.0);
+        let speed = MOUSE_KEYS_MIN_SPEED + (MOUSE_KEYS_MAX_SPEED - MOUSE_KEYS_MIN_SPEED) * progress;
+        Some(Point::from((
+            x.signum() as f64 * speed,
+            y.signum() as f64 * speed,
+        )))
+    }
+}
+
+fn mouse_keys_direction(key: u32) -> Option<(i32, i32)> {
+    match key {
+        KEY_KP7 => Some((-1, -1)),
+        KEY_KP8 => Some((0, -1)),
+        KEY_KP9 => Some((1, -1)),
+        KEY_KP4 => Some((-1, 0)),
+        KEY_KP6 => Some((1, 0)),
+        KEY_KP1 => Some((-1, 1)),
+        KEY_KP2 => Some((0, 1)),
+        KEY_KP3 => Some((1, 1)),
+        _ => None,
+    }
+}
+
+fn hot_corner_at(
+    geometry: Rectangle<i32, Global>,
+    position: Point<f64, Global>,
+) -> Option<HotCorner> {
+    let left = position.x < (geometry.loc.x + 1) as f64;
+    let right = position.x >= (geometry.loc.x + geometry.size.w - 1) as f64;
+    let top = position.y < (geometry.loc.y + 1) as f64;
+    let bottom = position.y >= (geometry.loc.y + geometry.size.h - 1) as f64;
+    match (left, right, top, bottom) {
+        (true, _, true, _) => Some(HotCorner::TopLeft),
+        (_, true, true, _) => Some(HotCorner::TopRight),
+        (true, _, _, true) => Some(HotCorner::BottomLeft),
+        (_, true, _, true) => Some(HotCorner::BottomRight),
+        (_, _, true, _) => Some(HotCorner::Top),
+        (_, _, _, true) => Some(HotCorner::Bottom),
+        (true, _, _, _) => Some(HotCorner::Left),
+        (_, true, _, _) => Some(HotCorner::Right),
+        _ => None,
+    }
+}
+
+fn is_mouse_keys_key(key: u32) -> bool {
+    mouse_keys_direction(key).is_some()
+        || matches!(
+            key,
+            KEY_KP5 | KEY_KP0 | KEY_KPDOT | KEY_KPPLUS | KEY_KPSLASH | KEY_KPASTERISK | KEY_KPMINUS
+        )
+}
+
+impl Accessibility {
+    /// Names of the latched and locked modifiers
+    pub fn sticky_modifiers(&self) -> (Vec<String>, Vec<String>) {
+        let state = self.0.borrow();
+        let names = |mods: &[(Keycode, Keysym)]| {
+            mods.iter()
+                .map(|(_, sym)| xkb::keysym_get_name(*sym))
+                .collect::<Vec<_>>()
+        };
+        (names(&state.latched), names(&state.locked))
+    }
+
+    pub fn is_suspended(&self) -> bool {
+        self.0.borrow().suspended
+    }
+
+    /// Latches and locks modifiers, returns true if the event must not reach clients.
+    fn sticky_keys(
+        &self,
+        handle: &KeysymHandle,
+        key_state: KeyState,
+        config: &AccessibilityConfig,
+    ) -> bool {
+        let mut state = self.0.borrow_mut();
+        let code = handle.raw_code();
+        let sym = handle.modified_sym();
+
+        if key_state == KeyState::Released {
+            if let Some(i) = state.intercepted.iter().position(|c| *c == code) {
+                state.intercepted.remove(i);
+                if state.tap == Some(code) {
+                    state.tap = None;
+                    state.tapped(code, sym);
+                }
+                return true;
+            }
+        }
+
+        if matches!(sym, Keysym::Shift_L | Keysym::Shift_R) {
+            match key_state {
+                KeyState::Pressed => {
+                    if config.is_enabled() && state.held_shifts.iter().any(|c| *c != code) {
+                        state.suspended = !state.suspended;
+                        state.tap = None;
+                        state.release_sticky_modifiers();
+                        state.held_shifts.push(code);
+                        info!(
+                            suspended = state.suspended,
+                            "Toggled keyboard accessibility."
+                        );
+                        return false;
+                    }
+                    state.held_shifts.push(code);
+                }
+                KeyState::Released => state.held_shifts.retain(|c| *c != code),
+            }
+        }
+
+        if !config.sticky_keys || state.suspended {
+            return false;
+        }
+
+        if !is_sticky_modifier(sym) {
+            match key_state {
+                KeyState::Pressed => state.tap = None,
+                KeyState::Released => {
+                    for (code, _) in std::mem::take(&mut state.latched) {
+                        state.queue.push((code, KeyState::Released));
+                    }
+                }
+            }
+            return false;
+        }
+
+        match key_state {
+            KeyState::Pressed => {
+                state.tap = Some(code);
+                // already held down by a synthetic press
+                let held = state
+                    .latched
+                    .iter()
+                    .chain(state.locked.iter())
+                    .any(|(c, _)| *c == code);
+                if held {
+                    state.intercepted.push(code);
+                }
+                held
+            }
+            KeyState::Released => {
+                if state.tap == Some(code) {
+                    state.tap = None;
+                    state.tapped(code, sym);
+                }
+                false
+            }
+        }
+    }
+
+    fn take_queued(&self) -> Vec<(Keycode, KeyState)> {
+        std::mem::take(&mut self.0.borrow_mut().queue)
+    }
+}
+
+impl AccessibilityState {
+    /// A sticky modifier was pressed and released without any other key.
+    fn tapped(&mut self, code: Keycode, sym: Keysym) {
+        if let Some(i) = self.locked.iter().position(|(c, _)| *c == code) {
+            self.locked.remove(i);
+            self.queue.push((code, KeyState::Released));
+        } else if let Some(i) = self.latched.iter().position(|(c, _)| *c == code) {
+            let latched = self.latched.remove(i);
+            let double_tap = self.last_tap.take().map_or(false, |(c, time)| {
+                c == code && time.elapsed() < STICKY_KEYS_LOCK_DELAY
+            });
+            if double_tap {
+                self.locked.push(latched);
+            } else {
+                self.queue.push((code, KeyState::Released));
+            }
+        } else {
+            // the release already reached the keymap, keep the modifier pressed
+            self.latched.push((code, sym));
+            self.queue.push((code, KeyState::Pressed));
+            self.last_tap = Some((code, Instant::now()));
+        }
+    }
+
+    fn release_sticky_modifiers(&mut self) {
+        let mods = std::mem::take(&mut self.latched)
+            .into_iter()
+            .chain(std::mem::take(&mut self.locked));
+        for (code, _) in mods {
+            self.queue.push((code, KeyState::Released));
+        }
+    }
+}
+
+fn is_sticky_modifier(sym: Keysym) -> bool {
+    matches!(
+        sym,
+        Keysym::Shift_L
+            | Keysym::Shift_R
+            | Keysym::Control_L
+            | Keysym::Control_R
+            | Keysym::Alt_L
+            | Keysym::Alt_R
+            | Keysym::Meta_L
+            | Keysym::Meta_R
+            | Keysym::Super_L
+            | Keysym::Super_R
+            | Keysym::Hyper_L
+            | Keysym::Hyper_R
+            | Keysym::ISO_Level3_Shift
+    )
+}
+
+impl ModifierTap {
+    /// Returns the modifier, if `sym` is the release of a modifier tapped on its own.
+    fn update(
+        &self,
+        sym: Keysym,
+        state: KeyState,
+        modifiers: &ModifiersState,
+    ) -> Option<KeyModifier> {
+        let modifier = match sym {
+            Keysym::Super_L | Keysym::Super_R => Some(KeyModifier::Super),
+            Keysym::Control_L | Keysym::Control_R => Some(KeyModifier::Ctrl),
+            Keysym::Alt_L | Keysym::Alt_R => Some(KeyModifier::Alt),
+            Keysym::Shift_L | Keysym::Shift_R => Some(KeyModifier::Shift),
+            _ => None,
+        };
+        match state {
+            KeyState::Pressed => {
+                // the pressed modifier is already part of `modifiers`, nothing else may be held
+                self.0.set(
+                    modifier.filter(|modifier| Into::<KeyModifiers>::into(*modifier) == *modifiers),
+                );
+                None
+            }
+            KeyState::Released => self.0.take().filter(|tapped| Some(*tapped) == modifier),
+        }
+    }
+
+    pub fn cancel(&self) {
+        self.0.set(None);
+    }
+}
+
 impl ModifiersShortcutQueue {
     pub fn set(&self, binding: KeyPattern) {
         let mut set = self.0.borrow_mut();
@@ -153,10 +600,6 @@ impl Devices {
         new_caps
     }
 
-    pub fn has_device<D: Device>(&self, device: &D) -> bool {
-        self.0.borrow().contains_key(&device.id())
-    }
-
     fn remove_device<D: Device>(&self, device: &D) -> Vec<DeviceCapability> {
         let id = device.id();
         let mut map = self.0.borrow_mut();
@@ -179,8 +622,20 @@ pub fn add_seat(
     let userdata = seat.user_data();
     userdata.insert_if_missing(SeatId::default);
     userdata.insert_if_missing(Devices::default);
+    userdata.insert_if_missing(Typing::default);
     userdata.insert_if_missing(SupressedKeys::default);
     userdata.insert_if_missing(ModifiersShortcutQueue::default);
+    userdata.insert_if_missing(ModalResize::default);
+    userdata.insert_if_missing(Accessibility::default);
+    userdata.insert_if_missing(MouseKeys::default);
+    userdata.insert_if_missing(HotCorners::default);
+    userdata.insert_if_missing(TouchPointer::default);
+    userdata.insert_if_missing(PointerHidden::default);
+    userdata.insert_if_missing(ModifierTap::default);
+    userdata.insert_if_missing(SwallowedButtons::default);
+    userdata.insert_if_missing(WheelStop::default);
+    userdata.insert_if_missing(PointerSelectionFocus::default);
+    userdata.insert_if_missing(CurrentModifiers::default);
     userdata.insert_if_missing(SeatMoveGrabState::default);
     userdata.insert_if_missing(CursorState::default);
     userdata.insert_if_missing(|| ActiveOutput(RefCell::new(output.clone())));
@@ -215,12 +670,27 @@ impl State {
         event: InputEvent<B>,
         needs_key_repetition: bool,
     ) where
+        <B as InputBackend>::Device: 'static,
         <B as InputBackend>::PointerAxisEvent: 'static,
+        <B as InputBackend>::PointerButtonEvent: 'static,
+        <B as InputBackend>::PointerMotionAbsoluteEvent: 'static,
+        <B as InputBackend>::TouchDownEvent: 'static,
+        <B as InputBackend>::TouchMotionEvent: 'static,
     {
+        #[cfg(feature = "puffin")]
+        puffin::profile_function!();
+        let _span = tracing::trace_span!("process_input_event").entered();
+
         use smithay::backend::input::Event;
+        if !matches!(
+            event,
+            InputEvent::DeviceAdded { .. } | InputEvent::DeviceRemoved { .. }
+        ) {
+            self.notify_activity();
+        }
         match event {
             InputEvent::DeviceAdded { device } => {
-                let seat = &mut self.common.last_active_seat();
+                let seat = self.common.last_active_seat().clone();
                 let userdata = seat.user_data();
                 let devices = userdata.get::<Devices>().unwrap();
                 for cap in devices.add_device(&device) {
@@ -229,24 +699,34 @@ impl State {
                         _ => {}
                     }
                 }
+                if let Some(device) = <dyn Any>::downcast_ref::<InputDevice>(&device)
+                    .filter(|device| device.config_tap_finger_count() > 0)
+                {
+                    let typing = userdata.get::<Typing>().unwrap();
+                    typing
+                        .0
+                        .borrow_mut()
+                        .touchpads
+                        .insert(device.id(), device.clone());
+                }
+                self.common.add_device_seat(&device, &seat);
                 #[cfg(feature = "debug")]
                 {
                     self.common.egui.state.handle_device_added(&device);
                 }
             }
             InputEvent::DeviceRemoved { device } => {
-                for seat in &mut self.common.seats() {
+                if let Some(seat) = self.common.remove_device_seat(&device) {
                     let userdata = seat.user_data();
                     let devices = userdata.get::<Devices>().unwrap();
-                    if devices.has_device(&device) {
-                        for cap in devices.remove_device(&device) {
-                            match cap {
-                                // TODO: Handle touch, tablet
-                                _ => {}
-                            }
+                    for cap in devices.remove_device(&device) {
+                        match cap {
+                            // TODO: Handle touch, tablet
+                            _ => {}
                         }
-                        break;
                     }
+                    let typing = userdata.get::<Typing>().unwrap();
+                    typing.0.borrow_mut().touchpads.remove(&device.id());
                 }
                 #[cfg(feature = "debug")]
                 {
@@ -256,730 +736,108 @@ impl State {
             InputEvent::Keyboard { event, .. } => {
                 use smithay::backend::input::KeyboardKeyEvent;
 
-                let loop_handle = self.common.event_loop_handle.clone();
-
                 if let Some(seat) = self.common.seat_with_device(&event.device()).cloned() {
-                    let userdata = seat.user_data();
-
-                    let current_output = seat.active_output();
-                    let workspace = self.common.shell.active_space_mut(&current_output);
-                    let shortcuts_inhibited = workspace
-                        .focus_stack
-                        .get(&seat)
-                        .last()
-                        .and_then(|window| {
-                            window.wl_surface().and_then(|surface| {
-                                seat.keyboard_shortcuts_inhibitor_for_surface(&surface)
-                            })
-                        })
-                        .map(|inhibitor| inhibitor.is_active())
-                        .unwrap_or(false);
-
                     let keycode = event.key_code();
                     let state = event.state();
-                    trace!(?keycode, ?state, "key");
-
-                    let serial = SERIAL_COUNTER.next_serial();
                     let time = Event::time_msec(&event);
-                    let keyboard = seat.get_keyboard().unwrap();
-                    let current_focus = keyboard.current_focus();
-                    if let Some((action, pattern)) = keyboard
-                            .input(
-                                self,
-                                keycode,
-                                state,
-                                serial,
-                                time,
-                                |data, modifiers, handle| {
-                                    // Leave move overview mode, if any modifier was released
-                                    if let OverviewMode::Started(Trigger::KeyboardMove(action_modifiers), _) =
-                                        data.common.shell.overview_mode().0
-                                    {
-                                        if (action_modifiers.ctrl && !modifiers.ctrl)
-                                            || (action_modifiers.alt && !modifiers.alt)
-                                            || (action_modifiers.logo && !modifiers.logo)
-                                            || (action_modifiers.shift && !modifiers.shift)
-                                        {
-                                            data.common.shell.set_overview_mode(None, data.common.event_loop_handle.clone());
-                                        }
-                                    }
-                                    // Leave swap overview mode, if any key was released
-                                    if let OverviewMode::Started(Trigger::KeyboardSwap(action_pattern, old_descriptor), _) =
-                                        data.common.shell.overview_mode().0
-                                    {
-                                        if (action_pattern.modifiers.ctrl && !modifiers.ctrl)
-                                            || (action_pattern.modifiers.alt && !modifiers.alt)
-                                            || (action_pattern.modifiers.logo && !modifiers.logo)
-                                            || (action_pattern.modifiers.shift && !modifiers.shift)
-                                            || (action_pattern.key.is_some() && handle.raw_syms().contains(&action_pattern.key.unwrap()) && state == KeyState::Released)
-                                        {
-                                            data.common.shell.set_overview_mode(None, data.common.event_loop_handle.clone());
-
-                                            if let Some(focus) = current_focus {
-                                                if let Some(new_descriptor) = data.common.shell.workspaces.active(&current_output).1.node_desc(focus) {
-                                                    let mut spaces = data.common.shell.workspaces.spaces_mut();
-                                                    if old_descriptor.handle != new_descriptor.handle {
-                                                        let (mut old_w, mut other_w) = spaces.partition::<Vec<_>, _>(|w| w.handle == old_descriptor.handle);
-                                                        if let Some(old_workspace) = old_w.get_mut(0) {
-                                                            if let Some(new_workspace) = other_w.iter_mut().find(|w| w.handle == new_descriptor.handle) {
-                                                                if let Some(focus) = TilingLayout::swap_trees(&mut old_workspace.tiling_layer, Some(&mut new_workspace.tiling_layer), &old_descriptor, &new_descriptor, &mut data.common.shell.toplevel_info_state) {
-                                                                    let seat = seat.clone();
-                                                                    data.common.event_loop_handle.insert_idle(move |state| {
-                                                                        Common::set_focus(state, Some(&focus), &seat, None);
-                                                                    });
-                                                                }
-                                                                old_workspace.refresh_focus_stack();
-                                                                new_workspace.refresh_focus_stack();
-                                                            }
-                                                        }
-                                                    } else {
-                                                        if let Some(workspace) = spaces.find(|w| w.handle == new_descriptor.handle) {
-                                                            if let Some(focus) = TilingLayout::swap_trees(&mut workspace.tiling_layer, None, &old_descriptor, &new_descriptor, &mut data.common.shell.toplevel_info_state) {
-                                                                std::mem::drop(spaces);
-                                                                let seat = seat.clone();
-                                                                data.common.event_loop_handle.insert_idle(move |state| {
-                                                                    Common::set_focus(state, Some(&focus), &seat, None);
-                                                                });
-                                                            }
-                                                            workspace.refresh_focus_stack();
-                                                        }
-                                                    }
-                                                }
-                                            } else {
-                                                let new_workspace = data.common.shell.workspaces.active(&current_output).1.handle;
-                                                if new_workspace != old_descriptor.handle {
-                                                    let spaces = data.common.shell.workspaces.spaces_mut();
-                                                    let (mut old_w, mut other_w) = spaces.partition::<Vec<_>, _>(|w| w.handle == old_descriptor.handle);
-                                                    if let Some(old_workspace) = old_w.get_mut(0) {
-                                                        if let Some(new_workspace) = other_w.iter_mut().find(|w| w.handle == new_workspace) {
-                                                            if new_workspace.tiling_layer.windows().next().is_none() {
-                                                                if let Some(focus) = TilingLayout::move_tree(&mut old_workspace.tiling_layer, &mut new_workspace.tiling_layer, &new_workspace.handle, &seat, new_workspace.focus_stack.get(&seat).iter(), old_descriptor, &mut data.common.shell.toplevel_info_state) {
-                                                                    let seat = seat.clone();
-                                                                    data.common.event_loop_handle.insert_idle(move |state| {
-                                                                        Common::set_focus(state, Some(&focus), &seat, None);
-                                                                    });
-                                                                }
-                                                                old_workspace.refresh_focus_stack();
-                                                            }
-                                                        }
-                                                    }
-                                                }
-                                            }
-                                        }
-                                    }
-
-                                    // Leave or update resize mode, if modifiers changed or initial key was released
-                                    if let (ResizeMode::Started(action_pattern, _, _), _) =
-                                        data.common.shell.resize_mode()
-                                    {
-                                        if action_pattern.key.is_some() && state == KeyState::Released
-                                            && handle.raw_syms().contains(&action_pattern.key.unwrap())
-                                        {
-                                            data.common.shell.set_resize_mode(None, &data.common.config, data.common.event_loop_handle.clone());
-                                        } else if action_pattern.modifiers != *modifiers {
-                                            let mut new_pattern = action_pattern.clone();
-                                            new_pattern.modifiers = modifiers.clone().into();
-                                            let enabled = data
-                                                .common
-                                                .config
-                                                .static_conf
-                                                .key_bindings
-                                                .iter()
-                                                .find_map(move |(binding, action)| {
-                                                    if binding == &new_pattern
-                                                        && matches!(action, Action::Resizing(_))
-                                                    {
-                                                        let Action::Resizing(direction) = action else { unreachable!() };
-                                                        Some((new_pattern.clone(), *direction))
-                                                    } else {
-                                                        None
-                                                    }
-                                                });
-                                            data.common.shell.set_resize_mode(enabled, &data.common.config, data.common.event_loop_handle.clone());
-                                        }
-                                    }
-
-                                    // Special case resizing with regards to arrow keys
-                                    if let (ResizeMode::Started(_, _, direction), _) =
-                                        data.common.shell.resize_mode()
-                                    {
-                                        let resize_edge = match handle.modified_sym() {
-                                            Keysym::Left | Keysym::h | Keysym::H => Some(ResizeEdge::LEFT),
-                                            Keysym::Down | Keysym::j | Keysym::J => Some(ResizeEdge::BOTTOM),
-                                            Keysym::Up | Keysym::k | Keysym::K => Some(ResizeEdge::TOP),
-                                            Keysym::Right | Keysym::l | Keysym::L => Some(ResizeEdge::RIGHT),
-                                            _ => None,
-                                        };
-
-                                        if let Some(mut edge) = resize_edge {
-                                            if direction == ResizeDirection::Inwards {
-                                                edge.flip_direction();
-                                            }
-                                            let action = Action::_ResizingInternal(direction, edge, state);
-                                            let key_pattern = KeyPattern {
-                                                modifiers: modifiers.clone().into(),
-                                                key: Some(Keysym::new(handle.raw_code().raw())),
-                                            };
-
-                                            if state == KeyState::Released {
-                                                if let Some(tokens) = userdata.get::<SupressedKeys>().unwrap().filter(&handle) {
-                                                    for token in tokens {
-                                                        loop_handle.remove(token);
-                                                    }
-                                                }
-                                            } else {
-                                                let token = if needs_key_repetition {
-                                                    let seat_clone = seat.clone();
-                                                    let action_clone = action.clone();
-                                                    let key_pattern_clone = key_pattern.clone();
-                                                    let start = Instant::now();
-                                                    loop_handle.insert_source(Timer::from_duration(Duration::from_millis(200)), move |current, _, state| {
-                                                        let duration = current.duration_since(start).as_millis();
-                                                        state.handle_action(action_clone.clone(), &seat_clone, serial, time.overflowing_add(duration as u32).0, key_pattern_clone.clone(), None);
-                                                        calloop::timer::TimeoutAction::ToDuration(Duration::from_millis(25))
-                                                    }).ok()
-                                                } else { None };
-
-                                                userdata
-                                                        .get::<SupressedKeys>()
-                                                        .unwrap()
-                                                        .add(&handle, token);
-                                            }
-                                            return FilterResult::Intercept(Some((
-                                                action,
-                                                key_pattern
-                                            )));
-                                        }
-                                    }
-
-                                    // Skip released events for initially surpressed keys
-                                    if state == KeyState::Released {
-                                        if let Some(tokens) = userdata.get::<SupressedKeys>().unwrap().filter(&handle) {
-                                            for token in tokens {
-                                                loop_handle.remove(token);
-                                            }
-                                            return FilterResult::Intercept(None);
-                                        }
-                                    }
-
-                                    // Pass keys to debug interface, if it has focus
-                                    #[cfg(feature = "debug")]
-                                    {
-                                        if data.common.seats().position(|x| x == &seat).unwrap() == 0
-                                            && data.common.egui.active
-                                        {
-                                            if data.common.egui.state.wants_keyboard() {
-                                                data.common.egui.state.handle_keyboard(
-                                                    &handle,
-                                                    state == KeyState::Pressed,
-                                                    modifiers.clone(),
-                                                );
-                                                userdata
-                                                    .get::<SupressedKeys>()
-                                                    .unwrap()
-                                                    .add(&handle, None);
-                                                return FilterResult::Intercept(None);
-                                            }
-                                        }
-                                    }
-
-                                    // Handle VT switches
-                                    if state == KeyState::Pressed
-                                        && (Keysym::XF86_Switch_VT_1.raw() ..= Keysym::XF86_Switch_VT_12.raw())
-                                            .contains(&handle.modified_sym().raw())
-                                    {
-                                        if let Err(err) = data.backend.kms().switch_vt(
-                                            (handle.modified_sym().raw() - Keysym::XF86_Switch_VT_1.raw()
-                                                + 1)
-                                                as i32,
-                                        ) {
-                                            error!(?err, "Failed switching virtual terminal.");
-                                        }
-                                        userdata.get::<SupressedKeys>().unwrap().add(&handle, None);
-                                        return FilterResult::Intercept(None);
-                                    }
-
-                                    // handle the rest of the global shortcuts
-                                    let mut can_clear_modifiers_shortcut = true;
-                                    if !shortcuts_inhibited {
-                                        let modifiers_queue = userdata.get::<ModifiersShortcutQueue>().unwrap();
-                                        for (binding, action) in
-                                            data.common.config.static_conf.key_bindings.iter()
-                                        {
-                                            let modifiers_bypass = binding.key.is_none()
-                                                && state == KeyState::Released
-                                                && binding.modifiers != *modifiers
-                                                && modifiers_queue.take(binding);
-
-                                            if !modifiers_bypass && binding.key.is_none() && state == KeyState::Pressed && binding.modifiers == *modifiers {
-                                                modifiers_queue.set(binding.clone());
-                                                can_clear_modifiers_shortcut = false;
-                                            }
-
-                                            if (
-                                                    binding.key.is_some()
-                                                    && state == KeyState::Pressed
-                                                    && handle.raw_syms().contains(&binding.key.unwrap())
-                                                    && binding.modifiers == *modifiers
-                                                ) || modifiers_bypass
-                                            {
-                                                modifiers_queue.clear();
-                                                userdata
-                                                    .get::<SupressedKeys>()
-                                                    .unwrap()
-                                                    .add(&handle, None);
-                                                return FilterResult::Intercept(Some((
-                                                    action.clone(),
-                                                    binding.clone(),
-                                                )));
-                                            }
-                                        }
-                                    }
-
-                                    if can_clear_modifiers_shortcut {
-                                        userdata.get::<ModifiersShortcutQueue>().unwrap().clear();
-                                    }
-
-                                    // keys are passed through to apps
-                                    FilterResult::Forward
-                                },
-                            )
-                            .flatten()
-                        {
-                            self.handle_action(action, &seat, serial, time, pattern, None)
-                        }
+                    if state == KeyState::Pressed && !MODIFIER_KEYS.contains(&keycode) {
+                        let typing = seat.user_data().get::<Typing>().unwrap();
+                        typing.0.borrow_mut().last_key = Some(Instant::now());
+                    }
+                    if !self.slow_keys(&seat, keycode, state, needs_key_repetition) {
+                        self.keyboard_key(&seat, keycode, state, time, needs_key_repetition);
+                    }
                 }
             }
             InputEvent::PointerMotion { event, .. } => {
                 use smithay::backend::input::PointerMotionEvent;
 
                 if let Some(seat) = self.common.seat_with_device(&event.device()).cloned() {
-                    let current_output = seat.active_output();
-
-                    let mut position = seat.get_pointer().unwrap().current_location().as_global();
-
-                    let overview = self.common.shell.overview_mode();
-                    let workspace = self.common.shell.workspaces.active_mut(&current_output);
-                    let under = State::surface_under(
-                        position,
-                        &current_output,
-                        &self.common.shell.override_redirect_windows,
-                        overview.0.clone(),
-                        workspace,
-                        self.common.session_lock.as_ref(),
-                    )
-                    .map(|(target, pos)| (target, pos.as_logical()));
-
-                    let ptr = seat.get_pointer().unwrap();
-
-                    let mut pointer_locked = false;
-                    let mut pointer_confined = false;
-                    let mut confine_region = None;
-                    if let Some((surface, surface_loc)) = under
-                        .as_ref()
-                        .and_then(|(target, l)| Some((target.wl_surface()?, l)))
-                    {
-                        with_pointer_constraint(&surface, &ptr, |constraint| match constraint {
-                            Some(constraint) if constraint.is_active() => {
-                                // Constraint does not apply if not within region
-                                if !constraint.region().map_or(true, |x| {
-                                    x.contains(ptr.current_location().to_i32_round() - *surface_loc)
-                                }) {
-                                    return;
-                                }
-                                match &*constraint {
-                                    PointerConstraint::Locked(_locked) => {
-                                        pointer_locked = true;
-                                    }
-                                    PointerConstraint::Confined(confine) => {
-                                        pointer_confined = true;
-                                        confine_region = confine.region().cloned();
-                                    }
-                                }
-                            }
-                            _ => {}
-                        });
+                    if self.typing_suppresses(&seat, &event.device()) {
+                        return;
                     }
-
-                    ptr.relative_motion(
-                        self,
-                        under.clone(),
-                        &RelativeMotionEvent {
-                            delta: event.delta(),
-                            delta_unaccel: event.delta_unaccel(),
-                            utime: event.time(),
-                        },
+                    seat.user_data()
+                        .get::<PointerHidden>()
+                        .unwrap()
+                        .0
+                        .set(false);
+                    self.pointer_motion(
+                        &seat,
+                        event.delta(),
+                        event.delta_unaccel(),
+                        event.time(),
+                        event.time_msec(),
                     );
+                }
+            }
+            InputEvent::PointerMotionAbsolute { event, .. } => {
+                if let Some(seat) = self.common.seat_with_device(&event.device()).cloned() {
+                    seat.user_data()
+                        .get::<PointerHidden>()
+                        .unwrap()
+                        .0
+                        .set(false);
+                    #[allow(deprecated)]
+                    let mapped_output =
+                        <dyn Any>::downcast_ref::<LibinputPointerMotionAbsoluteEvent>(&event)
+                            .and_then(|event| self.common.config.map_to_output(&event.device()));
+                    let (output, position) = self.absolute_position(&seat, &event, mapped_output);
+                    self.pointer_motion_absolute(&seat, &output, position, event.time_msec());
+                }
+            }
+            InputEvent::PointerButton { event, .. } => {
+                use smithay::backend::input::PointerButtonEvent;
 
-                    if pointer_locked {
-                        ptr.frame(self);
-                        return;
+                if let Some(seat) = self.common.seat_with_device(&event.device()).cloned() {
+                    #[cfg(feature = "debug")]
+                    if self.common.seat_uses_egui(&seat) {
+                        if self.common.egui.state.wants_pointer() {
+                            if let Some(button) = event.button() {
+                                self.common.egui.state.handle_pointer_button(
+                                    button,
+                                    event.state() == ButtonState::Pressed,
+                                );
+                            }
+                            return;
+                        }
                     }
 
-                    position += event.delta().as_global();
-
-                    let output = self
-                        .common
-                        .shell
-                        .outputs()
-                        .find(|output| output.geometry().to_f64().contains(position))
-                        .cloned()
-                        .unwrap_or(current_output.clone());
-
-                    if ptr.is_grabbed()
-                        && seat
-                            .user_data()
-                            .get::<ResizeGrabMarker>()
-                            .map(|marker| marker.get())
-                            .unwrap_or(false)
+                    #[allow(deprecated)]
+                    let button = if let Some(event) =
+                        <dyn Any>::downcast_ref::<LibinputPointerButtonEvent>(&event)
                     {
-                        if output != current_output {
-                            ptr.frame(self);
-                            return;
-                        }
+                        self.common
+                            .config
+                            .map_button(&event.device(), event.button_code())
+                    } else {
+                        event.button_code()
+                    };
+                    if event.state() == ButtonState::Pressed
+                        && self.typing_suppresses(&seat, &event.device())
+                    {
+                        let swallowed = seat.user_data().get::<SwallowedButtons>().unwrap();
+                        swallowed.0.borrow_mut().push(button);
+                        return;
                     }
-
-                    let output_geometry = output.geometry();
-
-                    let workspace = self.common.shell.workspaces.active_mut(&output);
-                    let new_under = State::surface_under(
-                        position,
-                        &output,
-                        &self.common.shell.override_redirect_windows,
-                        overview.0,
-                        workspace,
-                        self.common.session_lock.as_ref(),
-                    )
-                    .map(|(target, pos)| (target, pos.as_logical()));
-
-                    position.x = position.x.clamp(
-                        output_geometry.loc.x as f64,
-                        (output_geometry.loc.x + output_geometry.size.w) as f64,
-                    );
-                    position.y = position.y.clamp(
-                        output_geometry.loc.y as f64,
-                        (output_geometry.loc.y + output_geometry.size.h) as f64,
-                    );
-
-                    // If confined, don't move pointer if it would go outside surface or region
-                    if pointer_confined {
-                        if let Some((surface, surface_loc)) = &under {
-                            if new_under.as_ref().and_then(|(under, _)| under.wl_surface())
-                                != surface.wl_surface()
-                            {
-                                ptr.frame(self);
-                                return;
-                            }
-                            if let PointerFocusTarget::Element(element) = surface {
-                                //if !element.is_in_input_region(&(position.to_i32_round() - *surface_loc).to_f64()) {
-                                if !element.is_in_input_region(
-                                    &(position.as_logical() - surface_loc.to_f64()),
-                                ) {
-                                    ptr.frame(self);
-                                    return;
-                                }
-                            }
-                            if let Some(region) = confine_region {
-                                if !region
-                                    .contains(position.as_logical().to_i32_round() - *surface_loc)
-                                {
-                                    ptr.frame(self);
-                                    return;
-                                }
-                            }
-                        }
-                    }
-
-                    let serial = SERIAL_COUNTER.next_serial();
-                    ptr.motion(
-                        self,
-                        under,
-                        &MotionEvent {
-                            location: position.as_logical(),
-                            serial,
-                            time: event.time_msec(),
-                        },
-                    );
-                    ptr.frame(self);
-
-                    // If pointer is now in a constraint region, activate it
-                    if let Some((under, surface_location)) =
-                        new_under.and_then(|(target, loc)| Some((target.wl_surface()?, loc)))
-                    {
-                        with_pointer_constraint(&under, &ptr, |constraint| match constraint {
-                            Some(constraint) if !constraint.is_active() => {
-                                let region = match &*constraint {
-                                    PointerConstraint::Locked(locked) => locked.region(),
-                                    PointerConstraint::Confined(confined) => confined.region(),
-                                };
-                                let point =
-                                    ptr.current_location().to_i32_round() - surface_location;
-                                if region.map_or(true, |region| region.contains(point)) {
-                                    constraint.activate();
-                                }
-                            }
-                            _ => {}
-                        });
-                    }
-
-                    if output != current_output {
-                        for session in sessions_for_output(&self.common, &current_output) {
-                            session.cursor_leave(&seat, InputType::Pointer);
-                        }
-
-                        for session in sessions_for_output(&self.common, &output) {
-                            session.cursor_enter(&seat, InputType::Pointer);
-                        }
-
-                        seat.set_active_output(&output);
-                    }
-
-                    for session in sessions_for_output(&self.common, &output) {
-                        if let Some((geometry, offset)) = seat.cursor_geometry(
-                            position.as_logical().to_buffer(
-                                output.current_scale().fractional_scale(),
-                                output.current_transform(),
-                                &output_geometry.size.to_f64().as_logical(),
-                            ),
-                            self.common.clock.now(),
-                        ) {
-                            session.cursor_info(&seat, InputType::Pointer, geometry, offset);
-                        }
-                    }
-                    #[cfg(feature = "debug")]
-                    if self.common.seats().position(|x| x == &seat).unwrap() == 0 {
-                        if let Some(output) = self.common.shell.outputs().next() {
-                            let location = position.to_local(&output).to_i32_round().as_logical();
-                            self.common.egui.state.handle_pointer_motion(location);
-                        }
-                    }
-                }
-            }
-            InputEvent::PointerMotionAbsolute { event, .. } => {
-                if let Some(seat) = self.common.seat_with_device(&event.device()).cloned() {
-                    let output = seat.active_output();
-                    let geometry = output.geometry();
-                    let position = geometry.loc.to_f64()
-                        + smithay::backend::input::AbsolutePositionEvent::position_transformed(
-                            &event,
-                            geometry.size.as_logical(),
-                        )
-                        .as_global();
-                    let overview = self.common.shell.overview_mode();
-                    let workspace = self.common.shell.workspaces.active_mut(&output);
-                    let serial = SERIAL_COUNTER.next_serial();
-                    let under = State::surface_under(
-                        position,
-                        &output,
-                        &self.common.shell.override_redirect_windows,
-                        overview.0,
-                        workspace,
-                        self.common.session_lock.as_ref(),
-                    )
-                    .map(|(target, pos)| (target, pos.as_logical()));
-
-                    for session in sessions_for_output(&self.common, &output) {
-                        if let Some((geometry, offset)) = seat.cursor_geometry(
-                            position.as_logical().to_buffer(
-                                output.current_scale().fractional_scale(),
-                                output.current_transform(),
-                                &geometry.size.to_f64().as_logical(),
-                            ),
-                            self.common.clock.now(),
-                        ) {
-                            session.cursor_info(&seat, InputType::Pointer, geometry, offset);
-                        }
-                    }
-                    let ptr = seat.get_pointer().unwrap();
-                    ptr.motion(
-                        self,
-                        under,
-                        &MotionEvent {
-                            location: position.as_logical(),
-                            serial,
-                            time: event.time_msec(),
-                        },
-                    );
-                    ptr.frame(self);
-                    #[cfg(feature = "debug")]
-                    if self.common.seats().position(|x| x == &seat).unwrap() == 0 {
-                        if let Some(output) = self.common.shell.outputs().next() {
-                            let location = position.to_local(&output).to_i32_round().as_logical();
-                            self.common.egui.state.handle_pointer_motion(location);
-                        }
-                    }
-                }
-            }
-            InputEvent::PointerButton { event, .. } => {
-                use smithay::backend::input::{ButtonState, PointerButtonEvent};
+                    self.pointer_button(&seat, button, event.state(), event.time_msec());
+                }
+            }
+            InputEvent::PointerAxis { event, .. } => {
+                #[allow(deprecated)]
+                let scroll_factor = if let Some(event) =
+                    <dyn Any>::downcast_ref::<LibinputPointerAxisEvent>(&event)
+                {
+                    self.common.config.scroll_factor(&event.device())
+                } else {
+                    1.0
+                };
 
                 if let Some(seat) = self.common.seat_with_device(&event.device()).cloned() {
-                    #[cfg(feature = "debug")]
-                    if self.common.seats().position(|x| x == &seat).unwrap() == 0
-                        && self.common.egui.active
-                    {
-                        if self.common.egui.state.wants_pointer() {
-                            if let Some(button) = event.button() {
-                                self.common.egui.state.handle_pointer_button(
-                                    button,
-                                    event.state() == ButtonState::Pressed,
-                                );
-                            }
-                            return;
-                        }
+                    if self.typing_suppresses(&seat, &event.device()) {
+                        return;
                     }
-
-                    let serial = SERIAL_COUNTER.next_serial();
-                    let button = event.button_code();
-                    if event.state() == ButtonState::Pressed {
-                        // change the keyboard focus unless the pointer or keyboard is grabbed
-                        // We test for any matching surface type here but always use the root
-                        // (in case of a window the toplevel) surface for the focus.
-                        // see: https://gitlab.freedesktop.org/wayland/wayland/-/issues/294
-                        if !seat.get_pointer().unwrap().is_grabbed()
-                            && !seat.get_keyboard().map(|k| k.is_grabbed()).unwrap_or(false)
-                        {
-                            let output = seat.active_output();
-                            let pos = seat.get_pointer().unwrap().current_location().as_global();
-                            let relative_pos = pos.to_local(&output);
-                            let overview = self.common.shell.overview_mode();
-                            let workspace = self.common.shell.active_space_mut(&output);
-                            let mut under = None;
-
-                            if let Some(session_lock) = self.common.session_lock.as_ref() {
-                                under = session_lock
-                                    .surfaces
-                                    .get(&output)
-                                    .map(|lock| lock.clone().into());
-                            } else if let Some(window) = workspace.get_fullscreen() {
-                                let layers = layer_map_for_output(&output);
-                                if let Some(layer) =
-                                    layers.layer_under(WlrLayer::Overlay, relative_pos.as_logical())
-                                {
-                                    let layer_loc = layers.layer_geometry(layer).unwrap().loc;
-                                    if layer.can_receive_keyboard_focus()
-                                        && layer
-                                            .surface_under(
-                                                relative_pos.as_logical() - layer_loc.to_f64(),
-                                                WindowSurfaceType::ALL,
-                                            )
-                                            .is_some()
-                                    {
-                                        under = Some(layer.clone().into());
-                                    }
-                                } else {
-                                    under = Some(window.clone().into());
-                                }
-                            } else {
-                                let done = {
-                                    let layers = layer_map_for_output(&output);
-                                    if let Some(layer) = layers
-                                        .layer_under(WlrLayer::Overlay, relative_pos.as_logical())
-                                        .or_else(|| {
-                                            layers.layer_under(
-                                                WlrLayer::Top,
-                                                relative_pos.as_logical(),
-                                            )
-                                        })
-                                    {
-                                        let layer_loc = layers.layer_geometry(layer).unwrap().loc;
-                                        if layer.can_receive_keyboard_focus()
-                                            && layer
-                                                .surface_under(
-                                                    relative_pos.as_logical() - layer_loc.to_f64(),
-                                                    WindowSurfaceType::ALL,
-                                                )
-                                                .is_some()
-                                        {
-                                            under = Some(layer.clone().into());
-                                        }
-                                        true
-                                    } else {
-                                        false
-                                    }
-                                };
-                                if !done {
-                                    if let Some((target, _)) =
-                                        workspace.element_under(pos, overview.0)
-                                    {
-                                        under = Some(target);
-                                    } else {
-                                        let layers = layer_map_for_output(&output);
-                                        if let Some(layer) = layers
-                                            .layer_under(
-                                                WlrLayer::Bottom,
-                                                relative_pos.as_logical(),
-                                            )
-                                            .or_else(|| {
-                                                layers.layer_under(
-                                                    WlrLayer::Background,
-                                                    relative_pos.as_logical(),
-                                                )
-                                            })
-                                        {
-                                            let layer_loc =
-                                                layers.layer_geometry(layer).unwrap().loc;
-                                            if layer.can_receive_keyboard_focus()
-                                                && layer
-                                                    .surface_under(
-                                                        relative_pos.as_logical()
-                                                            - layer_loc.to_f64(),
-                                                        WindowSurfaceType::ALL,
-                                                    )
-                                                    .is_some()
-                                            {
-                                                under = Some(layer.clone().into());
-                                            }
-                                        };
-                                    }
-                                }
-                            }
-                            Common::set_focus(
-                                self,
-                                under.and_then(|target| target.try_into().ok()).as_ref(),
-                                &seat,
-                                Some(serial),
-                            );
-                        }
-                    } else {
-                        if let OverviewMode::Started(Trigger::Pointer(action_button), _) =
-                            self.common.shell.overview_mode().0
-                        {
-                            if action_button == button {
-                                self.common
-                                    .shell
-                                    .set_overview_mode(None, self.common.event_loop_handle.clone());
-                            }
-                        }
-                    };
-                    let ptr = seat.get_pointer().unwrap();
-                    ptr.button(
-                        self,
-                        &ButtonEvent {
-                            button,
-                            state: event.state(),
-                            serial,
-                            time: event.time_msec(),
-                        },
-                    );
-                    ptr.frame(self);
-                }
-            }
-            InputEvent::PointerAxis { event, .. } => {
-                #[allow(deprecated)]
-                let scroll_factor = if let Some(event) =
-                    <dyn Any>::downcast_ref::<LibinputPointerAxisEvent>(&event)
-                {
-                    self.common.config.scroll_factor(&event.device())
-                } else {
-                    1.0
-                };
-
-                if let Some(seat) = self.common.seat_with_device(&event.device()) {
                     #[cfg(feature = "debug")]
-                    if self.common.seats().position(|x| x == seat).unwrap() == 0
-                        && self.common.egui.active
-                    {
+                    if self.common.seat_uses_egui(&seat) {
                         if self.common.egui.state.wants_pointer() {
                             self.common.egui.state.handle_pointer_axis(
                                 event
@@ -995,6 +853,11 @@ impl State {
                         }
                     }
 
+                    // windows may have moved below the resting pointer since its last motion
+                    if self.common.config.static_conf.scroll_under_pointer {
+                        self.refresh_pointer_focus(&seat, event.time_msec());
+                    }
+
                     let horizontal_amount = event.amount(Axis::Horizontal).unwrap_or_else(|| {
                         event.amount_discrete(Axis::Horizontal).unwrap_or(0.0) * 3.0
                     });
@@ -1023,10 +886,19 @@ impl State {
                         } else if event.source() == AxisSource::Finger {
                             frame = frame.stop(Axis::Vertical);
                         }
+                        seat.user_data().get::<ModifierTap>().unwrap().cancel();
                         let ptr = seat.get_pointer().unwrap();
                         ptr.axis(self, frame);
                         ptr.frame(self);
                     }
+
+                    if event.source() == AxisSource::Wheel {
+                        self.schedule_wheel_stop(
+                            &seat,
+                            horizontal_amount != 0.0,
+                            vertical_amount != 0.0,
+                        );
+                    }
                 }
             }
             InputEvent::GestureSwipeBegin { event, .. } => {
@@ -1139,23 +1011,1625 @@ impl State {
                     );
                 }
             }
-            _ => { /* TODO e.g. tablet or touch events */ }
+            InputEvent::TouchDown { event, .. }
+                if self.common.config.static_conf.touch_as_pointer =>
+            {
+                use smithay::backend::input::TouchEvent;
+
+                if let Some(seat) = self.common.seat_with_device(&event.device()).cloned() {
+                    let touch = seat.user_data().get::<TouchPointer>().unwrap();
+                    // only the first finger drives the pointer
+                    if touch.0.get().is_some() {
+                        return;
+                    }
+                    touch.0.set(Some(event.slot()));
+                    if self.common.config.static_conf.hide_pointer_on_touch {
+                        seat.user_data().get::<PointerHidden>().unwrap().0.set(true);
+                    }
+
+                    #[allow(deprecated)]
+                    let mapped_output = <dyn Any>::downcast_ref::<LibinputTouchDownEvent>(&event)
+                        .and_then(|event| self.common.config.map_to_output(&event.device()));
+                    let (output, position) = self.absolute_position(&seat, &event, mapped_output);
+                    self.pointer_motion_absolute(&seat, &output, position, event.time_msec());
+                    self.pointer_button(&seat, BTN_LEFT, ButtonState::Pressed, event.time_msec());
+                }
+            }
+            InputEvent::TouchMotion { event, .. }
+                if self.common.config.static_conf.touch_as_pointer =>
+            {
+                use smithay::backend::input::TouchEvent;
+
+                if let Some(seat) = self.common.seat_with_device(&event.device()).cloned() {
+                    let touch = seat.user_data().get::<TouchPointer>().unwrap();
+                    if touch.0.get() != Some(event.slot()) {
+                        return;
+                    }
+
+                    #[allow(deprecated)]
+                    let mapped_output = <dyn Any>::downcast_ref::<LibinputTouchMotionEvent>(&event)
+                        .and_then(|event| self.common.config.map_to_output(&event.device()));
+                    let (output, position) = self.absolute_position(&seat, &event, mapped_output);
+                    self.pointer_motion_absolute(&seat, &output, position, event.time_msec());
+                }
+            }
+            InputEvent::TouchUp { event, .. }
+                if self.common.config.static_conf.touch_as_pointer =>
+            {
+                use smithay::backend::input::TouchEvent;
+
+                if let Some(seat) = self.common.seat_with_device(&event.device()).cloned() {
+                    let touch = seat.user_data().get::<TouchPointer>().unwrap();
+                    if touch.0.get() == Some(event.slot()) {
+                        touch.0.set(None);
+                        self.pointer_button(
+                            &seat,
+                            BTN_LEFT,
+                            ButtonState::Released,
+                            event.time_msec(),
+                        );
+                    }
+                }
+            }
+            InputEvent::TouchCancel { event, .. }
+                if self.common.config.static_conf.touch_as_pointer =>
+            {
+                use smithay::backend::input::TouchEvent;
+
+                if let Some(seat) = self.common.seat_with_device(&event.device()).cloned() {
+                    let touch = seat.user_data().get::<TouchPointer>().unwrap();
+                    if touch.0.get() == Some(event.slot()) {
+                        touch.0.set(None);
+                        self.pointer_button(
+                            &seat,
+                            BTN_LEFT,
+                            ButtonState::Released,
+                            event.time_msec(),
+                        );
+                    }
+                }
+            }
+            _ => { /* TODO e.g. tablet or native touch events */ }
         }
     }
 
-    pub fn handle_action(
+    /// Output and global position of an absolute event of `seat`,
+    /// on the output named `mapped_output` if any or else the active output.
+    fn absolute_position<B: InputBackend, E: AbsolutePositionEvent<B>>(
+        &self,
+        seat: &Seat<State>,
+        event: &E,
+        mapped_output: Option<String>,
+    ) -> (Output, Point<f64, Global>) {
+        mapped_output
+            .and_then(|name| absolute_position_on_output(event, self.common.shell.outputs(), &name))
+            .unwrap_or_else(|| {
+                let output = seat.active_output();
+                let geometry = output.geometry();
+                let position = geometry.loc.to_f64()
+                    + event
+                        .position_transformed(geometry.size.as_logical())
+                        .as_global();
+                (output, position)
+            })
+    }
+
+    /// Moves the pointer of `seat` to the absolute `position` on `output`.
+    fn pointer_motion_absolute(
         &mut self,
-        action: Action,
         seat: &Seat<State>,
-        serial: Serial,
+        output: &Output,
+        position: Point<f64, Global>,
         time: u32,
-        pattern: KeyPattern,
-        direction: Option<Direction>,
     ) {
-        // TODO: Detect if started from login manager or tty, and only allow
-        // `Terminate` if it will return to login manager.
-        if self.common.session_lock.is_some()
-            && !matches!(action, Action::Terminate | Action::Debug)
+        if *output != seat.active_output() {
+            seat.set_active_output(output);
+        }
+        let geometry = output.geometry();
+        let overview = self.common.shell.overview_mode();
+        let workspace = self.common.shell.workspaces.active_mut(output);
+        let serial = SERIAL_COUNTER.next_serial();
+        let under = State::surface_under(
+            position,
+            output,
+            &self.common.shell.override_redirect_windows,
+            overview.0,
+            workspace,
+            self.common.session_lock.as_ref(),
+        )
+        .map(|(target, pos)| (target, pos.as_logical()));
+
+        for session in sessions_for_output(&self.common, output) {
+            if let Some((geometry, offset)) = seat.cursor_geometry(
+                position.as_logical().to_buffer(
+                    output.current_scale().fractional_scale(),
+                    output.current_transform(),
+                    &geometry.size.to_f64().as_logical(),
+                ),
+                self.common.clock.now(),
+            ) {
+                session.cursor_info(seat, InputType::Pointer, geometry, offset);
+            }
+        }
+        let ptr = seat.get_pointer().unwrap();
+        ptr.motion(
+            self,
+            under,
+            &MotionEvent {
+                location: position.as_logical(),
+                serial,
+                time,
+            },
+        );
+        ptr.frame(self);
+        self.update_pointer_selection_focus(seat);
+        self.hot_corners(seat, output, position, (0.0, 0.0).into());
+        #[cfg(feature = "debug")]
+        if self.common.seat_uses_egui(seat) {
+            let output = seat.active_output();
+            let location = position.to_local(&output).to_i32_round().as_logical();
+            self.common.egui.state.handle_pointer_motion(location);
+        }
+    }
+
+    /// Handles a key event of `seat`, after it passed the slow keys delay.
+    fn keyboard_key(
+        &mut self,
+        seat: &Seat<State>,
+        keycode: u32,
+        state: KeyState,
+        time: u32,
+        needs_key_repetition: bool,
+    ) {
+        let loop_handle = self.common.event_loop_handle.clone();
+        let userdata = seat.user_data();
+
+        let current_output = seat.active_output();
+        let workspace = self.common.shell.active_space_mut(&current_output);
+        let shortcuts_inhibited = workspace
+            .focus_stack
+            .get(seat)
+            .last()
+            .and_then(|window| {
+                window
+                    .wl_surface()
+                    .and_then(|surface| seat.keyboard_shortcuts_inhibitor_for_surface(&surface))
+            })
+            .map(|inhibitor| inhibitor.is_active())
+            .unwrap_or(false);
+        let keybindings_inhibited = self.common.keybindings_inhibited(seat);
+
+        crate::logger::record_input(format_args!("key keycode={} state={:?}", keycode, state));
+
+        let serial = SERIAL_COUNTER.next_serial();
+        let keyboard = seat.get_keyboard().unwrap();
+        let current_focus = keyboard.current_focus();
+        let previous_focus = keyboard.current_focus();
+        if let Some((action, pattern)) = keyboard
+                .input(
+                    self,
+                    keycode,
+                    state,
+                    serial,
+                    time,
+                    |data, modifiers, handle| {
+                        *userdata.get::<CurrentModifiers>().unwrap().0.borrow_mut() = modifiers.clone();
+
+                        // Latched and locked modifiers stay pressed in the keymap, so they apply to bindings and clients alike
+                        if userdata.get::<Accessibility>().unwrap().sticky_keys(&handle, state, &data.common.config.static_conf.accessibility) {
+                            return FilterResult::Intercept(None);
+                        }
+
+                        // Numpad keys move the pointer instead of reaching clients, while mouse keys are active
+                        if state == KeyState::Pressed && keycode == KEY_NUMLOCK && modifiers.shift && data.common.config.static_conf.accessibility.mouse_keys {
+                            userdata.get::<SupressedKeys>().unwrap().add(&handle, None);
+                            return FilterResult::Intercept(Some((
+                                Action::ToggleMouseKeys,
+                                KeyPattern {
+                                    modifiers: modifiers.clone().into(),
+                                    key: Some(Keysym::Num_Lock),
+                                },
+                            )));
+                        }
+                        if is_mouse_keys_key(keycode) && userdata.get::<MouseKeys>().unwrap().is_active() {
+                            return FilterResult::Intercept(Some((
+                                Action::_MouseKeys(keycode, state),
+                                KeyPattern {
+                                    modifiers: modifiers.clone().into(),
+                                    key: Some(Keysym::new(handle.raw_code().raw())),
+                                },
+                            )));
+                        }
+
+                        // Leave move overview mode, if any modifier was released
+                        if let OverviewMode::Started(Trigger::KeyboardMove(action_modifiers), _) =
+                            data.common.shell.overview_mode().0
+                        {
+                            if (action_modifiers.ctrl && !modifiers.ctrl)
+                                || (action_modifiers.alt && !modifiers.alt)
+                                || (action_modifiers.logo && !modifiers.logo)
+                                || (action_modifiers.shift && !modifiers.shift)
+                            {
+                                data.common.shell.set_overview_mode(None, data.common.event_loop_handle.clone());
+                            }
+                        }
+                        // Leave swap overview mode, if any key was released
+                        if let OverviewMode::Started(Trigger::KeyboardSwap(action_pattern, old_descriptor), _) =
+                            data.common.shell.overview_mode().0
+                        {
+                            if (action_pattern.modifiers.ctrl && !modifiers.ctrl)
+                                || (action_pattern.modifiers.alt && !modifiers.alt)
+                                || (action_pattern.modifiers.logo && !modifiers.logo)
+                                || (action_pattern.modifiers.shift && !modifiers.shift)
+                                || (action_pattern.key.is_some() && handle.raw_syms().contains(&action_pattern.key.unwrap()) && state == KeyState::Released)
+                            {
+                                data.common.shell.set_overview_mode(None, data.common.event_loop_handle.clone());
+
+                                if let Some(focus) = current_focus {
+                                    if let Some(new_descriptor) = data.common.shell.workspaces.active(&current_output).1.node_desc(focus) {
+                                        let mut spaces = data.common.shell.workspaces.spaces_mut();
+                                        if old_descriptor.handle != new_descriptor.handle {
+                                            let (mut old_w, mut other_w) = spaces.partition::<Vec<_>, _>(|w| w.handle == old_descriptor.handle);
+                                            if let Some(old_workspace) = old_w.get_mut(0) {
+                                                if let Some(new_workspace) = other_w.iter_mut().find(|w| w.handle == new_descriptor.handle) {
+                                                    if let Some(focus) = TilingLayout::swap_trees(&mut old_workspace.tiling_layer, Some(&mut new_workspace.tiling_layer), &old_descriptor, &new_descriptor, &mut data.common.shell.toplevel_info_state) {
+                                                        let seat = seat.clone();
+                                                        data.common.event_loop_handle.insert_idle(move |state| {
+                                                            Common::set_focus(state, Some(&focus), &seat, None);
+                                                        });
+                                                    }
+                                                    old_workspace.refresh_focus_stack();
+                                                    new_workspace.refresh_focus_stack();
+                                                }
+                                            }
+                                        } else {
+                                            if let Some(workspace) = spaces.find(|w| w.handle == new_descriptor.handle) {
+                                                if let Some(focus) = TilingLayout::swap_trees(&mut workspace.tiling_layer, None, &old_descriptor, &new_descriptor, &mut data.common.shell.toplevel_info_state) {
+                                                    std::mem::drop(spaces);
+                                                    let seat = seat.clone();
+                                                    data.common.event_loop_handle.insert_idle(move |state| {
+                                                        Common::set_focus(state, Some(&focus), &seat, None);
+                                                    });
+                                                }
+                                                workspace.refresh_focus_stack();
+                                            }
+                                        }
+                                    }
+                                } else {
+                                    let new_workspace = data.common.shell.workspaces.active(&current_output).1.handle;
+                                    if new_workspace != old_descriptor.handle {
+                                        let spaces = data.common.shell.workspaces.spaces_mut();
+                                        let (mut old_w, mut other_w) = spaces.partition::<Vec<_>, _>(|w| w.handle == old_descriptor.handle);
+                                        if let Some(old_workspace) = old_w.get_mut(0) {
+                                            if let Some(new_workspace) = other_w.iter_mut().find(|w| w.handle == new_workspace) {
+                                                if new_workspace.tiling_layer.windows().next().is_none() {
+                                                    if let Some(focus) = TilingLayout::move_tree(&mut old_workspace.tiling_layer, &mut new_workspace.tiling_layer, &new_workspace.handle, seat, new_workspace.focus_stack.get(seat).iter(), old_descriptor, &mut data.common.shell.toplevel_info_state) {
+                                                        let seat = seat.clone();
+                                                        data.common.event_loop_handle.insert_idle(move |state| {
+                                                            Common::set_focus(state, Some(&focus), &seat, None);
+                                                        });
+                                                    }
+                                                    old_workspace.refresh_focus_stack();
+                                                }
+                                            }
+                                        }
+                                    }
+                                }
+                            }
+                        }
+
+                        // Modal resizing consumes the arrow keys, Enter and Escape
+                        if let Some(pattern) = userdata.get::<ModalResize>().unwrap().pattern() {
+                            let direction = if modifiers.shift {
+                                ResizeDirection::Inwards
+                            } else {
+                                ResizeDirection::Outwards
+                            };
+                            if !matches!(data.common.shell.resize_mode().0, ResizeMode::Started(_, _, current) if current == direction) {
+                                data.common.shell.set_resize_mode(Some((pattern, direction)), &data.common.config, data.common.event_loop_handle.clone());
+                            }
+
+                            if state == KeyState::Released {
+                                if userdata.get::<SupressedKeys>().unwrap().filter(&handle).is_some() {
+                                    return FilterResult::Intercept(None);
+                                }
+                            } else {
+                                let action = match handle.modified_sym() {
+                                    Keysym::Return | Keysym::KP_Enter => Some(Action::_ExitResizeMode(true)),
+                                    Keysym::Escape => Some(Action::_ExitResizeMode(false)),
+                                    sym => match sym {
+                                        Keysym::Left | Keysym::h | Keysym::H => Some(ResizeEdge::LEFT),
+                                        Keysym::Down | Keysym::j | Keysym::J => Some(ResizeEdge::BOTTOM),
+                                        Keysym::Up | Keysym::k | Keysym::K => Some(ResizeEdge::TOP),
+                                        Keysym::Right | Keysym::l | Keysym::L => Some(ResizeEdge::RIGHT),
+                                        _ => None,
+                                    }
+                                    .map(|mut edge| {
+                                        if direction == ResizeDirection::Inwards {
+                                            edge.flip_direction();
+                                        }
+                                        Action::_ResizeModeStep(direction, edge)
+                                    }),
+                                };
+                                if let Some(action) = action {
+                                    userdata.get::<SupressedKeys>().unwrap().add(&handle, None);
+                                    return FilterResult::Intercept(Some((
+                                        action,
+                                        KeyPattern {
+                                            modifiers: modifiers.clone().into(),
+                                            key: Some(Keysym::new(handle.raw_code().raw())),
+                                        },
+                                    )));
+                                }
+                            }
+                        }
+
+                        // Leave or update resize mode, if modifiers changed or initial key was released
+                        if let (ResizeMode::Started(action_pattern, _, _), None) =
+                            (data.common.shell.resize_mode().0, userdata.get::<ModalResize>().unwrap().pattern())
+                        {
+                            if action_pattern.key.is_some() && state == KeyState::Released
+                                && handle.raw_syms().contains(&action_pattern.key.unwrap())
+                            {
+                                data.common.shell.set_resize_mode(None, &data.common.config, data.common.event_loop_handle.clone());
+                            } else if action_pattern.modifiers != *modifiers {
+                                let mut new_pattern = action_pattern.clone();
+                                new_pattern.modifiers = modifiers.clone().into();
+                                let enabled = data
+                                    .common
+                                    .config
+                                    .static_conf
+                                    .key_bindings
+                                    .iter()
+                                    .find_map(move |(binding, action)| {
+                                        if binding == &new_pattern
+                                            && matches!(action, Action::Resizing(_))
+                                        {
+                                            let Action::Resizing(direction) = action else { unreachable!() };
+                                            Some((new_pattern.clone(), *direction))
+                                        } else {
+                                            None
+                                        }
+                                    });
+                                data.common.shell.set_resize_mode(enabled, &data.common.config, data.common.event_loop_handle.clone());
+                            }
+                        }
+
+                        // Special case resizing with regards to arrow keys
+                        if let (ResizeMode::Started(_, _, direction), _) =
+                            data.common.shell.resize_mode()
+                        {
+                            let resize_edge = match handle.modified_sym() {
+                                Keysym::Left | Keysym::h | Keysym::H => Some(ResizeEdge::LEFT),
+                                Keysym::Down | Keysym::j | Keysym::J => Some(ResizeEdge::BOTTOM),
+                                Keysym::Up | Keysym::k | Keysym::K => Some(ResizeEdge::TOP),
+                                Keysym::Right | Keysym::l | Keysym::L => Some(ResizeEdge::RIGHT),
+                                _ => None,
+                            };
+
+                            if let Some(mut edge) = resize_edge {
+                                if direction == ResizeDirection::Inwards {
+                                    edge.flip_direction();
+                                }
+                                let action = Action::_ResizingInternal(direction, edge, state);
+                                let key_pattern = KeyPattern {
+                                    modifiers: modifiers.clone().into(),
+                                    key: Some(Keysym::new(handle.raw_code().raw())),
+                                };
+
+                                if state == KeyState::Released {
+                                    if let Some(tokens) = userdata.get::<SupressedKeys>().unwrap().filter(&handle) {
+                                        for token in tokens {
+                                            loop_handle.remove(token);
+                                        }
+                                    }
+                                } else {
+                                    let token = if needs_key_repetition {
+                                        let seat_clone = seat.clone();
+                                        let action_clone = action.clone();
+                                        let key_pattern_clone = key_pattern.clone();
+                                        let start = Instant::now();
+                                        loop_handle.insert_source(Timer::from_duration(Duration::from_millis(200)), move |current, _, state| {
+                                            let duration = current.duration_since(start).as_millis();
+                                            state.handle_action(action_clone.clone(), &seat_clone, serial, time.overflowing_add(duration as u32).0, key_pattern_clone.clone(), None);
+                                            calloop::timer::TimeoutAction::ToDuration(Duration::from_millis(25))
+                                        }).ok()
+                                    } else { None };
+
+                                    userdata
+                                            .get::<SupressedKeys>()
+                                            .unwrap()
+                                            .add(&handle, token);
+                                }
+                                return FilterResult::Intercept(Some((
+                                    action,
+                                    key_pattern
+                                )));
+                            }
+                        }
+
+                        // Skip released events for initially surpressed keys
+                        if state == KeyState::Released {
+                            if let Some(tokens) = userdata.get::<SupressedKeys>().unwrap().filter(&handle) {
+                                for token in tokens {
+                                    loop_handle.remove(token);
+                                }
+                                return FilterResult::Intercept(None);
+                            }
+                        }
+
+                        // Pass keys to debug interface, if it has focus
+                        #[cfg(feature = "debug")]
+                        {
+                            if data.common.seat_uses_egui(seat) {
+                                if data.common.egui.state.wants_keyboard() {
+                                    data.common.egui.state.handle_keyboard(
+                                        &handle,
+                                        state == KeyState::Pressed,
+                                        modifiers.clone(),
+                                    );
+                                    userdata
+                                        .get::<SupressedKeys>()
+                                        .unwrap()
+                                        .add(&handle, None);
+                                    return FilterResult::Intercept(None);
+                                }
+                            }
+                        }
+
+                        // Handle VT switches
+                        if state == KeyState::Pressed
+                            && (Keysym::XF86_Switch_VT_1.raw() ..= Keysym::XF86_Switch_VT_12.raw())
+                                .contains(&handle.modified_sym().raw())
+                        {
+                            if let Err(err) = data.backend.kms().switch_vt(
+                                (handle.modified_sym().raw() - Keysym::XF86_Switch_VT_1.raw()
+                                    + 1)
+                                    as i32,
+                            ) {
+                                error!(?err, "Failed switching virtual terminal.");
+                            }
+                            userdata.get::<SupressedKeys>().unwrap().add(&handle, None);
+                            return FilterResult::Intercept(None);
+                        }
+
+                        // Tapping a lone modifier triggers its tap binding on release.
+                        // The release still reaches the client, which saw the press.
+                        let tapped = userdata.get::<ModifierTap>().unwrap().update(handle.modified_sym(), state, modifiers);
+                        if let Some(modifier) = tapped.filter(|_| !shortcuts_inhibited && !keybindings_inhibited && data.common.shortcuts_enabled) {
+                            if let Some(action) = data.common.config.static_conf.tap_bindings.get(&modifier).cloned() {
+                                let seat = seat.clone();
+                                data.common.event_loop_handle.insert_idle(move |state| {
+                                    state.handle_action(action, &seat, serial, time, KeyPattern::new(modifier, None), None);
+                                });
+                            }
+                        }
+
+                        // handle the rest of the global shortcuts, unless the bypass modifier is held
+                        let bypassed = data
+                            .common
+                            .config
+                            .static_conf
+                            .bypass_modifier
+                            .is_some_and(|modifier| modifier.is_held(modifiers));
+                        let mut can_clear_modifiers_shortcut = true;
+                        if !bypassed {
+                            let modifiers_queue = userdata.get::<ModifiersShortcutQueue>().unwrap();
+                            let shortcuts_enabled = data.common.shortcuts_enabled;
+                            for (binding, action) in
+                                data.common.config.static_conf.key_bindings.iter().filter(|(binding, action)| {
+                                    binding_active(binding, action, shortcuts_inhibited, shortcuts_enabled, keybindings_inhibited)
+                                })
+                            {
+                                let modifiers_bypass = binding.key.is_none()
+                                    && state == KeyState::Released
+                                    && binding.modifiers != *modifiers
+                                    && modifiers_queue.take(binding);
+
+                                if !modifiers_bypass && binding.key.is_none() && state == KeyState::Pressed && binding.modifiers == *modifiers {
+                                    modifiers_queue.set(binding.clone());
+                                    can_clear_modifiers_shortcut = false;
+                                }
+
+                                if (state == KeyState::Pressed && binding_pressed(binding, handle.raw_syms(), modifiers))
+                                    || modifiers_bypass
+                                {
+                                    modifiers_queue.clear();
+                                    userdata
+                                        .get::<SupressedKeys>()
+                                        .unwrap()
+                                        .add(&handle, None);
+                                    return FilterResult::Intercept(Some((
+                                        action.clone(),
+                                        binding.clone(),
+                                    )));
+                                }
+                            }
+                        }
+
+                        if can_clear_modifiers_shortcut {
+                            userdata.get::<ModifiersShortcutQueue>().unwrap().clear();
+                        }
+
+                        // keys are passed through to apps, or the input method first,
+                        // if it grabbed the keyboard for an active text input of the focused surface
+                        FilterResult::Forward
+                    },
+                )
+                .flatten()
+            {
+                self.handle_action(action, seat, serial, time, pattern, None);
+                self.warp_pointer_to_focus(seat, previous_focus, time);
+            }
+
+        let queued = userdata.get::<Accessibility>().unwrap().take_queued();
+        if !queued.is_empty() {
+            for (code, state) in queued {
+                keyboard.input::<(), _>(
+                    self,
+                    code.raw() - 8,
+                    state,
+                    SERIAL_COUNTER.next_serial(),
+                    time,
+                    |_, _, _| FilterResult::Forward,
+                );
+            }
+            *userdata.get::<CurrentModifiers>().unwrap().0.borrow_mut() = keyboard.modifier_state();
+        }
+        self.update_keyboard_leds(seat);
+    }
+
+    /// Shows the name of the active workspace of `output` in the osd.
+    fn show_workspace_osd(&mut self, output: &Output) {
+        let (handle, idx) = {
+            let workspaces = &self.common.shell.workspaces;
+            (
+                workspaces.active(output).1.handle,
+                workspaces.active_num(output).1,
+            )
+        };
+        let name = self
+            .common
+            .shell
+            .workspace_state
+            .workspace_name(&handle)
+            .map(str::to_string)
+            .unwrap_or_else(|| (idx + 1).to_string());
+        osd::show(self, output, OsdContent::Workspace(name));
+    }
+
+    /// Holds back key presses until they were held down for `accessibility.slow_keys` milliseconds,
+    /// returns true if the event was consumed.
+    fn slow_keys(
+        &mut self,
+        seat: &Seat<State>,
+        keycode: u32,
+        state: KeyState,
+        needs_key_repetition: bool,
+    ) -> bool {
+        let accessibility = seat.user_data().get::<Accessibility>().unwrap();
+        match state {
+            KeyState::Pressed => {
+                let Some(delay) = self.common.config.static_conf.accessibility.slow_keys else {
+                    return false;
+                };
+                if accessibility.is_suspended() {
+                    return false;
+                }
+
+                let seat_clone = seat.clone();
+                match self.common.event_loop_handle.insert_source(
+                    Timer::from_duration(Duration::from_millis(delay)),
+                    move |_, _, state| {
+                        let accessibility = seat_clone.user_data().get::<Accessibility>().unwrap();
+                        let mut pending = accessibility.0.borrow_mut();
+                        if let Some(i) = pending
+                            .slow_pending
+                            .iter()
+                            .position(|(code, _)| *code == keycode)
+                        {
+                            pending.slow_pending.remove(i);
+                            std::mem::drop(pending);
+                            let time = Duration::from(state.common.clock.now()).as_millis() as u32;
+                            state.keyboard_key(
+                                &seat_clone,
+                                keycode,
+                                KeyState::Pressed,
+                                time,
+                                needs_key_repetition,
+                            );
+                        }
+                        calloop::timer::TimeoutAction::Drop
+                    },
+                ) {
+                    Ok(token) => {
+                        accessibility
+                            .0
+                            .borrow_mut()
+                            .slow_pending
+                            .push((keycode, token));
+                        true
+                    }
+                    Err(err) => {
+                        warn!(?err, "Failed to delay key press for slow keys.");
+                        false
+                    }
+                }
+            }
+            KeyState::Released => {
+                let mut pending = accessibility.0.borrow_mut();
+                // released too early, the key never registered
+                if let Some(i) = pending
+                    .slow_pending
+                    .iter()
+                    .position(|(code, _)| *code == keycode)
+                {
+                    let (_, token) = pending.slow_pending.remove(i);
+                    self.common.event_loop_handle.remove(token);
+                    true
+                } else {
+                    false
+                }
+            }
+        }
+    }
+
+    /// Runs the hot corner action, once the pointer rested in a corner or was pushed against it.
+    fn hot_corners(
+        &mut self,
+        seat: &Seat<State>,
+        output: &Output,
+        position: Point<f64, Global>,
+        delta: Point<f64, Global>,
+    ) {
+        let config = &self.common.config.static_conf.hot_corners;
+        let hot_corners = seat.user_data().get::<HotCorners>().unwrap();
+        // grabs include drag-and-drop and held buttons
+        let corner = (config.enabled_for(&output.name())
+            && !seat.get_pointer().unwrap().is_grabbed())
+        .then(|| hot_corner_at(output.geometry(), position))
+        .flatten()
+        .filter(|corner| config.actions.contains_key(corner));
+
+        let mut hot_corners = hot_corners.0.borrow_mut();
+        if hot_corners
+            .current
+            .as_ref()
+            .map(|(output, corner)| (output, *corner))
+            != corner.map(|corner| (output, corner))
+        {
+            if let Some(token) = hot_corners.timer.take() {
+                self.common.event_loop_handle.remove(token);
+            }
+            hot_corners.current = corner.map(|corner| (output.clone(), corner));
+            hot_corners.pressure = 0.0;
+            hot_corners.triggered = false;
+
+            if let Some(corner) = corner.filter(|corner| !corner.is_edge()) {
+                let seat_clone = seat.clone();
+                match self.common.event_loop_handle.insert_source(
+                    Timer::from_duration(Duration::from_millis(config.delay)),
+                    move |_, _, state| {
+                        let hot_corners = seat_clone.user_data().get::<HotCorners>().unwrap();
+                        let mut hot_corners = hot_corners.0.borrow_mut();
+                        hot_corners.timer = None;
+                        if !hot_corners.triggered && !seat_clone.get_pointer().unwrap().is_grabbed()
+                        {
+                            hot_corners.triggered = true;
+                            std::mem::drop(hot_corners);
+                            state.trigger_hot_corner(&seat_clone, corner);
+                        }
+                        calloop::timer::TimeoutAction::Drop
+                    },
+                ) {
+                    Ok(token) => hot_corners.timer = Some(token),
+                    Err(err) => warn!(?err, "Failed to start hot corner timer."),
+                }
+            }
+        }
+
+        let Some((_, corner)) = hot_corners.current.clone() else {
+            return;
+        };
+        if hot_corners.triggered {
+            return;
+        }
+        hot_corners.pressure += corner.pressure(delta);
+        if hot_corners.pressure >= config.pressure {
+            hot_corners.triggered = true;
+            if let Some(token) = hot_corners.timer.take() {
+                self.common.event_loop_handle.remove(token);
+            }
+            std::mem::drop(hot_corners);
+            self.trigger_hot_corner(seat, corner);
+        }
+    }
+
+    fn trigger_hot_corner(&mut self, seat: &Seat<State>, corner: HotCorner) {
+        let Some(action) = self
+            .common
+            .config
+            .static_conf
+            .hot_corners
+            .actions
+            .get(&corner)
+            .cloned()
+        else {
+            return;
+        };
+        let serial = SERIAL_COUNTER.next_serial();
+        let time = Duration::from(self.common.clock.now()).as_millis() as u32;
+        self.handle_action(
+            action,
+            seat,
+            serial,
+            time,
+            KeyPattern::new(KeyModifiers::default(), None),
+            None,
+        );
+    }
+
+    /// Updates the pointer focus of `seat` to the surface below it, without moving the pointer
+    /// or changing the keyboard focus.
+    fn refresh_pointer_focus(&mut self, seat: &Seat<State>, time: u32) {
+        let ptr = seat.get_pointer().unwrap();
+        if ptr.is_grabbed() {
+            return;
+        }
+
+        let output = seat.active_output();
+        let position = ptr.current_location().as_global();
+        let overview = self.common.shell.overview_mode();
+        let workspace = self.common.shell.workspaces.active_mut(&output);
+        let under = State::surface_under(
+            position,
+            &output,
+            &self.common.shell.override_redirect_windows,
+            overview.0,
+            workspace,
+            self.common.session_lock.as_ref(),
+        )
+        .map(|(target, pos)| (target, pos.as_logical()));
+
+        ptr.motion(
+            self,
+            under,
+            &MotionEvent {
+                location: position.as_logical(),
+                serial: SERIAL_COUNTER.next_serial(),
+                time,
+            },
+        );
+        ptr.frame(self);
+    }
+
+    /// Focuses the window on the active workspace of `output`,
+    /// that is the closest to the current keyboard focus.
+    fn focus_adjacent_output(&mut self, seat: &Seat<State>, output: &Output) {
+        let center = |geo: Rectangle<i32, Global>| geo.loc + geo.size.downscale(2).to_point();
+        let previous = match seat.get_keyboard().unwrap().current_focus() {
+            Some(KeyboardFocusTarget::Element(mapped)) => {
+                self.common.shell.space_for(&mapped).and_then(|workspace| {
+                    workspace
+                        .element_geometry(&mapped)
+                        .map(|geo| geo.to_global(workspace.output()))
+                })
+            }
+            _ => None,
+        }
+        .unwrap_or_else(|| seat.active_output().geometry());
+        let previous = center(previous);
+
+        seat.set_active_output(output);
+        let workspace = self.common.shell.active_space(output);
+        let nearest = workspace
+            .get_fullscreen()
+            .is_none()
+            .then(|| {
+                workspace
+                    .mapped()
+                    .filter_map(|mapped| {
+                        let geo = workspace.element_geometry(mapped)?.to_global(output);
+                        let offset = center(geo) - previous;
+                        Some((
+                            mapped,
+                            offset.x as i64 * offset.x as i64 + offset.y as i64 * offset.y as i64,
+                        ))
+                    })
+                    .min_by_key(|(_, distance)| *distance)
+                    .map(|(mapped, _)| mapped.clone())
+            })
+            .flatten();
+
+        match nearest {
+            Some(mapped) => Common::set_focus(
+                self,
+                Some(&KeyboardFocusTarget::Element(mapped)),
+                seat,
+                None,
+            ),
+            None => Common::set_workspace_focus(self, seat, output),
+        }
+    }
+
+    /// Moves the pointer onto the keyboard focus, if a key binding changed it,
+    /// see `StaticConfig::mouse_warping`.
+    fn warp_pointer_to_focus(
+        &mut self,
+        seat: &Seat<State>,
+        previous_focus: Option<KeyboardFocusTarget>,
+        time: u32,
+    ) {
+        let mode = self.common.config.static_conf.mouse_warping;
+        if mode == MouseWarping::Disabled {
+            return;
+        }
+        // also covers drag and drop
+        let ptr = seat.get_pointer().unwrap();
+        if ptr.is_grabbed() {
+            return;
+        }
+        let Some(focus) = seat.get_keyboard().unwrap().current_focus() else {
+            return;
+        };
+        if previous_focus.as_ref() == Some(&focus) {
+            return;
+        }
+
+        let (output, geometry) = match &focus {
+            KeyboardFocusTarget::Element(mapped) => {
+                let Some(workspace) = self.common.shell.space_for(mapped) else {
+                    return;
+                };
+                let Some(geometry) = workspace.element_geometry(mapped) else {
+                    return;
+                };
+                (
+                    workspace.output().clone(),
+                    geometry.to_global(workspace.output()),
+                )
+            }
+            KeyboardFocusTarget::Fullscreen(surface) => {
+                let Some(workspace) = self
+                    .common
+                    .shell
+                    .workspaces
+                    .spaces()
+                    .find(|w| w.get_fullscreen() == Some(surface))
+                else {
+                    return;
+                };
+                (workspace.output().clone(), workspace.output().geometry())
+            }
+            _ => return,
+        };
+
+        let position = ptr.current_location().as_global();
+        let target = match mode {
+            MouseWarping::Output if !output.geometry().to_f64().contains(position) => {
+                output.geometry()
+            }
+            MouseWarping::Window if !geometry.to_f64().contains(position) => geometry,
+            _ => return,
+        };
+        let center = target.loc.to_f64() + target.size.to_f64().downscale(2.0).to_point();
+        self.pointer_motion_absolute(seat, &output, center, time);
+    }
+
+    /// Moves the pointer of `seat` by `delta`, respecting pointer constraints and grabs.
+    pub fn pointer_motion(
+        &mut self,
+        seat: &Seat<State>,
+        delta: Point<f64, Logical>,
+        delta_unaccel: Point<f64, Logical>,
+        utime: u64,
+        time: u32,
+    ) {
+        let current_output = seat.active_output();
+
+        let mut position = seat.get_pointer().unwrap().current_location().as_global();
+
+        let overview = self.common.shell.overview_mode();
+        let workspace = self.common.shell.workspaces.active_mut(&current_output);
+        let under = State::surface_under(
+            position,
+            &current_output,
+            &self.common.shell.override_redirect_windows,
+            overview.0.clone(),
+            workspace,
+            self.common.session_lock.as_ref(),
+        )
+        .map(|(target, pos)| (target, pos.as_logical()));
+
+        let ptr = seat.get_pointer().unwrap();
+
+        let mut pointer_locked = false;
+        let mut pointer_confined = false;
+        let mut confine_region = None;
+        if let Some((surface, surface_loc)) = under
+            .as_ref()
+            .and_then(|(target, l)| Some((target.wl_surface()?, l)))
+        {
+            with_pointer_constraint(&surface, &ptr, |constraint| match constraint {
+                Some(constraint) if constraint.is_active() => {
+                    // Constraint does not apply if not within region
+                    if !constraint.region().map_or(true, |x| {
+                        x.contains(ptr.current_location().to_i32_round() - *surface_loc)
+                    }) {
+                        return;
+                    }
+                    match &*constraint {
+                        PointerConstraint::Locked(_locked) => {
+                            pointer_locked = true;
+                        }
+                        PointerConstraint::Confined(confine) => {
+                            pointer_confined = true;
+                            confine_region = confine.region().cloned();
+                        }
+                    }
+                }
+                _ => {}
+            });
+        }
+
+        ptr.relative_motion(
+            self,
+            under.clone(),
+            &RelativeMotionEvent {
+                delta: delta,
+                delta_unaccel: delta_unaccel,
+                utime: utime,
+            },
+        );
+
+        if pointer_locked {
+            ptr.frame(self);
+            return;
+        }
+
+        position += delta.as_global();
+
+        let output = self
+            .common
+            .shell
+            .outputs()
+            .find(|output| {
+                !output.is_powered_off() && output.geometry().to_f64().contains(position)
+            })
+            .cloned()
+            .unwrap_or(current_output.clone());
+
+        if ptr.is_grabbed()
+            && seat
+                .user_data()
+                .get::<ResizeGrabMarker>()
+                .map(|marker| marker.get())
+                .unwrap_or(false)
+        {
+            if output != current_output {
+                ptr.frame(self);
+                return;
+            }
+        }
+
+        let output_geometry = output.geometry();
+
+        let workspace = self.common.shell.workspaces.active_mut(&output);
+        let new_under = State::surface_under(
+            position,
+            &output,
+            &self.common.shell.override_redirect_windows,
+            overview.0,
+            workspace,
+            self.common.session_lock.as_ref(),
+        )
+        .map(|(target, pos)| (target, pos.as_logical()));
+
+        position.x = position.x.clamp(
+            output_geometry.loc.x as f64,
+            (output_geometry.loc.x + output_geometry.size.w) as f64,
+        );
+        position.y = position.y.clamp(
+            output_geometry.loc.y as f64,
+            (output_geometry.loc.y + output_geometry.size.h) as f64,
+        );
+
+        // If confined, don't move pointer if it would go outside surface or region
+        if pointer_confined {
+            if let Some((surface, surface_loc)) = &under {
+                if new_under.as_ref().and_then(|(under, _)| under.wl_surface())
+                    != surface.wl_surface()
+                {
+                    ptr.frame(self);
+                    return;
+                }
+                if let PointerFocusTarget::Element(element) = surface {
+                    //if !element.is_in_input_region(&(position.to_i32_round() - *surface_loc).to_f64()) {
+                    if !element.is_in_input_region(&(position.as_logical() - surface_loc.to_f64()))
+                    {
+                        ptr.frame(self);
+                        return;
+                    }
+                }
+                if let Some(region) = confine_region {
+                    if !region.contains(position.as_logical().to_i32_round() - *surface_loc) {
+                        ptr.frame(self);
+                        return;
+                    }
+                }
+            }
+        }
+
+        let serial = SERIAL_COUNTER.next_serial();
+        ptr.motion(
+            self,
+            under,
+            &MotionEvent {
+                location: position.as_logical(),
+                serial,
+                time,
+            },
+        );
+        ptr.frame(self);
+        self.update_pointer_selection_focus(seat);
+
+        // If pointer is now in a constraint region, activate it
+        if let Some((under, surface_location)) =
+            new_under.and_then(|(target, loc)| Some((target.wl_surface()?, loc)))
+        {
+            with_pointer_constraint(&under, &ptr, |constraint| match constraint {
+                Some(constraint) if !constraint.is_active() => {
+                    let region = match &*constraint {
+                        PointerConstraint::Locked(locked) => locked.region(),
+                        PointerConstraint::Confined(confined) => confined.region(),
+                    };
+                    let point = ptr.current_location().to_i32_round() - surface_location;
+                    if region.map_or(true, |region| region.contains(point)) {
+                        constraint.activate();
+                    }
+                }
+                _ => {}
+            });
+        }
+
+        if output != current_output {
+            for session in sessions_for_output(&self.common, &current_output) {
+                session.cursor_leave(seat, InputType::Pointer);
+            }
+
+            for session in sessions_for_output(&self.common, &output) {
+                session.cursor_enter(seat, InputType::Pointer);
+            }
+
+            seat.set_active_output(&output);
+        }
+        self.hot_corners(seat, &output, position, delta.as_global());
+
+        for session in sessions_for_output(&self.common, &output) {
+            if let Some((geometry, offset)) = seat.cursor_geometry(
+                position.as_logical().to_buffer(
+                    output.current_scale().fractional_scale(),
+                    output.current_transform(),
+                    &output_geometry.size.to_f64().as_logical(),
+                ),
+                self.common.clock.now(),
+            ) {
+                session.cursor_info(seat, InputType::Pointer, geometry, offset);
+            }
+        }
+        #[cfg(feature = "debug")]
+        if self.common.seat_uses_egui(seat) {
+            let output = seat.active_output();
+            let location = position.to_local(&output).to_i32_round().as_logical();
+            self.common.egui.state.handle_pointer_motion(location);
+        }
+    }
+
+    /// Presses or releases `button` of `seat`, focusing the surface below the pointer on presses.
+    pub fn pointer_button(
+        &mut self,
+        seat: &Seat<State>,
+        button: u32,
+        state: ButtonState,
+        time: u32,
+    ) {
+        let serial = SERIAL_COUNTER.next_serial();
+        if state == ButtonState::Pressed {
+            seat.user_data().get::<ModifierTap>().unwrap().cancel();
+            let output = seat.active_output();
+            let pos = seat.get_pointer().unwrap().current_location().as_global();
+            let overview = self.common.shell.overview_mode();
+            let workspace = self.common.shell.workspaces.active_mut(&output);
+            // use the same hit-test as pointer motion, so hover and click agree
+            let under = State::surface_under(
+                pos,
+                &output,
+                &self.common.shell.override_redirect_windows,
+                overview.0,
+                workspace,
+                self.common.session_lock.as_ref(),
+            )
+            .map(|(target, _)| target);
+
+            let dismissed = match self.dismiss_popup_grab(seat, under.as_ref()) {
+                Some(true) => {
+                    let swallowed = seat.user_data().get::<SwallowedButtons>().unwrap();
+                    swallowed.0.borrow_mut().push(button);
+                    return;
+                }
+                Some(false) => true,
+                None => false,
+            };
+
+            // change the keyboard focus unless the pointer or keyboard is grabbed
+            // (an ended popup grab is only removed by its next event)
+            // We test for any matching surface type here but always use the root
+            // (in case of a window the toplevel) surface for the focus.
+            // see: https://gitlab.freedesktop.org/wayland/wayland/-/issues/294
+            if dismissed
+                || (!seat.get_pointer().unwrap().is_grabbed()
+                    && !seat.get_keyboard().map(|k| k.is_grabbed()).unwrap_or(false))
+            {
+                // the first click on a window not responding asks to kill it, the second does
+                if let Some(PointerFocusTarget::Element(mapped)) = under.as_ref() {
+                    let window = mapped.active_window();
+                    match window.responsiveness() {
+                        Responsiveness::Responsive => {}
+                        Responsiveness::NotResponding => {
+                            window.set_responsiveness(Responsiveness::ConfirmKill);
+                            self.backend.schedule_render(
+                                &self.common.event_loop_handle,
+                                &output,
+                                None,
+                            );
+                            return;
+                        }
+                        Responsiveness::ConfirmKill => {
+                            window.kill(
+                                &self.common.display_handle,
+                                self.common.xwayland_state.as_ref(),
+                            );
+                            return;
+                        }
+                    }
+                }
+
+                let focus_button = self
+                    .common
+                    .config
+                    .static_conf
+                    .focus_buttons
+                    .as_ref()
+                    .map_or(true, |buttons| buttons.contains(&button));
+                // override redirect windows (e.g. X11 menus) never take the focus
+                if focus_button && !matches!(under, Some(PointerFocusTarget::OverrideRedirect(_))) {
+                    let target = under
+                        // clicks on popups of layer surfaces (e.g. panel menus) focus the layer
+                        // surface, just like clicks on window popups focus the window
+                        .and_then(|target| match target {
+                            PointerFocusTarget::Popup(popup) => {
+                                let root = find_popup_root_surface(&popup).ok()?;
+                                self.common.shell.outputs().find_map(|output| {
+                                    layer_map_for_output(output)
+                                        .layer_for_surface(&root, WindowSurfaceType::TOPLEVEL)
+                                        .cloned()
+                                        .map(PointerFocusTarget::LayerSurface)
+                                })
+                            }
+                            target => Some(target),
+                        })
+                        .filter(|target| match target {
+                            PointerFocusTarget::LayerSurface(layer) => {
+                                layer.can_receive_keyboard_focus()
+                                    && !fullscreen_keeps_focus(self, layer)
+                            }
+                            _ => true,
+                        })
+                        .and_then(|target| target.try_into().ok());
+                    Common::set_focus_with_raise(
+                        self,
+                        target.as_ref(),
+                        seat,
+                        Some(serial),
+                        self.common.config.static_conf.raise_on_click,
+                    );
+                }
+            }
+        } else {
+            let swallowed = seat.user_data().get::<SwallowedButtons>().unwrap();
+            let mut swallowed = swallowed.0.borrow_mut();
+            if let Some(idx) = swallowed.iter().position(|b| *b == button) {
+                swallowed.remove(idx);
+                return;
+            }
+            std::mem::drop(swallowed);
+
+            if let OverviewMode::Started(Trigger::Pointer(action_button), _) =
+                self.common.shell.overview_mode().0
+            {
+                if action_button == button {
+                    self.common
+                        .shell
+                        .set_overview_mode(None, self.common.event_loop_handle.clone());
+                }
+            }
+        };
+        let ptr = seat.get_pointer().unwrap();
+        ptr.button(
+            self,
+            &ButtonEvent {
+                button,
+                state,
+                serial,
+                time,
+            },
+        );
+        ptr.frame(self);
+    }
+
+    /// (Re)starts the timer sending an axis stop for the scrolled wheel axes.
+    fn schedule_wheel_stop(&mut self, seat: &Seat<State>, horizontal: bool, vertical: bool) {
+        let Some(delay) = self.common.config.static_conf.wheel_stop_delay else {
+            return;
+        };
+        let wheel_stop = seat.user_data().get::<WheelStop>().unwrap();
+        let mut axes = [horizontal, vertical];
+        if let Some((token, previous)) = wheel_stop.0.borrow_mut().take() {
+            self.common.event_loop_handle.remove(token);
+            axes = [axes[0] || previous[0], axes[1] || previous[1]];
+        }
+
+        let seat_clone = seat.clone();
+        match self.common.event_loop_handle.insert_source(
+            Timer::from_duration(Duration::from_millis(delay)),
+            move |_, _, state| {
+                let wheel_stop = seat_clone.user_data().get::<WheelStop>().unwrap();
+                let pending = wheel_stop.0.borrow_mut().take();
+                if let Some((_, [horizontal, vertical])) = pending {
+                    let time = Duration::from(state.common.clock.now()).as_millis() as u32;
+                    let mut frame = AxisFrame::new(time).source(AxisSource::Wheel);
+                    if horizontal {
+                        frame = frame.stop(Axis::Horizontal);
+                    }
+                    if vertical {
+                        frame = frame.stop(Axis::Vertical);
+                    }
+                    let ptr = seat_clone.get_pointer().unwrap();
+                    ptr.axis(state, frame);
+                    ptr.frame(state);
+                }
+                calloop::timer::TimeoutAction::Drop
+            },
+        ) {
+            Ok(token) => *wheel_stop.0.borrow_mut() = Some((token, axes)),
+            Err(err) => warn!(?err, "Failed to schedule wheel axis stop."),
+        }
+    }
+
+    /// Whether input of the touchpad `device` is ignored, because a key was typed recently
+    fn typing_suppresses<D: Device>(&self, seat: &Seat<State>, device: &D) -> bool {
+        let typing = seat.user_data().get::<Typing>().unwrap().0.borrow();
+        let (Some(last_key), Some(touchpad)) = (typing.last_key, typing.touchpads.get(&device.id()))
+        else {
+            return false;
+        };
+        self.common
+            .config
+            .typing_timeout(touchpad)
+            .is_some_and(|timeout| last_key.elapsed() < timeout)
+    }
+
+    /// Focuses the most recently used window with the given `app_id`, or the least recently used
+    /// one if a match is focused already. Returns `false` if there is no such window.
+    fn focus_app_id(
+        &mut self,
+        seat: &Seat<State>,
+        app_id: &str,
+        serial: Serial,
+        time: u32,
+    ) -> bool {
+        let history = FocusHistory::windows(seat);
+        let mut matches = self
+            .common
+            .shell
+            .workspaces
+            .spaces()
+            .flat_map(|w| w.mapped())
+            .flat_map(|mapped| {
+                mapped
+                    .windows()
+                    .filter(|(window, _)| window.app_id() == app_id)
+                    .map(|(window, _)| (mapped.clone(), window))
+                    .collect::<Vec<_>>()
+            })
+            .collect::<Vec<_>>();
+        // most recently used first
+        matches.sort_by_key(|(mapped, _)| {
+            history
+                .iter()
+                .position(|m| m == mapped)
+                .unwrap_or(usize::MAX)
+        });
+
+        let focused = matches!(
+            seat.get_keyboard().and_then(|k| k.current_focus()),
+            Some(KeyboardFocusTarget::Element(mapped)) if mapped.active_window().app_id() == app_id
+        );
+        if matches.is_empty() {
+            return false;
+        }
+
+        // if a match is focused already, continue with the least recently used one,
+        // so repeated invocations cycle through all of them
+        let next = if focused {
+            matches.into_iter().skip(1).last()
+        } else {
+            matches.into_iter().next()
+        };
+        if let Some((mapped, window)) = next {
+            mapped.focus_window(&window);
+            self.activate_mapped(seat, mapped, serial, time);
+        }
+        true
+    }
+
+    /// Switches to the workspace and output of `mapped` and focuses it.
+    fn activate_mapped(
+        &mut self,
+        seat: &Seat<State>,
+        mapped: CosmicMapped,
+        serial: Serial,
+        time: u32,
+    ) {
+        let location = self.common.shell.outputs().find_map(|output| {
+            self.common
+                .shell
+                .workspaces
+                .spaces_for_output(output)
+                .position(|w| w.mapped().any(|m| m == &mapped))
+                .map(|idx| (output.clone(), idx))
+        });
+        let Some((output, idx)) = location else {
+            return;
+        };
+
+        match self.common.shell.activate(&output, idx) {
+            Ok(Some(new_pos)) => {
+                seat.set_active_output(&output);
+                if let Some(ptr) = seat.get_pointer() {
+                    ptr.motion(
+                        self,
+                        None,
+                        &MotionEvent {
+                            location: new_pos.to_f64().as_logical(),
+                            serial,
+                            time,
+                        },
+                    );
+                    ptr.frame(self);
+                }
+            }
+            Ok(None) => {
+                seat.set_active_output(&output);
+            }
+            Err(_) => return,
+        }
+        Common::set_focus(self, Some(&KeyboardFocusTarget::from(mapped)), seat, None);
+    }
+
+    /// Dismisses the popup grab of `seat`, if a click on `under` lands outside of the client
+    /// owning it. The whole popup chain is dismissed, innermost popup first.
+    ///
+    /// Returns `None` if there is no grab or it stays active (the click is then handled by the
+    /// grab), otherwise if the click should be swallowed. Clicks dismissing popups of layer
+    /// surfaces (e.g. panel menus) are swallowed, any other click is handled as usual.
+    fn dismiss_popup_grab(
+        &mut self,
+        seat: &Seat<State>,
+        under: Option<&PointerFocusTarget>,
+    ) -> Option<bool> {
+        let data = seat.user_data().get::<PopupGrabData>()?;
+        let mut grab = data.take()?;
+        if grab.has_ended() {
+            return None;
+        }
+        let Some(current) = grab.current_grab() else {
+            data.set(Some(grab));
+            return None;
+        };
+        let inside = current.wl_surface().is_some_and(|surface| {
+            under.is_some_and(|target| target.same_client_as(&surface.id()))
+        });
+        if inside {
+            data.set(Some(grab));
+            return None;
+        }
+
+        let from_layer = match &current {
+            KeyboardFocusTarget::LayerSurface(_) => true,
+            KeyboardFocusTarget::Popup(popup) => find_popup_root_surface(popup)
+                .map(|root| {
+                    self.common.shell.outputs().any(|output| {
+                        layer_map_for_output(output)
+                            .layer_for_surface(&root, WindowSurfaceType::TOPLEVEL)
+                            .is_some()
+                    })
+                })
+                .unwrap_or(false),
+            _ => false,
+        };
+        grab.ungrab(PopupUngrabStrategy::All);
+        Some(from_layer)
+    }
+
+    /// Moves the pointer and clicks for numpad keys, while mouse keys are active.
+    fn mouse_keys(&mut self, seat: &Seat<State>, keycode: u32, key_state: KeyState, time: u32) {
+        let mouse_keys = seat.user_data().get::<MouseKeys>().unwrap();
+        let mut state = mouse_keys.0.borrow_mut();
+
+        if mouse_keys_direction(keycode).is_some() {
+            match key_state {
+                KeyState::Pressed => {
+                    if !state.held.contains(&keycode) {
+                        state.held.push(keycode);
+                    }
+                    if state.timer.is_none() {
+                        state.motion_start = Instant::now();
+                        let seat = seat.clone();
+                        state.timer = self
+                            .common
+                            .event_loop_handle
+                            .insert_source(Timer::immediate(), move |_, _, state| {
+                                let Some(delta) =
+                                    seat.user_data().get::<MouseKeys>().unwrap().step()
+                                else {
+                                    return calloop::timer::TimeoutAction::Drop;
+                                };
+                                let now = Duration::from(state.common.clock.now());
+                                state.pointer_motion(
+                                    &seat,
+                                    delta,
+                                    delta,
+                                    now.as_micros() as u64,
+                                    now.as_millis() as u32,
+                                );
+                                calloop::timer::TimeoutAction::ToDuration(MOUSE_KEYS_INTERVAL)
+                            })
+                            .map_err(|err| warn!(?err, "Failed to start mouse keys motion."))
+                            .ok();
+                    }
+                }
+                KeyState::Released => state.held.retain(|key| *key != keycode),
+            }
+            return;
+        }
+        if key_state == KeyState::Released {
+            return;
+        }
+
+        let button = state.button;
+        match keycode {
+            KEY_KPSLASH => state.button = BTN_LEFT,
+            KEY_KPASTERISK => state.button = BTN_MIDDLE,
+            KEY_KPMINUS => state.button = BTN_RIGHT,
+            KEY_KP5 | KEY_KPPLUS => {
+                std::mem::drop(state);
+                let clicks = if keycode == KEY_KPPLUS { 2 } else { 1 };
+                for _ in 0..clicks {
+                    self.pointer_button(seat, button, ButtonState::Pressed, time);
+                    self.pointer_button(seat, button, ButtonState::Released, time);
+                }
+            }
+            KEY_KP0 => {
+                if state.dragging.is_none() {
+                    state.dragging = Some(button);
+                    std::mem::drop(state);
+                    self.pointer_button(seat, button, ButtonState::Pressed, time);
+                }
+            }
+            KEY_KPDOT => {
+                if let Some(button) = state.dragging.take() {
+                    std::mem::drop(state);
+                    self.pointer_button(seat, button, ButtonState::Released, time);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// Toggles a lock modifier by sending a press and release of its key through the keyboard.
+    pub fn press_lock_key(&mut self, seat: &Seat<State>, keycode: u32) {
+        let keyboard = seat.get_keyboard().unwrap();
+        let time = Duration::from(self.common.clock.now()).as_millis() as u32;
+        for state in [KeyState::Pressed, KeyState::Released] {
+            keyboard.input::<(), _>(
+                self,
+                keycode,
+                state,
+                SERIAL_COUNTER.next_serial(),
+                time,
+                |_, _, _| FilterResult::Forward,
+            );
+        }
+    }
+
+    /// Mirrors the lock modifiers of `seat` on the keyboard leds.
+    pub fn update_keyboard_leds(&mut self, seat: &Seat<State>) {
+        let modifiers = seat.get_keyboard().unwrap().modifier_state();
+        let mut leds = Led::empty();
+        if modifiers.num_lock {
+            leds |= Led::NUMLOCK;
+        }
+        if modifiers.caps_lock {
+            leds |= Led::CAPSLOCK;
+        }
+        if let BackendData::Kms(kms) = &mut self.backend {
+            kms.update_keyboard_leds(leds);
+        }
+    }
+
+    /// Leaves modal resizing, undoing all steps unless `commit` is set.
+    fn exit_resize_mode(&mut self, seat: &Seat<State>, commit: bool) {
+        let Some(modal_state) = seat
+            .user_data()
+            .get::<ModalResize>()
+            .unwrap()
+            .0
+            .borrow_mut()
+            .take()
+        else {
+            return;
+        };
+        if let Some(token) = modal_state.timer {
+            self.common.event_loop_handle.remove(token);
+        }
+
+        if !commit {
+            for (direction, edge) in modal_state.steps.into_iter().rev() {
+                let direction = match direction {
+                    ResizeDirection::Inwards => ResizeDirection::Outwards,
+                    ResizeDirection::Outwards => ResizeDirection::Inwards,
+                };
+                self.common.shell.resize(seat, direction, edge);
+                self.common.shell.finish_resize(direction, edge);
+            }
+        }
+
+        self.common.shell.set_resize_mode(
+            None,
+            &self.common.config,
+            self.common.event_loop_handle.clone(),
+        );
+    }
+
+    pub fn handle_action(
+        &mut self,
+        action: Action,
+        seat: &Seat<State>,
+        serial: Serial,
+        time: u32,
+        pattern: KeyPattern,
+        direction: Option<Direction>,
+    ) {
+        // TODO: Detect if started from login manager or tty, and only allow
+        // `Terminate` if it will return to login manager.
+        if self.common.session_lock.is_some()
+            && !matches!(action, Action::Terminate | Action::Debug)
+        {
+            return;
+        }
+        // an exclusive layer surface keeps the focus, until it unmaps or gives it up
+        if matches!(
+            action,
+            Action::Focus(_)
+                | Action::Workspace(_)
+                | Action::NextWorkspace
+                | Action::PreviousWorkspace
+                | Action::LastWorkspace
+                | Action::WorkspaceBackAndForth
+                | Action::NextOutput
+                | Action::PreviousOutput
+        ) && has_exclusive_layer_focus(seat)
         {
             return;
         }
@@ -1167,7 +2641,8 @@ impl State {
             #[cfg(feature = "debug")]
             Action::Debug => {
                 self.common.egui.active = !self.common.egui.active;
-                puffin::set_scopes_on(self.common.egui.active);
+                puffin::set_scopes_on(self.common.egui.active || cfg!(feature = "profile"));
+                crate::logger::set_input_capture(self.common.egui.active);
                 for mapped in self
                     .common
                     .shell
@@ -1182,6 +2657,182 @@ impl State {
             Action::Debug => {
                 info!("Debug overlay not included in this build.")
             }
+            Action::DumpState => {
+                let state = self.common.shell.dump_state(self.common.seats());
+                info!("Compositor state:\n{}", state);
+            }
+            Action::CycleLogLevel => {
+                crate::logger::cycle_level();
+            }
+            Action::ToggleShortcuts => {
+                self.common.shortcuts_enabled = !self.common.shortcuts_enabled;
+                info!(
+                    enabled = self.common.shortcuts_enabled,
+                    "Toggled compositor shortcuts."
+                );
+                #[cfg(feature = "debug")]
+                self.show_toast(if self.common.shortcuts_enabled {
+                    "Shortcuts enabled"
+                } else {
+                    "Shortcuts disabled"
+                });
+            }
+            Action::ToggleKeybindingsInhibit => {
+                let current_output = seat.active_output();
+                let workspace = self.common.shell.active_space(&current_output);
+                if let Some(window) = workspace
+                    .focus_stack
+                    .get(seat)
+                    .last()
+                    .map(|mapped| mapped.active_window())
+                {
+                    let inhibit = !self.common.keybindings_inhibited(seat);
+                    window.set_inhibit_keybindings_override(inhibit);
+                    info!(
+                        app_id = window.app_id(),
+                        inhibit, "Toggled keybindings inhibit of focused window."
+                    );
+                    #[cfg(feature = "debug")]
+                    self.show_toast(if inhibit {
+                        "Keybindings inhibited"
+                    } else {
+                        "Keybindings restored"
+                    });
+                }
+            }
+            Action::ReloadBackground => self.reload_backgrounds(),
+            Action::ToggleNumlock => {
+                self.press_lock_key(seat, KEY_NUMLOCK);
+                self.update_keyboard_leds(seat);
+            }
+            Action::ToggleMouseKeys => {
+                let mouse_keys = seat.user_data().get::<MouseKeys>().unwrap();
+                let mut state = mouse_keys.0.borrow_mut();
+                state.active = !state.active;
+                info!(active = state.active, "Toggled mouse keys.");
+                state.held.clear();
+                if let Some(token) = state.timer.take() {
+                    self.common.event_loop_handle.remove(token);
+                }
+                let dragging = state.dragging.take();
+                std::mem::drop(state);
+                if let Some(button) = dragging {
+                    self.pointer_button(seat, button, ButtonState::Released, time);
+                }
+                #[cfg(feature = "debug")]
+                self.show_toast(if mouse_keys.is_active() {
+                    "Mouse keys enabled"
+                } else {
+                    "Mouse keys disabled"
+                });
+            }
+            Action::_MouseKeys(keycode, state) => self.mouse_keys(seat, keycode, state, time),
+            Action::ToggleOutputPower(connector) => {
+                let output = match connector {
+                    Some(ref name) => self
+                        .common
+                        .shell
+                        .outputs()
+                        .find(|output| &output.name() == name)
+                        .cloned(),
+                    None => Some(seat.active_output()),
+                };
+                let Some(output) = output else {
+                    warn!(?connector, "No output to toggle the power of.");
+                    return;
+                };
+                let off = !output.is_powered_off();
+                if let Err(err) =
+                    self.backend
+                        .set_output_power(&output, !off, &self.common.event_loop_handle)
+                {
+                    warn!(
+                        ?err,
+                        output = output.name(),
+                        "Failed to toggle output power."
+                    );
+                    return;
+                }
+                output.set_powered_off(off);
+                info!(output = output.name(), off, "Toggled output power.");
+
+                if off {
+                    // move seats over to an output, that is still displayed
+                    let fallback = self
+                        .common
+                        .shell
+                        .outputs()
+                        .find(|output| !output.is_powered_off())
+                        .cloned();
+                    if let Some(fallback) = fallback {
+                        let geometry = fallback.geometry().to_f64();
+                        let center = geometry.loc + geometry.size.downscale(2.0).to_point();
+                        for seat in self.common.seats().cloned().collect::<Vec<_>>() {
+                            if seat.active_output() != output {
+                                continue;
+                            }
+                            seat.set_active_output(&fallback);
+                            if let Some(ptr) = seat.get_pointer() {
+                                ptr.motion(
+                                    self,
+                                    None,
+                                    &MotionEvent {
+                                        location: center.as_logical(),
+                                        serial,
+                                        time,
+                                    },
+                                );
+                                ptr.frame(self);
+                            }
+                        }
+                    }
+                } else {
+                    self.backend
+                        .schedule_render(&self.common.event_loop_handle, &output, None);
+                }
+            }
+            Action::RotateOutput(step) => {
+                let output = seat.active_output();
+                let current = output
+                    .user_data()
+                    .get::<RefCell<OutputConfig>>()
+                    .unwrap()
+                    .borrow()
+                    .transform;
+                let transform = rotate_transform(current, step);
+                if !self.output_configuration(
+                    false,
+                    vec![(
+                        output.clone(),
+                        OutputConfiguration::Enabled {
+                            mode: None,
+                            position: None,
+                            transform: Some(transform),
+                            scale: None,
+                        },
+                    )],
+                ) {
+                    warn!(
+                        output = output.name(),
+                        ?transform,
+                        "Failed to rotate output."
+                    );
+                    return;
+                }
+                layer_map_for_output(&output).arrange();
+                for workspace in self.common.shell.workspaces.spaces_mut() {
+                    workspace.recalculate();
+                }
+                info!(output = output.name(), ?transform, "Rotated output.");
+            }
+            Action::ToggleNightLight => match self.backend.toggle_night_light() {
+                Ok(enabled) => info!(enabled, "Toggled night light."),
+                Err(err) => warn!(?err, "Failed to toggle night light."),
+            },
+            Action::SetPrimaryOutput => {
+                let output = seat.active_output();
+                self.common.set_primary_output(&output);
+            }
             Action::Close => {
                 let current_output = seat.active_output();
                 let workspace = self.common.shell.active_space_mut(&current_output);
@@ -1189,16 +2840,76 @@ impl State {
                     window.send_close();
                 }
             }
+            Action::Kill => {
+                let current_output = seat.active_output();
+                let workspace = self.common.shell.active_space(&current_output);
+                if let Some(window) = workspace
+                    .focus_stack
+                    .get(seat)
+                    .last()
+                    .map(|mapped| mapped.active_window())
+                {
+                    window.kill(
+                        &self.common.display_handle,
+                        self.common.xwayland_state.as_ref(),
+                    );
+                }
+            }
             Action::Workspace(key_num) => {
                 let current_output = seat.active_output();
                 let workspace = match key_num {
                     0 => 9,
                     x => x - 1,
                 };
-                let _ = self
+                if self
                     .common
                     .shell
-                    .activate(&current_output, workspace as usize);
+                    .activate(&current_output, workspace as usize)
+                    .is_ok()
+                {
+                    Common::set_workspace_focus(self, seat, &current_output);
+                    self.show_workspace_osd(&current_output);
+                }
+            }
+            Action::SwapWorkspaces(a, b) => {
+                let output = seat.active_output();
+                let to_idx = |key_num: u8| match key_num {
+                    0 => 9,
+                    x => x as usize - 1,
+                };
+                let (a, b) = (to_idx(a), to_idx(b));
+                let active = self.common.shell.workspaces.active_num(&output).1;
+                let focus = seat
+                    .get_keyboard()
+                    .unwrap()
+                    .current_focus()
+                    .filter(|target| {
+                        matches!(
+                            target,
+                            KeyboardFocusTarget::Element(_)
+                                | KeyboardFocusTarget::Fullscreen(_)
+                                | KeyboardFocusTarget::Group(_)
+                        )
+                    });
+                if self.common.shell.swap_workspaces(&output, a, b).is_ok() {
+                    // the focused window keeps the focus, so follow it to the other workspace
+                    let other = if active == a {
+                        Some(b)
+                    } else if active == b {
+                        Some(a)
+                    } else {
+                        None
+                    };
+                    match (other, focus) {
+                        (Some(idx), Some(focus)) => {
+                            if self.common.shell.activate(&output, idx).is_ok() {
+                                Common::set_focus(self, Some(&focus), seat, None);
+                            }
+                        }
+                        (Some(_), None) => Common::set_workspace_focus(self, seat, &output),
+                        _ => {}
+                    }
+                }
             }
             Action::NextWorkspace => {
                 let current_output = seat.active_output();
@@ -1213,8 +2924,11 @@ impl State {
                     .common
                     .shell
                     .activate(&current_output, workspace)
-                    .is_err()
+                    .is_ok()
                 {
+                    Common::set_workspace_focus(self, seat, &current_output);
+                    self.show_workspace_osd(&current_output);
+                } else {
                     self.handle_action(Action::NextOutput, seat, serial, time, pattern, direction);
                 }
             }
@@ -1231,8 +2945,11 @@ impl State {
                     .common
                     .shell
                     .activate(&current_output, workspace)
-                    .is_err()
+                    .is_ok()
                 {
+                    Common::set_workspace_focus(self, seat, &current_output);
+                    self.show_workspace_osd(&current_output);
+                } else {
                     self.handle_action(
                         Action::PreviousOutput,
                         seat,
@@ -1251,7 +2968,54 @@ impl State {
                     .workspaces
                     .len(&current_output)
                     .saturating_sub(1);
-                let _ = self.common.shell.activate(&current_output, workspace);
+                if self
+                    .common
+                    .shell
+                    .activate(&current_output, workspace)
+                    .is_ok()
+                {
+                    Common::set_workspace_focus(self, seat, &current_output);
+                    self.show_workspace_osd(&current_output);
+                }
+            }
+            Action::WorkspaceBackAndForth => {
+                let current_output = seat.active_output();
+                if let Some(workspace) = self
+                    .common
+                    .shell
+                    .workspaces
+                    .last_active_num(&current_output)
+                {
+                    if self
+                        .common
+                        .shell
+                        .activate(&current_output, workspace)
+                        .is_ok()
+                    {
+                        Common::set_workspace_focus(self, seat, &current_output);
+                        self.show_workspace_osd(&current_output);
+                    }
+                }
+            }
+            Action::FocusPrevWindow => {
+                if let Some(mapped) = FocusHistory::previous(seat, &self.common.shell) {
+                    self.activate_mapped(seat, mapped, serial, time);
+                }
+            }
+            Action::FocusAppId(app_id) => {
+                self.focus_app_id(seat, &app_id, serial, time);
+            }
+            Action::FocusOrSpawn { app_id, command } => {
+                if !self.focus_app_id(seat, &app_id, serial, time) {
+                    self.handle_action(
+                        Action::Spawn(command),
+                        seat,
+                        serial,
+                        time,
+                        pattern,
+                        direction,
+                    );
+                }
             }
             x @ Action::MoveToWorkspace(_) | x @ Action::SendToWorkspace(_) => {
                 let current_output = seat.active_output();
@@ -1509,6 +3273,15 @@ impl State {
 
                 match result {
                     FocusResult::None => {
+                        let wrap = self.common.config.static_conf.focus_wraps_outputs;
+                        if let Some(output) =
+                            self.common
+                                .shell
+                                .output_in_direction(&current_output, focus, wrap)
+                        {
+                            self.focus_adjacent_output(seat, &output);
+                            return;
+                        }
                         match (focus, self.common.config.workspace.workspace_layout) {
                             (FocusDirection::Left, WorkspaceLayout::Horizontal)
                             | (FocusDirection::Up, WorkspaceLayout::Vertical) => self
@@ -1647,6 +3420,37 @@ impl State {
                     workspace.maximize_toggle(&window);
                 }
             }
+            Action::AdjustOpacity(delta) => {
+                let current_output = seat.active_output();
+                let workspace = self.common.shell.active_space(&current_output);
+                if let Some(window) = workspace
+                    .focus_stack
+                    .get(seat)
+                    .last()
+                    .map(|f| f.active_window())
+                {
+                    window.set_opacity(window.opacity() + delta);
+                    self.backend.schedule_render(
+                        &self.common.event_loop_handle,
+                        &current_output,
+                        None,
+                    );
+                }
+            }
+            Action::TileHalf(side) => {
+                let current_output = seat.active_output();
+                let workspace = self.common.shell.active_space_mut(&current_output);
+                if let Some(mapped) = workspace.focus_stack.get(seat).last().cloned() {
+                    workspace.tile_half(&mapped, side);
+                }
+            }
+            Action::TileQuarter(corner) => {
+                let current_output = seat.active_output();
+                let workspace = self.common.shell.active_space_mut(&current_output);
+                if let Some(mapped) = workspace.focus_stack.get(seat).last().cloned() {
+                    workspace.tile_quarter(&mapped, corner);
+                }
+            }
             Action::Resizing(direction) => self.common.shell.set_resize_mode(
                 Some((pattern, direction)),
                 &self.common.config,
@@ -1659,6 +3463,66 @@ impl State {
                     self.common.shell.finish_resize(direction, edge);
                 }
             }
+            Action::EnterResizeMode => {
+                let modal = seat.user_data().get::<ModalResize>().unwrap();
+                if modal.pattern().is_some() {
+                    return;
+                }
+
+                let seat_clone = seat.clone();
+                let timer = self
+                    .common
+                    .event_loop_handle
+                    .insert_source(
+                        Timer::from_duration(RESIZE_MODE_TIMEOUT),
+                        move |_, _, state| {
+                            let modal = seat_clone.user_data().get::<ModalResize>().unwrap();
+                            let mut modal_state = modal.0.borrow_mut();
+                            let Some(modal_state) = modal_state.as_mut() else {
+                                return calloop::timer::TimeoutAction::Drop;
+                            };
+                            let deadline = modal_state.last_activity + RESIZE_MODE_TIMEOUT;
+                            if Instant::now() < deadline {
+                                return calloop::timer::TimeoutAction::ToInstant(deadline);
+                            }
+                            // the timer is dropped by returning, don't remove it
+                            modal_state.timer = None;
+                            std::mem::drop(modal_state);
+                            state.exit_resize_mode(&seat_clone, true);
+                            calloop::timer::TimeoutAction::Drop
+                        },
+                    )
+                    .map_err(|err| warn!(?err, "Failed to schedule resize mode timeout."))
+                    .ok();
+
+                *modal.0.borrow_mut() = Some(ModalResizeState {
+                    pattern: pattern.clone(),
+                    steps: Vec::new(),
+                    last_activity: Instant::now(),
+                    timer,
+                });
+                self.common.shell.set_resize_mode(
+                    Some((pattern, ResizeDirection::Outwards)),
+                    &self.common.config,
+                    self.common.event_loop_handle.clone(),
+                );
+            }
+            Action::_ResizeModeStep(direction, edge) => {
+                if let Some(modal_state) = seat
+                    .user_data()
+                    .get::<ModalResize>()
+                    .unwrap()
+                    .0
+                    .borrow_mut()
+                    .as_mut()
+                {
+                    modal_state.steps.push((direction, edge));
+                    modal_state.last_activity = Instant::now();
+                }
+                self.common.shell.resize(seat, direction, edge);
+                self.common.shell.finish_resize(direction, edge);
+            }
+            Action::_ExitResizeMode(commit) => self.exit_resize_mode(seat, commit),
             Action::ToggleOrientation => {
                 let output = seat.active_output();
                 let workspace = self.common.shell.active_space_mut(&output);
@@ -1671,6 +3535,21 @@ impl State {
                     .tiling_layer
                     .update_orientation(Some(orientation), &seat);
             }
+            Action::BalanceWindows => {
+                let output = seat.active_output();
+                let workspace = self.common.shell.active_space_mut(&output);
+                workspace.tiling_layer.balance(&seat, false);
+            }
+            Action::BalanceWorkspace => {
+                let output = seat.active_output();
+                let workspace = self.common.shell.active_space_mut(&output);
+                workspace.tiling_layer.balance(&seat, true);
+            }
+            Action::ResetWorkspaceLayout => {
+                let output = seat.active_output();
+                let workspace = self.common.shell.active_space_mut(&output);
+                workspace.tiling_layer.reset_layout();
+            }
             Action::ToggleStacking => {
                 let output = seat.active_output();
                 let workspace = self.common.shell.active_space_mut(&output);
@@ -1687,6 +3566,13 @@ impl State {
                 let workspace = self.common.shell.active_space_mut(&output);
                 workspace.toggle_floating_window(seat);
             }
+            Action::MoveToScratchpad => Shell::move_to_scratchpad(self, seat),
+            Action::ShowScratchpad => Shell::toggle_scratchpad(self, seat),
+            Action::Brightness(step) => {
+                if let Some(percent) = crate::utils::backlight::adjust_brightness(step) {
+                    osd::show(self, &seat.active_output(), OsdContent::Brightness(percent));
+                }
+            }
             Action::Spawn(command) => {
                 let wayland_display = self.common.socket.clone();
 
@@ -1697,12 +3583,31 @@ impl State {
                     .map(|s| format!(":{}", s.display))
                     .unwrap_or_default();
 
+                let shell = self.common.config.static_conf.spawn_shell.clone();
+
                 std::thread::spawn(move || {
-                    let mut cmd = std::process::Command::new("/bin/sh");
+                    let mut cmd = match shell {
+                        Some(shell) => {
+                            let mut cmd = std::process::Command::new(shell);
+                            cmd.arg("-c").arg(command.clone());
+                            cmd
+                        }
+                        None => {
+                            let args = match shell_words::split(&command) {
+                                Ok(args) if !args.is_empty() => args,
+                                Ok(_) => return,
+                                Err(err) => {
+                                    tracing::warn!(?err, "Failed to parse \"{}\"", command);
+                                    return;
+                                }
+                            };
+                            let mut cmd = std::process::Command::new(&args[0]);
+                            cmd.args(&args[1..]);
+                            cmd
+                        }
+                    };
 
-                    cmd.arg("-c")
-                        .arg(command.clone())
-                        .env("WAYLAND_DISPLAY", &wayland_display)
+                    cmd.env("WAYLAND_DISPLAY", &wayland_display)
                         .env("DISPLAY", &display)
                         .env_remove("COSMIC_SESSION_SOCK");
 
@@ -1740,45 +3645,33 @@ impl State {
         }
 
         if let Some(window) = workspace.get_fullscreen() {
-            let layers = layer_map_for_output(output);
-            if let Some(layer) = layers.layer_under(WlrLayer::Overlay, relative_pos.as_logical()) {
-                let layer_loc = layers.layer_geometry(layer).unwrap().loc;
-                if layer
-                    .surface_under(
-                        relative_pos.as_logical() - layer_loc.to_f64(),
-                        WindowSurfaceType::ALL,
-                    )
-                    .is_some()
-                {
-                    return Some((layer.clone().into(), output_geo.loc + layer_loc.as_global()));
-                }
+            if let Some(target) = layer_popup_under(output, WlrLayer::Overlay, relative_pos) {
+                return Some(target);
+            }
+            if let Some(target) = layer_surface_under(output, &[WlrLayer::Overlay], relative_pos) {
+                return Some(target);
             }
             if let Some(or) = override_redirect_windows.iter().find(|or| {
                 or.is_in_input_region(&(global_pos.as_logical() - or.geometry().loc.to_f64()))
             }) {
                 return Some((or.clone().into(), or.geometry().loc.as_global()));
             }
-            Some((window.clone().into(), output_geo.loc))
+            // smaller fullscreen windows are centered, just like they are rendered.
+            // input-transparent areas still don't reach the hidden windows below.
+            let offset = workspace
+                .fullscreen_geometry()
+                .map_or_else(Point::default, |geo| geo.loc);
+            window
+                .is_in_input_region(&(relative_pos - offset.to_f64()).as_logical())
+                .then(|| (window.clone().into(), output_geo.loc + offset.as_global()))
         } else {
-            {
-                let layers = layer_map_for_output(output);
-                if let Some(layer) = layers
-                    .layer_under(WlrLayer::Overlay, relative_pos.as_logical())
-                    .or_else(|| layers.layer_under(WlrLayer::Top, relative_pos.as_logical()))
-                {
-                    let layer_loc = layers.layer_geometry(layer).unwrap().loc;
-                    if layer
-                        .surface_under(
-                            relative_pos.as_logical() - layer_loc.to_f64(),
-                            WindowSurfaceType::ALL,
-                        )
-                        .is_some()
-                    {
-                        return Some((
-                            layer.clone().into(),
-                            output_geo.loc + layer_loc.as_global(),
-                        ));
-                    }
+            // popups are rendered above their own layer and need to be hit first
+            for wlr_layer in [WlrLayer::Overlay, WlrLayer::Top] {
+                if let Some(target) = layer_popup_under(output, wlr_layer, relative_pos) {
+                    return Some(target);
+                }
+                if let Some(target) = layer_surface_under(output, &[wlr_layer], relative_pos) {
+                    return Some(target);
                 }
             }
             if let Some(or) = override_redirect_windows.iter().find(|or| {
@@ -1786,35 +3679,183 @@ impl State {
             }) {
                 return Some((or.clone().into(), or.geometry().loc.as_global()));
             }
+            // popups of lower layers are still rendered above windows
+            for wlr_layer in [WlrLayer::Bottom, WlrLayer::Background] {
+                if let Some(target) = layer_popup_under(output, wlr_layer, relative_pos) {
+                    return Some(target);
+                }
+            }
             if let Some((target, loc)) = workspace.element_under(global_pos, overview) {
                 return Some((target, loc));
             }
-            {
-                let layers = layer_map_for_output(output);
-                if let Some(layer) = layers
-                    .layer_under(WlrLayer::Bottom, relative_pos.as_logical())
-                    .or_else(|| layers.layer_under(WlrLayer::Background, relative_pos.as_logical()))
-                {
-                    let layer_loc = layers.layer_geometry(layer).unwrap().loc;
-                    if layer
-                        .surface_under(
-                            relative_pos.as_logical() - layer_loc.to_f64(),
-                            WindowSurfaceType::ALL,
-                        )
-                        .is_some()
-                    {
-                        return Some((
-                            layer.clone().into(),
-                            output_geo.loc + layer_loc.as_global(),
-                        ));
-                    }
-                }
-            }
-            None
+            layer_surface_under(
+                output,
+                &[WlrLayer::Bottom, WlrLayer::Background],
+                relative_pos,
+            )
         }
     }
 }
 
+/// Whether the key binding `binding` of `action` can currently trigger.
+///
+/// Keyboard grabs of clients (e.g. of a popup menu) don't matter here, bindings are
+/// matched before a grab sees the key. Only a shortcuts inhibitor blocks them.
+fn binding_active(
+    binding: &KeyPattern,
+    action: &Action,
+    shortcuts_inhibited: bool,
+    shortcuts_enabled: bool,
+    keybindings_inhibited: bool,
+) -> bool {
+    if shortcuts_inhibited {
+        // clients may inhibit shortcuts, but never the backlight
+        matches!(action, Action::Brightness(_))
+            || matches!(
+                binding.key,
+                Some(Keysym::XF86_MonBrightnessUp | Keysym::XF86_MonBrightnessDown)
+            )
+    } else if !shortcuts_enabled {
+        matches!(action, Action::ToggleShortcuts | Action::Terminate)
+    } else if keybindings_inhibited {
+        matches!(action, Action::ToggleKeybindingsInhibit | Action::Terminate)
+    } else {
+        true
+    }
+}
+
+/// Whether pressing a key producing `raw_syms` with `modifiers` held triggers `binding`.
+fn binding_pressed(binding: &KeyPattern, raw_syms: &[Keysym], modifiers: &ModifiersState) -> bool {
+    binding.key.is_some_and(|key| raw_syms.contains(&key)) && binding.modifiers == *modifiers
+}
+
+/// Finds the topmost layer surface on the given layers, whose input region contains `relative_pos`.
+/// Layer surfaces excluding the point from their input region let it pass through to the ones below.
+fn layer_surface_under(
+    output: &Output,
+    wlr_layers: &[WlrLayer],
+    relative_pos: Point<f64, Local>,
+) -> Option<(PointerFocusTarget, Point<i32, Global>)> {
+    let layers = layer_map_for_output(output);
+    let output_loc = output.geometry().loc;
+    wlr_layers.iter().find_map(|wlr_layer| {
+        layers.layers_on(*wlr_layer).rev().find_map(|layer| {
+            let layer_loc = layers.layer_geometry(layer)?.loc;
+            layer
+                .surface_under(
+                    relative_pos.as_logical() - layer_loc.to_f64(),
+                    WindowSurfaceType::ALL,
+                )
+                .map(|_| (layer.clone().into(), output_loc + layer_loc.as_global()))
+        })
+    })
+}
+
+/// Finds the topmost popup of a layer surface on the given layer under `relative_pos`.
+fn layer_popup_under(
+    output: &Output,
+    wlr_layer: WlrLayer,
+    relative_pos: Point<f64, Local>,
+) -> Option<(PointerFocusTarget, Point<i32, Global>)> {
+    let layers = layer_map_for_output(output);
+    let output_loc = output.geometry().loc;
+    let under = layers.layers_on(wlr_layer).rev().find_map(|layer| {
+        let layer_loc = layers.layer_geometry(layer)?.loc;
+        let popups = PopupManager::popups_for_surface(layer.wl_surface()).collect::<Vec<_>>();
+        popups.into_iter().rev().find_map(|(popup, popup_offset)| {
+            let popup_loc = layer_loc + popup_offset - popup.geometry().loc;
+            under_from_surface_tree(
+                popup.wl_surface(),
+                relative_pos.as_logical(),
+                popup_loc,
+                WindowSurfaceType::ALL,
+            )
+            .map(|_| {
+                (
+                    PointerFocusTarget::Popup(popup),
+                    output_loc + popup_loc.as_global(),
+                )
+            })
+        })
+    });
+    under
+}
+
+// Maps an absolute event onto the output named `name`, taking its transform into account.
+// Falls back to the bounding box of all outputs, if no such output exists.
+fn absolute_position_on_output<'a, E: AbsolutePositionEvent<B>, B: InputBackend>(
+    event: &E,
+    outputs: impl Iterator<Item = &'a Output>,
+    name: &str,
+) -> Option<(Output, Point<f64, Global>)> {
+    let outputs = outputs.cloned().collect::<Vec<_>>();
+    if let Some(output) = outputs.iter().find(|o| o.name() == name) {
+        let geometry = output.geometry();
+        let transform = Transform::from(output.current_transform());
+        let panel_size = transform
+            .invert()
+            .transform_size(geometry.size.as_logical());
+        let position = transform
+            .transform_point_in(event.position_transformed(panel_size), &panel_size.to_f64());
+        return Some((output.clone(), geometry.loc.to_f64() + position.as_global()));
+    }
+
+    let bbox = outputs
+        .iter()
+        .map(|o| o.geometry())
+        .reduce(|a, b| a.merge(b))?;
+    let position = bbox.loc.to_f64()
+        + event
+            .position_transformed(bbox.size.as_logical())
+            .as_global();
+    let output = outputs
+        .iter()
+        .find(|o| o.geometry().to_f64().contains(position))
+        .or(outputs.first())?
+        .clone();
+    Some((output, position))
+}
+
+/// Adds the rotation of `step` to `transform`, flipping it, if `step` is flipped.
+fn has_exclusive_layer_focus(seat: &Seat<State>) -> bool {
+    matches!(
+        seat.get_keyboard().unwrap().current_focus(),
+        Some(KeyboardFocusTarget::LayerSurface(layer))
+            if layer.cached_state().keyboard_interactivity == KeyboardInteractivity::Exclusive
+    )
+}
+
+fn rotate_transform(transform: Transform, step: Transform) -> Transform {
+    let quarter_turns = |transform| match transform {
+        Transform::Normal | Transform::Flipped => 0,
+        Transform::_90 | Transform::Flipped90 => 1,
+        Transform::_180 | Transform::Flipped180 => 2,
+        Transform::_270 | Transform::Flipped270 => 3,
+    };
+    let is_flipped = |transform| {
+        matches!(
+            transform,
+            Transform::Flipped
+                | Transform::Flipped90
+                | Transform::Flipped180
+                | Transform::Flipped270
+        )
+    };
+    match (
+        (quarter_turns(transform) + quarter_turns(step)) % 4,
+        is_flipped(transform) != is_flipped(step),
+    ) {
+        (0, false) => Transform::Normal,
+        (1, false) => Transform::_90,
+        (2, false) => Transform::_180,
+        (3, false) => Transform::_270,
+        (0, true) => Transform::Flipped,
+        (1, true) => Transform::Flipped90,
+        (2, true) => Transform::Flipped180,
+        _ => Transform::Flipped270,
+    }
+}
+
 fn sessions_for_output(state: &Common, output: &Output) -> impl Iterator<Item = Session> {
     let workspace = state.shell.active_space(&output);
     let maybe_fullscreen = workspace.get_fullscreen();
@@ -1861,3 +3902,281 @@ fn sessions_for_output(state: &Common, output: &Output) -> impl Iterator<Item =
         .collect::<Vec<_>>()
         .into_iter()
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{binding_active, binding_pressed, layer_popup_under, layer_surface_under};
+    use crate::{
+        config::{Action, KeyModifiers, KeyPattern},
+        shell::focus::target::PointerFocusTarget,
+        utils::{
+            prelude::{Global, Local, OutputExt, SeatExt},
+            testing::{self, TestClient, TestServer},
+        },
+    };
+    use smithay::{
+        desktop::{layer_map_for_output, LayerSurface, PopupManager},
+        input::keyboard::ModifiersState,
+        output::Output,
+        utils::{Point, SERIAL_COUNTER},
+        wayland::shell::wlr_layer::Layer as WlrLayer,
+    };
+    use wayland_client::protocol::{wl_compositor::WlCompositor, wl_surface::WlSurface};
+    use wayland_protocols::xdg::shell::client::{
+        xdg_positioner::{Anchor, Gravity},
+        xdg_wm_base::XdgWmBase,
+    };
+    use wayland_protocols_wlr::layer_shell::v1::client::{
+        zwlr_layer_shell_v1::{self, ZwlrLayerShellV1},
+        zwlr_layer_surface_v1::{self, ZwlrLayerSurfaceV1},
+    };
+    use xkbcommon::xkb::Keysym;
+
+    /// Creates a top layer surface of `size` in the top left corner of `output`,
+    /// which accepts input only within `input_region`.
+    ///
+    /// The surface is kept out of the shell, as nothing can be rendered without backend.
+    fn layer_surface(
+        server: &mut TestServer,
+        client: &mut TestClient,
+        output: &Output,
+        size: (i32, i32),
+        input_region: (i32, i32, i32, i32),
+    ) -> (LayerSurface, ZwlrLayerSurfaceV1) {
+        let qh = client.handle();
+        let surface = client.bind::<WlCompositor>(5).create_surface(&qh, ());
+        let layer_surface = client.bind::<ZwlrLayerShellV1>(4).get_layer_surface(
+            &surface,
+            None,
+            zwlr_layer_shell_v1::Layer::Top,
+            String::from("test"),
+            &qh,
+            (),
+        );
+        layer_surface.set_size(size.0 as u32, size.1 as u32);
+        layer_surface
+            .set_anchor(zwlr_layer_surface_v1::Anchor::Top | zwlr_layer_surface_v1::Anchor::Left);
+        server.roundtrip(client);
+        let (layer, _, _) = server.state.common.shell.pending_layers.pop().unwrap();
+
+        surface.commit();
+        server.roundtrip(client);
+        layer_map_for_output(output).map_layer(&layer).unwrap();
+        layer.layer_surface().send_configure();
+        server.roundtrip(client);
+
+        attach(client, &surface, size, input_region);
+        server.roundtrip(client);
+        layer_map_for_output(output).arrange();
+        (layer, layer_surface)
+    }
+
+    fn attach(
+        client: &TestClient,
+        surface: &WlSurface,
+        size: (i32, i32),
+        (x, y, width, height): (i32, i32, i32, i32),
+    ) {
+        surface.attach(Some(&client.create_buffer(size.0, size.1)), 0, 0);
+        surface.set_input_region(Some(&client.create_region(&[(x, y, width, height)])));
+        surface.commit();
+    }
+
+    fn pos(x: f64, y: f64) -> Point<f64, Local> {
+        Point::from((x, y))
+    }
+
+    fn loc(x: i32, y: i32) -> Point<i32, Global> {
+        Point::from((x, y))
+    }
+
+    #[test]
+    fn layer_surface_margins_pass_through() {
+        let mut server = TestServer::new();
+        let mut client = server.connect();
+        let output = testing::create_output("LAYERS-1", (0, 0));
+        let (below, _) = layer_surface(
+            &mut server,
+            &mut client,
+            &output,
+            (200, 200),
+            (0, 0, 200, 200),
+        );
+        // a shadow of 20px around it is excluded from its input region
+        let (above, _) = layer_surface(
+            &mut server,
+            &mut client,
+            &output,
+            (100, 100),
+            (20, 20, 60, 60),
+        );
+
+        let under = |pos| layer_surface_under(&output, &[WlrLayer::Top], pos);
+        assert_eq!(
+            under(pos(50., 50.)),
+            Some((PointerFocusTarget::LayerSurface(above), loc(0, 0)))
+        );
+        let below = Some((PointerFocusTarget::LayerSurface(below), loc(0, 0)));
+        assert_eq!(under(pos(10., 10.)), below);
+        assert_eq!(under(pos(90., 50.)), below);
+        assert_eq!(under(pos(150., 150.)), below);
+        assert_eq!(under(pos(250., 250.)), None);
+        assert_eq!(
+            layer_surface_under(&output, &[WlrLayer::Bottom], pos(50., 50.)),
+            None
+        );
+    }
+
+    #[test]
+    fn layer_popups_are_hit_within_their_input_region() {
+        let mut server = TestServer::new();
+        let mut client = server.connect();
+        let output = testing::create_output("LAYERS-1", (0, 0));
+        let (layer, layer_surface) = layer_surface(
+            &mut server,
+            &mut client,
+            &output,
+            (100, 100),
+            (0, 0, 100, 100),
+        );
+
+        // a 50x50 popup below the bottom right corner of the layer surface
+        let qh = client.handle();
+        let wm_base = client.bind::<XdgWmBase>(2);
+        let positioner = wm_base.create_positioner(&qh, ());
+        positioner.set_size(50, 50);
+        positioner.set_anchor_rect(0, 0, 100, 100);
+        positioner.set_anchor(Anchor::BottomRight);
+        positioner.set_gravity(Gravity::BottomRight);
+        let surface = client.bind::<WlCompositor>(5).create_surface(&qh, ());
+        let xdg_popup =
+            wm_base
+                .get_xdg_surface(&surface, &qh, ())
+                .get_popup(None, &positioner, &qh, ());
+        layer_surface.get_popup(&xdg_popup);
+        surface.commit();
+        server.roundtrip(&mut client);
+        attach(&client, &surface, (50, 50), (10, 10, 30, 30));
+        server.roundtrip(&mut client);
+
+        let (popup, _) = PopupManager::popups_for_surface(layer.wl_surface())
+            .next()
+            .unwrap();
+        let under = |pos| layer_popup_under(&output, WlrLayer::Top, pos);
+        assert_eq!(
+            under(pos(120., 120.)),
+            Some((PointerFocusTarget::Popup(popup), loc(100, 100)))
+        );
+        // within the popup, but outside of its input region
+        assert_eq!(under(pos(105., 105.)), None);
+        // the layer surface itself isn't a popup
+        assert_eq!(under(pos(50., 50.)), None);
+    }
+
+    #[test]
+    fn next_output_only_moves_the_triggering_seat() {
+        let mut server = TestServer::new();
+        let left = server.state.common.shell.outputs().next().unwrap().clone();
+        let right = server.add_output("TEST-2", (1920, 0));
+        let seat0 = server.state.common.seats().next().unwrap().clone();
+        let seat1 = server.add_seat("seat-1");
+        let location1 = seat1.get_pointer().unwrap().current_location();
+
+        server.state.handle_action(
+            Action::NextOutput,
+            &seat0,
+            SERIAL_COUNTER.next_serial(),
+            0,
+            KeyPattern {
+                modifiers: KeyModifiers::default(),
+                key: None,
+            },
+            None,
+        );
+
+        assert_eq!(seat0.active_output(), right);
+        let location0 = seat0.get_pointer().unwrap().current_location();
+        assert!(right.geometry().as_logical().to_f64().contains(location0));
+        assert_eq!(seat1.active_output(), left);
+        assert_eq!(seat1.get_pointer().unwrap().current_location(), location1);
+    }
+
+    fn terminate() -> (KeyPattern, Action) {
+        (
+            KeyPattern {
+                modifiers: KeyModifiers {
+                    ctrl: true,
+                    alt: true,
+                    ..Default::default()
+                },
+                key: Some(Keysym::BackSpace),
+            },
+            Action::Terminate,
+        )
+    }
+
+    #[test]
+    fn terminate_is_matched_without_shortcuts_inhibitor() {
+        let (binding, action) = terminate();
+        assert!(binding_active(&binding, &action, false, true, false));
+        let modifiers = ModifiersState {
+            ctrl: true,
+            alt: true,
+            ..Default::default()
+        };
+        assert!(binding_pressed(&binding, &[Keysym::BackSpace], &modifiers));
+    }
+
+    #[test]
+    fn shortcuts_inhibitor_blocks_terminate() {
+        let (binding, action) = terminate();
+        assert!(!binding_active(&binding, &action, true, true, false));
+    }
+
+    #[test]
+    fn terminate_survives_disabled_shortcuts() {
+        let (binding, action) = terminate();
+        assert!(binding_active(&binding, &action, false, false, false));
+        assert!(binding_active(&binding, &action, false, true, true));
+        assert!(!binding_active(
+            &binding,
+            &Action::Close,
+            false,
+            false,
+            false
+        ));
+        assert!(!binding_active(&binding, &Action::Close, false, true, true));
+    }
+
+    #[test]
+    fn brightness_keys_survive_inhibitor() {
+        let binding = KeyPattern {
+            modifiers: KeyModifiers::default(),
+            key: Some(Keysym::XF86_MonBrightnessUp),
+        };
+        assert!(binding_active(&binding, &Action::Close, true, true, false));
+    }
+
+    #[test]
+    fn bindings_need_exact_modifiers() {
+        let (binding, _) = terminate();
+        let ctrl = ModifiersState {
+            ctrl: true,
+            ..Default::default()
+        };
+        assert!(!binding_pressed(&binding, &[Keysym::BackSpace], &ctrl));
+        let all = ModifiersState {
+            ctrl: true,
+            alt: true,
+            shift: true,
+            ..Default::default()
+        };
+        assert!(!binding_pressed(&binding, &[Keysym::BackSpace], &all));
+        let modifiers = ModifiersState {
+            ctrl: true,
+            alt: true,
+            ..Default::default()
+        };
+        assert!(!binding_pressed(&binding, &[Keysym::Delete], &modifiers));
+    }
+}
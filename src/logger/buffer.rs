@@ -0,0 +1,123 @@
+// SPDX-License-Identifier: GPL-3.0-only
+
+use std::{
+    collections::VecDeque, fmt::Write as _, io::Write, path::Path, sync::Mutex, time::SystemTime,
+};
+
+use tracing::{
+    field::{Field, Visit},
+    Event, Level, Subscriber,
+};
+use tracing_subscriber::{layer::Context, Layer};
+
+pub const LOG_BUFFER_SIZE: usize = 10_000;
+
+#[derive(Debug, Clone)]
+pub struct LogEntry {
+    pub time: SystemTime,
+    pub level: Level,
+    pub target: &'static str,
+    pub message: String,
+}
+
+/// Bounded in-memory log history.
+///
+/// Entries are formatted before the lock is taken, so the critical section
+/// is a single push/pop and logging from the input path never waits on a reader for long.
+#[derive(Debug)]
+pub struct LogBuffer {
+    entries: Mutex<VecDeque<LogEntry>>,
+}
+
+impl LogBuffer {
+    pub const fn new() -> LogBuffer {
+        LogBuffer {
+            entries: Mutex::new(VecDeque::new()),
+        }
+    }
+
+    pub(super) fn push(&self, entry: LogEntry) {
+        let mut entries = self.entries.lock().unwrap();
+        if entries.len() >= LOG_BUFFER_SIZE {
+            entries.pop_front();
+        }
+        entries.push_back(entry);
+    }
+
+    /// Returns up to `limit` of the most recent entries matching `filter`, oldest first.
+    pub fn recent(&self, limit: usize, mut filter: impl FnMut(&LogEntry) -> bool) -> Vec<LogEntry> {
+        let entries = self.entries.lock().unwrap();
+        let mut result = entries
+            .iter()
+            .rev()
+            .filter(|entry| filter(entry))
+            .take(limit)
+            .cloned()
+            .collect::<Vec<_>>();
+        result.reverse();
+        result
+    }
+
+    pub fn dump(&self, path: &Path) -> std::io::Result<()> {
+        let entries = self.recent(LOG_BUFFER_SIZE, |_| true);
+        let mut file = std::io::BufWriter::new(std::fs::File::create(path)?);
+        for entry in entries {
+            writeln!(file, "{}", entry)?;
+        }
+        file.flush()
+    }
+}
+
+impl std::fmt::Display for LogEntry {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let time = self
+            .time
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .unwrap_or_default();
+        write!(
+            f,
+            "{}.{:06} {:>5} {}: {}",
+            time.as_secs(),
+            time.subsec_micros(),
+            self.level,
+            self.target,
+            self.message
+        )
+    }
+}
+
+/// Layer feeding every event it is handed into a [`LogBuffer`].
+pub struct BufferLayer(pub &'static LogBuffer);
+
+impl<S: Subscriber> Layer<S> for BufferLayer {
+    fn on_event(&self, event: &Event<'_>, _ctx: Context<'_, S>) {
+        let mut visitor = MessageVisitor(String::new());
+        event.record(&mut visitor);
+        let metadata = event.metadata();
+        self.0.push(LogEntry {
+            time: SystemTime::now(),
+            level: *metadata.level(),
+            target: metadata.target(),
+            message: visitor.0,
+        });
+    }
+}
+
+struct MessageVisitor(String);
+
+impl Visit for MessageVisitor {
+    fn record_debug(&mut self, field: &Field, value: &dyn std::fmt::Debug) {
+        if field.name() == "message" {
+            if self.0.is_empty() {
+                let _ = write!(self.0, "{:?}", value);
+            } else {
+                self.0 = format!("{:?} {}", value, self.0);
+            }
+        } else {
+            if !self.0.is_empty() {
+                self.0.push(' ');
+            }
+            let _ = write!(self.0, "{}={:?}", field.name(), value);
+        }
+    }
+}
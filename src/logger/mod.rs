@@ -1,48 +1,94 @@
 // SPDX-License-Identifier: GPL-3.0-only
 
-use std::str::FromStr;
+use std::{
+    path::Path,
+    str::FromStr,
+    sync::{
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        OnceLock,
+    },
+    time::SystemTime,
+};
 
 use anyhow::Result;
 
-use tracing::{debug, info, warn};
+use tracing::{debug, info, level_filters::STATIC_MAX_LEVEL, warn, Level, Metadata};
 use tracing_journald as journald;
-use tracing_subscriber::{filter::Directive, fmt, prelude::*, EnvFilter};
+use tracing_subscriber::{
+    filter::{filter_fn, Directive, LevelFilter},
+    fmt,
+    prelude::*,
+    reload, EnvFilter, Registry,
+};
 
-pub fn init_logger() -> Result<()> {
-    let level = if cfg!(debug_assertions) {
-        "debug"
-    } else {
-        "warn"
-    };
-    let filter = EnvFilter::try_from_default_env()
+mod buffer;
+pub use self::buffer::{LogBuffer, LogEntry, LOG_BUFFER_SIZE};
+
+pub static LOG_BUFFER: LogBuffer = LogBuffer::new();
+
+const LEVELS: [LevelFilter; 5] = [
+    LevelFilter::ERROR,
+    LevelFilter::WARN,
+    LevelFilter::INFO,
+    LevelFilter::DEBUG,
+    LevelFilter::TRACE,
+];
+
+static FILTER: OnceLock<reload::Handle<EnvFilter, Registry>> = OnceLock::new();
+static LEVEL: AtomicUsize = AtomicUsize::new(1);
+static CAPTURE_INPUT: AtomicBool = AtomicBool::new(false);
+
+fn filter(level: LevelFilter) -> EnvFilter {
+    // directives of RUST_LOG stay in place, only the level of the compositor itself changes
+    EnvFilter::try_from_default_env()
         .unwrap_or_else(|_| {
             EnvFilter::new(if cfg!(debug_assertions) {
                 "info"
             } else {
                 "warn"
             })
+            .add_directive(Directive::from_str("cosmic_text=error").unwrap())
+            .add_directive(Directive::from_str("calloop=error").unwrap())
         })
-        .add_directive(Directive::from_str("cosmic_text=error").unwrap())
-        .add_directive(Directive::from_str("calloop=error").unwrap())
         .add_directive(Directive::from_str(&format!("smithay={level}")).unwrap())
-        .add_directive(Directive::from_str(&format!("cosmic_comp={level}")).unwrap());
+        .add_directive(Directive::from_str(&format!("cosmic_comp={level}")).unwrap())
+}
 
-    let fmt_layer = fmt::layer().compact();
+fn buffer_enabled(metadata: &Metadata<'_>) -> bool {
+    let target = metadata.target();
+    let level = if target.starts_with("cosmic_comp") || target.starts_with("smithay") {
+        level()
+    } else {
+        LevelFilter::WARN
+    };
+    *metadata.level() <= level
+        || (CAPTURE_INPUT.load(Ordering::Relaxed) && target.starts_with("cosmic_comp::input"))
+}
+
+pub fn init_logger() -> Result<()> {
+    let level = if cfg!(debug_assertions) {
+        LevelFilter::DEBUG
+    } else {
+        LevelFilter::WARN
+    };
+    store_level(level);
+    let filter = EnvFilter::try_from_default_env().unwrap_or_else(|_| filter(level));
+    let (filter, handle) = reload::Layer::new(filter);
+    let _ = FILTER.set(handle);
 
-    match journald::layer() {
-        Ok(journald_layer) => tracing_subscriber::registry()
-            .with(fmt_layer)
-            .with(journald_layer)
-            .with(filter)
-            .init(),
-        Err(err) => {
-            tracing_subscriber::registry()
-                .with(fmt_layer)
-                .with(filter)
-                .init();
-            warn!(?err, "Failed to init journald logging.");
-        }
+    let fmt_layer = fmt::layer().compact();
+    let (journald_layer, journald_err) = match journald::layer() {
+        Ok(journald_layer) => (Some(journald_layer), None),
+        Err(err) => (None, Some(err)),
     };
+
+    tracing_subscriber::registry()
+        .with(fmt_layer.and_then(journald_layer).with_filter(filter))
+        .with(buffer::BufferLayer(&LOG_BUFFER).with_filter(filter_fn(buffer_enabled)))
+        .init();
+    if let Some(err) = journald_err {
+        warn!(?err, "Failed to init journald logging.");
+    }
     log_panics::init();
 
     info!("Version: {}", std::env!("CARGO_PKG_VERSION"));
@@ -55,3 +101,70 @@ pub fn init_logger() -> Result<()> {
 
     Ok(())
 }
+
+fn store_level(level: LevelFilter) {
+    let index = LEVELS.iter().position(|l| *l == level).unwrap_or(0);
+    LEVEL.store(index, Ordering::Relaxed);
+}
+
+/// Current runtime log level of the compositor.
+pub fn level() -> LevelFilter {
+    LEVELS[LEVEL.load(Ordering::Relaxed)]
+}
+
+pub fn set_level(level: LevelFilter) {
+    let level = level.min(STATIC_MAX_LEVEL).max(LevelFilter::ERROR);
+    let Some(handle) = FILTER.get() else {
+        return;
+    };
+    if let Err(err) = handle.reload(filter(level)) {
+        warn!(?err, "Failed to change log level.");
+        return;
+    }
+    store_level(level);
+    info!(%level, "Changed log level.");
+}
+
+/// Switches to the next more verbose log level, wrapping around to errors only.
+pub fn cycle_level() -> LevelFilter {
+    let next = LEVELS
+        .iter()
+        .copied()
+        .skip_while(|l| *l <= level())
+        .find(|l| *l <= STATIC_MAX_LEVEL)
+        .unwrap_or(LevelFilter::ERROR);
+    set_level(next);
+    level()
+}
+
+/// Records input events into the log buffer regardless of the log level,
+/// used while the debug overlay is open.
+pub fn set_input_capture(capture: bool) {
+    CAPTURE_INPUT.store(capture, Ordering::Relaxed);
+}
+
+/// Whether logging every input event is worth the cost right now.
+pub fn trace_input() -> bool {
+    CAPTURE_INPUT.load(Ordering::Relaxed) || level() == LevelFilter::TRACE
+}
+
+/// Records an input event into the log buffer, if `trace_input` is set.
+///
+/// Goes straight to the buffer, because the `max_level_*` features of `tracing`
+/// compile out trace events in every build.
+pub fn record_input(message: std::fmt::Arguments<'_>) {
+    if trace_input() {
+        LOG_BUFFER.push(LogEntry {
+            time: SystemTime::now(),
+            level: Level::TRACE,
+            target: "cosmic_comp::input",
+            message: message.to_string(),
+        });
+    }
+}
+
+pub fn dump_logs(path: &Path) -> std::io::Result<()> {
+    LOG_BUFFER.dump(path)?;
+    info!(?path, "Dumped log buffer.");
+    Ok(())
+}
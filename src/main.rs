@@ -2,14 +2,21 @@
 
 use smithay::{
     reexports::{
-        calloop::{generic::Generic, EventLoop, Interest, Mode, PostAction},
+        calloop::{
+            generic::Generic,
+            signals::{Signal, Signals},
+            EventLoop, Interest, Mode, PostAction,
+        },
         wayland_server::{Display, DisplayHandle},
     },
     wayland::socket::ListeningSocketSource,
 };
 
 use anyhow::{Context, Result};
-use std::{ffi::OsString, sync::Arc};
+use std::{
+    ffi::OsString,
+    panic::{self, AssertUnwindSafe},
+};
 use tracing::{error, info, warn};
 
 use crate::wayland::handlers::compositor::client_compositor_state;
@@ -20,6 +27,7 @@ pub mod config;
 pub mod debug;
 pub mod input;
 mod logger;
+pub mod recovery;
 pub mod session;
 pub mod shell;
 pub mod state;
@@ -35,6 +43,23 @@ fn main() -> Result<()> {
     logger::init_logger()?;
     info!("Cosmic starting up!");
 
+    // potentially start the profiler
+    #[cfg(feature = "profile")]
+    let _profiler = {
+        puffin::set_scopes_on(true);
+        let addr = format!("127.0.0.1:{}", puffin_http::DEFAULT_PORT);
+        match puffin_http::Server::new(&addr) {
+            Ok(server) => {
+                info!("Profiler listening on {}", addr);
+                Some(server)
+            }
+            Err(err) => {
+                warn!(?err, "Failed to start profiling server");
+                None
+            }
+        }
+    };
+
     // init event loop
     let mut event_loop = EventLoop::try_new().with_context(|| "Failed to initialize event loop")?;
     // init wayland
@@ -48,10 +73,30 @@ fn main() -> Result<()> {
     );
     // init backend
     backend::init_backend_auto(&display, &mut event_loop, &mut state)?;
+    // measure how often clients commit, for debugging and throttling
+    utils::commit_stats::init(&event_loop.handle());
+    // detect windows, that stopped responding or never ack their configures
+    shell::init_configure_timeouts(&event_loop.handle());
+    wayland::handlers::xdg_shell::ping::init(&event_loop.handle());
+    // let portals and D-Bus activated apps find us
+    session::import_environment(&state);
     // potentially tell systemd we are setup now
     #[cfg(feature = "systemd")]
     if let state::BackendData::Kms(_) = &state.backend {
-        systemd::ready(&state);
+        systemd::ready();
+        systemd::watchdog(&event_loop.handle());
+    }
+    // shutdown cleanly on SIGTERM, the same way as `Action::Terminate`
+    match Signals::new(&[Signal::SIGTERM]) {
+        Ok(signals) => {
+            if let Err(err) = event_loop.handle().insert_source(signals, |_, _, state| {
+                info!("Received SIGTERM");
+                state.common.should_stop = true;
+            }) {
+                warn!(?err, "Failed to handle SIGTERM");
+            }
+        }
+        Err(err) => warn!(?err, "Failed to handle SIGTERM"),
     }
     // potentially tell the session we are setup now
     session::setup_socket(event_loop.handle(), &state)?;
@@ -61,35 +106,55 @@ fn main() -> Result<()> {
     }
 
     // run the event loop
-    event_loop.run(None, &mut state, |state| {
-        // shall we shut down?
-        if state.common.shell.outputs().next().is_none() || state.common.should_stop {
-            info!("Shutting down");
-            state.common.event_loop_signal.stop();
-            state.common.event_loop_signal.wakeup();
-            return;
-        }
+    let result = panic::catch_unwind(AssertUnwindSafe(|| {
+        event_loop.run(None, &mut state, |state| {
+            // shall we shut down?
+            if state.common.shell.outputs().next().is_none()
+                || (state.common.should_stop && state.ready_to_stop())
+            {
+                info!("Shutting down");
+                state.common.event_loop_signal.stop();
+                state.common.event_loop_signal.wakeup();
+                return;
+            }
 
-        // trigger routines
-        let clients = state.common.shell.update_animations();
-        {
-            let dh = state.common.display_handle.clone();
-            for client in clients.values() {
-                client_compositor_state(&client).blocker_cleared(state, &dh);
+            // trigger routines
+            let clients = state.common.shell.update_animations();
+            {
+                let dh = state.common.display_handle.clone();
+                for client in clients.values() {
+                    client_compositor_state(&client).blocker_cleared(state, &dh);
+                }
             }
-        }
-        state.common.shell.refresh();
-        state::Common::refresh_focus(state);
+            state.common.shell.refresh();
+            state.common.publish_primary_output();
+            state::Common::refresh_focus(state);
+            state.refresh_idle_inhibit();
+            state.disconnect_stalled_clients();
+
+            // send out events
+            state.flush_clients();
+        })
+    }));
 
-        // send out events
-        let _ = state.common.display_handle.flush_clients();
-    })?;
+    let failed = !matches!(result, Ok(Ok(()))) || state.common.recovery.failed;
+    let fallback = state.common.config.static_conf.fallback_compositor.clone();
 
-    // drop eventloop & state before logger
+    // drop eventloop & state before logger,
+    // which also removes the wayland socket and hands the tty back to the session
     std::mem::drop(event_loop);
     std::mem::drop(state);
 
-    Ok(())
+    if failed {
+        if let Some(command) = fallback {
+            recovery::exec_fallback(&command);
+        }
+    }
+
+    match result {
+        Ok(result) => Ok(result?),
+        Err(payload) => panic::resume_unwind(payload),
+    }
 }
 
 fn init_wayland_display(
@@ -105,14 +170,12 @@ fn init_wayland_display(
     event_loop
         .handle()
         .insert_source(source, |client_stream, _, state| {
-            if let Err(err) = state.common.display_handle.insert_client(
-                client_stream,
-                Arc::new(if cfg!(debug_assertions) {
-                    state.new_privileged_client_state()
-                } else {
-                    state.new_client_state()
-                }),
-            ) {
+            let client_state = if cfg!(debug_assertions) {
+                state.new_privileged_client_state()
+            } else {
+                state.new_client_state()
+            };
+            if let Err(err) = state.insert_client(client_stream, client_state) {
                 warn!(?err, "Error adding wayland client");
             };
         })
@@ -0,0 +1,206 @@
+// SPDX-License-Identifier: GPL-3.0-only
+
+use std::{
+    any::Any,
+    collections::{HashMap, VecDeque},
+    os::unix::process::CommandExt,
+    panic::{self, AssertUnwindSafe},
+    process::Command,
+    time::{Duration, Instant},
+};
+
+use smithay::{
+    desktop::space::SpaceElement,
+    reexports::calloop::timer::{TimeoutAction, Timer},
+    utils::{Rectangle, Size},
+};
+use tracing::{error, warn};
+
+use crate::{
+    shell::element::error_banner::{error_banner, ErrorBanner},
+    utils::prelude::*,
+};
+
+/// Panics of the same code path within this window count as repeated.
+const PANIC_WINDOW: Duration = Duration::from_secs(1);
+const PANIC_LIMIT: usize = 5;
+/// Hitting the limit again this soon after recovering means recovery did not help.
+const RECOVERY_GRACE: Duration = Duration::from_secs(10);
+const BANNER_HEIGHT: i32 = 48;
+const BANNER_TIMEOUT: Duration = Duration::from_secs(10);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum CodePath {
+    Render,
+    Input,
+}
+
+#[derive(Debug, Default)]
+pub struct Recovery {
+    panics: HashMap<CodePath, VecDeque<Instant>>,
+    recovered: Option<Instant>,
+    pub banner: Option<ErrorBanner>,
+    /// Recovery failed and the compositor is shutting down.
+    pub failed: bool,
+}
+
+impl Recovery {
+    /// Records a panic and returns if the code path panicked too often recently.
+    fn record(&mut self, path: CodePath) -> bool {
+        let now = Instant::now();
+        let panics = self.panics.entry(path).or_default();
+        panics.push_back(now);
+        while panics
+            .front()
+            .is_some_and(|time| now.duration_since(*time) > PANIC_WINDOW)
+        {
+            panics.pop_front();
+        }
+        if panics.len() >= PANIC_LIMIT {
+            panics.clear();
+            true
+        } else {
+            false
+        }
+    }
+}
+
+fn panic_message(payload: &(dyn Any + Send)) -> &str {
+    if let Some(msg) = payload.downcast_ref::<&str>() {
+        msg
+    } else if let Some(msg) = payload.downcast_ref::<String>() {
+        msg.as_str()
+    } else {
+        "Box<dyn Any>"
+    }
+}
+
+impl State {
+    /// Runs `func`, turning a panic into a skipped frame or event instead of a crashed session.
+    ///
+    /// The panic hook installed by the logger already logs the backtrace.
+    pub fn catch_panic<T>(
+        &mut self,
+        path: CodePath,
+        func: impl FnOnce(&mut State) -> T,
+    ) -> Option<T> {
+        match panic::catch_unwind(AssertUnwindSafe(|| func(self))) {
+            Ok(result) => Some(result),
+            Err(payload) => {
+                error!(
+                    ?path,
+                    message = panic_message(&*payload),
+                    "Recovered from panic, skipping."
+                );
+                if self.common.recovery.record(path) {
+                    self.enter_recovery(path);
+                }
+                None
+            }
+        }
+    }
+
+    fn enter_recovery(&mut self, path: CodePath) {
+        let now = Instant::now();
+        if self
+            .common
+            .recovery
+            .recovered
+            .is_some_and(|time| now.duration_since(time) < RECOVERY_GRACE)
+        {
+            error!(?path, "Recovery failed, shutting down.");
+            self.common.recovery.failed = true;
+            self.common.should_stop = true;
+            return;
+        }
+        warn!(?path, "Repeated panics, entering recovery mode.");
+        self.common.recovery.recovered = Some(now);
+
+        #[cfg(feature = "debug")]
+        {
+            self.common.egui.active = false;
+            crate::logger::set_input_capture(false);
+            for mapped in self
+                .common
+                .shell
+                .workspaces
+                .spaces()
+                .flat_map(|w| w.mapped())
+            {
+                mapped.set_debug(false);
+            }
+        }
+
+        // move away from whatever we were showing, in case it triggered the issue
+        let outputs = self.common.shell.outputs().cloned().collect::<Vec<_>>();
+        for output in &outputs {
+            if self
+                .common
+                .shell
+                .active_space(output)
+                .mapped()
+                .next()
+                .is_none()
+            {
+                continue;
+            }
+            let empty = self
+                .common
+                .shell
+                .workspaces
+                .spaces_for_output(output)
+                .position(|w| w.mapped().next().is_none());
+            match empty {
+                Some(idx) => {
+                    let _ = self.common.shell.activate(output, idx);
+                }
+                None => warn!(output = output.name(), "No empty workspace to recover to."),
+            }
+        }
+
+        let width = outputs
+            .iter()
+            .map(|output| output.geometry().size.w)
+            .max()
+            .unwrap_or(0);
+        let banner = error_banner(
+            crate::fl!("compositor-recovered"),
+            Size::from((width, BANNER_HEIGHT)),
+            self.common.event_loop_handle.clone(),
+            self.common.theme.clone(),
+        );
+        for output in &outputs {
+            banner.output_enter(
+                output,
+                Rectangle::from_loc_and_size((0, 0), output.geometry().size.as_logical()),
+            );
+        }
+        self.common.recovery.banner = Some(banner);
+        if let Err(err) = self.common.event_loop_handle.insert_source(
+            Timer::from_duration(BANNER_TIMEOUT),
+            |_, _, state| {
+                state.common.recovery.banner = None;
+                for output in state.common.shell.outputs().cloned().collect::<Vec<_>>() {
+                    state
+                        .backend
+                        .schedule_render(&state.common.event_loop_handle, &output, None);
+                }
+                TimeoutAction::Drop
+            },
+        ) {
+            warn!(?err, "Failed to schedule removal of the error banner.");
+        }
+
+        for output in outputs {
+            self.backend
+                .schedule_render(&self.common.event_loop_handle, &output, None);
+        }
+    }
+}
+
+/// Replaces the process with the configured fallback compositor.
+pub fn exec_fallback(command: &str) {
+    warn!(command, "Starting fallback compositor.");
+    let err = Command::new("/bin/sh").arg("-c").arg(command).exec();
+    error!(?err, "Failed to start fallback compositor.");
+}
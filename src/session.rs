@@ -1,8 +1,11 @@
 // SPDX-License-Identifier: GPL-3.0-only
 
-use smithay::reexports::{
-    calloop::{generic::Generic, Interest, LoopHandle, Mode, PostAction},
-    rustix,
+use smithay::{
+    output::Output,
+    reexports::{
+        calloop::{generic::Generic, Interest, LoopHandle, Mode, PostAction},
+        rustix,
+    },
 };
 
 use anyhow::{anyhow, Context, Result};
@@ -15,17 +18,139 @@ use std::{
         io::{AsFd, BorrowedFd, FromRawFd, RawFd},
         net::UnixStream,
     },
-    sync::Arc,
+    path::PathBuf,
+    process::Command,
 };
-use tracing::{error, warn};
+use tracing::{error, info, level_filters::LevelFilter, warn};
 
-use crate::state::State;
+use crate::{
+    input::Accessibility,
+    shell::{element::surface::WindowIdentity, CosmicSurface},
+    state::State,
+    utils::{
+        commit_stats::{top_offenders, WindowStats},
+        prelude::*,
+    },
+};
 
 #[derive(Debug, Serialize, Deserialize)]
 #[serde(rename_all = "snake_case", tag = "message")]
 pub enum Message {
-    SetEnv { variables: HashMap<String, String> },
-    NewPrivilegedClient { count: usize },
+    SetEnv {
+        variables: HashMap<String, String>,
+    },
+    NewPrivilegedClient {
+        count: usize,
+    },
+    SetLogLevel {
+        level: String,
+    },
+    DumpLogs {
+        path: PathBuf,
+    },
+    GetInputState,
+    InputState {
+        shortcuts: bool,
+        inhibited: bool,
+    },
+    ReloadBackground,
+    GetLockState,
+    LockState {
+        caps_lock: bool,
+        num_lock: bool,
+    },
+    GetAccessibilityState,
+    AccessibilityState {
+        suspended: bool,
+        latched: Vec<String>,
+        locked: Vec<String>,
+    },
+    GetFocusedWindow,
+    FocusedWindow {
+        window: Option<WindowInfo>,
+    },
+    /// Ask for `WindowChanged` messages, whenever a title or app_id changes
+    SubscribeWindowEvents,
+    WindowChanged {
+        title: String,
+        app_id: String,
+        focused: bool,
+    },
+    GetPrimaryOutput,
+    PrimaryOutput {
+        name: Option<String>,
+    },
+    SetPrimaryOutput {
+        name: String,
+    },
+    /// Ask for `PrimaryOutput` messages, whenever the primary output changes
+    SubscribeOutputEvents,
+    GetClientStats,
+    /// Commit rates of all windows, the most frequently committing first
+    ClientStats {
+        clients: Vec<WindowStats>,
+    },
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct WindowInfo {
+    pub title: String,
+    pub app_id: String,
+    /// Global x, y, width and height
+    pub geometry: [i32; 4],
+}
+
+/// Session socket subscribed to window events, see `Message::SubscribeWindowEvents`
+#[derive(Debug, Default)]
+pub struct WindowEvents(Option<UnixStream>);
+
+impl WindowEvents {
+    pub fn send(&mut self, window: &CosmicSurface, identity: WindowIdentity) {
+        let Some(stream) = self.0.as_mut() else {
+            return;
+        };
+        let message = Message::WindowChanged {
+            title: identity.title,
+            app_id: identity.app_id,
+            focused: window.is_activated(false),
+        };
+        if let Err(err) = send_message(stream, &message) {
+            warn!(?err, "Failed to send window event, unsubscribing");
+            self.0 = None;
+        }
+    }
+}
+
+/// Session socket subscribed to output events, see `Message::SubscribeOutputEvents`
+#[derive(Debug, Default)]
+pub struct OutputEvents(Option<UnixStream>);
+
+impl OutputEvents {
+    pub fn send_primary(&mut self, output: Option<&Output>) {
+        let Some(stream) = self.0.as_mut() else {
+            return;
+        };
+        let message = Message::PrimaryOutput {
+            name: output.map(|output| output.name()),
+        };
+        if let Err(err) = send_message(stream, &message) {
+            warn!(?err, "Failed to send output event, unsubscribing");
+            self.0 = None;
+        }
+    }
+}
+
+fn send_message(stream: &mut UnixStream, message: &Message) -> Result<()> {
+    let bytes = serde_json::to_string(message)
+        .with_context(|| "Failed to encode message into json")?
+        .into_bytes();
+    let len = (bytes.len() as u16).to_ne_bytes();
+    stream
+        .write_all(&len)
+        .with_context(|| "Failed to write message len")?;
+    stream
+        .write_all(&bytes)
+        .with_context(|| "Failed to write message bytes")
 }
 
 struct StreamWrapper {
@@ -59,6 +184,79 @@ unsafe fn set_cloexec(fd: RawFd) -> rustix::io::Result<()> {
     rustix::io::fcntl_setfd(fd, flags | rustix::io::FdFlags::CLOEXEC)
 }
 
+/// Variables clients need to connect to the compositor
+fn environment(state: &State) -> Result<HashMap<String, String>> {
+    let mut env = HashMap::new();
+    env.insert(
+        String::from("WAYLAND_DISPLAY"),
+        state
+            .common
+            .socket
+            .clone()
+            .into_string()
+            .map_err(|_| anyhow!("wayland socket is no valid utf-8 string?"))?,
+    );
+    if let Some(display) = state.common.xwayland_state.as_ref().map(|s| s.display) {
+        env.insert(String::from("DISPLAY"), format!(":{}", display));
+    }
+    Ok(env)
+}
+
+/// Pushes our variables into the systemd user and D-Bus activation environments,
+/// so portals and D-Bus activated apps can connect to the compositor as well.
+pub fn import_environment(state: &State) {
+    if !state.common.config.static_conf.import_environment {
+        info!("Not importing the environment into the session, disabled by the config.");
+        return;
+    }
+
+    let mut env = match environment(state) {
+        Ok(env) => env,
+        Err(err) => {
+            warn!(?err, "Failed to import the environment into the session.");
+            return;
+        }
+    };
+    env.insert(
+        String::from("XDG_CURRENT_DESKTOP"),
+        std::env::var("XDG_CURRENT_DESKTOP").unwrap_or_else(|_| String::from("COSMIC")),
+    );
+    let names = env.keys().cloned().collect::<Vec<_>>();
+
+    #[cfg(feature = "systemd")]
+    if libsystemd::daemon::booted() {
+        let mut cmd = Command::new("systemctl");
+        cmd.args(["--user", "import-environment"]).args(&names);
+        run_import(cmd, &env);
+    }
+
+    let bus_socket = std::env::var_os("XDG_RUNTIME_DIR")
+        .map(|dir| PathBuf::from(dir).join("bus"))
+        .filter(|path| path.exists());
+    if std::env::var_os("DBUS_SESSION_BUS_ADDRESS").is_none() && bus_socket.is_none() {
+        info!("No D-Bus session bus, not updating its activation environment.");
+        return;
+    }
+    let mut cmd = Command::new("dbus-update-activation-environment");
+    cmd.args(&names);
+    run_import(cmd, &env);
+}
+
+fn run_import(mut cmd: Command, env: &HashMap<String, String>) {
+    let program = cmd.get_program().to_string_lossy().into_owned();
+    match cmd.envs(env).status() {
+        Ok(status) if status.success() => {
+            info!(?env, "Imported the environment with {}.", program)
+        }
+        Ok(status) => warn!(
+            exit_code = ?status.code(),
+            "Failed to import the environment with {}.",
+            program,
+        ),
+        Err(err) => warn!(?err, "Failed to run {}.", program),
+    }
+}
+
 pub fn setup_socket(handle: LoopHandle<State>, state: &State) -> Result<()> {
     if let Ok(fd_num) = std::env::var("COSMIC_SESSION_SOCK") {
         if let Ok(fd) = fd_num.parse::<RawFd>() {
@@ -72,29 +270,8 @@ pub fn setup_socket(handle: LoopHandle<State>, state: &State) -> Result<()> {
                 }
             };
 
-            let mut env = HashMap::new();
-            env.insert(
-                String::from("WAYLAND_DISPLAY"),
-                state
-                    .common
-                    .socket
-                    .clone()
-                    .into_string()
-                    .map_err(|_| anyhow!("wayland socket is no valid utf-8 string?"))?,
-            );
-            if let Some(display) = state.common.xwayland_state.as_ref().map(|s| s.display) {
-                env.insert(String::from("DISPLAY"), format!(":{}", display));
-            }
-            let message = serde_json::to_string(&Message::SetEnv { variables: env })
-                .with_context(|| "Failed to encode environment variables into json")?;
-            let bytes = message.into_bytes();
-            let len = (bytes.len() as u16).to_ne_bytes();
-            session_socket
-                .write_all(&len)
-                .with_context(|| "Failed to write message len")?;
-            session_socket
-                .write_all(&bytes)
-                .with_context(|| "Failed to write message bytes")?;
+            let env = environment(state)?;
+            send_message(&mut session_socket, &Message::SetEnv { variables: env })?;
 
             handle.insert_source(
                 Generic::new(StreamWrapper::from(session_socket), Interest::READ, Mode::Level),
@@ -141,7 +318,8 @@ pub fn setup_socket(handle: LoopHandle<State>, state: &State) -> Result<()> {
                                                         continue;
                                                     }
                                                     let stream = unsafe { UnixStream::from_raw_fd(fd) };
-                                                    if let Err(err) = state.common.display_handle.insert_client(stream, Arc::new(state.new_privileged_client_state())) {
+                                                    let client_state = state.new_privileged_client_state();
+                                                    if let Err(err) = state.insert_client(stream, client_state) {
                                                         warn!(?err, "Failed to add privileged client to display");
                                                     }
                                                 }
@@ -151,6 +329,98 @@ pub fn setup_socket(handle: LoopHandle<State>, state: &State) -> Result<()> {
                                             }
                                         }
                                     },
+                                    Ok(Message::SetLogLevel { level }) => match level.parse::<LevelFilter>() {
+                                        Ok(level) => crate::logger::set_level(level),
+                                        Err(err) => warn!(?err, level, "Invalid log level from session sock"),
+                                    },
+                                    Ok(Message::DumpLogs { path }) => {
+                                        if let Err(err) = crate::logger::dump_logs(&path) {
+                                            warn!(?err, ?path, "Failed to dump logs");
+                                        }
+                                    },
+                                    Ok(Message::GetInputState) => {
+                                        let seat = state.common.last_active_seat().clone();
+                                        let reply = Message::InputState {
+                                            shortcuts: state.common.shortcuts_enabled,
+                                            inhibited: state.common.keybindings_inhibited(&seat),
+                                        };
+                                        if let Err(err) = send_message(&mut stream.stream, &reply) {
+                                            warn!(?err, "Failed to answer session sock");
+                                        }
+                                    },
+                                    Ok(Message::GetClientStats) => {
+                                        let reply = Message::ClientStats {
+                                            clients: top_offenders(&state.common, usize::MAX),
+                                        };
+                                        if let Err(err) = send_message(&mut stream.stream, &reply) {
+                                            warn!(?err, "Failed to answer session sock");
+                                        }
+                                    },
+                                    Ok(Message::ReloadBackground) => state.reload_backgrounds(),
+                                    Ok(Message::GetLockState) => {
+                                        let seat = state.common.last_active_seat().clone();
+                                        let modifiers = seat.get_keyboard().unwrap().modifier_state();
+                                        let reply = Message::LockState {
+                                            caps_lock: modifiers.caps_lock,
+                                            num_lock: modifiers.num_lock,
+                                        };
+                                        if let Err(err) = send_message(&mut stream.stream, &reply) {
+                                            warn!(?err, "Failed to answer session sock");
+                                        }
+                                    },
+                                    Ok(Message::GetAccessibilityState) => {
+                                        let seat = state.common.last_active_seat().clone();
+                                        let accessibility = seat.user_data().get::<Accessibility>().unwrap();
+                                        let (latched, locked) = accessibility.sticky_modifiers();
+                                        let reply = Message::AccessibilityState {
+                                            suspended: accessibility.is_suspended(),
+                                            latched,
+                                            locked,
+                                        };
+                                        if let Err(err) = send_message(&mut stream.stream, &reply) {
+                                            warn!(?err, "Failed to answer session sock");
+                                        }
+                                    },
+                                    Ok(Message::GetFocusedWindow) => {
+                                        let seat = state.common.last_active_seat().clone();
+                                        let output = seat.active_output();
+                                        let workspace = state.common.shell.active_space(&output);
+                                        let window = workspace.focus_stack.get(&seat).last().and_then(|mapped| {
+                                            let geometry = workspace.element_geometry(mapped)?.to_global(&output);
+                                            let identity = mapped.active_window().identity();
+                                            Some(WindowInfo {
+                                                title: identity.title,
+                                                app_id: identity.app_id,
+                                                geometry: [geometry.loc.x, geometry.loc.y, geometry.size.w, geometry.size.h],
+                                            })
+                                        });
+                                        let reply = Message::FocusedWindow { window };
+                                        if let Err(err) = send_message(&mut stream.stream, &reply) {
+                                            warn!(?err, "Failed to answer session sock");
+                                        }
+                                    },
+                                    Ok(Message::SubscribeWindowEvents) => match stream.stream.try_clone() {
+                                        Ok(events) => state.common.window_events = WindowEvents(Some(events)),
+                                        Err(err) => warn!(?err, "Failed to subscribe session sock to window events"),
+                                    },
+                                    Ok(Message::GetPrimaryOutput) => {
+                                        let reply = Message::PrimaryOutput {
+                                            name: state.common.shell.primary_output().map(|output| output.name()),
+                                        };
+                                        if let Err(err) = send_message(&mut stream.stream, &reply) {
+                                            warn!(?err, "Failed to answer session sock");
+                                        }
+                                    },
+                                    Ok(Message::SetPrimaryOutput { name }) => {
+                                        match state.common.shell.outputs().find(|output| output.name() == name).cloned() {
+                                            Some(output) => state.common.set_primary_output(&output),
+                                            None => warn!(name, "Unknown primary output from session sock"),
+                                        }
+                                    },
+                                    Ok(Message::SubscribeOutputEvents) => match stream.stream.try_clone() {
+                                        Ok(events) => state.common.output_events = OutputEvents(Some(events)),
+                                        Err(err) => warn!(?err, "Failed to subscribe session sock to output events"),
+                                    },
                                     Ok(Message::SetEnv { .. }) => warn!("Got SetEnv from session? What is this?"),
                                     _ => warn!("Unknown session socket message, are you using incompatible cosmic-session and cosmic-comp versions?"),
                                 };
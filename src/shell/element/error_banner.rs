@@ -0,0 +1,57 @@
+use crate::utils::iced::{IcedElement, Program};
+
+use calloop::LoopHandle;
+use cosmic::{
+    iced::widget::{container, horizontal_space, row},
+    iced_core::{Alignment, Background, Color, Length},
+    theme,
+    widget::{icon::from_name, text},
+    Apply,
+};
+use smithay::utils::{Logical, Size};
+
+pub type ErrorBanner = IcedElement<ErrorBannerInternal>;
+
+pub fn error_banner(
+    message: String,
+    size: Size<i32, Logical>,
+    evlh: LoopHandle<'static, crate::state::State>,
+    theme: cosmic::Theme,
+) -> ErrorBanner {
+    ErrorBanner::new(ErrorBannerInternal { message }, size, evlh, theme)
+}
+
+pub struct ErrorBannerInternal {
+    pub message: String,
+}
+
+impl Program for ErrorBannerInternal {
+    type Message = ();
+
+    fn view(&self) -> crate::utils::iced::Element<'_, Self::Message> {
+        row(vec![
+            from_name("dialog-error-symbolic")
+                .size(24)
+                .prefer_svg(true)
+                .icon()
+                .into(),
+            horizontal_space(12).into(),
+            text(&self.message).font(cosmic::font::FONT).size(18).into(),
+        ])
+        .align_items(Alignment::Center)
+        .apply(container)
+        .center_x()
+        .center_y()
+        .width(Length::Fill)
+        .height(Length::Fill)
+        .style(theme::Container::custom(|theme| container::Appearance {
+            icon_color: Some(Color::from(theme.cosmic().destructive.on)),
+            text_color: Some(Color::from(theme.cosmic().destructive.on)),
+            background: Some(Background::Color(theme.cosmic().destructive.base.into())),
+            border_radius: 0.0.into(),
+            border_width: 0.0,
+            border_color: Color::TRANSPARENT,
+        }))
+        .into()
+    }
+}
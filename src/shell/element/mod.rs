@@ -1,13 +1,14 @@
 use crate::{
     backend::render::{
         element::{AsGlowFrame, AsGlowRenderer},
-        GlMultiError, GlMultiFrame, GlMultiRenderer,
+        BackdropShader, GlMultiError, GlMultiFrame, GlMultiRenderer, Key, Usage,
     },
     state::State,
     utils::prelude::*,
 };
 use calloop::LoopHandle;
 use id_tree::NodeId;
+use keyframe::{ease, functions::EaseInOutCubic};
 use smithay::{
     backend::{
         input::KeyState,
@@ -51,6 +52,7 @@ use std::{
     fmt,
     hash::Hash,
     sync::{Arc, Mutex},
+    time::{Duration, Instant},
 };
 
 pub mod surface;
@@ -60,6 +62,8 @@ pub mod stack;
 pub use self::stack::CosmicStack;
 pub mod window;
 pub use self::window::CosmicWindow;
+pub mod error_banner;
+pub mod osd;
 pub mod resize_indicator;
 pub mod stack_hover;
 pub mod swap_indicator;
@@ -90,6 +94,14 @@ pub struct MaximizedState {
     pub original_layer: ManagedLayer,
 }
 
+/// Why the compositor is waiting for a client to acknowledge a configure.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConfigureReason {
+    Maximize,
+    Fullscreen,
+    Resize,
+}
+
 #[derive(Clone)]
 pub struct CosmicMapped {
     element: CosmicMappedInternal,
@@ -103,6 +115,10 @@ pub struct CosmicMapped {
     //floating
     pub(super) resize_state: Arc<Mutex<Option<ResizeState>>>,
     pub last_geometry: Arc<Mutex<Option<Rectangle<i32, Local>>>>,
+    /// Brightness to render the element with, if it isn't focused
+    dimmed: Arc<Mutex<Option<f32>>>,
+    /// Start and duration of the animation of a freshly mapped element
+    opening: Arc<Mutex<Option<(Instant, Duration)>>>,
 
     #[cfg(feature = "debug")]
     debug: Arc<Mutex<Option<smithay_egui::EguiState>>>,
@@ -117,6 +133,8 @@ impl fmt::Debug for CosmicMapped {
             .field("tiling_node_id", &self.tiling_node_id)
             .field("resize_state", &self.resize_state)
             .field("last_geometry", &self.last_geometry)
+            .field("dimmed", &self.dimmed)
+            .field("opening", &self.opening)
             .finish()
     }
 }
@@ -447,8 +465,16 @@ impl CosmicMapped {
         }
     }
 
+    /// Sets the bounds of all windows to `size`, minus the space taken by our decorations.
     pub fn set_bounds(&self, size: impl Into<Option<Size<i32, Logical>>>) {
-        let size = size.into();
+        let decoration = match &self.element {
+            CosmicMappedInternal::Stack(s) => s.offset().y,
+            CosmicMappedInternal::Window(w) => w.offset().y,
+            _ => unreachable!(),
+        };
+        let size = size
+            .into()
+            .map(|size| Size::from((size.w, (size.h - decoration).max(0))));
         for (surface, _) in self.windows() {
             surface.set_bounds(size.clone())
         }
@@ -468,6 +494,21 @@ impl CosmicMapped {
         }
     }
 
+    /// Like `configure`, but throttled, see `CosmicSurface::send_throttled_configure`.
+    pub fn configure_throttled(&self) -> Option<Serial> {
+        match &self.element {
+            CosmicMappedInternal::Stack(s) => {
+                let active = s.active();
+                for surface in s.surfaces().filter(|s| s != &active) {
+                    surface.send_throttled_configure();
+                }
+                active.send_throttled_configure()
+            }
+            CosmicMappedInternal::Window(w) => w.surface().send_throttled_configure(),
+            _ => unreachable!(),
+        }
+    }
+
     pub fn send_close(&self) {
         let window = match &self.element {
             CosmicMappedInternal::Stack(s) => s.active(),
@@ -575,6 +616,57 @@ impl CosmicMapped {
         }
     }
 
+    /// Remembers a configure the layout depends on, so clients never acking it can be detected.
+    pub fn expect_ack(&self, serial: Option<Serial>, reason: ConfigureReason) {
+        if let Some(serial) = serial {
+            self.active_window().expect_ack(serial, reason);
+        }
+    }
+
+    /// Whether a configure the layout depends on wasn't acked yet.
+    pub fn awaits_ack(&self) -> bool {
+        self.active_window().awaits_ack()
+    }
+
+    /// Returns the reason of the expected configure, if it wasn't acked within `timeout`.
+    pub fn take_timed_out_configure(&self, timeout: Duration) -> Option<ConfigureReason> {
+        self.active_window().take_overdue_configure(timeout)
+    }
+
+    pub fn set_dimmed(&self, brightness: Option<f32>) {
+        *self.dimmed.lock().unwrap() = brightness.map(|brightness| brightness.clamp(0.1, 1.0));
+    }
+
+    /// Fades and scales in the element, after it was mapped.
+    pub fn animate_open(&self, duration: Duration) {
+        *self.opening.lock().unwrap() = (!duration.is_zero()).then(|| (Instant::now(), duration));
+    }
+
+    /// Eased progress of the open animation, `None` if it isn't running.
+    pub fn open_progress(&self) -> Option<f64> {
+        let (start, duration) = (*self.opening.lock().unwrap())?;
+        let elapsed = start.elapsed();
+        (elapsed < duration).then(|| {
+            ease(
+                EaseInOutCubic,
+                0.0,
+                1.0,
+                elapsed.as_secs_f64() / duration.as_secs_f64(),
+            )
+        })
+    }
+
+    /// Drops a finished open animation, returns if it just ended.
+    pub fn update_open_animation(&self) -> bool {
+        let mut opening = self.opening.lock().unwrap();
+        if opening.is_some_and(|(start, duration)| start.elapsed() >= duration) {
+            *opening = None;
+            true
+        } else {
+            false
+        }
+    }
+
     pub fn split_render_elements<R, C>(
         &self,
         renderer: &mut R,
@@ -752,8 +844,8 @@ impl CosmicMapped {
         #[cfg(not(feature = "debug"))]
         let debug_elements = Vec::new();
 
-        #[cfg_attr(not(feature = "debug"), allow(unused_mut))]
-        let (window_elements, popup_elements) = match &self.element {
+        let alpha = alpha * self.active_window().opacity();
+        let (mut window_elements, popup_elements) = match &self.element {
             CosmicMappedInternal::Stack(s) => s
                 .split_render_elements::<R, CosmicMappedRenderElement<R>>(
                     renderer, location, scale, alpha,
@@ -765,6 +857,27 @@ impl CosmicMapped {
             _ => unreachable!(),
         };
 
+        if let Some(brightness) = *self.dimmed.lock().unwrap() {
+            let geometry = self.geometry();
+            let geo = Rectangle::from_loc_and_size(
+                location.to_f64().to_logical(scale).to_i32_round() + geometry.loc,
+                geometry.size,
+            )
+            .as_local();
+            window_elements.insert(
+                0,
+                BackdropShader::element(
+                    renderer,
+                    Key::Window(Usage::Dim, self.clone()),
+                    geo,
+                    0.,
+                    (1.0 - brightness) * alpha,
+                    [0.0, 0.0, 0.0],
+                )
+                .into(),
+            );
+        }
+
         (
             debug_elements
                 .into_iter()
@@ -1102,6 +1215,8 @@ impl From<CosmicWindow> for CosmicMapped {
             tiling_node_id: Arc::new(Mutex::new(None)),
             resize_state: Arc::new(Mutex::new(None)),
             last_geometry: Arc::new(Mutex::new(None)),
+            dimmed: Arc::new(Mutex::new(None)),
+            opening: Arc::new(Mutex::new(None)),
             #[cfg(feature = "debug")]
             debug: Arc::new(Mutex::new(None)),
         }
@@ -1117,6 +1232,8 @@ impl From<CosmicStack> for CosmicMapped {
             tiling_node_id: Arc::new(Mutex::new(None)),
             resize_state: Arc::new(Mutex::new(None)),
             last_geometry: Arc::new(Mutex::new(None)),
+            dimmed: Arc::new(Mutex::new(None)),
+            opening: Arc::new(Mutex::new(None)),
             #[cfg(feature = "debug")]
             debug: Arc::new(Mutex::new(None)),
         }
@@ -1145,6 +1262,10 @@ where
     ),
     GrabbedStack(RescaleRenderElement<self::stack::CosmicStackRenderElement<R>>),
     GrabbedWindow(RescaleRenderElement<self::window::CosmicWindowRenderElement<R>>),
+    /// Freshly mapped elements scaling in, see `CosmicMapped::animate_open`
+    OpeningStack(RescaleRenderElement<self::stack::CosmicStackRenderElement<R>>),
+    OpeningWindow(RescaleRenderElement<self::window::CosmicWindowRenderElement<R>>),
+    Snapshot(RescaleRenderElement<MemoryRenderBufferRenderElement<R>>),
     FocusIndicator(PixelShaderElement),
     Overlay(PixelShaderElement),
     StackHoverIndicator(MemoryRenderBufferRenderElement<R>),
@@ -1152,6 +1273,31 @@ where
     Egui(TextureRenderElement<GlesTexture>),
 }
 
+/// Scale of an element at `progress` of its open animation.
+pub fn open_scale(progress: f64) -> f64 {
+    0.9 + 0.1 * progress
+}
+
+impl<R> CosmicMappedRenderElement<R>
+where
+    R: Renderer + ImportAll + ImportMem,
+    <R as Renderer>::TextureId: 'static,
+{
+    /// Scales a window or stack element in around `center`, see `CosmicMapped::open_progress`.
+    pub fn opening(self, center: Point<i32, Physical>, progress: f64) -> Self {
+        let scale = open_scale(progress);
+        match self {
+            CosmicMappedRenderElement::Stack(elem) => CosmicMappedRenderElement::OpeningStack(
+                RescaleRenderElement::from_element(elem, center, scale),
+            ),
+            CosmicMappedRenderElement::Window(elem) => CosmicMappedRenderElement::OpeningWindow(
+                RescaleRenderElement::from_element(elem, center, scale),
+            ),
+            x => x,
+        }
+    }
+}
+
 impl<R> Element for CosmicMappedRenderElement<R>
 where
     R: Renderer + ImportAll + ImportMem,
@@ -1166,6 +1312,9 @@ where
             CosmicMappedRenderElement::TiledOverlay(elem) => elem.id(),
             CosmicMappedRenderElement::GrabbedStack(elem) => elem.id(),
             CosmicMappedRenderElement::GrabbedWindow(elem) => elem.id(),
+            CosmicMappedRenderElement::OpeningStack(elem) => elem.id(),
+            CosmicMappedRenderElement::OpeningWindow(elem) => elem.id(),
+            CosmicMappedRenderElement::Snapshot(elem) => elem.id(),
             CosmicMappedRenderElement::FocusIndicator(elem) => elem.id(),
             CosmicMappedRenderElement::Overlay(elem) => elem.id(),
             CosmicMappedRenderElement::StackHoverIndicator(elem) => elem.id(),
@@ -1183,6 +1332,9 @@ where
             CosmicMappedRenderElement::TiledOverlay(elem) => elem.current_commit(),
             CosmicMappedRenderElement::GrabbedStack(elem) => elem.current_commit(),
             CosmicMappedRenderElement::GrabbedWindow(elem) => elem.current_commit(),
+            CosmicMappedRenderElement::OpeningStack(elem) => elem.current_commit(),
+            CosmicMappedRenderElement::OpeningWindow(elem) => elem.current_commit(),
+            CosmicMappedRenderElement::Snapshot(elem) => elem.current_commit(),
             CosmicMappedRenderElement::FocusIndicator(elem) => elem.current_commit(),
             CosmicMappedRenderElement::Overlay(elem) => elem.current_commit(),
             CosmicMappedRenderElement::StackHoverIndicator(elem) => elem.current_commit(),
@@ -1200,6 +1352,9 @@ where
             CosmicMappedRenderElement::TiledOverlay(elem) => elem.src(),
             CosmicMappedRenderElement::GrabbedStack(elem) => elem.src(),
             CosmicMappedRenderElement::GrabbedWindow(elem) => elem.src(),
+            CosmicMappedRenderElement::OpeningStack(elem) => elem.src(),
+            CosmicMappedRenderElement::OpeningWindow(elem) => elem.src(),
+            CosmicMappedRenderElement::Snapshot(elem) => elem.src(),
             CosmicMappedRenderElement::FocusIndicator(elem) => elem.src(),
             CosmicMappedRenderElement::Overlay(elem) => elem.src(),
             CosmicMappedRenderElement::StackHoverIndicator(elem) => elem.src(),
@@ -1217,6 +1372,9 @@ where
             CosmicMappedRenderElement::TiledOverlay(elem) => elem.geometry(scale),
             CosmicMappedRenderElement::GrabbedStack(elem) => elem.geometry(scale),
             CosmicMappedRenderElement::GrabbedWindow(elem) => elem.geometry(scale),
+            CosmicMappedRenderElement::OpeningStack(elem) => elem.geometry(scale),
+            CosmicMappedRenderElement::OpeningWindow(elem) => elem.geometry(scale),
+            CosmicMappedRenderElement::Snapshot(elem) => elem.geometry(scale),
             CosmicMappedRenderElement::FocusIndicator(elem) => elem.geometry(scale),
             CosmicMappedRenderElement::Overlay(elem) => elem.geometry(scale),
             CosmicMappedRenderElement::StackHoverIndicator(elem) => elem.geometry(scale),
@@ -1234,6 +1392,9 @@ where
             CosmicMappedRenderElement::TiledOverlay(elem) => elem.location(scale),
             CosmicMappedRenderElement::GrabbedStack(elem) => elem.location(scale),
             CosmicMappedRenderElement::GrabbedWindow(elem) => elem.location(scale),
+            CosmicMappedRenderElement::OpeningStack(elem) => elem.location(scale),
+            CosmicMappedRenderElement::OpeningWindow(elem) => elem.location(scale),
+            CosmicMappedRenderElement::Snapshot(elem) => elem.location(scale),
             CosmicMappedRenderElement::FocusIndicator(elem) => elem.location(scale),
             CosmicMappedRenderElement::Overlay(elem) => elem.location(scale),
             CosmicMappedRenderElement::StackHoverIndicator(elem) => elem.location(scale),
@@ -1251,6 +1412,9 @@ where
             CosmicMappedRenderElement::TiledOverlay(elem) => elem.transform(),
             CosmicMappedRenderElement::GrabbedStack(elem) => elem.transform(),
             CosmicMappedRenderElement::GrabbedWindow(elem) => elem.transform(),
+            CosmicMappedRenderElement::OpeningStack(elem) => elem.transform(),
+            CosmicMappedRenderElement::OpeningWindow(elem) => elem.transform(),
+            CosmicMappedRenderElement::Snapshot(elem) => elem.transform(),
             CosmicMappedRenderElement::FocusIndicator(elem) => elem.transform(),
             CosmicMappedRenderElement::Overlay(elem) => elem.transform(),
             CosmicMappedRenderElement::StackHoverIndicator(elem) => elem.transform(),
@@ -1272,6 +1436,9 @@ where
             CosmicMappedRenderElement::TiledOverlay(elem) => elem.damage_since(scale, commit),
             CosmicMappedRenderElement::GrabbedStack(elem) => elem.damage_since(scale, commit),
             CosmicMappedRenderElement::GrabbedWindow(elem) => elem.damage_since(scale, commit),
+            CosmicMappedRenderElement::OpeningStack(elem) => elem.damage_since(scale, commit),
+            CosmicMappedRenderElement::OpeningWindow(elem) => elem.damage_since(scale, commit),
+            CosmicMappedRenderElement::Snapshot(elem) => elem.damage_since(scale, commit),
             CosmicMappedRenderElement::FocusIndicator(elem) => elem.damage_since(scale, commit),
             CosmicMappedRenderElement::Overlay(elem) => elem.damage_since(scale, commit),
             CosmicMappedRenderElement::StackHoverIndicator(elem) => {
@@ -1291,6 +1458,9 @@ where
             CosmicMappedRenderElement::TiledOverlay(elem) => elem.opaque_regions(scale),
             CosmicMappedRenderElement::GrabbedStack(elem) => elem.opaque_regions(scale),
             CosmicMappedRenderElement::GrabbedWindow(elem) => elem.opaque_regions(scale),
+            CosmicMappedRenderElement::OpeningStack(elem) => elem.opaque_regions(scale),
+            CosmicMappedRenderElement::OpeningWindow(elem) => elem.opaque_regions(scale),
+            CosmicMappedRenderElement::Snapshot(elem) => elem.opaque_regions(scale),
             CosmicMappedRenderElement::FocusIndicator(elem) => elem.opaque_regions(scale),
             CosmicMappedRenderElement::Overlay(elem) => elem.opaque_regions(scale),
             CosmicMappedRenderElement::StackHoverIndicator(elem) => elem.opaque_regions(scale),
@@ -1308,6 +1478,9 @@ where
             CosmicMappedRenderElement::TiledOverlay(elem) => elem.alpha(),
             CosmicMappedRenderElement::GrabbedStack(elem) => elem.alpha(),
             CosmicMappedRenderElement::GrabbedWindow(elem) => elem.alpha(),
+            CosmicMappedRenderElement::OpeningStack(elem) => elem.alpha(),
+            CosmicMappedRenderElement::OpeningWindow(elem) => elem.alpha(),
+            CosmicMappedRenderElement::Snapshot(elem) => elem.alpha(),
             CosmicMappedRenderElement::FocusIndicator(elem) => elem.alpha(),
             CosmicMappedRenderElement::Overlay(elem) => elem.alpha(),
             CosmicMappedRenderElement::StackHoverIndicator(elem) => elem.alpha(),
@@ -1335,6 +1508,9 @@ impl RenderElement<GlowRenderer> for CosmicMappedRenderElement<GlowRenderer> {
             }
             CosmicMappedRenderElement::GrabbedStack(elem) => elem.draw(frame, src, dst, damage),
             CosmicMappedRenderElement::GrabbedWindow(elem) => elem.draw(frame, src, dst, damage),
+            CosmicMappedRenderElement::OpeningStack(elem) => elem.draw(frame, src, dst, damage),
+            CosmicMappedRenderElement::OpeningWindow(elem) => elem.draw(frame, src, dst, damage),
+            CosmicMappedRenderElement::Snapshot(elem) => elem.draw(frame, src, dst, damage),
             CosmicMappedRenderElement::FocusIndicator(elem) => {
                 RenderElement::<GlowRenderer>::draw(elem, frame, src, dst, damage)
             }
@@ -1360,6 +1536,9 @@ impl RenderElement<GlowRenderer> for CosmicMappedRenderElement<GlowRenderer> {
             CosmicMappedRenderElement::TiledOverlay(elem) => elem.underlying_storage(renderer),
             CosmicMappedRenderElement::GrabbedStack(elem) => elem.underlying_storage(renderer),
             CosmicMappedRenderElement::GrabbedWindow(elem) => elem.underlying_storage(renderer),
+            CosmicMappedRenderElement::OpeningStack(elem) => elem.underlying_storage(renderer),
+            CosmicMappedRenderElement::OpeningWindow(elem) => elem.underlying_storage(renderer),
+            CosmicMappedRenderElement::Snapshot(elem) => elem.underlying_storage(renderer),
             CosmicMappedRenderElement::FocusIndicator(elem) => elem.underlying_storage(renderer),
             CosmicMappedRenderElement::Overlay(elem) => elem.underlying_storage(renderer),
             CosmicMappedRenderElement::StackHoverIndicator(elem) => {
@@ -1392,6 +1571,9 @@ impl<'a, 'b> RenderElement<GlMultiRenderer<'a, 'b>>
             }
             CosmicMappedRenderElement::GrabbedStack(elem) => elem.draw(frame, src, dst, damage),
             CosmicMappedRenderElement::GrabbedWindow(elem) => elem.draw(frame, src, dst, damage),
+            CosmicMappedRenderElement::OpeningStack(elem) => elem.draw(frame, src, dst, damage),
+            CosmicMappedRenderElement::OpeningWindow(elem) => elem.draw(frame, src, dst, damage),
+            CosmicMappedRenderElement::Snapshot(elem) => elem.draw(frame, src, dst, damage),
             CosmicMappedRenderElement::FocusIndicator(elem) => {
                 RenderElement::<GlowRenderer>::draw(elem, frame.glow_frame_mut(), src, dst, damage)
                     .map_err(|err| GlMultiError::Render(err))
@@ -1426,6 +1608,9 @@ impl<'a, 'b> RenderElement<GlMultiRenderer<'a, 'b>>
             }
             CosmicMappedRenderElement::GrabbedStack(elem) => elem.underlying_storage(renderer),
             CosmicMappedRenderElement::GrabbedWindow(elem) => elem.underlying_storage(renderer),
+            CosmicMappedRenderElement::OpeningStack(elem) => elem.underlying_storage(renderer),
+            CosmicMappedRenderElement::OpeningWindow(elem) => elem.underlying_storage(renderer),
+            CosmicMappedRenderElement::Snapshot(elem) => elem.underlying_storage(renderer),
             CosmicMappedRenderElement::FocusIndicator(elem) => {
                 elem.underlying_storage(renderer.glow_renderer_mut())
             }
@@ -0,0 +1,214 @@
+use std::{
+    cell::RefCell,
+    time::{Duration, Instant},
+};
+
+use crate::{
+    state::State,
+    utils::iced::{IcedElement, Program},
+};
+
+use calloop::{
+    timer::{TimeoutAction, Timer},
+    RegistrationToken,
+};
+use cosmic::{
+    iced::widget::{column, container, row, vertical_space},
+    iced_core::{Alignment, Background, Color, Length},
+    theme,
+    widget::text,
+    Apply,
+};
+use smithay::{
+    desktop::space::SpaceElement,
+    output::Output,
+    utils::{Logical, Point, Rectangle, Size},
+};
+use tracing::warn;
+
+const OSD_SIZE: (i32, i32) = (240, 64);
+/// How long the osd is fully visible, before it fades out
+const OSD_TIMEOUT: Duration = Duration::from_secs(1);
+const OSD_FADE: Duration = Duration::from_millis(250);
+const OSD_FRAME: Duration = Duration::from_millis(16);
+
+pub type Osd = IcedElement<OsdInternal>;
+
+/// What an on-screen display shows
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum OsdContent {
+    /// Name of the workspace that was switched to
+    Workspace(String),
+    /// Backlight brightness in percent
+    Brightness(u32),
+}
+
+pub struct OsdInternal {
+    pub content: OsdContent,
+}
+
+impl Program for OsdInternal {
+    type Message = ();
+
+    fn view(&self) -> crate::utils::iced::Element<'_, Self::Message> {
+        let bar_style = |filled: bool| {
+            theme::Container::custom(move |theme| container::Appearance {
+                icon_color: None,
+                text_color: None,
+                background: Some(Background::Color(if filled {
+                    theme.cosmic().accent_color().into()
+                } else {
+                    theme.cosmic().palette.neutral_5.into()
+                })),
+                border_radius: 4.0.into(),
+                border_width: 0.0,
+                border_color: Color::TRANSPARENT,
+            })
+        };
+
+        let content: crate::utils::iced::Element<'_, Self::Message> = match &self.content {
+            OsdContent::Workspace(name) => text(crate::fl!("osd-workspace", name = name.as_str()))
+                .font(cosmic::font::FONT)
+                .size(18)
+                .into(),
+            OsdContent::Brightness(percent) => {
+                let percent = (*percent).min(100) as u16;
+                column(vec![
+                    text(crate::fl!("osd-brightness", percent = percent))
+                        .font(cosmic::font::FONT)
+                        .size(14)
+                        .into(),
+                    vertical_space(8).into(),
+                    row(vec![
+                        vertical_space(8)
+                            .apply(container)
+                            .width(Length::FillPortion(percent))
+                            .style(bar_style(true))
+                            .into(),
+                        vertical_space(8)
+                            .apply(container)
+                            .width(Length::FillPortion(100 - percent))
+                            .style(bar_style(false))
+                            .into(),
+                    ])
+                    .into(),
+                ])
+                .align_items(Alignment::Center)
+                .padding([0, 24])
+                .into()
+            }
+        };
+
+        content
+            .apply(container)
+            .center_x()
+            .center_y()
+            .width(Length::Fill)
+            .height(Length::Fill)
+            .style(theme::Container::custom(|theme| container::Appearance {
+                icon_color: Some(Color::from(theme.cosmic().background.on)),
+                text_color: Some(Color::from(theme.cosmic().background.on)),
+                background: Some(Background::Color(theme.cosmic().background.base.into())),
+                border_radius: 12.0.into(),
+                border_width: 0.0,
+                border_color: Color::TRANSPARENT,
+            }))
+            .into()
+    }
+}
+
+/// Osd currently shown on an output
+#[derive(Default)]
+struct OutputOsd(RefCell<Option<ShownOsd>>);
+
+struct ShownOsd {
+    element: Osd,
+    shown: Instant,
+    timer: Option<RegistrationToken>,
+}
+
+impl ShownOsd {
+    fn alpha(&self) -> f32 {
+        let elapsed = self.shown.elapsed();
+        if elapsed <= OSD_TIMEOUT {
+            1.0
+        } else {
+            (1.0 - (elapsed - OSD_TIMEOUT).as_secs_f32() / OSD_FADE.as_secs_f32()).max(0.0)
+        }
+    }
+}
+
+/// Shows `content` near the bottom of `output`, replacing whatever the osd showed before.
+///
+/// The osd is drawn by the compositor above the top layer and never takes input.
+pub fn show(state: &mut State, output: &Output, content: OsdContent) {
+    if !state.common.config.static_conf.osd {
+        return;
+    }
+
+    let element = Osd::new(
+        OsdInternal { content },
+        Size::from(OSD_SIZE),
+        state.common.event_loop_handle.clone(),
+        state.common.theme.clone(),
+    );
+    // renders the buffer at the scale of the output
+    element.output_enter(output, Rectangle::from_loc_and_size((0, 0), OSD_SIZE));
+
+    let timer_output = output.clone();
+    let timer = state
+        .common
+        .event_loop_handle
+        .insert_source(Timer::from_duration(OSD_TIMEOUT), move |_, _, state| {
+            let output_osd = timer_output.user_data().get::<OutputOsd>().unwrap();
+            let faded = output_osd
+                .0
+                .borrow()
+                .as_ref()
+                .map_or(true, |osd| osd.alpha() <= 0.0);
+            if faded {
+                output_osd.0.borrow_mut().take();
+            }
+            state
+                .backend
+                .schedule_render(&state.common.event_loop_handle, &timer_output, None);
+            if faded {
+                TimeoutAction::Drop
+            } else {
+                TimeoutAction::ToDuration(OSD_FRAME)
+            }
+        })
+        .map_err(|err| warn!(?err, "Failed to schedule hiding the osd."))
+        .ok();
+
+    output.user_data().insert_if_missing(OutputOsd::default);
+    let previous = output
+        .user_data()
+        .get::<OutputOsd>()
+        .unwrap()
+        .0
+        .borrow_mut()
+        .replace(ShownOsd {
+            element,
+            shown: Instant::now(),
+            timer,
+        });
+    if let Some(token) = previous.and_then(|osd| osd.timer) {
+        state.common.event_loop_handle.remove(token);
+    }
+
+    state
+        .backend
+        .schedule_render(&state.common.event_loop_handle, output, None);
+}
+
+/// Returns the osd of `output` with its location and current alpha, if one is shown.
+pub fn osd_for_output(output: &Output) -> Option<(Osd, Point<i32, Logical>, f32)> {
+    let output_osd = output.user_data().get::<OutputOsd>()?;
+    let shown = output_osd.0.borrow();
+    let osd = shown.as_ref()?;
+
+    let size = output.geometry().size;
+    let location = Point::from(((size.w - OSD_SIZE.0) / 2, size.h - OSD_SIZE.1 - size.h / 8));
+    Some((osd.element.clone(), location, osd.alpha()))
+}
@@ -23,8 +23,8 @@ use smithay::{
         input::KeyState,
         renderer::{
             element::{
-                memory::MemoryRenderBufferRenderElement, surface::WaylandSurfaceRenderElement,
-                AsRenderElements,
+                memory::MemoryRenderBufferRenderElement, solid::SolidColorRenderElement,
+                surface::WaylandSurfaceRenderElement, AsRenderElements,
             },
             ImportAll, ImportMem, Renderer,
         },
@@ -1091,6 +1091,7 @@ impl PointerTarget<State> for CosmicStack {
                                         start_data,
                                         mapped,
                                         seat,
+                                        workspace.handle,
                                         pos,
                                         pos.to_i32_round() - Point::from((elem_geo.size.w / 2, 24)),
                                         indicator_thickness,
@@ -1362,4 +1363,5 @@ render_elements! {
     pub CosmicStackRenderElement<R> where R: ImportAll + ImportMem;
     Header = MemoryRenderBufferRenderElement<R>,
     Window = WaylandSurfaceRenderElement<R>,
+    Solid = SolidColorRenderElement,
 }
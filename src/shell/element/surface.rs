@@ -1,9 +1,13 @@
-use std::time::Duration;
+use std::{
+    cell::{Cell, RefCell},
+    time::{Duration, Instant},
+};
 
 use smithay::{
     backend::renderer::{
         element::{
             self,
+            solid::SolidColorRenderElement,
             surface::{render_elements_from_surface_tree, WaylandSurfaceRenderElement},
             utils::select_dmabuf_feedback,
             AsRenderElements, RenderElementStates,
@@ -13,10 +17,10 @@ use smithay::{
     desktop::{
         utils::{
             send_dmabuf_feedback_surface_tree, send_frames_surface_tree,
-            take_presentation_feedback_surface_tree, with_surfaces_surface_tree,
-            OutputPresentationFeedback,
+            take_presentation_feedback_surface_tree, under_from_surface_tree,
+            with_surfaces_surface_tree, OutputPresentationFeedback,
         },
-        PopupManager, Window,
+        PopupManager, Window, WindowSurfaceType,
     },
     input::{
         keyboard::{KeyboardTarget, KeysymHandle, ModifiersState},
@@ -37,12 +41,15 @@ use smithay::{
                 shell::server::xdg_toplevel::State as ToplevelState,
             },
         },
-        wayland_server::protocol::wl_surface::WlSurface,
+        wayland_server::{
+            backend::DisconnectReason, protocol::wl_surface::WlSurface, DisplayHandle, Resource,
+        },
     },
     space_elements,
-    utils::{user_data::UserDataMap, Logical, Rectangle, Serial, Size},
+    utils::{user_data::UserDataMap, Logical, Point, Rectangle, Serial, Size},
     wayland::{
         compositor::{with_states, SurfaceData},
+        dmabuf::DmabufFeedback,
         seat::WaylandFocus,
         shell::xdg::{SurfaceCachedState, ToplevelSurface, XdgToplevelSurfaceData},
     },
@@ -50,10 +57,19 @@ use smithay::{
 };
 
 use crate::{
+    shell::element::ConfigureReason,
     state::{State, SurfaceDmabufFeedback},
     utils::prelude::*,
-    wayland::handlers::decoration::PreferredDecorationMode,
+    wayland::{
+        handlers::{decoration::PreferredDecorationMode, single_pixel_buffer::solid_color_element},
+        protocols::{
+            content_type::{content_type, ContentType},
+            xdg_foreign::foreign_parent,
+        },
+    },
+    xwayland::XWaylandState,
 };
+use tracing::warn;
 
 space_elements! {
     #[derive(Debug, Clone, PartialEq)]
@@ -82,6 +98,90 @@ impl From<X11Surface> for CosmicSurface {
 
 pub const SSD_HEIGHT: i32 = 48;
 
+/// Manual override of the `inhibit_keybindings` window rule.
+struct InhibitKeybindings(Cell<Option<bool>>);
+
+/// Windows can't be made more transparent than this, so they stay recoverable.
+pub const MIN_OPACITY: f32 = 0.1;
+
+struct Opacity(Cell<f32>);
+
+/// Serial of the configure, that changed the suspended state of a window
+struct SuspendConfigure(Cell<Option<Serial>>);
+
+/// Last configure sent to a window, see `CosmicSurface::send_throttled_configure`.
+#[derive(Debug, Default)]
+struct ThrottledConfigure(Cell<Option<SentConfigure>>);
+
+#[derive(Debug, Clone, Copy)]
+struct SentConfigure {
+    serial: Serial,
+    sent: Instant,
+    /// A throttled configure was held back, until this one is acked
+    deferred: bool,
+    /// The layout waits for this configure, see `CosmicSurface::expect_ack`
+    awaited: Option<ConfigureReason>,
+}
+
+/// Time after which a held back configure is sent, even if the previous one wasn't acked yet.
+const CONFIGURE_THROTTLE_TIMEOUT: Duration = Duration::from_millis(100);
+
+/// `xdg_toplevel` version introducing the suspended state
+const XDG_TOPLEVEL_SUSPENDED_SINCE: u32 = 6;
+
+/// Title and app_id of a window.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct WindowIdentity {
+    pub title: String,
+    pub app_id: String,
+}
+
+/// Identity last announced to foreign-toplevel and IPC clients, see `CosmicSurface::publish_identity`.
+#[derive(Debug, Default)]
+struct PublishedIdentity(RefCell<PublishedIdentityState>);
+
+#[derive(Debug, Default)]
+struct PublishedIdentityState {
+    identity: Option<WindowIdentity>,
+    published_at: Option<Instant>,
+    /// A delayed update was already requested
+    pending: bool,
+    /// The window had a non-empty title before
+    titled: bool,
+}
+
+/// Whether the client of a window answers pings and acks configures, see `State::ping_clients`.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum Responsiveness {
+    #[default]
+    Responsive,
+    NotResponding,
+    /// The window was clicked once, the next click kills its client
+    ConfirmKill,
+}
+
+impl Responsiveness {
+    /// State of a window after checking on its client.
+    ///
+    /// A pending kill confirmation outlives further missed pings or configures.
+    pub fn update(self, responding: bool) -> Responsiveness {
+        match (self, responding) {
+            (_, true) => Responsiveness::Responsive,
+            (Responsiveness::Responsive, false) => Responsiveness::NotResponding,
+            (state, false) => state,
+        }
+    }
+}
+
+struct WindowResponsiveness(Cell<Responsiveness>);
+
+pub enum IdentityUpdate {
+    Unchanged,
+    /// Changed too recently, try again after the given delay
+    Throttled(Duration),
+    Published(WindowIdentity),
+}
+
 impl CosmicSurface {
     pub fn title(&self) -> String {
         match self {
@@ -123,6 +223,86 @@ impl CosmicSurface {
         }
     }
 
+    pub fn identity(&self) -> WindowIdentity {
+        WindowIdentity {
+            title: self.title(),
+            app_id: self.app_id(),
+        }
+    }
+
+    /// The identity last published by `publish_identity`, or the current one if it never was.
+    pub fn published_identity(&self) -> WindowIdentity {
+        self.user_data()
+            .get::<PublishedIdentity>()
+            .and_then(|published| published.0.borrow().identity.clone())
+            .unwrap_or_else(|| self.identity())
+    }
+
+    /// Publishes the current title and app_id, unless the last change is less than `interval` ago.
+    pub fn publish_identity(&self, interval: Duration) -> IdentityUpdate {
+        self.user_data()
+            .insert_if_missing(PublishedIdentity::default);
+        let mut published = self
+            .user_data()
+            .get::<PublishedIdentity>()
+            .unwrap()
+            .0
+            .borrow_mut();
+
+        let identity = self.identity();
+        if published.identity.as_ref() == Some(&identity) {
+            published.pending = false;
+            return IdentityUpdate::Unchanged;
+        }
+        if let Some(elapsed) = published
+            .published_at
+            .map(|time| time.elapsed())
+            .filter(|elapsed| *elapsed < interval)
+        {
+            if std::mem::replace(&mut published.pending, true) {
+                return IdentityUpdate::Unchanged;
+            }
+            return IdentityUpdate::Throttled(interval - elapsed);
+        }
+
+        published.identity = Some(identity.clone());
+        published.published_at = Some(Instant::now());
+        published.pending = false;
+        IdentityUpdate::Published(identity)
+    }
+
+    /// Returns `true` exactly once, when the window has a non-empty title for the first time.
+    pub fn take_first_title(&self) -> bool {
+        self.user_data()
+            .insert_if_missing(PublishedIdentity::default);
+        let mut published = self
+            .user_data()
+            .get::<PublishedIdentity>()
+            .unwrap()
+            .0
+            .borrow_mut();
+        if published.titled || self.title().is_empty() {
+            return false;
+        }
+        published.titled = true;
+        true
+    }
+
+    /// Whether this is a transient window (like a dialog) of `parent`.
+    pub fn is_child_of(&self, parent: &CosmicSurface) -> bool {
+        match (self, parent) {
+            (CosmicSurface::Wayland(window), CosmicSurface::Wayland(parent)) => {
+                let parent = Some(parent.toplevel().wl_surface());
+                window.toplevel().parent().as_ref() == parent
+                    || foreign_parent(window.toplevel().wl_surface()).as_ref() == parent
+            }
+            (CosmicSurface::X11(surface), CosmicSurface::X11(parent)) => {
+                surface.is_transient_for() == Some(parent.window_id())
+            }
+            _ => false,
+        }
+    }
+
     pub fn pending_size(&self) -> Option<Size<i32, Logical>> {
         match self {
             CosmicSurface::Wayland(window) => {
@@ -190,6 +370,61 @@ impl CosmicSurface {
         }
     }
 
+    /// Suspends or resumes a window, if the client supports it.
+    ///
+    /// Sends a configure and returns `true`, if the state changed.
+    pub fn set_suspended(&self, suspended: bool) -> bool {
+        let CosmicSurface::Wayland(window) = self else {
+            return false;
+        };
+        let toplevel = window.toplevel();
+        if toplevel.xdg_toplevel().version() < XDG_TOPLEVEL_SUSPENDED_SINCE {
+            return false;
+        }
+        let changed = toplevel.with_pending_state(|state| {
+            if state.states.contains(ToplevelState::Suspended) == suspended {
+                return false;
+            }
+            if suspended {
+                state.states.set(ToplevelState::Suspended);
+            } else {
+                state.states.unset(ToplevelState::Suspended);
+            }
+            true
+        });
+        if changed {
+            let serial = toplevel.send_pending_configure();
+            self.user_data()
+                .insert_if_missing(|| SuspendConfigure(Cell::new(None)));
+            self.user_data()
+                .get::<SuspendConfigure>()
+                .unwrap()
+                .0
+                .set(serial);
+        }
+        changed
+    }
+
+    /// Whether the window was told it is suspended.
+    pub fn is_suspended(&self) -> bool {
+        match self {
+            CosmicSurface::Wayland(window) => window
+                .toplevel()
+                .with_pending_state(|state| state.states.contains(ToplevelState::Suspended)),
+            _ => false,
+        }
+    }
+
+    /// Whether the client didn't ack the configure changing its suspended state yet.
+    ///
+    /// Clients might wait for a frame callback before doing so.
+    pub fn awaits_suspend_ack(&self) -> bool {
+        self.user_data()
+            .get::<SuspendConfigure>()
+            .and_then(|configure| configure.0.get())
+            .is_some_and(|serial| !self.serial_acked(&serial))
+    }
+
     pub fn is_decorated(&self, pending: bool) -> bool {
         match self {
             CosmicSurface::Wayland(window) => {
@@ -457,7 +692,11 @@ impl CosmicSurface {
 
     pub fn send_configure(&self) -> Option<Serial> {
         match self {
-            CosmicSurface::Wayland(window) => window.toplevel().send_pending_configure(),
+            CosmicSurface::Wayland(window) => {
+                let serial = window.toplevel().send_pending_configure();
+                self.record_configure(serial);
+                serial
+            }
             CosmicSurface::X11(surface) => {
                 let _ = surface.configure(None);
                 None
@@ -466,6 +705,128 @@ impl CosmicSurface {
         }
     }
 
+    /// Like `send_configure`, but holds the configure back while a previous one wasn't acked yet.
+    ///
+    /// Used for size changes, that may happen on every pointer motion (interactive resizes)
+    /// or for many windows at once (re-layouts). Newer sizes simply replace the pending state,
+    /// which is sent once the client acked and committed the previous configure,
+    /// or after `CONFIGURE_THROTTLE_TIMEOUT`. Slow clients thereby only see the latest size.
+    pub fn send_throttled_configure(&self) -> Option<Serial> {
+        if let CosmicSurface::Wayland(_) = self {
+            if let Some(mut configure) = self.last_configure() {
+                if !self.serial_acked(&configure.serial)
+                    && configure.sent.elapsed() < CONFIGURE_THROTTLE_TIMEOUT
+                {
+                    configure.deferred = true;
+                    self.set_last_configure(configure);
+                    return None;
+                }
+            }
+        }
+        self.send_configure()
+    }
+
+    /// Sends a configure held back by `send_throttled_configure`, if it is due by now.
+    pub fn flush_throttled_configure(&self) {
+        if self
+            .last_configure()
+            .is_some_and(|configure| configure.deferred)
+        {
+            self.send_throttled_configure();
+        }
+    }
+
+    fn last_configure(&self) -> Option<SentConfigure> {
+        self.user_data()
+            .get::<ThrottledConfigure>()
+            .and_then(|configure| configure.0.get())
+    }
+
+    fn set_last_configure(&self, configure: SentConfigure) {
+        self.user_data()
+            .insert_if_missing(ThrottledConfigure::default);
+        self.user_data()
+            .get::<ThrottledConfigure>()
+            .unwrap()
+            .0
+            .set(Some(configure));
+    }
+
+    /// Whether a configure was held back or the last one wasn't acked yet.
+    pub fn has_pending_configure(&self) -> bool {
+        self.last_configure()
+            .is_some_and(|configure| configure.deferred || !self.serial_acked(&configure.serial))
+    }
+
+    /// Whether the last configure wasn't acked within `timeout`.
+    pub fn configure_overdue(&self, timeout: Duration) -> bool {
+        self.last_configure().is_some_and(|configure| {
+            configure.sent.elapsed() >= timeout && !self.serial_acked(&configure.serial)
+        })
+    }
+
+    /// Marks the configure with `serial` as one the layout waits for, until it is acked
+    /// or `take_overdue_configure` gives up on it.
+    pub fn expect_ack(&self, serial: Serial, reason: ConfigureReason) {
+        if let Some(configure) = self
+            .last_configure()
+            .filter(|configure| configure.serial == serial)
+        {
+            self.set_last_configure(SentConfigure {
+                awaited: Some(reason),
+                ..configure
+            });
+        }
+    }
+
+    /// Whether the layout waits for the last configure to be acked.
+    pub fn awaits_ack(&self) -> bool {
+        self.last_configure().is_some_and(|configure| {
+            configure.awaited.is_some() && !self.serial_acked(&configure.serial)
+        })
+    }
+
+    /// Stops waiting for the last configure, if it was acked or not within `timeout`.
+    /// Returns the reason the layout waited for it in the latter case.
+    pub fn take_overdue_configure(&self, timeout: Duration) -> Option<ConfigureReason> {
+        let configure = self.last_configure()?;
+        let reason = configure.awaited?;
+        let acked = self.serial_acked(&configure.serial);
+        if !acked && configure.sent.elapsed() < timeout {
+            return None;
+        }
+        self.set_last_configure(SentConfigure {
+            awaited: None,
+            ..configure
+        });
+        (!acked).then_some(reason)
+    }
+
+    fn record_configure(&self, serial: Option<Serial>) {
+        let previous = self.last_configure();
+        // a configure carries the whole pending state, so nothing is held back anymore
+        let configure = match serial {
+            Some(serial) => SentConfigure {
+                serial,
+                sent: Instant::now(),
+                deferred: false,
+                // the new configure carries whatever the layout waited for
+                awaited: previous
+                    .filter(|configure| !self.serial_acked(&configure.serial))
+                    .and_then(|configure| configure.awaited),
+            },
+            // nothing new was sent, the previous configure might still wait for its ack
+            None => match previous {
+                Some(configure) => SentConfigure {
+                    deferred: false,
+                    ..configure
+                },
+                None => return,
+            },
+        };
+        self.set_last_configure(configure);
+    }
+
     pub fn close(&self) {
         match self {
             CosmicSurface::Wayland(window) => window.toplevel().send_close(),
@@ -476,9 +837,53 @@ impl CosmicSurface {
         }
     }
 
+    /// Disconnects the client of the window, for clients that don't react to `close` anymore.
+    pub fn kill(&self, dh: &DisplayHandle, xwayland: Option<&XWaylandState>) {
+        match self {
+            CosmicSurface::Wayland(window) => {
+                if let Some(client) = window.toplevel().wl_surface().client() {
+                    dh.backend_handle()
+                        .kill_client(client.id(), DisconnectReason::ConnectionClosed);
+                }
+            }
+            // all X11 windows share the Xwayland wayland client, so the X11 client owning
+            // the window is disconnected from the X server instead.
+            CosmicSurface::X11(surface) => {
+                let Some(xwayland) = xwayland else {
+                    return;
+                };
+                if let Err(err) = xwayland.kill_client(surface.window_id()) {
+                    warn!(
+                        ?err,
+                        window = surface.window_id(),
+                        "Failed to kill X11 client."
+                    );
+                }
+            }
+            _ => unreachable!(),
+        }
+    }
+
+    pub fn responsiveness(&self) -> Responsiveness {
+        self.user_data()
+            .get::<WindowResponsiveness>()
+            .map_or(Responsiveness::Responsive, |state| state.0.get())
+    }
+
+    /// Returns `true`, if the responsiveness changed.
+    pub fn set_responsiveness(&self, responsiveness: Responsiveness) -> bool {
+        self.user_data()
+            .insert_if_missing(|| WindowResponsiveness(Cell::new(Responsiveness::Responsive)));
+        let state = self.user_data().get::<WindowResponsiveness>().unwrap();
+        state.0.replace(responsiveness) != responsiveness
+    }
+
     pub fn on_commit(&self) {
         match self {
-            CosmicSurface::Wayland(window) => window.on_commit(),
+            CosmicSurface::Wayland(window) => {
+                window.on_commit();
+                self.flush_throttled_configure();
+            }
             _ => {}
         }
     }
@@ -524,12 +929,7 @@ impl CosmicSurface {
         match self {
             CosmicSurface::Wayland(window) => {
                 window.send_dmabuf_feedback(output, primary_scan_out_output, |surface, _| {
-                    select_dmabuf_feedback(
-                        surface,
-                        render_element_states,
-                        &feedback.render_feedback,
-                        &feedback.scanout_feedback,
-                    )
+                    surface_dmabuf_feedback(surface, render_element_states, feedback)
                 })
             }
             CosmicSurface::X11(surface) => {
@@ -539,12 +939,7 @@ impl CosmicSurface {
                         output,
                         primary_scan_out_output,
                         |surface, _| {
-                            select_dmabuf_feedback(
-                                surface,
-                                render_element_states,
-                                &feedback.render_feedback,
-                                &feedback.scanout_feedback,
-                            )
+                            surface_dmabuf_feedback(surface, render_element_states, feedback)
                         },
                     )
                 }
@@ -605,6 +1000,38 @@ impl CosmicSurface {
         }
     }
 
+    pub fn inhibit_keybindings_override(&self) -> Option<bool> {
+        self.user_data()
+            .get::<InhibitKeybindings>()
+            .and_then(|inhibit| inhibit.0.get())
+    }
+
+    pub fn set_inhibit_keybindings_override(&self, inhibit: bool) {
+        self.user_data()
+            .insert_if_missing(|| InhibitKeybindings(Cell::new(None)));
+        self.user_data()
+            .get::<InhibitKeybindings>()
+            .unwrap()
+            .0
+            .set(Some(inhibit));
+    }
+
+    pub fn opacity(&self) -> f32 {
+        self.user_data()
+            .get::<Opacity>()
+            .map_or(1.0, |opacity| opacity.0.get())
+    }
+
+    pub fn set_opacity(&self, opacity: f32) {
+        self.user_data()
+            .insert_if_missing(|| Opacity(Cell::new(1.0)));
+        self.user_data()
+            .get::<Opacity>()
+            .unwrap()
+            .0
+            .set(opacity.clamp(MIN_OPACITY, 1.0));
+    }
+
     pub fn split_render_elements<R, C>(
         &self,
         renderer: &mut R,
@@ -615,7 +1042,7 @@ impl CosmicSurface {
     where
         R: Renderer + ImportAll,
         <R as Renderer>::TextureId: 'static,
-        C: From<WaylandSurfaceRenderElement<R>>,
+        C: From<WaylandSurfaceRenderElement<R>> + From<SolidColorRenderElement>,
     {
         match self {
             CosmicSurface::Wayland(window) => {
@@ -637,14 +1064,18 @@ impl CosmicSurface {
                     })
                     .collect();
 
-                let window_render_elements = render_elements_from_surface_tree(
-                    renderer,
-                    surface,
-                    location,
-                    scale,
-                    alpha,
-                    element::Kind::Unspecified,
-                );
+                let window_render_elements =
+                    match solid_color_element(surface, location, scale, alpha) {
+                        Some(element) => vec![C::from(element)],
+                        None => render_elements_from_surface_tree(
+                            renderer,
+                            surface,
+                            location,
+                            scale,
+                            alpha,
+                            element::Kind::Unspecified,
+                        ),
+                    };
 
                 (window_render_elements, popup_render_elements)
             }
@@ -723,10 +1154,108 @@ impl KeyboardTarget<State> for CosmicSurface {
     }
 }
 
+/// Dmabuf feedback for `surface`. Games hinting their content type get the formats
+/// for direct scanout up front, instead of only once they are scanned out already.
+fn surface_dmabuf_feedback<'a>(
+    surface: &WlSurface,
+    render_element_states: &RenderElementStates,
+    feedback: &'a SurfaceDmabufFeedback,
+) -> &'a DmabufFeedback {
+    if content_type(surface) == ContentType::Game {
+        &feedback.scanout_feedback
+    } else {
+        select_dmabuf_feedback(
+            surface,
+            render_element_states,
+            &feedback.render_feedback,
+            &feedback.scanout_feedback,
+        )
+    }
+}
+
+/// Surfaces of a wayland window's tree the pointers of seats are over, with their offsets
+#[derive(Default)]
+struct PointerSurfaces(RefCell<Vec<(Seat<State>, WlSurface, Point<i32, Logical>)>>);
+
+fn pointer_surfaces(window: &Window) -> &PointerSurfaces {
+    window
+        .user_data()
+        .insert_if_missing(PointerSurfaces::default);
+    window.user_data().get::<PointerSurfaces>().unwrap()
+}
+
+/// Surface of `window` the pointer of `seat` is over, the toplevel if none was entered yet
+fn pointer_surface(window: &Window, seat: &Seat<State>) -> WlSurface {
+    pointer_surfaces(window)
+        .0
+        .borrow()
+        .iter()
+        .find(|(s, _, _)| s == seat)
+        .map(|(_, surface, _)| surface.clone())
+        .unwrap_or_else(|| window.toplevel().wl_surface().clone())
+}
+
+/// Routes pointer motion over `window` to the topmost (sub-)surface at its location,
+/// which honors the subsurface stacking order and input regions.
+/// Entering a different surface leaves the previous one.
+fn pointer_motion_surface(
+    window: &Window,
+    seat: &Seat<State>,
+    data: &mut State,
+    event: &MotionEvent,
+) {
+    let (surface, offset) = under_from_surface_tree(
+        window.toplevel().wl_surface(),
+        event.location,
+        (0, 0),
+        WindowSurfaceType::TOPLEVEL | WindowSurfaceType::SUBSURFACE,
+    )
+    .unwrap_or_else(|| (window.toplevel().wl_surface().clone(), (0, 0).into()));
+
+    let previous = {
+        let mut surfaces = pointer_surfaces(window).0.borrow_mut();
+        let previous = surfaces
+            .iter()
+            .position(|(s, _, _)| s == seat)
+            .map(|idx| surfaces.remove(idx).1);
+        surfaces.push((seat.clone(), surface.clone(), offset));
+        previous
+    };
+
+    let mut event = event.clone();
+    event.location -= offset.to_f64();
+    match previous {
+        Some(previous) if previous == surface => {
+            PointerTarget::motion(&surface, seat, data, &event)
+        }
+        previous => {
+            if let Some(previous) = previous.filter(|surface| surface.is_alive()) {
+                PointerTarget::leave(&previous, seat, data, event.serial, event.time);
+            }
+            PointerTarget::enter(&surface, seat, data, &event);
+        }
+    }
+}
+
+fn pointer_leave_surface(
+    window: &Window,
+    seat: &Seat<State>,
+    data: &mut State,
+    serial: Serial,
+    time: u32,
+) {
+    let surface = pointer_surface(window, seat);
+    pointer_surfaces(window)
+        .0
+        .borrow_mut()
+        .retain(|(s, _, _)| s != seat);
+    PointerTarget::leave(&surface, seat, data, serial, time)
+}
+
 impl PointerTarget<State> for CosmicSurface {
     fn enter(&self, seat: &Seat<State>, data: &mut State, event: &MotionEvent) {
         match self {
-            CosmicSurface::Wayland(window) => PointerTarget::enter(window, seat, data, event),
+            CosmicSurface::Wayland(window) => pointer_motion_surface(window, seat, data, event),
             CosmicSurface::X11(surface) => PointerTarget::enter(surface, seat, data, event),
             _ => unreachable!(),
         }
@@ -734,7 +1263,7 @@ impl PointerTarget<State> for CosmicSurface {
 
     fn motion(&self, seat: &Seat<State>, data: &mut State, event: &MotionEvent) {
         match self {
-            CosmicSurface::Wayland(window) => PointerTarget::motion(window, seat, data, event),
+            CosmicSurface::Wayland(window) => pointer_motion_surface(window, seat, data, event),
             CosmicSurface::X11(surface) => PointerTarget::motion(surface, seat, data, event),
             _ => unreachable!(),
         }
@@ -743,7 +1272,7 @@ impl PointerTarget<State> for CosmicSurface {
     fn relative_motion(&self, seat: &Seat<State>, data: &mut State, event: &RelativeMotionEvent) {
         match self {
             CosmicSurface::Wayland(window) => {
-                PointerTarget::relative_motion(window, seat, data, event)
+                PointerTarget::relative_motion(&pointer_surface(window, seat), seat, data, event)
             }
             CosmicSurface::X11(surface) => {
                 PointerTarget::relative_motion(surface, seat, data, event)
@@ -754,7 +1283,9 @@ impl PointerTarget<State> for CosmicSurface {
 
     fn button(&self, seat: &Seat<State>, data: &mut State, event: &ButtonEvent) {
         match self {
-            CosmicSurface::Wayland(window) => PointerTarget::button(window, seat, data, event),
+            CosmicSurface::Wayland(window) => {
+                PointerTarget::button(&pointer_surface(window, seat), seat, data, event)
+            }
             CosmicSurface::X11(surface) => PointerTarget::button(surface, seat, data, event),
             _ => unreachable!(),
         }
@@ -762,7 +1293,9 @@ impl PointerTarget<State> for CosmicSurface {
 
     fn axis(&self, seat: &Seat<State>, data: &mut State, frame: AxisFrame) {
         match self {
-            CosmicSurface::Wayland(window) => PointerTarget::axis(window, seat, data, frame),
+            CosmicSurface::Wayland(window) => {
+                PointerTarget::axis(&pointer_surface(window, seat), seat, data, frame)
+            }
             CosmicSurface::X11(surface) => PointerTarget::axis(surface, seat, data, frame),
             _ => unreachable!(),
         }
@@ -770,7 +1303,9 @@ impl PointerTarget<State> for CosmicSurface {
 
     fn frame(&self, seat: &Seat<State>, data: &mut State) {
         match self {
-            CosmicSurface::Wayland(window) => PointerTarget::frame(window, seat, data),
+            CosmicSurface::Wayland(window) => {
+                PointerTarget::frame(&pointer_surface(window, seat), seat, data)
+            }
             CosmicSurface::X11(surface) => PointerTarget::frame(surface, seat, data),
             _ => unreachable!(),
         }
@@ -785,7 +1320,7 @@ impl PointerTarget<State> for CosmicSurface {
     ) {
         match self {
             CosmicSurface::Wayland(window) => {
-                PointerTarget::leave(window, seat, data, serial, time)
+                pointer_leave_surface(window, seat, data, serial, time)
             }
             CosmicSurface::X11(surface) => PointerTarget::leave(surface, seat, data, serial, time),
             _ => unreachable!(),
@@ -799,9 +1334,12 @@ impl PointerTarget<State> for CosmicSurface {
         event: &GestureSwipeBeginEvent,
     ) {
         match self {
-            CosmicSurface::Wayland(window) => {
-                PointerTarget::gesture_swipe_begin(window, seat, data, event)
-            }
+            CosmicSurface::Wayland(window) => PointerTarget::gesture_swipe_begin(
+                &pointer_surface(window, seat),
+                seat,
+                data,
+                event,
+            ),
             CosmicSurface::X11(surface) => {
                 PointerTarget::gesture_swipe_begin(surface, seat, data, event)
             }
@@ -816,9 +1354,12 @@ impl PointerTarget<State> for CosmicSurface {
         event: &GestureSwipeUpdateEvent,
     ) {
         match self {
-            CosmicSurface::Wayland(window) => {
-                PointerTarget::gesture_swipe_update(window, seat, data, event)
-            }
+            CosmicSurface::Wayland(window) => PointerTarget::gesture_swipe_update(
+                &pointer_surface(window, seat),
+                seat,
+                data,
+                event,
+            ),
             CosmicSurface::X11(surface) => {
                 PointerTarget::gesture_swipe_update(surface, seat, data, event)
             }
@@ -834,7 +1375,7 @@ impl PointerTarget<State> for CosmicSurface {
     ) {
         match self {
             CosmicSurface::Wayland(window) => {
-                PointerTarget::gesture_swipe_end(window, seat, data, event)
+                PointerTarget::gesture_swipe_end(&pointer_surface(window, seat), seat, data, event)
             }
             CosmicSurface::X11(surface) => {
                 PointerTarget::gesture_swipe_end(surface, seat, data, event)
@@ -850,9 +1391,12 @@ impl PointerTarget<State> for CosmicSurface {
         event: &GesturePinchBeginEvent,
     ) {
         match self {
-            CosmicSurface::Wayland(window) => {
-                PointerTarget::gesture_pinch_begin(window, seat, data, event)
-            }
+            CosmicSurface::Wayland(window) => PointerTarget::gesture_pinch_begin(
+                &pointer_surface(window, seat),
+                seat,
+                data,
+                event,
+            ),
             CosmicSurface::X11(surface) => {
                 PointerTarget::gesture_pinch_begin(surface, seat, data, event)
             }
@@ -867,9 +1411,12 @@ impl PointerTarget<State> for CosmicSurface {
         event: &GesturePinchUpdateEvent,
     ) {
         match self {
-            CosmicSurface::Wayland(window) => {
-                PointerTarget::gesture_pinch_update(window, seat, data, event)
-            }
+            CosmicSurface::Wayland(window) => PointerTarget::gesture_pinch_update(
+                &pointer_surface(window, seat),
+                seat,
+                data,
+                event,
+            ),
             CosmicSurface::X11(surface) => {
                 PointerTarget::gesture_pinch_update(surface, seat, data, event)
             }
@@ -885,7 +1432,7 @@ impl PointerTarget<State> for CosmicSurface {
     ) {
         match self {
             CosmicSurface::Wayland(window) => {
-                PointerTarget::gesture_pinch_end(window, seat, data, event)
+                PointerTarget::gesture_pinch_end(&pointer_surface(window, seat), seat, data, event)
             }
             CosmicSurface::X11(surface) => {
                 PointerTarget::gesture_pinch_end(surface, seat, data, event)
@@ -902,7 +1449,7 @@ impl PointerTarget<State> for CosmicSurface {
     ) {
         match self {
             CosmicSurface::Wayland(window) => {
-                PointerTarget::gesture_hold_begin(window, seat, data, event)
+                PointerTarget::gesture_hold_begin(&pointer_surface(window, seat), seat, data, event)
             }
             CosmicSurface::X11(surface) => {
                 PointerTarget::gesture_hold_begin(surface, seat, data, event)
@@ -914,7 +1461,7 @@ impl PointerTarget<State> for CosmicSurface {
     fn gesture_hold_end(&self, seat: &Seat<State>, data: &mut State, event: &GestureHoldEndEvent) {
         match self {
             CosmicSurface::Wayland(window) => {
-                PointerTarget::gesture_hold_end(window, seat, data, event)
+                PointerTarget::gesture_hold_end(&pointer_surface(window, seat), seat, data, event)
             }
             CosmicSurface::X11(surface) => {
                 PointerTarget::gesture_hold_end(surface, seat, data, event)
@@ -968,3 +1515,181 @@ where
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use smithay::{
+        input::pointer::{MotionEvent, PointerTarget},
+        utils::SERIAL_COUNTER,
+    };
+    use wayland_client::protocol::{
+        wl_compositor::WlCompositor, wl_pointer, wl_seat::WlSeat,
+        wl_subcompositor::WlSubcompositor, wl_subsurface::WlSubsurface, wl_surface::WlSurface,
+    };
+
+    use super::{CosmicSurface, Responsiveness};
+    use crate::utils::testing::{ClientEvent, TestClient, TestServer};
+
+    #[test]
+    fn missed_checks_mark_not_responding() {
+        assert_eq!(
+            Responsiveness::Responsive.update(false),
+            Responsiveness::NotResponding
+        );
+        assert_eq!(
+            Responsiveness::NotResponding.update(false),
+            Responsiveness::NotResponding
+        );
+    }
+
+    #[test]
+    fn kill_confirmation_outlives_missed_checks() {
+        assert_eq!(
+            Responsiveness::ConfirmKill.update(false),
+            Responsiveness::ConfirmKill
+        );
+    }
+
+    #[test]
+    fn responding_clients_recover() {
+        for state in [
+            Responsiveness::Responsive,
+            Responsiveness::NotResponding,
+            Responsiveness::ConfirmKill,
+        ] {
+            assert_eq!(state.update(true), Responsiveness::Responsive);
+        }
+    }
+
+    /// Creates a desynchronized subsurface of `parent` with a buffer of `size`,
+    /// which is placed at `location` with the next commit of `parent`.
+    fn subsurface(
+        client: &TestClient,
+        parent: &WlSurface,
+        location: (i32, i32),
+        size: (i32, i32),
+    ) -> (WlSurface, WlSubsurface) {
+        let qh = client.handle();
+        let surface = client.bind::<WlCompositor>(5).create_surface(&qh, ());
+        let subsurface =
+            client
+                .bind::<WlSubcompositor>(1)
+                .get_subsurface(&surface, parent, &qh, ());
+        subsurface.set_position(location.0, location.1);
+        subsurface.set_desync();
+        surface.attach(Some(&client.create_buffer(size.0, size.1)), 0, 0);
+        surface.commit();
+        (surface, subsurface)
+    }
+
+    /// Moves the pointer to `location` within `window` and returns what the client was told.
+    fn move_pointer(
+        server: &mut TestServer,
+        client: &mut TestClient,
+        window: &CosmicSurface,
+        location: (f64, f64),
+    ) -> Vec<wl_pointer::Event> {
+        let seat = server.state.common.last_active_seat().clone();
+        let event = MotionEvent {
+            location: location.into(),
+            serial: SERIAL_COUNTER.next_serial(),
+            time: 0,
+        };
+        PointerTarget::motion(window, &seat, &mut server.state, &event);
+        server.roundtrip(client);
+        client
+            .take_events()
+            .into_iter()
+            .filter_map(|event| match event {
+                ClientEvent::Pointer(wl_pointer::Event::Frame) => None,
+                ClientEvent::Pointer(event) => Some(event),
+                _ => None,
+            })
+            .collect()
+    }
+
+    fn is_crossing(
+        events: &[wl_pointer::Event],
+        from: &WlSurface,
+        to: &WlSurface,
+        location: (f64, f64),
+    ) -> bool {
+        matches!(events, [
+            wl_pointer::Event::Leave { surface: left, .. },
+            wl_pointer::Event::Enter { surface: entered, surface_x, surface_y, .. },
+        ] if left == from && entered == to && (*surface_x, *surface_y) == location)
+    }
+
+    #[test]
+    fn pointer_enters_the_topmost_surface_accepting_input() {
+        let mut server = TestServer::new();
+        let mut client = server.connect();
+        client.bind::<WlSeat>(7).get_pointer(&client.handle(), ());
+        let (toplevel, _xdg_toplevel) = client.create_toplevel();
+        toplevel.commit();
+        server.roundtrip(&mut client);
+        // keep the window out of the shell, nothing can be rendered without backend
+        let (window, _, _) = server.state.common.shell.pending_windows.remove(0);
+
+        // toplevel 100x100, with two overlapping subsurfaces and a subsurface of the lower one
+        toplevel.attach(Some(&client.create_buffer(100, 100)), 0, 0);
+        let (upper, upper_subsurface) = subsurface(&client, &toplevel, (10, 10), (40, 40));
+        let (lower, _lower_subsurface) = subsurface(&client, &toplevel, (30, 30), (40, 40));
+        let (nested, _nested_subsurface) = subsurface(&client, &lower, (20, 20), (10, 10));
+        lower.commit();
+        upper.set_input_region(Some(&client.create_region(&[(0, 0, 30, 30)])));
+        upper.commit();
+        upper_subsurface.place_above(&lower);
+        toplevel.commit();
+        server.roundtrip(&mut client);
+        client.take_events();
+
+        let events = move_pointer(&mut server, &mut client, &window, (5., 5.));
+        assert!(
+            matches!(&events[..], [
+                wl_pointer::Event::Enter { surface, surface_x, surface_y, .. },
+            ] if *surface == toplevel && (*surface_x, *surface_y) == (5., 5.)),
+            "{:?}",
+            events
+        );
+
+        let events = move_pointer(&mut server, &mut client, &window, (15., 15.));
+        assert!(
+            is_crossing(&events, &toplevel, &upper, (5., 5.)),
+            "{:?}",
+            events
+        );
+
+        // where both subsurfaces overlap the one placed above wins
+        let events = move_pointer(&mut server, &mut client, &window, (35., 35.));
+        assert!(
+            matches!(&events[..], [
+                wl_pointer::Event::Motion { surface_x, surface_y, .. },
+            ] if (*surface_x, *surface_y) == (25., 25.)),
+            "{:?}",
+            events
+        );
+
+        // outside of its input region the pointer goes through it
+        let events = move_pointer(&mut server, &mut client, &window, (45., 45.));
+        assert!(
+            is_crossing(&events, &upper, &lower, (15., 15.)),
+            "{:?}",
+            events
+        );
+
+        let events = move_pointer(&mut server, &mut client, &window, (55., 55.));
+        assert!(
+            is_crossing(&events, &lower, &nested, (5., 5.)),
+            "{:?}",
+            events
+        );
+
+        let events = move_pointer(&mut server, &mut client, &window, (95., 95.));
+        assert!(
+            is_crossing(&events, &nested, &toplevel, (95., 95.)),
+            "{:?}",
+            events
+        );
+    }
+}
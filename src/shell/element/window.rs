@@ -15,8 +15,8 @@ use smithay::{
         input::KeyState,
         renderer::{
             element::{
-                memory::MemoryRenderBufferRenderElement, surface::WaylandSurfaceRenderElement,
-                AsRenderElements,
+                memory::MemoryRenderBufferRenderElement, solid::SolidColorRenderElement,
+                surface::WaylandSurfaceRenderElement, AsRenderElements,
             },
             ImportAll, ImportMem, Renderer,
         },
@@ -48,11 +48,24 @@ use std::{
 };
 use wayland_backend::server::ObjectId;
 
-use super::{surface::SSD_HEIGHT, CosmicSurface};
+use super::{
+    surface::{Responsiveness, SSD_HEIGHT},
+    CosmicSurface,
+};
 
 #[derive(Clone, PartialEq, Eq, Hash)]
 pub struct CosmicWindow(IcedElement<CosmicWindowInternal>);
 
+// follows the rate-limited title, like toplevel-info does
+fn header_title(window: &CosmicSurface) -> String {
+    let title = window.published_identity().title;
+    match window.responsiveness() {
+        Responsiveness::Responsive => title,
+        Responsiveness::NotResponding => crate::fl!("not-responding", title = title),
+        Responsiveness::ConfirmKill => crate::fl!("confirm-kill", title = title),
+    }
+}
+
 impl fmt::Debug for CosmicWindow {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         f.debug_struct("CosmicWindow")
@@ -118,7 +131,7 @@ impl CosmicWindow {
     ) -> CosmicWindow {
         let window = window.into();
         let width = window.geometry().size.w;
-        let last_title = window.title();
+        let last_title = header_title(&window);
         CosmicWindow(IcedElement::new(
             CosmicWindowInternal {
                 window,
@@ -424,7 +437,7 @@ impl SpaceElement for CosmicWindow {
         SpaceElement::refresh(&self.0);
         if self.0.with_program(|p| {
             SpaceElement::refresh(&p.window);
-            let title = p.window.title();
+            let title = header_title(&p.window);
             let mut last_title = p.last_title.lock().unwrap();
             if *last_title != title {
                 *last_title = title;
@@ -735,4 +748,5 @@ render_elements! {
     pub CosmicWindowRenderElement<R> where R: ImportAll + ImportMem;
     Header = MemoryRenderBufferRenderElement<R>,
     Window = WaylandSurfaceRenderElement<R>,
+    Solid = SolidColorRenderElement,
 }
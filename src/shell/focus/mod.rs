@@ -1,12 +1,13 @@
 use crate::{
+    config::{FullscreenFocusPriority, WorkspaceFocus},
     shell::{element::CosmicMapped, Shell, Workspace},
     state::Common,
     utils::prelude::*,
-    wayland::handlers::xdg_shell::PopupGrabData,
+    wayland::{handlers::xdg_shell::PopupGrabData, protocols::workspace::WorkspaceHandle},
 };
 use indexmap::IndexSet;
 use smithay::{
-    desktop::{layer_map_for_output, PopupUngrabStrategy},
+    desktop::{layer_map_for_output, LayerSurface, PopupUngrabStrategy},
     input::Seat,
     output::Output,
     utils::{IsAlive, Serial, SERIAL_COUNTER},
@@ -15,12 +16,16 @@ use smithay::{
         shell::wlr_layer::{KeyboardInteractivity, Layer},
     },
 };
-use std::cell::RefCell;
+use std::{
+    cell::RefCell,
+    collections::VecDeque,
+    time::{Duration, Instant},
+};
 use tracing::{debug, trace};
 
-use self::target::{KeyboardFocusTarget, WindowGroup};
+use self::target::{KeyboardFocusTarget, PointerFocusTarget, WindowGroup};
 
-use super::{layout::floating::FloatingLayout, CosmicSurface};
+use super::layout::floating::FloatingLayout;
 
 pub mod target;
 
@@ -73,6 +78,9 @@ pub struct ActiveFocus(RefCell<Option<KeyboardFocusTarget>>);
 
 impl ActiveFocus {
     fn set(seat: &Seat<State>, target: Option<KeyboardFocusTarget>) {
+        if target.is_some() {
+            UnfocusedWorkspace::set(seat, None);
+        }
         if !seat
             .user_data()
             .insert_if_missing(|| ActiveFocus(RefCell::new(target.clone())))
@@ -93,12 +101,108 @@ impl ActiveFocus {
     }
 }
 
+/// Workspace deliberately left without focus by a workspace switch, see `WorkspaceFocus`
+struct UnfocusedWorkspace(RefCell<Option<WorkspaceHandle>>);
+
+impl UnfocusedWorkspace {
+    fn set(seat: &Seat<State>, handle: Option<WorkspaceHandle>) {
+        seat.user_data()
+            .insert_if_missing(|| UnfocusedWorkspace(RefCell::new(None)));
+        *seat
+            .user_data()
+            .get::<UnfocusedWorkspace>()
+            .unwrap()
+            .0
+            .borrow_mut() = handle;
+    }
+
+    fn get(seat: &Seat<State>) -> Option<WorkspaceHandle> {
+        seat.user_data()
+            .get::<UnfocusedWorkspace>()
+            .and_then(|w| w.0.borrow().clone())
+    }
+}
+
+const FOCUS_HISTORY_LENGTH: usize = 32;
+const FOCUS_HISTORY_CYCLE_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// Recently focused windows of a seat across all workspaces and outputs
+#[derive(Default)]
+pub struct FocusHistory(RefCell<FocusHistoryInner>);
+
+#[derive(Default)]
+struct FocusHistoryInner {
+    /// most recent first
+    windows: VecDeque<CosmicMapped>,
+    /// history as of the first of consecutive `previous` calls, the position reached and when
+    cycle: Option<(Vec<CosmicMapped>, usize, Instant)>,
+}
+
+impl FocusHistory {
+    fn push(seat: &Seat<State>, mapped: &CosmicMapped) {
+        seat.user_data().insert_if_missing(FocusHistory::default);
+        let mut history = seat
+            .user_data()
+            .get::<FocusHistory>()
+            .unwrap()
+            .0
+            .borrow_mut();
+        history.windows.retain(|w| w != mapped);
+        history.windows.push_front(mapped.clone());
+        history.windows.truncate(FOCUS_HISTORY_LENGTH);
+    }
+
+    /// Recently focused windows, most recent first.
+    pub fn windows(seat: &Seat<State>) -> Vec<CosmicMapped> {
+        seat.user_data()
+            .get::<FocusHistory>()
+            .map(|history| history.0.borrow().windows.iter().cloned().collect())
+            .unwrap_or_default()
+    }
+
+    /// Returns the window focused before the current one.
+    ///
+    /// Repeated calls within a short timeout walk further back into the history.
+    pub fn previous(seat: &Seat<State>, shell: &Shell) -> Option<CosmicMapped> {
+        seat.user_data().insert_if_missing(FocusHistory::default);
+        let mut history = seat
+            .user_data()
+            .get::<FocusHistory>()
+            .unwrap()
+            .0
+            .borrow_mut();
+        // drop closed and unmapped windows
+        history
+            .windows
+            .retain(|w| w.alive() && shell.space_for(w).is_some());
+
+        let (snapshot, mut idx) = match history.cycle.take() {
+            Some((snapshot, idx, at)) if at.elapsed() < FOCUS_HISTORY_CYCLE_TIMEOUT => {
+                (snapshot, idx)
+            }
+            _ => (history.windows.iter().cloned().collect::<Vec<_>>(), 0),
+        };
+        idx += 1;
+        while snapshot
+            .get(idx)
+            .is_some_and(|w| !w.alive() || shell.space_for(w).is_none())
+        {
+            idx += 1;
+        }
+
+        let previous = snapshot.get(idx).cloned()?;
+        history.cycle = Some((snapshot, idx, Instant::now()));
+        Some(previous)
+    }
+}
+
 impl Shell {
     pub fn set_focus<'a>(
         state: &mut State,
         target: Option<&KeyboardFocusTarget>,
         active_seat: &Seat<State>,
         serial: Option<Serial>,
+        raise: bool,
     ) {
         // update FocusStack and notify layouts about new focus (if any window)
         let element = match target {
@@ -126,6 +230,11 @@ impl Shell {
                         }
                     }
                 }
+                std::mem::drop(focus_stack);
+                if raise {
+                    raise_with_children(&mut workspace.floating_layer, &mapped);
+                }
+                FocusHistory::push(active_seat, &mapped);
             }
         }
 
@@ -163,9 +272,6 @@ impl Shell {
         for output in self.outputs().cloned().collect::<Vec<_>>().into_iter() {
             // TODO: Add self.workspaces.active_workspaces()
             let workspace = self.workspaces.active_mut(&output);
-            for focused in focused_windows.iter() {
-                raise_with_children(&mut workspace.floating_layer, focused);
-            }
             for window in workspace.mapped() {
                 window.set_activated(focused_windows.contains(&window));
                 window.configure();
@@ -175,25 +281,63 @@ impl Shell {
 }
 
 fn raise_with_children(floating_layer: &mut FloatingLayout, focused: &CosmicMapped) {
-    if floating_layer.mapped().any(|m| m == focused) {
-        floating_layer.space.raise_element(focused, true);
-        for element in floating_layer
-            .space
-            .elements()
-            .filter(|elem| {
-                let parent = match elem.active_window() {
-                    CosmicSurface::Wayland(w) => w.toplevel().parent(),
-                    _ => None,
-                };
+    let elements = floating_layer.mapped().cloned().collect::<Vec<_>>();
+    for element in raise_order(&elements, focused, |child, parent| {
+        child.active_window().is_child_of(&parent.active_window())
+    }) {
+        floating_layer.space.raise_element(&element, true);
+    }
+}
 
-                parent == focused.active_window().wl_surface()
-            })
-            .cloned()
-            .collect::<Vec<_>>()
-            .into_iter()
-        {
-            raise_with_children(floating_layer, &element);
+/// Order in which to raise `elements` (bottom to top) when `focused` gets focus.
+///
+/// Transient windows are raised as a group, the focused one ends up on top,
+/// but still below its own transient children.
+fn raise_order<T: Clone + PartialEq>(
+    elements: &[T],
+    focused: &T,
+    is_child_of: impl Fn(&T, &T) -> bool,
+) -> Vec<T> {
+    if !elements.contains(focused) {
+        return Vec::new();
+    }
+
+    let mut root = focused.clone();
+    let mut ancestors = vec![focused.clone()];
+    while let Some(parent) = elements
+        .iter()
+        .find(|elem| is_child_of(&root, *elem))
+        .cloned()
+    {
+        if ancestors.contains(&parent) {
+            break;
         }
+        ancestors.push(parent.clone());
+        root = parent;
+    }
+
+    let mut order = Vec::new();
+    raise_subtree(elements, &root, &is_child_of, &mut Vec::new(), &mut order);
+    if &root != focused {
+        raise_subtree(elements, focused, &is_child_of, &mut Vec::new(), &mut order);
+    }
+    order
+}
+
+fn raise_subtree<T: Clone + PartialEq>(
+    elements: &[T],
+    element: &T,
+    is_child_of: &impl Fn(&T, &T) -> bool,
+    raised: &mut Vec<T>,
+    order: &mut Vec<T>,
+) {
+    if raised.contains(element) {
+        return;
+    }
+    raised.push(element.clone());
+    order.push(element.clone());
+    for child in elements.iter().filter(|elem| is_child_of(*elem, element)) {
+        raise_subtree(elements, child, is_child_of, raised, order);
     }
 }
 
@@ -204,17 +348,89 @@ impl Common {
         active_seat: &Seat<State>,
         serial: Option<Serial>,
     ) {
-        Shell::set_focus(state, target, active_seat, serial);
+        Self::set_focus_with_raise(state, target, active_seat, serial, true)
+    }
+
+    /// Like `set_focus`, but floating windows are only raised if `raise` is set.
+    pub fn set_focus_with_raise(
+        state: &mut State,
+        target: Option<&KeyboardFocusTarget>,
+        active_seat: &Seat<State>,
+        serial: Option<Serial>,
+        raise: bool,
+    ) {
+        Shell::set_focus(state, target, active_seat, serial, raise);
         let seats = state.common.seats().cloned().collect::<Vec<_>>();
         state.common.shell.update_active(seats.iter());
     }
 
+    /// Focuses a window of the now active workspace of `output` after a workspace switch,
+    /// as configured by `workspace_focus`.
+    pub fn set_workspace_focus(state: &mut State, seat: &Seat<State>, output: &Output) {
+        let target = match state.common.config.static_conf.workspace_focus {
+            WorkspaceFocus::LastFocused => {
+                let workspace = state.common.shell.active_space(output);
+                workspace
+                    .get_fullscreen()
+                    .cloned()
+                    .map(KeyboardFocusTarget::Fullscreen)
+                    .or_else(|| {
+                        workspace
+                            .focus_stack
+                            .get(seat)
+                            .last()
+                            .cloned()
+                            .map(KeyboardFocusTarget::Element)
+                    })
+            }
+            WorkspaceFocus::UnderCursor => seat.get_pointer().and_then(|ptr| {
+                let position = ptr.current_location().as_global();
+                if !output.geometry().to_f64().contains(position) {
+                    return None;
+                }
+                let overview = state.common.shell.overview_mode().0;
+                let workspace = state.common.shell.workspaces.active_mut(output);
+                match State::surface_under(
+                    position,
+                    output,
+                    &state.common.shell.override_redirect_windows,
+                    overview,
+                    workspace,
+                    state.common.session_lock.as_ref(),
+                ) {
+                    Some((PointerFocusTarget::Element(mapped), _)) => {
+                        Some(KeyboardFocusTarget::Element(mapped))
+                    }
+                    Some((PointerFocusTarget::Fullscreen(surface), _)) => {
+                        Some(KeyboardFocusTarget::Fullscreen(surface))
+                    }
+                    _ => None,
+                }
+            }),
+            WorkspaceFocus::None => None,
+        };
+        Common::set_focus(state, target.as_ref(), seat, None);
+        if target.is_none() {
+            let handle = state.common.shell.active_space(output).handle.clone();
+            UnfocusedWorkspace::set(seat, Some(handle));
+        }
+    }
+
     pub fn refresh_focus(state: &mut State) {
         let seats = state.common.seats().cloned().collect::<Vec<_>>();
         for seat in seats {
+            release_dead_grabs(state, &seat);
+
             let output = seat.active_output();
             if !state.common.shell.outputs().any(|o| o == &output) {
-                seat.set_active_output(&state.common.shell.outputs().next().unwrap());
+                let fallback = state
+                    .common
+                    .shell
+                    .primary_output()
+                    .or_else(|| state.common.shell.outputs().next())
+                    .unwrap()
+                    .clone();
+                seat.set_active_output(&fallback);
                 continue;
             }
             let last_known_focus = ActiveFocus::get(&seat);
@@ -258,7 +474,12 @@ impl Common {
                 let workspace = state.common.shell.active_space(&output);
                 let focus_stack = workspace.focus_stack.get(&seat);
 
-                if focus_stack.last().is_none() {
+                // keep the focus a workspace switch left empty, unless something must take it
+                let left_unfocused = state.common.session_lock.is_none()
+                    && exclusive_layer_surface_layer(state).is_none()
+                    && UnfocusedWorkspace::get(&seat).as_ref() == Some(&workspace.handle);
+
+                if focus_stack.last().is_none() || left_unfocused {
                     continue; // Focus is valid
                 } else {
                     trace!("No previous window, focus fixup");
@@ -293,6 +514,23 @@ impl Common {
     }
 }
 
+/// Releases grabs started on a surface, that is gone by now (e.g. because its client disconnected),
+/// so the seat doesn't stay stuck until the next input event.
+fn release_dead_grabs(state: &mut State, seat: &Seat<State>) {
+    if let Some(pointer) = seat.get_pointer() {
+        if pointer.is_grabbed()
+            && pointer
+                .grab_start_data()
+                .and_then(|start_data| start_data.focus)
+                .is_some_and(|(target, _)| !target.alive())
+        {
+            trace!("Pointer grab target dead, releasing grab");
+            let time = Duration::from(state.common.clock.now()).as_millis() as u32;
+            pointer.unset_grab(state, SERIAL_COUNTER.next_serial(), time);
+        }
+    }
+}
+
 fn focus_target_is_valid(
     state: &State,
     seat: &Seat<State>,
@@ -323,7 +561,9 @@ fn focus_target_is_valid(
                 && workspace.get_fullscreen().is_none()
         }
         KeyboardFocusTarget::LayerSurface(layer) => {
-            layer_map_for_output(&output).layers().any(|l| l == &layer)
+            layer.cached_state().keyboard_interactivity != KeyboardInteractivity::None
+                && layer_map_for_output(&output).layers().any(|l| l == &layer)
+                && !fullscreen_keeps_focus(state, &layer)
         }
         KeyboardFocusTarget::Group(WindowGroup { node, .. }) => state
             .common
@@ -348,6 +588,30 @@ fn focus_target_is_valid(
     }
 }
 
+/// Whether `layer` can't take the keyboard focus, because `fullscreen_focus_priority`
+/// lets the fullscreen window on its output keep it.
+pub fn fullscreen_keeps_focus(state: &State, layer: &LayerSurface) -> bool {
+    if state.common.config.static_conf.fullscreen_focus_priority
+        != FullscreenFocusPriority::Fullscreen
+        || layer.cached_state().keyboard_interactivity != KeyboardInteractivity::OnDemand
+    {
+        return false;
+    }
+    state
+        .common
+        .shell
+        .outputs()
+        .find(|output| layer_map_for_output(output).layers().any(|l| l == layer))
+        .is_some_and(|output| {
+            state
+                .common
+                .shell
+                .active_space(output)
+                .get_fullscreen()
+                .is_some()
+        })
+}
+
 fn update_focus_target(
     state: &State,
     seat: &Seat<State>,
@@ -400,3 +664,112 @@ fn exclusive_layer_surface_layer(state: &State) -> Option<Layer> {
     }
     layer
 }
+
+#[cfg(test)]
+mod tests {
+    use smithay::{
+        backend::input::ButtonState,
+        input::pointer::{ButtonEvent, MotionEvent},
+        utils::SERIAL_COUNTER,
+    };
+
+    use super::{raise_order, target::PointerFocusTarget};
+    use crate::{state::Common, utils::testing::TestServer};
+
+    /// Applies a raise order to a stack of windows, bottom to top.
+    fn raise(stack: &mut Vec<u32>, parents: &[(u32, u32)], focused: u32) {
+        let order = raise_order(stack, &focused, |child, parent| {
+            parents.contains(&(*child, *parent))
+        });
+        for window in order {
+            stack.retain(|w| *w != window);
+            stack.push(window);
+        }
+    }
+
+    #[test]
+    fn clicking_lower_window_raises_it() {
+        let mut stack = vec![1, 2];
+        raise(&mut stack, &[], 1);
+        assert_eq!(stack, vec![2, 1]);
+    }
+
+    #[test]
+    fn raising_top_window_keeps_order() {
+        let mut stack = vec![1, 2];
+        raise(&mut stack, &[], 2);
+        assert_eq!(stack, vec![1, 2]);
+    }
+
+    #[test]
+    fn unknown_window_is_not_raised() {
+        let mut stack = vec![1, 2];
+        raise(&mut stack, &[], 3);
+        assert_eq!(stack, vec![1, 2]);
+    }
+
+    #[test]
+    fn transient_children_stay_above_parent() {
+        // 3 is a dialog of 1
+        let mut stack = vec![1, 3, 2];
+        raise(&mut stack, &[(3, 1)], 1);
+        assert_eq!(stack, vec![2, 1, 3]);
+    }
+
+    #[test]
+    fn focused_dialog_raises_its_group() {
+        // 3 and 4 are dialogs of 1, 3 gets focused
+        let mut stack = vec![1, 3, 4, 2];
+        raise(&mut stack, &[(3, 1), (4, 1)], 3);
+        assert_eq!(stack, vec![2, 1, 4, 3]);
+    }
+
+    #[test]
+    fn cyclic_parents_terminate() {
+        let mut stack = vec![1, 2, 3];
+        raise(&mut stack, &[(1, 2), (2, 1)], 1);
+        assert_eq!(stack[0], 3);
+    }
+
+    #[test]
+    fn client_dying_mid_grab_releases_the_pointer() {
+        let mut server = TestServer::new();
+        let mut client = server.connect();
+        let (surface, _toplevel) = client.create_toplevel();
+        surface.commit();
+        server.roundtrip(&mut client);
+        let (window, _, _) = server.state.common.shell.pending_windows[0].clone();
+
+        // press a button over the window, which grabs the pointer until it is released
+        let pointer = server
+            .state
+            .common
+            .last_active_seat()
+            .get_pointer()
+            .unwrap();
+        pointer.motion(
+            &mut server.state,
+            Some((PointerFocusTarget::Fullscreen(window), (0, 0).into())),
+            &MotionEvent {
+                location: (10., 10.).into(),
+                serial: SERIAL_COUNTER.next_serial(),
+                time: 0,
+            },
+        );
+        pointer.button(
+            &mut server.state,
+            &ButtonEvent {
+                button: 0x110,
+                state: ButtonState::Pressed,
+                serial: SERIAL_COUNTER.next_serial(),
+                time: 1,
+            },
+        );
+        assert!(pointer.is_grabbed());
+
+        drop(client);
+        server.dispatch();
+        Common::refresh_focus(&mut server.state);
+        assert!(!pointer.is_grabbed());
+    }
+}
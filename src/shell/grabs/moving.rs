@@ -15,6 +15,7 @@ use crate::{
         CosmicMapped, CosmicSurface,
     },
     utils::prelude::*,
+    wayland::protocols::workspace::WorkspaceHandle,
 };
 
 use cosmic::theme::CosmicTheme;
@@ -37,7 +38,7 @@ use smithay::{
     output::Output,
     reexports::wayland_server::protocol::wl_surface::WlSurface,
     utils::{IsAlive, Logical, Point, Rectangle, Scale, Serial},
-    wayland::compositor::SurfaceData,
+    wayland::{compositor::SurfaceData, fractional_scale::with_fractional_scale},
 };
 use std::{
     cell::RefCell,
@@ -72,7 +73,7 @@ impl MoveGrabState {
         CosmicMappedRenderElement<R>: RenderElement<R>,
         I: From<CosmicMappedRenderElement<R>>,
     {
-        #[cfg(feature = "debug")]
+        #[cfg(feature = "puffin")]
         puffin::profile_function!();
 
         let scale = if self.tiling {
@@ -213,8 +214,14 @@ pub struct MoveGrab {
     window: CosmicMapped,
     start_data: PointerGrabStartData<State>,
     seat: Seat<State>,
+    /// Output below the cursor, the window is announced on
     cursor_output: Output,
+    /// Workspace the window is announced on
+    workspace: WorkspaceHandle,
+    /// Workspace the grab started on, transient children stay there during the grab
+    initial_workspace: WorkspaceHandle,
     window_outputs: HashSet<Output>,
+    initial_window_location: Point<i32, Global>,
     tiling: bool,
 }
 
@@ -234,12 +241,13 @@ impl PointerGrab<State> for MoveGrab {
                 output
                     .geometry()
                     .as_logical()
-                    .contains(handle.current_location().to_i32_round())
+                    .contains(event.location.to_i32_round())
             })
             .cloned()
         else {
             return;
         };
+        let mut outputs_changed = false;
         if self.cursor_output != current_output {
             state
                 .common
@@ -248,7 +256,10 @@ impl PointerGrab<State> for MoveGrab {
                 .active_mut(&self.cursor_output)
                 .tiling_layer
                 .cleanup_drag();
-            self.cursor_output = current_output.clone();
+            // hand the window over to the output below the cursor right away
+            let workspace = state.common.shell.active_space(&current_output).handle;
+            self.transfer(state, &current_output, workspace);
+            outputs_changed = true;
         }
 
         let mut borrow = self
@@ -262,6 +273,7 @@ impl PointerGrab<State> for MoveGrab {
             for output in state.common.shell.outputs() {
                 if let Some(overlap) = output.geometry().as_logical().intersection(window_geo) {
                     if self.window_outputs.insert(output.clone()) {
+                        outputs_changed = true;
                         self.window.output_enter(output, overlap);
                         if let Some(indicator) =
                             grab_state.stacking_indicator.as_ref().map(|x| &x.0)
@@ -270,12 +282,30 @@ impl PointerGrab<State> for MoveGrab {
                         }
                     }
                 } else if self.window_outputs.remove(&output) {
+                    outputs_changed = true;
                     self.window.output_leave(output);
                     if let Some(indicator) = grab_state.stacking_indicator.as_ref().map(|x| &x.0) {
                         indicator.output_leave(output);
                     }
                 }
             }
+            if outputs_changed {
+                self.update_preferred_scale();
+            }
+
+            // keep reactive popups, like tooltips, attached to the moving window
+            let element_loc =
+                (event.location.to_i32_round() + grab_state.window_offset).as_global();
+            for (window, offset) in self.window.windows() {
+                if let CosmicSurface::Wayland(window) = window {
+                    let window_geo_offset = window.geometry().loc.as_global();
+                    update_reactive_popups(
+                        &window,
+                        element_loc + offset.as_global() + window_geo_offset,
+                        state.common.shell.outputs(),
+                    );
+                }
+            }
 
             if self.tiling {
                 let indicator_location = state
@@ -433,6 +463,7 @@ impl MoveGrab {
         start_data: PointerGrabStartData<State>,
         window: CosmicMapped,
         seat: &Seat<State>,
+        workspace: WorkspaceHandle,
         initial_cursor_location: Point<f64, Global>,
         initial_window_location: Point<i32, Global>,
         indicator_thickness: u8,
@@ -470,6 +501,9 @@ impl MoveGrab {
             seat: seat.clone(),
             window_outputs: outputs,
             cursor_output: output,
+            workspace,
+            initial_workspace: workspace,
+            initial_window_location,
             tiling: was_tiled,
         }
     }
@@ -478,6 +512,85 @@ impl MoveGrab {
         self.tiling
     }
 
+    /// Moves the window from the announced output and workspace over to the given ones.
+    fn transfer(&mut self, state: &mut State, output: &Output, workspace: WorkspaceHandle) {
+        if self.cursor_output == *output && self.workspace == workspace {
+            return;
+        }
+
+        let toplevel_info = &mut state.common.shell.toplevel_info_state;
+        for (window, _) in self.window.windows() {
+            toplevel_info.toplevel_leave_workspace(&window, &self.workspace);
+            toplevel_info.toplevel_leave_output(&window, &self.cursor_output);
+            toplevel_info.toplevel_enter_workspace(&window, &workspace);
+            toplevel_info.toplevel_enter_output(&window, output);
+        }
+        self.cursor_output = output.clone();
+        self.workspace = workspace;
+    }
+
+    /// Moves the transient children of the window from the workspace the grab started on
+    /// to the active workspace of `output`, keeping their offset to the window.
+    fn transfer_children(&self, state: &mut State, output: &Output, delta: Point<i32, Global>) {
+        let Some(source) = state
+            .common
+            .shell
+            .space_for_handle_mut(&self.initial_workspace)
+        else {
+            return;
+        };
+        let source_output = source.output.clone();
+        let children = source
+            .transient_children(&self.window)
+            .into_iter()
+            .filter_map(|child| {
+                let geometry = source.element_geometry(&child)?.to_global(&source_output);
+                source.unmap(&child);
+                Some((child, geometry))
+            })
+            .collect::<Vec<_>>();
+
+        let destination = state.common.shell.active_space_mut(output);
+        let handle = destination.handle;
+        for (child, geometry) in &children {
+            destination.floating_layer.map_internal(
+                child.clone(),
+                Some((geometry.loc + delta).to_local(output)),
+                None,
+            );
+        }
+
+        let toplevel_info = &mut state.common.shell.toplevel_info_state;
+        for (window, _) in children.iter().flat_map(|(child, _)| child.windows()) {
+            toplevel_info.toplevel_leave_workspace(&window, &self.initial_workspace);
+            toplevel_info.toplevel_enter_workspace(&window, &handle);
+            if source_output != *output {
+                toplevel_info.toplevel_leave_output(&window, &source_output);
+                toplevel_info.toplevel_enter_output(&window, output);
+            }
+        }
+    }
+
+    /// Asks the window to render at the largest scale of all outputs it overlaps.
+    ///
+    /// The grabbed window isn't part of any space, so its preferred scale
+    /// wouldn't be updated on render, until it is dropped again.
+    fn update_preferred_scale(&self) {
+        let scale = self
+            .window_outputs
+            .iter()
+            .chain(std::iter::once(&self.cursor_output))
+            .map(|output| output.current_scale().fractional_scale())
+            .fold(0.0, f64::max);
+        for (window, _) in self.window.windows() {
+            window.with_surfaces(move |_, states| {
+                with_fractional_scale(states, |fractional_scale| {
+                    fractional_scale.set_preferred_scale(scale);
+                });
+            });
+        }
+    }
+
     fn ungrab(
         &mut self,
         state: &mut State,
@@ -503,18 +616,10 @@ impl MoveGrab {
                 for old_output in self.window_outputs.iter().filter(|o| *o != &output) {
                     grab_state.window.output_leave(old_output);
                 }
-                for (window, _) in grab_state.window.windows() {
-                    state
-                        .common
-                        .shell
-                        .toplevel_info_state
-                        .toplevel_enter_workspace(&window, &workspace_handle);
-                    state
-                        .common
-                        .shell
-                        .toplevel_info_state
-                        .toplevel_enter_output(&window, &output);
-                }
+                self.window_outputs.retain(|o| o == &output);
+                // the workspace might have been switched during the grab
+                self.transfer(state, &output, workspace_handle);
+                self.update_preferred_scale();
 
                 if self.tiling {
                     let (window, location) = state
@@ -529,12 +634,23 @@ impl MoveGrab {
                         window_location,
                         grab_state.window.geometry().size.as_global(),
                     ));
+                    let follow = state.common.config.static_conf.dialogs_follow_parent;
                     let workspace = state.common.shell.active_space_mut(&output);
                     workspace.floating_layer.map_internal(
                         grab_state.window,
                         Some(window_location.to_local(&workspace.output)),
                         None,
                     );
+                    if follow {
+                        let delta = window_location - self.initial_window_location;
+                        if workspace.handle == self.initial_workspace {
+                            workspace
+                                .floating_layer
+                                .move_children(&self.window, delta.as_logical().as_local());
+                        } else {
+                            self.transfer_children(state, &output, delta);
+                        }
+                    }
 
                     Some((self.window.clone(), window_location))
                 }
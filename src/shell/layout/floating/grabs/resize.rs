@@ -4,7 +4,10 @@ use std::sync::atomic::{AtomicBool, Ordering};
 
 use crate::{
     shell::{
-        element::CosmicMapped, focus::target::PointerFocusTarget, grabs::ResizeEdge, CosmicSurface,
+        element::{ConfigureReason, CosmicMapped},
+        focus::target::PointerFocusTarget,
+        grabs::ResizeEdge,
+        CosmicSurface,
     },
     utils::prelude::*,
 };
@@ -119,7 +122,7 @@ impl PointerGrab<State> for ResizeSurfaceGrab {
             },
             self.last_window_size.as_global(),
         ));
-        self.window.configure();
+        self.window.configure_throttled();
     }
 
     fn relative_motion(
@@ -163,7 +166,8 @@ impl PointerGrab<State> for ResizeSurfaceGrab {
                 },
                 self.last_window_size.as_global(),
             ));
-            self.window.configure();
+            self.window
+                .expect_ack(self.window.configure(), ConfigureReason::Resize);
 
             let mut resize_state = self.window.resize_state.lock().unwrap();
             if let Some(ResizeState::Resizing(resize_data)) = *resize_state {
@@ -19,11 +19,11 @@ use crate::{
             resize_indicator::ResizeIndicator,
             stack::{CosmicStackRenderElement, MoveResult as StackMoveResult},
             window::CosmicWindowRenderElement,
-            CosmicMapped, CosmicMappedRenderElement, CosmicWindow,
+            ConfigureReason, CosmicMapped, CosmicMappedRenderElement, CosmicWindow,
         },
         focus::{target::KeyboardFocusTarget, FocusDirection},
         grabs::ResizeEdge,
-        CosmicSurface, Direction, FocusResult, MoveResult, ResizeDirection, ResizeMode,
+        Corner, CosmicSurface, Direction, FocusResult, MoveResult, ResizeDirection, ResizeMode,
     },
     state::State,
     utils::prelude::*,
@@ -68,6 +68,37 @@ impl FloatingLayout {
         self.map_internal(mapped, position, None)
     }
 
+    /// Maps a transient window (like a dialog) centered over the geometry of its parent.
+    pub fn map_transient(&mut self, mapped: CosmicMapped, parent_geometry: Rectangle<i32, Local>) {
+        let size = mapped.geometry().size;
+        let position = parent_geometry.loc
+            + Point::from((
+                (parent_geometry.size.w - size.w) / 2,
+                (parent_geometry.size.h - size.h) / 2,
+            ));
+        self.map_internal(mapped, Some(position), None)
+    }
+
+    /// Maps `mapped` centered in the non-exclusive zone, ignoring its previous position.
+    pub fn map_centered(&mut self, mapped: CosmicMapped) {
+        let output = self.space.outputs().next().unwrap().clone();
+        let geometry = layer_map_for_output(&output)
+            .non_exclusive_zone()
+            .as_local();
+        let size = mapped
+            .last_geometry
+            .lock()
+            .unwrap()
+            .map(|geo| geo.size)
+            .unwrap_or_else(|| mapped.geometry().size.as_local());
+        let position = geometry.loc
+            + Point::from((
+                (geometry.size.w - size.w) / 2,
+                (geometry.size.h - size.h) / 2,
+            ));
+        self.map_internal(mapped, Some(position), None)
+    }
+
     pub fn map_maximized(&mut self, mapped: CosmicMapped) {
         let output = self.space.outputs().next().unwrap().clone();
         let layers = layer_map_for_output(&output);
@@ -77,7 +108,7 @@ impl FloatingLayout {
         mapped.set_tiled(true);
         mapped.set_maximized(true);
         mapped.set_geometry(geometry.to_global(&output));
-        mapped.configure();
+        mapped.expect_ack(mapped.configure(), ConfigureReason::Maximize);
 
         self.space
             .map_element(mapped, geometry.loc.as_logical(), true);
@@ -154,6 +185,35 @@ impl FloatingLayout {
         self.space.map_element(mapped, position.as_logical(), false);
     }
 
+    /// Moves the transient windows of `parent` by `delta`, so dialogs follow their parent.
+    pub fn move_children(&mut self, parent: &CosmicMapped, delta: Point<i32, Local>) {
+        let output = self.space.outputs().next().unwrap().clone();
+        let mut parents = vec![parent.clone()];
+        let mut moved = vec![parent.clone()];
+        while let Some(parent) = parents.pop() {
+            for child in self
+                .space
+                .elements()
+                .filter(|elem| {
+                    !moved.contains(elem)
+                        && elem.active_window().is_child_of(&parent.active_window())
+                })
+                .cloned()
+                .collect::<Vec<_>>()
+                .into_iter()
+            {
+                let location = self.space.element_location(&child).unwrap() + delta.as_logical();
+                let mut geometry = self.space.element_geometry(&child).unwrap().as_local();
+                geometry.loc += delta;
+                child.set_geometry(geometry.to_global(&output));
+                child.configure();
+                self.space.map_element(child.clone(), location, false);
+                moved.push(child.clone());
+                parents.push(child);
+            }
+        }
+    }
+
     pub fn unmap(&mut self, window: &CosmicMapped) -> bool {
         if !window.is_maximized(true) || !window.is_fullscreen(true) {
             if let Some(location) = self.space.element_location(window) {
@@ -291,11 +351,88 @@ impl FloatingLayout {
             geo.as_local()
                 .to_global(self.space.outputs().next().unwrap()),
         );
-        mapped.configure();
+        mapped.expect_ack(mapped.configure(), ConfigureReason::Resize);
 
         true
     }
 
+    /// Snap a window to one half of the output.
+    ///
+    /// Snapping to the same side again cycles through 1/2, 1/3 and 2/3 of the output.
+    pub fn tile_half(&mut self, mapped: &CosmicMapped, side: Direction) -> bool {
+        let Some(current_geo) = self.element_geometry(mapped) else {
+            return false;
+        };
+        let output = self.space.outputs().next().unwrap().clone();
+        let zone = layer_map_for_output(&output)
+            .non_exclusive_zone()
+            .as_local();
+
+        let horizontal = matches!(side, Direction::Left | Direction::Right);
+        let (full, current) = if horizontal {
+            (zone.size.w, current_geo.size.w)
+        } else {
+            (zone.size.h, current_geo.size.h)
+        };
+        let snapped = Self::half_geometry(zone, side, current);
+        let length = if current_geo == snapped && current == full / 2 {
+            full / 3
+        } else if current_geo == snapped && current == full / 3 {
+            full * 2 / 3
+        } else {
+            full / 2
+        };
+
+        let geo = Self::half_geometry(zone, side, length);
+        self.map_internal(mapped.clone(), Some(geo.loc), Some(geo.size.as_logical()));
+        true
+    }
+
+    fn half_geometry(
+        zone: Rectangle<i32, Local>,
+        side: Direction,
+        length: i32,
+    ) -> Rectangle<i32, Local> {
+        match side {
+            Direction::Left => Rectangle::from_loc_and_size(zone.loc, (length, zone.size.h)),
+            Direction::Right => Rectangle::from_loc_and_size(
+                (zone.loc.x + zone.size.w - length, zone.loc.y),
+                (length, zone.size.h),
+            ),
+            Direction::Up => Rectangle::from_loc_and_size(zone.loc, (zone.size.w, length)),
+            Direction::Down => Rectangle::from_loc_and_size(
+                (zone.loc.x, zone.loc.y + zone.size.h - length),
+                (zone.size.w, length),
+            ),
+        }
+    }
+
+    /// Snap a window to one quarter of the output.
+    pub fn tile_quarter(&mut self, mapped: &CosmicMapped, corner: Corner) -> bool {
+        if self.element_geometry(mapped).is_none() {
+            return false;
+        }
+        let output = self.space.outputs().next().unwrap().clone();
+        let zone = layer_map_for_output(&output)
+            .non_exclusive_zone()
+            .as_local();
+
+        let size = Size::<i32, Local>::from((zone.size.w / 2, zone.size.h / 2));
+        let loc = match corner {
+            Corner::TopLeft => zone.loc,
+            Corner::TopRight => (zone.loc.x + zone.size.w - size.w, zone.loc.y).into(),
+            Corner::BottomLeft => (zone.loc.x, zone.loc.y + zone.size.h - size.h).into(),
+            Corner::BottomRight => (
+                zone.loc.x + zone.size.w - size.w,
+                zone.loc.y + zone.size.h - size.h,
+            )
+                .into(),
+        };
+
+        self.map_internal(mapped.clone(), Some(loc), Some(size.as_logical()));
+        true
+    }
+
     pub fn next_focus<'a>(
         &mut self,
         direction: FocusDirection,
@@ -435,7 +572,7 @@ impl FloatingLayout {
     }
 
     pub fn refresh(&mut self) {
-        #[cfg(feature = "debug")]
+        #[cfg(feature = "puffin")]
         puffin::profile_function!();
 
         self.space.refresh();
@@ -485,7 +622,7 @@ impl FloatingLayout {
         CosmicWindowRenderElement<R>: RenderElement<R>,
         CosmicStackRenderElement<R>: RenderElement<R>,
     {
-        #[cfg(feature = "debug")]
+        #[cfg(feature = "puffin")]
         puffin::profile_function!();
 
         let output = self.space.outputs().next().unwrap();
@@ -496,12 +633,25 @@ impl FloatingLayout {
 
         self.space.elements().rev().for_each(|elem| {
             let render_location = self.space.element_location(elem).unwrap() - elem.geometry().loc;
-            let (w_elements, p_elements) = elem.split_render_elements(
+            let progress = elem.open_progress();
+            let (mut w_elements, p_elements) = elem.split_render_elements(
                 renderer,
                 render_location.to_physical_precise_round(output_scale),
                 output_scale.into(),
-                alpha,
+                alpha * progress.unwrap_or(1.0) as f32,
             );
+            if let Some(progress) = progress {
+                let geometry = self.space.element_geometry(elem).unwrap();
+                let center = Point::<i32, Logical>::from((
+                    geometry.loc.x + geometry.size.w / 2,
+                    geometry.loc.y + geometry.size.h / 2,
+                ))
+                .to_physical_precise_round(output_scale);
+                w_elements = w_elements
+                    .into_iter()
+                    .map(|elem| elem.opening(center, progress))
+                    .collect();
+            }
 
             if focused == Some(elem) && !elem.is_maximized(false) {
                 let mut indicator_geometry = Rectangle::from_loc_and_size(
@@ -1,5 +1,6 @@
 // SPDX-License-Identifier: GPL-3.0-only
 
+use cosmic_comp_config::workspace::TilingOrientation;
 use regex::RegexSet;
 use smithay::{
     wayland::{compositor::with_states, shell::xdg::XdgToplevelSurfaceData},
@@ -7,6 +8,7 @@ use smithay::{
 };
 
 use super::CosmicSurface;
+use crate::wayland::protocols::xdg_foreign::foreign_parent;
 
 pub mod floating;
 pub mod tiling;
@@ -17,6 +19,15 @@ pub enum Orientation {
     Vertical,
 }
 
+impl From<TilingOrientation> for Orientation {
+    fn from(orientation: TilingOrientation) -> Self {
+        match orientation {
+            TilingOrientation::Horizontal => Orientation::Horizontal,
+            TilingOrientation::Vertical => Orientation::Vertical,
+        }
+    }
+}
+
 impl std::ops::Not for Orientation {
     type Output = Self;
     fn not(self) -> Self::Output {
@@ -110,7 +121,8 @@ pub fn should_be_floating(window: &CosmicSurface) -> bool {
                     .lock()
                     .unwrap();
                 attrs.parent.is_some()
-            }) {
+            }) || foreign_parent(window.toplevel().wl_surface()).is_some()
+            {
                 return true;
             }
         }
@@ -18,7 +18,9 @@ use std::{
 
 #[derive(Debug, Clone)]
 pub struct TilingBlocker {
-    pub necessary_acks: Vec<(CosmicSurface, Serial)>,
+    /// Surfaces without a serial had their configure held back by throttling,
+    /// they are waited on until it is sent and acked.
+    pub necessary_acks: Vec<(CosmicSurface, Option<Serial>)>,
     ready: Arc<AtomicBool>,
     signaled: Arc<AtomicBool>,
     start: Instant,
@@ -36,7 +38,7 @@ impl Blocker for TilingBlocker {
 }
 
 impl TilingBlocker {
-    pub fn new(configures: impl IntoIterator<Item = (CosmicSurface, Serial)>) -> Self {
+    pub fn new(configures: impl IntoIterator<Item = (CosmicSurface, Option<Serial>)>) -> Self {
         TilingBlocker {
             necessary_acks: configures.into_iter().collect(),
             ready: Arc::new(AtomicBool::new(false)),
@@ -47,10 +49,13 @@ impl TilingBlocker {
 
     pub fn is_ready(&self) -> bool {
         Instant::now().duration_since(self.start) >= Duration::from_millis(300)
-            || self
-                .necessary_acks
-                .iter()
-                .all(|(surf, serial)| !surf.alive() || surf.serial_acked(serial))
+            || self.necessary_acks.iter().all(|(surf, serial)| {
+                !surf.alive()
+                    || match serial {
+                        Some(serial) => surf.serial_acked(serial),
+                        None => !surf.has_pending_configure(),
+                    }
+            })
     }
 
     pub fn is_signaled(&self) -> bool {
@@ -4,6 +4,7 @@ use crate::{
     backend::render::{element::AsGlowRenderer, BackdropShader, IndicatorShader, Key, Usage},
     shell::{
         element::{
+            open_scale,
             resize_indicator::ResizeIndicator,
             stack::{
                 CosmicStackRenderElement, MoveResult as StackMoveResult,
@@ -120,9 +121,27 @@ pub struct TilingLayout {
     placeholder_id: Id,
     swapping_stack_surface_id: Id,
     last_overview_hover: Option<(Option<Instant>, TargetZone)>,
+    options: TilingOptions,
     pub theme: cosmic::Theme,
 }
 
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct TilingOptions {
+    /// Drop the gaps, if there is only one window
+    pub smart_gaps: bool,
+    /// Hide the focus indicator, if there is only one window
+    pub smart_borders: bool,
+    /// Orientation of new splits, instead of picking it from the aspect ratio
+    pub orientation: Option<Orientation>,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Gaps {
+    outer: i32,
+    inner: i32,
+    smart: bool,
+}
+
 #[derive(Debug, Clone, PartialEq)]
 pub enum PillIndicator {
     Outer(Direction),
@@ -259,6 +278,25 @@ impl Data {
         }
     }
 
+    fn equalize(&mut self) {
+        if let Data::Group {
+            sizes,
+            last_geometry,
+            orientation,
+            ..
+        } = self
+        {
+            let length = match orientation {
+                Orientation::Horizontal => last_geometry.size.h,
+                Orientation::Vertical => last_geometry.size.w,
+            };
+            let count = sizes.len() as i32;
+            sizes.iter_mut().for_each(|size| *size = length / count);
+            // fix rounding issues
+            *sizes.last_mut().unwrap() += length % count;
+        }
+    }
+
     fn geometry(&self) -> &Rectangle<i32, Local> {
         match self {
             Data::Group { last_geometry, .. } => last_geometry,
@@ -318,7 +356,7 @@ enum FocusedNodeData {
 }
 
 impl TilingLayout {
-    pub fn new(theme: cosmic::Theme, output: &Output) -> TilingLayout {
+    pub fn new(theme: cosmic::Theme, options: TilingOptions, output: &Output) -> TilingLayout {
         TilingLayout {
             queue: TreeQueue {
                 trees: {
@@ -333,6 +371,7 @@ impl TilingLayout {
             placeholder_id: Id::new(),
             swapping_stack_surface_id: Id::new(),
             last_overview_hover: None,
+            options,
             theme,
         }
     }
@@ -369,7 +408,10 @@ impl TilingLayout {
         direction: Option<Direction>,
     ) {
         window.output_enter(&self.output, window.bbox());
-        window.set_bounds(self.output.geometry().size.as_logical());
+        {
+            let layer_map = layer_map_for_output(&self.output);
+            window.set_bounds(layer_map.non_exclusive_zone().size);
+        }
         self.map_internal(window, focus_stack, direction);
     }
 
@@ -382,7 +424,14 @@ impl TilingLayout {
         let gaps = self.gaps();
 
         let mut tree = self.queue.trees.back().unwrap().0.copy_clone();
-        TilingLayout::map_to_tree(&mut tree, window, &self.output, focus_stack, direction);
+        TilingLayout::map_to_tree(
+            &mut tree,
+            window,
+            &self.output,
+            focus_stack,
+            direction,
+            self.options.orientation,
+        );
         let blocker = TilingLayout::update_positions(&self.output, &mut tree, gaps);
         self.queue.push_tree(tree, ANIMATION_DURATION, blocker);
     }
@@ -393,6 +442,7 @@ impl TilingLayout {
         output: &Output,
         focus_stack: Option<impl Iterator<Item = &'a CosmicMapped> + 'a>,
         direction: Option<Direction>,
+        orientation: Option<Orientation>,
     ) {
         let window = window.into();
         let new_window = Node::new(Data::Mapped {
@@ -435,28 +485,28 @@ impl TilingLayout {
                     return;
                 }
 
-                let orientation = {
+                let orientation = orientation.unwrap_or_else(|| {
                     let window_size = tree.get(node_id).unwrap().data().geometry().size;
                     if window_size.w > window_size.h {
                         Orientation::Vertical
                     } else {
                         Orientation::Horizontal
                     }
-                };
+                });
                 let new_id = tree.insert(new_window, InsertBehavior::AsRoot).unwrap();
                 TilingLayout::new_group(&mut tree, &node_id, &new_id, orientation).unwrap();
                 new_id
             } else {
                 // nothing? then we add to the root
                 if let Some(root_id) = tree.root_node_id().cloned() {
-                    let orientation = {
+                    let orientation = orientation.unwrap_or_else(|| {
                         let output_size = output.geometry().size;
                         if output_size.w > output_size.h {
                             Orientation::Vertical
                         } else {
                             Orientation::Horizontal
                         }
-                    };
+                    });
                     let new_id = tree.insert(new_window, InsertBehavior::AsRoot).unwrap();
                     TilingLayout::new_group(&mut tree, &root_id, &new_id, orientation).unwrap();
                     new_id
@@ -1902,6 +1952,94 @@ impl TilingLayout {
         }
     }
 
+    /// Splits the container of the focused window evenly, or every container if `workspace` is set
+    pub fn balance(&mut self, seat: &Seat<State>, workspace: bool) {
+        let gaps = self.gaps();
+
+        let mut tree = self.queue.trees.back().unwrap().0.copy_clone();
+        let container = if workspace {
+            tree.root_node_id().cloned()
+        } else {
+            let Some(target) = seat.get_keyboard().unwrap().current_focus() else {
+                return;
+            };
+            TilingLayout::currently_focused_node(&tree, target).and_then(|(node_id, data)| {
+                match data {
+                    FocusedNodeData::Group(_, _) => Some(node_id),
+                    FocusedNodeData::Window(_) => tree.get(&node_id).unwrap().parent().cloned(),
+                }
+            })
+        };
+        let Some(container) = container else {
+            return;
+        };
+
+        for node_id in tree
+            .traverse_pre_order_ids(&container)
+            .unwrap()
+            .collect::<Vec<_>>()
+        {
+            tree.get_mut(&node_id).unwrap().data_mut().equalize();
+        }
+
+        let blocker = TilingLayout::update_positions(&self.output, &mut tree, gaps);
+        self.queue.push_tree(tree, ANIMATION_DURATION, blocker);
+    }
+
+    /// Re-inserts all windows into a fresh tree, as if they were opened one after another
+    /// with the previous one focused. Windows keep their current order.
+    pub fn reset_layout(&mut self) {
+        let gaps = self.gaps();
+
+        let old_tree = &self.queue.trees.back().unwrap().0;
+        let windows = old_tree
+            .root_node_id()
+            .map(|root_id| {
+                old_tree
+                    .traverse_pre_order(root_id)
+                    .unwrap()
+                    .filter_map(|node| match node.data() {
+                        Data::Mapped { mapped, .. } => Some(mapped.clone()),
+                        _ => None,
+                    })
+                    .collect::<Vec<_>>()
+            })
+            .unwrap_or_default();
+
+        let mut tree = Tree::new();
+        // geometry the last inserted window will end up with, to pick the orientation of the next split
+        let mut geo = layer_map_for_output(&self.output)
+            .non_exclusive_zone()
+            .as_local();
+        let mut previous = None;
+        for mapped in windows {
+            let new_window = Node::new(Data::Mapped {
+                mapped: mapped.clone(),
+                last_geometry: Rectangle::from_loc_and_size((0, 0), (100, 100)),
+            });
+            let new_id = tree.insert(new_window, InsertBehavior::AsRoot).unwrap();
+            if let Some(previous_id) = previous.as_ref() {
+                let orientation = self.options.orientation.unwrap_or_else(|| {
+                    if geo.size.w > geo.size.h {
+                        Orientation::Vertical
+                    } else {
+                        Orientation::Horizontal
+                    }
+                });
+                TilingLayout::new_group(&mut tree, previous_id, &new_id, orientation).unwrap();
+                match orientation {
+                    Orientation::Horizontal => geo.size.h /= 2,
+                    Orientation::Vertical => geo.size.w /= 2,
+                }
+            }
+            *mapped.tiling_node_id.lock().unwrap() = Some(new_id.clone());
+            previous = Some(new_id);
+        }
+
+        let blocker = TilingLayout::update_positions(&self.output, &mut tree, gaps);
+        self.queue.push_tree(tree, ANIMATION_DURATION, blocker);
+    }
+
     pub fn toggle_stacking<'a>(&mut self, seat: &Seat<State>, mut focus_stack: FocusStackMut) {
         let gaps = self.gaps();
 
@@ -1968,6 +2106,7 @@ impl TilingLayout {
                                 &self.output,
                                 Some(focus_stack.iter()),
                                 None,
+                                self.options.orientation,
                             )
                         }
 
@@ -2041,7 +2180,7 @@ impl TilingLayout {
     }
 
     pub fn refresh(&mut self) {
-        #[cfg(feature = "debug")]
+        #[cfg(feature = "puffin")]
         puffin::profile_function!();
 
         let dead_windows = self
@@ -2420,6 +2559,7 @@ impl TilingLayout {
                     &self.output,
                     Option::<std::iter::Empty<_>>::None,
                     None,
+                    self.options.orientation,
                 );
                 window
             }
@@ -2614,15 +2754,24 @@ impl TilingLayout {
     fn update_positions(
         output: &Output,
         tree: &mut Tree<Data>,
-        gaps: (i32, i32),
+        gaps: Gaps,
     ) -> Option<TilingBlocker> {
-        #[cfg(feature = "debug")]
+        #[cfg(feature = "puffin")]
         puffin::profile_function!();
 
         if let Some(root_id) = tree.root_node_id() {
             let mut configures = Vec::new();
 
-            let (outer, inner) = gaps;
+            let Gaps {
+                outer,
+                inner,
+                smart,
+            } = gaps;
+            let (outer, inner) = if smart && TilingLayout::has_single_window(tree) {
+                (0, 0)
+            } else {
+                (outer, inner)
+            };
             let mut geo = layer_map_for_output(&output)
                 .non_exclusive_zone()
                 .as_local();
@@ -2742,8 +2891,15 @@ impl TilingLayout {
                                 mapped.set_tiled(true);
                                 let internal_geometry = geo.to_global(&output);
                                 mapped.set_geometry(internal_geometry);
-                                if let Some(serial) = mapped.configure() {
-                                    configures.push((mapped.active_window(), serial));
+                                match mapped.configure_throttled() {
+                                    Some(serial) => {
+                                        configures.push((mapped.active_window(), Some(serial)))
+                                    }
+                                    // held back, the blocker has to wait for it as well
+                                    None if mapped.active_window().has_pending_configure() => {
+                                        configures.push((mapped.active_window(), None))
+                                    }
+                                    None => {}
                                 }
                             }
                         }
@@ -3493,7 +3649,7 @@ impl TilingLayout {
         CosmicWindowRenderElement<R>: RenderElement<R>,
         CosmicStackRenderElement<R>: RenderElement<R>,
     {
-        #[cfg(feature = "debug")]
+        #[cfg(feature = "puffin")]
         puffin::profile_function!();
 
         let output_scale = self.output.current_scale().fractional_scale();
@@ -3511,6 +3667,12 @@ impl TilingLayout {
             .animation_start
             .is_some()
             .then(|| &self.queue.trees.front().unwrap().0);
+        let indicator_thickness =
+            if self.options.smart_borders && TilingLayout::has_single_window(target_tree) {
+                0
+            } else {
+                indicator_thickness
+            };
 
         let percentage = if let Some(animation_start) = self.queue.animation_start {
             let percentage = Instant::now().duration_since(animation_start).as_millis() as f32
@@ -3635,9 +3797,23 @@ impl TilingLayout {
         Ok((window_elements, popup_elements))
     }
 
-    fn gaps(&self) -> (i32, i32) {
+    fn gaps(&self) -> Gaps {
         let g = self.theme.cosmic().gaps;
-        (g.0 as i32, g.1 as i32)
+        Gaps {
+            outer: g.0 as i32,
+            inner: g.1 as i32,
+            smart: self.options.smart_gaps,
+        }
+    }
+
+    fn has_single_window(tree: &Tree<Data>) -> bool {
+        tree.root_node_id().is_some_and(|root_id| {
+            tree.traverse_pre_order(root_id)
+                .unwrap()
+                .filter(|node| !node.data().is_group())
+                .count()
+                == 1
+        })
     }
 }
 
@@ -4723,6 +4899,26 @@ where
                 crop_rect = &geo;
             }
 
+            // freshly mapped windows fade and scale in around the center of their tile
+            let open_progress = match data {
+                Data::Mapped { mapped, .. } => mapped.open_progress(),
+                _ => None,
+            };
+            let (geo, scale, alpha) = match open_progress {
+                Some(progress) => {
+                    let open_scale = open_scale(progress);
+                    let size = geo.size.to_f64().upscale(open_scale).to_i32_round();
+                    let loc = geo.loc
+                        + Point::from(((geo.size.w - size.w) / 2, (geo.size.h - size.h) / 2));
+                    (
+                        Rectangle::from_loc_and_size(loc, size),
+                        scale * open_scale,
+                        alpha * progress as f32,
+                    )
+                }
+                None => (geo, scale, alpha),
+            };
+
             if swap_desc.as_ref().map(|desc| &desc.node) == Some(&node_id)
                 || focused.as_ref() == Some(&node_id)
             {
@@ -1,13 +1,18 @@
-use calloop::LoopHandle;
+use calloop::{
+    timer::{TimeoutAction, Timer},
+    LoopHandle,
+};
 use indexmap::IndexMap;
 use std::{
+    cell::RefCell,
     collections::HashMap,
     sync::atomic::{AtomicBool, Ordering},
     time::{Duration, Instant},
 };
+use tracing::warn;
 use wayland_backend::server::ClientId;
 
-use cosmic_comp_config::workspace::{WorkspaceAmount, WorkspaceMode};
+use cosmic_comp_config::workspace::{DefaultLayout, WorkspaceAmount, WorkspaceMode};
 use cosmic_protocols::workspace::v1::server::zcosmic_workspace_handle_v1::State as WState;
 use keyframe::{ease, functions::EaseInOutCubic};
 use smithay::{
@@ -19,7 +24,7 @@ use smithay::{
         Seat,
     },
     output::Output,
-    reexports::wayland_server::{protocol::wl_surface::WlSurface, Client, DisplayHandle},
+    reexports::wayland_server::{protocol::wl_surface::WlSurface, Client, DisplayHandle, Resource},
     utils::{Point, Rectangle, Serial, SERIAL_COUNTER},
     wayland::{
         compositor::with_states,
@@ -35,7 +40,8 @@ use smithay::{
 };
 
 use crate::{
-    config::{Config, KeyModifiers, KeyPattern},
+    backend::render::snapshot::snapshot,
+    config::{window_rule, Config, KeyModifiers, KeyPattern, OutputConfig, WindowPosition},
     state::client_has_security_context,
     utils::prelude::*,
     wayland::protocols::{
@@ -61,12 +67,34 @@ use self::{
         swap_indicator::{swap_indicator, SwapIndicator},
         CosmicWindow,
     },
-    focus::target::KeyboardFocusTarget,
+    focus::{fullscreen_keeps_focus, target::KeyboardFocusTarget, FocusDirection},
     grabs::ResizeEdge,
-    layout::{floating::ResizeState, tiling::NodeDesc},
+    layout::{
+        floating::ResizeState,
+        tiling::{NodeDesc, TilingOptions},
+    },
 };
 
 const ANIMATION_DURATION: Duration = Duration::from_millis(200);
+/// How often configures, that the layout waits for, are checked for timeouts
+const CONFIGURE_CHECK_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Checks for clients not acking configures periodically, see `Workspace::check_configure_timeouts`.
+pub fn init_configure_timeouts(handle: &LoopHandle<'static, State>) {
+    if let Err(err) = handle.insert_source(
+        Timer::from_duration(CONFIGURE_CHECK_INTERVAL),
+        |_, _, state| {
+            for output in state.common.shell.workspaces.check_configure_timeouts() {
+                state
+                    .backend
+                    .schedule_render(&state.common.event_loop_handle, &output, None);
+            }
+            TimeoutAction::ToDuration(CONFIGURE_CHECK_INTERVAL)
+        },
+    ) {
+        warn!(?err, "Failed to setup configure timeouts");
+    }
+}
 
 #[derive(Debug, Clone)]
 pub enum Trigger {
@@ -154,6 +182,15 @@ pub struct Shell {
     pub pending_windows: Vec<(CosmicSurface, Seat<State>, Option<Output>)>,
     pub pending_layers: Vec<(LayerSurface, Output, Seat<State>)>,
     pub override_redirect_windows: Vec<X11Surface>,
+    /// Windows hidden by `Action::MoveToScratchpad`, the most recent one last
+    pub scratchpad: Vec<CosmicMapped>,
+    /// Scratchpad window currently shown by `Action::ShowScratchpad`
+    scratchpad_shown: Option<CosmicMapped>,
+    /// Windows hidden by a window launched from them, see `WindowRule::swallow`
+    swallowed: Vec<(CosmicSurface, CosmicMapped)>,
+    primary_output: Option<Output>,
+    /// Whether the primary output changed since `take_primary_output_changed`
+    primary_output_changed: bool,
 
     // wayland_state
     pub layer_shell_state: WlrLayerShellState,
@@ -180,23 +217,61 @@ pub struct Shell {
 #[derive(Debug)]
 pub struct WorkspaceSet {
     previously_active: Option<(usize, Instant)>,
+    /// Workspace active before the current one, kept after the switch animation ended
+    last_active: Option<WorkspaceHandle>,
     active: usize,
     group: WorkspaceGroupHandle,
     idx: usize,
-    tiling_enabled: bool,
+    defaults: WorkspaceDefaults,
     output: Output,
     theme: cosmic::Theme,
     pub(crate) workspaces: Vec<Workspace>,
 }
 
+/// Layout new workspaces start with
+#[derive(Debug, Clone)]
+struct WorkspaceDefaults {
+    tiling_enabled: bool,
+    tiling_options: TilingOptions,
+    /// See `WorkspaceConfig::default_layouts`
+    layouts: Vec<DefaultLayout>,
+}
+
+impl WorkspaceDefaults {
+    /// Tiling state and options of a new workspace at `idx` on `output`
+    fn layout(&self, output: &Output, idx: usize) -> (bool, TilingOptions) {
+        let mut tiling = self.tiling_enabled;
+        let mut options = self.tiling_options;
+        for layout in self.layouts.iter().filter(|layout| {
+            layout
+                .output
+                .as_ref()
+                .map_or(true, |name| *name == output.name())
+                && layout
+                    .workspace
+                    .map_or(true, |number| number as usize == idx + 1)
+        }) {
+            if let Some(enabled) = layout.tiling {
+                tiling = enabled;
+            }
+            if let Some(orientation) = layout.orientation {
+                options.orientation = Some(orientation.into());
+            }
+        }
+        (tiling, options)
+    }
+}
+
 fn create_workspace(
     state: &mut WorkspaceUpdateGuard<'_, State>,
     output: &Output,
     group_handle: &WorkspaceGroupHandle,
     active: bool,
-    tiling: bool,
+    idx: usize,
+    defaults: &WorkspaceDefaults,
     theme: cosmic::Theme,
 ) -> Workspace {
+    let (tiling, tiling_options) = defaults.layout(output, idx);
     let workspace_handle = state.create_workspace(&group_handle).unwrap();
     if active {
         state.add_workspace_state(&workspace_handle, WState::Active);
@@ -205,7 +280,13 @@ fn create_workspace(
         &workspace_handle,
         [WorkspaceCapabilities::Activate].into_iter(),
     );
-    Workspace::new(workspace_handle, output.clone(), tiling, theme.clone())
+    Workspace::new(
+        workspace_handle,
+        output.clone(),
+        tiling,
+        tiling_options,
+        theme.clone(),
+    )
 }
 
 impl WorkspaceSet {
@@ -214,7 +295,7 @@ impl WorkspaceSet {
         output: &Output,
         amount: WorkspaceAmount,
         idx: usize,
-        tiling_enabled: bool,
+        defaults: WorkspaceDefaults,
         theme: cosmic::Theme,
     ) -> WorkspaceSet {
         let group_handle = state.create_workspace_group();
@@ -225,7 +306,8 @@ impl WorkspaceSet {
                     output,
                     &group_handle,
                     true,
-                    tiling_enabled,
+                    0,
+                    &defaults,
                     theme.clone(),
                 );
                 workspace_set_idx(state, 1, idx, &workspace.handle);
@@ -242,7 +324,8 @@ impl WorkspaceSet {
                         output,
                         &group_handle,
                         i == 0,
-                        tiling_enabled,
+                        i as usize,
+                        &defaults,
                         theme.clone(),
                     );
                     workspace_set_idx(state, i + 1, idx, &workspace.handle);
@@ -257,10 +340,11 @@ impl WorkspaceSet {
 
         WorkspaceSet {
             previously_active: None,
+            last_active: None,
             active: 0,
             group: group_handle,
             idx,
-            tiling_enabled,
+            defaults,
             theme,
             workspaces,
             output: output.clone(),
@@ -270,6 +354,7 @@ impl WorkspaceSet {
     fn activate(
         &mut self,
         idx: usize,
+        switch_duration: Duration,
         state: &mut WorkspaceUpdateGuard<'_, State>,
     ) -> Result<bool, InvalidWorkspaceIndex> {
         if idx >= self.workspaces.len() {
@@ -280,7 +365,9 @@ impl WorkspaceSet {
             let old_active = self.active;
             state.remove_workspace_state(&self.workspaces[old_active].handle, WState::Active);
             state.add_workspace_state(&self.workspaces[idx].handle, WState::Active);
-            self.previously_active = Some((old_active, Instant::now()));
+            self.previously_active =
+                (!switch_duration.is_zero()).then(|| (old_active, Instant::now()));
+            self.last_active = Some(self.workspaces[old_active].handle.clone());
             self.active = idx;
             Ok(true)
         } else {
@@ -299,9 +386,13 @@ impl WorkspaceSet {
         self.output = new_output.clone();
     }
 
-    fn refresh<'a>(&mut self) {
+    fn refresh<'a>(&mut self, switch_duration: Duration) {
+        for workspace in &self.workspaces {
+            workspace.flush_throttled_configures();
+        }
+
         if let Some((_, start)) = self.previously_active {
-            if Instant::now().duration_since(start).as_millis() >= ANIMATION_DURATION.as_millis() {
+            if Instant::now().duration_since(start) >= switch_duration {
                 self.previously_active = None;
             }
         } else {
@@ -315,7 +406,8 @@ impl WorkspaceSet {
             &self.output,
             &self.group,
             false,
-            self.tiling_enabled,
+            self.workspaces.len(),
+            &self.defaults,
             self.theme.clone(),
         );
         workspace_set_idx(
@@ -410,7 +502,8 @@ impl WorkspaceSet {
                     &self.output,
                     &self.group,
                     false,
-                    self.tiling_enabled,
+                    self.workspaces.len(),
+                    &self.defaults,
                     self.theme.clone(),
                 );
                 workspace_set_idx(
@@ -428,6 +521,57 @@ impl WorkspaceSet {
         }
     }
 
+    /// Exchanges the windows of the workspaces at `a` and `b`, the workspaces themselves stay in place
+    fn swap(
+        &mut self,
+        a: usize,
+        b: usize,
+        toplevel_info: &mut ToplevelInfoState<State, CosmicSurface>,
+    ) -> Result<(), InvalidWorkspaceIndex> {
+        if a >= self.workspaces.len() || b >= self.workspaces.len() {
+            return Err(InvalidWorkspaceIndex);
+        }
+        if a == b {
+            return Ok(());
+        }
+
+        let (first, second) = self.workspaces.split_at_mut(a.max(b));
+        let (a, b) = (&mut first[a.min(b)], &mut second[0]);
+
+        for (workspace, new_handle) in [(&*a, &b.handle), (&*b, &a.handle)] {
+            for mapped in workspace.mapped() {
+                for (toplevel, _) in mapped.windows() {
+                    toplevel_info.toplevel_leave_workspace(&toplevel, &workspace.handle);
+                    toplevel_info.toplevel_enter_workspace(&toplevel, new_handle);
+                }
+            }
+        }
+
+        std::mem::swap(a, b);
+        // the protocol object and workspace captures belong to the position
+        std::mem::swap(&mut a.handle, &mut b.handle);
+        std::mem::swap(&mut a.pending_buffers, &mut b.pending_buffers);
+        std::mem::swap(&mut a.screencopy_sessions, &mut b.screencopy_sessions);
+        std::mem::swap(&mut a.output_stack, &mut b.output_stack);
+
+        let (handle_a, handle_b) = (a.handle, b.handle);
+        for workspace in [a, b] {
+            if let Some((_, handle)) = workspace
+                .fullscreen
+                .as_mut()
+                .and_then(|f| f.previously.as_mut())
+            {
+                if *handle == handle_a {
+                    *handle = handle_b;
+                } else if *handle == handle_b {
+                    *handle = handle_a;
+                }
+            }
+        }
+
+        Ok(())
+    }
+
     fn update_idx(&mut self, state: &mut WorkspaceUpdateGuard<'_, State>, idx: usize) {
         self.idx = idx;
         for (i, workspace) in self.workspaces.iter().enumerate() {
@@ -436,7 +580,7 @@ impl WorkspaceSet {
     }
 
     fn update_tiling_status(&mut self, seat: &Seat<State>, tiling_enabled: bool) {
-        self.tiling_enabled = tiling_enabled;
+        self.defaults.tiling_enabled = tiling_enabled;
         for workspace in &mut self.workspaces {
             if workspace.tiling_enabled != tiling_enabled {
                 workspace.toggle_tiling(seat);
@@ -451,7 +595,9 @@ pub struct Workspaces {
     backup_set: Option<WorkspaceSet>,
     amount: WorkspaceAmount,
     mode: WorkspaceMode,
-    tiling_enabled: bool,
+    defaults: WorkspaceDefaults,
+    switch_duration: Duration,
+    configure_timeout: Duration,
     theme: cosmic::Theme,
 }
 
@@ -462,7 +608,19 @@ impl Workspaces {
             backup_set: None,
             amount: config.workspace.workspace_amount,
             mode: config.workspace.workspace_mode,
-            tiling_enabled: config.static_conf.tiling_enabled,
+            defaults: WorkspaceDefaults {
+                tiling_enabled: config.static_conf.tiling_enabled,
+                tiling_options: TilingOptions {
+                    smart_gaps: config.static_conf.smart_gaps,
+                    smart_borders: config.static_conf.smart_borders,
+                    orientation: None,
+                },
+                layouts: config.workspace.default_layouts.clone(),
+            },
+            switch_duration: config
+                .static_conf
+                .animation_duration(|d| d.workspace_switch),
+            configure_timeout: Duration::from_millis(config.static_conf.configure_timeout),
             theme,
         }
     }
@@ -490,7 +648,7 @@ impl Workspaces {
                     &output,
                     self.amount,
                     self.sets.len(),
-                    self.tiling_enabled,
+                    self.defaults.clone(),
                     self.theme.clone(),
                 )
             });
@@ -590,6 +748,16 @@ impl Workspaces {
 
         self.mode = config.workspace.workspace_mode;
         self.amount = config.workspace.workspace_amount;
+        self.switch_duration = config
+            .static_conf
+            .animation_duration(|d| d.workspace_switch);
+        self.configure_timeout = Duration::from_millis(config.static_conf.configure_timeout);
+
+        // only applies to workspaces created from now on
+        self.defaults.layouts = config.workspace.default_layouts.clone();
+        for set in self.sets.values_mut().chain(self.backup_set.as_mut()) {
+            set.defaults.layouts = config.workspace.default_layouts.clone();
+        }
 
         if self.sets.len() <= 1 {
             return;
@@ -634,7 +802,8 @@ impl Workspaces {
                                     output,
                                     &set.group,
                                     false,
-                                    config.static_conf.tiling_enabled,
+                                    j,
+                                    &set.defaults,
                                     self.theme.clone(),
                                 ),
                             );
@@ -753,10 +922,23 @@ impl Workspaces {
         }
 
         for set in self.sets.values_mut() {
-            set.refresh()
+            set.refresh(self.switch_duration)
         }
     }
 
+    /// Gives up on configures, that weren't acked within `configure_timeout`,
+    /// returns the outputs of the workspaces, that changed as a result.
+    pub fn check_configure_timeouts(&mut self) -> Vec<Output> {
+        let timeout = self.configure_timeout;
+        self.spaces_mut()
+            .filter_map(|workspace| {
+                workspace
+                    .check_configure_timeouts(timeout)
+                    .then(|| workspace.output().clone())
+            })
+            .collect()
+    }
+
     pub fn get(&self, num: usize, output: &Output) -> Option<&Workspace> {
         self.sets
             .get(output)
@@ -792,6 +974,12 @@ impl Workspaces {
         (set.previously_active.map(|(idx, _)| idx), set.active)
     }
 
+    pub fn last_active_num(&self, output: &Output) -> Option<usize> {
+        let set = self.sets.get(output)?;
+        let handle = set.last_active.as_ref()?;
+        set.workspaces.iter().position(|w| &w.handle == handle)
+    }
+
     pub fn len(&self, output: &Output) -> usize {
         let set = self.sets.get(output).unwrap();
         set.workspaces.len()
@@ -894,6 +1082,11 @@ impl Shell {
             resize_mode: ResizeMode::None,
             resize_state: None,
             resize_indicator: None,
+            scratchpad: Vec::new(),
+            scratchpad_shown: None,
+            swallowed: Vec::new(),
+            primary_output: None,
+            primary_output_changed: false,
         }
     }
 
@@ -903,18 +1096,69 @@ impl Shell {
             &mut self.workspace_state.update(),
             &mut self.toplevel_info_state,
         );
+
+        let primary = output
+            .user_data()
+            .get::<RefCell<OutputConfig>>()
+            .is_some_and(|config| config.borrow().primary);
+        if primary && self.primary_output.as_ref() != Some(output) {
+            self.set_primary_output(output);
+        }
     }
 
     pub fn remove_output(&mut self, output: &Output, seats: impl Iterator<Item = Seat<State>>) {
+        let seats = seats.collect::<Vec<_>>();
+        let moved_seats = seats
+            .iter()
+            .filter(|seat| &seat.active_output() == output)
+            .cloned()
+            .collect::<Vec<_>>();
+
         self.workspaces.remove_output(
             output,
-            seats,
+            seats.into_iter(),
             &mut self.workspace_state.update(),
             &mut self.toplevel_info_state,
         );
+
+        // the next output takes over
+        if self.primary_output.as_ref() == Some(output) {
+            self.primary_output = None;
+            if let Some(next) = self.outputs().next().cloned() {
+                self.set_primary_output(&next);
+            } else {
+                self.primary_output_changed = true;
+            }
+        }
+        if let Some(primary) = self.primary_output.as_ref() {
+            for seat in moved_seats {
+                seat.set_active_output(primary);
+            }
+        }
+
         self.refresh(); // cleans up excess of workspaces and empty workspaces
     }
 
+    /// Output explicitly marked as primary, if any.
+    pub fn primary_output(&self) -> Option<&Output> {
+        self.primary_output.as_ref()
+    }
+
+    pub fn set_primary_output(&mut self, output: &Output) {
+        for other in self.outputs() {
+            if let Some(config) = other.user_data().get::<RefCell<OutputConfig>>() {
+                config.borrow_mut().primary = other == output;
+            }
+        }
+        self.primary_output = Some(output.clone());
+        self.primary_output_changed = true;
+    }
+
+    /// Whether the primary output changed since the last call.
+    pub fn take_primary_output_changed(&mut self) -> bool {
+        std::mem::take(&mut self.primary_output_changed)
+    }
+
     pub fn update_config(&mut self, config: &Config) {
         let mut workspace_state = self.workspace_state.update();
         let toplevel_info_state = &mut self.toplevel_info_state;
@@ -936,7 +1180,11 @@ impl Shell {
                     ) {
                         set.workspaces[set.active].tiling_layer.cleanup_drag();
                     }
-                    set.activate(idx, &mut self.workspace_state.update())?;
+                    set.activate(
+                        idx,
+                        self.workspaces.switch_duration,
+                        &mut self.workspace_state.update(),
+                    )?;
 
                     let output_geo = output.geometry();
                     Ok(Some(
@@ -949,13 +1197,113 @@ impl Shell {
             }
             WorkspaceMode::Global => {
                 for set in self.workspaces.sets.values_mut() {
-                    set.activate(idx, &mut self.workspace_state.update())?;
+                    set.activate(
+                        idx,
+                        self.workspaces.switch_duration,
+                        &mut self.workspace_state.update(),
+                    )?;
                 }
                 Ok(None)
             }
         }
     }
 
+    /// Describes outputs, workspaces, windows and seat focus in a human readable form for bug reports
+    pub fn dump_state<'a>(&self, seats: impl Iterator<Item = &'a Seat<State>>) -> String {
+        use std::fmt::Write;
+
+        let seats = seats.collect::<Vec<_>>();
+        let mut out = String::new();
+        for (output, set) in self.workspaces.iter() {
+            let _ = writeln!(
+                out,
+                "output {} geometry={:?} scale={}",
+                output.name(),
+                output.geometry(),
+                output.current_scale().fractional_scale()
+            );
+            for (idx, workspace) in set.workspaces.iter().enumerate() {
+                let _ = writeln!(
+                    out,
+                    "  workspace {}{} tiling={}",
+                    idx + 1,
+                    if idx == set.active { " (active)" } else { "" },
+                    workspace.tiling_enabled
+                );
+                for mapped in workspace.mapped() {
+                    let layer = if workspace.is_floating(mapped) {
+                        "floating"
+                    } else {
+                        "tiling"
+                    };
+                    let geometry = workspace.element_geometry(mapped);
+                    for (window, _) in mapped.windows() {
+                        let _ = writeln!(
+                            out,
+                            "    {} app_id={:?} title={:?} geometry={:?}{}{}{}",
+                            layer,
+                            window.app_id(),
+                            window.title(),
+                            geometry,
+                            if window == mapped.active_window() {
+                                ""
+                            } else {
+                                " (stacked)"
+                            },
+                            if window.is_maximized(false) {
+                                " maximized"
+                            } else {
+                                ""
+                            },
+                            if workspace.get_fullscreen() == Some(&window) {
+                                " fullscreen"
+                            } else {
+                                ""
+                            },
+                        );
+                    }
+                }
+                for seat in &seats {
+                    let stack = workspace
+                        .focus_stack
+                        .get(seat)
+                        .iter()
+                        .map(|mapped| mapped.active_window().app_id())
+                        .collect::<Vec<_>>();
+                    if !stack.is_empty() {
+                        let _ = writeln!(out, "    focus stack of {}: {:?}", seat.name(), stack);
+                    }
+                }
+            }
+        }
+        for seat in seats {
+            let _ = writeln!(
+                out,
+                "seat {} active_output={} keyboard_focus={:?} pointer_focus={:?}",
+                seat.name(),
+                seat.active_output().name(),
+                seat.get_keyboard().and_then(|k| k.current_focus()),
+                seat.get_pointer().and_then(|p| p.current_focus()),
+            );
+        }
+        out
+    }
+
+    /// Exchanges the windows of the workspaces `a` and `b` on `output`
+    pub fn swap_workspaces(
+        &mut self,
+        output: &Output,
+        a: usize,
+        b: usize,
+    ) -> Result<(), InvalidWorkspaceIndex> {
+        let set = self
+            .workspaces
+            .sets
+            .get_mut(output)
+            .ok_or(InvalidWorkspaceIndex)?;
+        set.swap(a, b, &mut self.toplevel_info_state)
+    }
+
     pub fn active_space(&self, output: &Output) -> &Workspace {
         self.workspaces.active(output).1
     }
@@ -1080,6 +1428,149 @@ impl Shell {
         )
     }
 
+    /// The output next to `current` in `direction`, that overlaps with it on the other axis.
+    ///
+    /// With `wrap` the farthest output on the opposite side is returned,
+    /// if there is no output in `direction`.
+    pub fn output_in_direction(
+        &self,
+        current: &Output,
+        direction: FocusDirection,
+        wrap: bool,
+    ) -> Option<Output> {
+        let geo = current.geometry();
+        // distance of `other` from `geo` in `direction`, negative if it is on the opposite side
+        let distance = |other: Rectangle<i32, Global>| -> Option<i32> {
+            let (overlaps, distance) = match direction {
+                FocusDirection::Left | FocusDirection::Right => (
+                    other.loc.y < geo.loc.y + geo.size.h && geo.loc.y < other.loc.y + other.size.h,
+                    other.loc.x - geo.loc.x,
+                ),
+                FocusDirection::Up | FocusDirection::Down => (
+                    other.loc.x < geo.loc.x + geo.size.w && geo.loc.x < other.loc.x + other.size.w,
+                    other.loc.y - geo.loc.y,
+                ),
+                _ => return None,
+            };
+            let distance = match direction {
+                FocusDirection::Left | FocusDirection::Up => -distance,
+                _ => distance,
+            };
+            (overlaps && distance != 0).then_some(distance)
+        };
+
+        let candidates = self
+            .outputs()
+            .filter(|o| *o != current)
+            .filter_map(|o| distance(o.geometry()).map(|d| (o, d)))
+            .collect::<Vec<_>>();
+        candidates
+            .iter()
+            .filter(|(_, d)| *d > 0)
+            .min_by_key(|(_, d)| *d)
+            .or_else(|| {
+                candidates
+                    .iter()
+                    .filter(|(_, d)| wrap && *d < 0)
+                    .min_by_key(|(_, d)| *d)
+            })
+            .map(|(o, _)| (*o).clone())
+    }
+
+    /// Current geometries of all floating windows, grouped by app_id
+    pub fn floating_window_positions(&self) -> HashMap<String, Vec<WindowPosition>> {
+        let mut positions = HashMap::<String, Vec<WindowPosition>>::new();
+        for workspace in self.workspaces.spaces() {
+            for mapped in workspace.floating_layer.mapped() {
+                if mapped.is_maximized(false) || mapped.is_fullscreen(false) {
+                    continue;
+                }
+                if let Some(geometry) = workspace.floating_layer.element_geometry(mapped) {
+                    positions
+                        .entry(mapped.active_window().app_id())
+                        .or_default()
+                        .push(WindowPosition {
+                            output: workspace.output.name(),
+                            position: (geometry.loc.x, geometry.loc.y),
+                            size: (geometry.size.w, geometry.size.h),
+                        });
+                }
+            }
+        }
+        positions
+    }
+
+    pub fn restore_floating_window_positions(
+        &mut self,
+        positions: &HashMap<String, Vec<WindowPosition>>,
+    ) {
+        let mut restored = HashMap::<String, usize>::new();
+        let mut windows = Vec::new();
+        for workspace in self.workspaces.spaces() {
+            for mapped in workspace.floating_layer.mapped() {
+                if mapped.is_maximized(false) || mapped.is_fullscreen(false) {
+                    continue;
+                }
+                let app_id = mapped.active_window().app_id();
+                let idx = restored.entry(app_id.clone()).or_default();
+                if let Some(position) = positions.get(&app_id).and_then(|p| p.get(*idx)) {
+                    *idx += 1;
+                    windows.push((workspace.handle.clone(), mapped.clone(), position));
+                }
+            }
+        }
+
+        for (handle, mapped, position) in windows {
+            let geometry = position.geometry();
+            let Some(workspace) = self.workspaces.spaces_mut().find(|w| w.handle == handle) else {
+                continue;
+            };
+            if position.output == workspace.output.name() {
+                workspace.floating_layer.map_internal(
+                    mapped,
+                    Some(geometry.loc),
+                    Some(geometry.size.as_logical()),
+                );
+                continue;
+            }
+
+            // the window was moved away, when its output was disconnected. Now that the output
+            // is back (e.g. after docking again), move the window back onto it.
+            let Some(output) = self
+                .workspaces
+                .sets
+                .keys()
+                .find(|output| output.name() == position.output)
+                .cloned()
+            else {
+                continue;
+            };
+            let from_output = workspace.output.clone();
+            if workspace.unmap(&mapped).is_none() {
+                continue;
+            }
+            for (toplevel, _) in mapped.windows() {
+                self.toplevel_info_state
+                    .toplevel_leave_workspace(&toplevel, &handle);
+                self.toplevel_info_state
+                    .toplevel_leave_output(&toplevel, &from_output);
+            }
+
+            let workspace = self.workspaces.active_mut(&output);
+            workspace.floating_layer.map_internal(
+                mapped.clone(),
+                Some(geometry.loc),
+                Some(geometry.size.as_logical()),
+            );
+            for (toplevel, _) in mapped.windows() {
+                self.toplevel_info_state
+                    .toplevel_enter_output(&toplevel, &output);
+                self.toplevel_info_state
+                    .toplevel_enter_workspace(&toplevel, &workspace.handle);
+            }
+        }
+    }
+
     pub fn global_space(&self) -> Rectangle<i32, Global> {
         self.outputs()
             .fold(
@@ -1195,7 +1686,7 @@ impl Shell {
     }
 
     pub fn refresh(&mut self) {
-        #[cfg(feature = "debug")]
+        #[cfg(feature = "puffin")]
         puffin::profile_function!();
 
         self.popups.cleanup();
@@ -1210,6 +1701,8 @@ impl Shell {
             map.cleanup();
         }
 
+        self.scratchpad.retain(|mapped| mapped.alive());
+        self.swallowed.retain(|(_, parent)| parent.alive());
         self.override_redirect_windows.retain(|or| or.alive());
         self.override_redirect_windows
             .iter()
@@ -1217,6 +1710,30 @@ impl Shell {
 
         self.toplevel_info_state
             .refresh(Some(&self.workspace_state));
+
+        self.update_suspended();
+    }
+
+    /// Suspends the windows of workspaces, that aren't visible on any output, and resumes the others.
+    fn update_suspended(&self) {
+        for set in self.workspaces.sets.values() {
+            for (idx, workspace) in set.workspaces.iter().enumerate() {
+                let visible = idx == set.active
+                    || set
+                        .previously_active
+                        .is_some_and(|(previous, _)| previous == idx);
+                for mapped in workspace.mapped() {
+                    for (window, _) in mapped.windows() {
+                        window.set_suspended(!visible);
+                    }
+                }
+            }
+        }
+    }
+
+    /// Whether the overview is shown or animating.
+    pub fn is_overview_active(&self) -> bool {
+        self.overview_mode.alpha().is_some()
     }
 
     pub fn remap_unfullscreened_window(
@@ -1269,7 +1786,19 @@ impl Shell {
         let (window, seat, output) = state.common.shell.pending_windows.remove(pos);
 
         let should_be_fullscreen = output.is_some();
-        let output = output.unwrap_or_else(|| seat.active_output());
+        // dialogs open next to their parent, everything else on the primary output, if any
+        let output = output
+            .or_else(|| {
+                state
+                    .common
+                    .shell
+                    .workspaces
+                    .spaces()
+                    .find(|workspace| workspace.transient_parent(&window).is_some())
+                    .map(|workspace| workspace.output().clone())
+            })
+            .or_else(|| state.common.shell.primary_output().cloned())
+            .unwrap_or_else(|| seat.active_output());
 
         let workspace = state.common.shell.workspaces.active_mut(&output);
         if let Some((mapped, layer, previous_workspace)) = workspace.remove_fullscreen() {
@@ -1290,6 +1819,28 @@ impl Shell {
             );
         };
 
+        let saved_position =
+            state
+                .common
+                .config
+                .window_position(&state.common.shell, &window.app_id(), &output);
+        if let Some(opacity) = window_rule(
+            &state.common.config.static_conf.window_rules,
+            &window,
+            |rule| rule.opacity,
+        ) {
+            window.set_opacity(opacity);
+        }
+        let swallowed = (!should_be_fullscreen)
+            .then(|| swallow_parent(state, &window, &seat, &output))
+            .flatten();
+        // rules already saw this title, don't apply them again
+        window.take_first_title();
+        let open_duration = state
+            .common
+            .config
+            .static_conf
+            .animation_duration(|d| d.window_open);
         let workspace = state.common.shell.workspaces.active_mut(&output);
         state.common.shell.toplevel_info_state.new_toplevel(&window);
         state
@@ -1312,8 +1863,35 @@ impl Shell {
         {
             mapped.set_debug(state.common.egui.active);
         }
-        if layout::should_be_floating(&window) || !workspace.tiling_enabled {
-            workspace.floating_layer.map(mapped.clone(), None);
+        if let Some(parent) = swallowed.as_ref() {
+            if workspace.is_floating(parent) {
+                let geometry = workspace.element_geometry(parent).unwrap();
+                workspace.unmap(parent);
+                workspace.floating_layer.map_internal(
+                    mapped.clone(),
+                    Some(geometry.loc),
+                    Some(geometry.size.as_logical()),
+                );
+            } else {
+                workspace.tiling_layer.replace_window(parent, &mapped);
+            }
+        } else if layout::should_be_floating(&window) || !workspace.tiling_enabled {
+            let parent_geometry = workspace
+                .transient_parent(&window)
+                .and_then(|parent| workspace.element_geometry(&parent));
+            if let Some(geometry) = saved_position {
+                workspace.floating_layer.map_internal(
+                    mapped.clone(),
+                    Some(geometry.loc),
+                    Some(geometry.size.as_logical()),
+                );
+            } else if let Some(parent_geometry) = parent_geometry {
+                workspace
+                    .floating_layer
+                    .map_transient(mapped.clone(), parent_geometry);
+            } else {
+                workspace.floating_layer.map(mapped.clone(), None);
+            }
         } else {
             for mapped in workspace
                 .mapped()
@@ -1329,17 +1907,159 @@ impl Shell {
                 .tiling_layer
                 .map(mapped.clone(), Some(focus_stack.iter()), None);
         }
+        mapped.animate_open(open_duration);
 
         if should_be_fullscreen {
             workspace.fullscreen_request(&mapped.active_window(), None);
         }
 
-        Shell::set_focus(state, Some(&KeyboardFocusTarget::from(mapped)), &seat, None);
+        Shell::set_focus(
+            state,
+            Some(&KeyboardFocusTarget::from(mapped)),
+            &seat,
+            None,
+            true,
+        );
 
         let active_space = state.common.shell.active_space(&output);
         for mapped in active_space.mapped() {
             state.common.shell.update_reactive_popups(mapped);
         }
+
+        if let Some(parent) = swallowed {
+            let shell = &mut state.common.shell;
+            let handle = shell.active_space(&output).handle;
+            for (toplevel, _) in parent.windows() {
+                shell
+                    .toplevel_info_state
+                    .toplevel_leave_workspace(&toplevel, &handle);
+                shell
+                    .toplevel_info_state
+                    .toplevel_leave_output(&toplevel, &output);
+            }
+            shell.swallowed.push((window, parent));
+        }
+    }
+
+    /// Puts the window swallowed by the closing toplevel `surface` back in its place,
+    /// wherever that window is by now.
+    pub fn unswallow(state: &mut State, surface: &WlSurface) {
+        let shell = &mut state.common.shell;
+        let Some(idx) = shell
+            .swallowed
+            .iter()
+            .position(|(child, _)| child.wl_surface().as_ref() == Some(surface))
+        else {
+            return;
+        };
+        let (_, parent) = shell.swallowed.remove(idx);
+        if !parent.alive() {
+            return;
+        }
+
+        let child = shell.element_for_wl_surface(surface).cloned();
+        let in_place = child
+            .clone()
+            .filter(|child| !child.is_stack())
+            .and_then(|child| {
+                let workspace = shell.space_for_mut(&child)?;
+                if workspace.is_floating(&child) {
+                    let geometry = workspace.element_geometry(&child)?;
+                    workspace.unmap(&child);
+                    workspace.floating_layer.map_internal(
+                        parent.clone(),
+                        Some(geometry.loc),
+                        Some(geometry.size.as_logical()),
+                    );
+                } else if workspace.is_tiled(&child) {
+                    workspace.tiling_layer.replace_window(&child, &parent);
+                } else {
+                    return None;
+                }
+                Some((workspace.handle, workspace.output().clone()))
+            });
+        let (handle, output) = in_place.unwrap_or_else(|| {
+            // e.g. the window is stacked, fullscreen or in the scratchpad
+            let output = state.common.last_active_seat().active_output();
+            let workspace = state.common.shell.active_space_mut(&output);
+            if workspace.tiling_enabled {
+                workspace.tiling_layer.map(
+                    parent.clone(),
+                    Option::<std::iter::Empty<_>>::None,
+                    None,
+                );
+            } else {
+                workspace.floating_layer.map(parent.clone(), None);
+            }
+            (workspace.handle, output)
+        });
+
+        let shell = &mut state.common.shell;
+        for (toplevel, _) in parent.windows() {
+            shell
+                .toplevel_info_state
+                .toplevel_enter_output(&toplevel, &output);
+            shell
+                .toplevel_info_state
+                .toplevel_enter_workspace(&toplevel, &handle);
+        }
+
+        // hand the focus back to the window the closing one was launched from
+        let Some(child) = child else {
+            return;
+        };
+        let focused = KeyboardFocusTarget::from(child);
+        for seat in state.common.seats().cloned().collect::<Vec<_>>() {
+            if seat.get_keyboard().unwrap().current_focus().as_ref() == Some(&focused) {
+                Common::set_focus(
+                    state,
+                    Some(&KeyboardFocusTarget::from(parent.clone())),
+                    &seat,
+                    None,
+                );
+            }
+        }
+    }
+
+    /// Keeps a snapshot of a window that is about to be destroyed around, to fade it out.
+    pub fn animate_close(state: &mut State, surface: &WlSurface) {
+        let duration = state
+            .common
+            .config
+            .static_conf
+            .animation_duration(|d| d.window_close);
+        if duration.is_zero() {
+            return;
+        }
+        let Some(mapped) = state.common.shell.element_for_wl_surface(surface).cloned() else {
+            return;
+        };
+        // closing a tab leaves the stack in place
+        if mapped.windows().count() > 1 {
+            return;
+        }
+        let Some(workspace) = state.common.shell.space_for(&mapped) else {
+            return;
+        };
+        if workspace.handle != state.common.shell.active_space(&workspace.output).handle
+            || workspace
+                .fullscreen
+                .as_ref()
+                .is_some_and(|f| f.surface == mapped.active_window())
+        {
+            return;
+        }
+        let Some(geometry) = workspace.element_geometry(&mapped) else {
+            return;
+        };
+        let handle = workspace.handle.clone();
+        let scale = workspace.output.current_scale().integer_scale();
+
+        if let Some(buffer) = snapshot(state, &mapped, scale) {
+            if let Some(workspace) = state.common.shell.space_for_handle_mut(&handle) {
+                workspace.animate_close(buffer, geometry, duration);
+            }
+        }
     }
 
     pub fn map_override_redirect(state: &mut State, window: X11Surface) {
@@ -1366,11 +2086,11 @@ impl Shell {
             .unwrap();
         let (layer_surface, output, seat) = state.common.shell.pending_layers.remove(pos);
 
-        let wants_focus = {
+        let interactivity = {
             with_states(layer_surface.wl_surface(), |states| {
                 let state = states.cached_state.current::<LayerSurfaceCachedState>();
                 matches!(state.layer, Layer::Top | Layer::Overlay)
-                    && state.keyboard_interactivity != KeyboardInteractivity::None
+                    .then_some(state.keyboard_interactivity)
             })
         };
 
@@ -1382,9 +2102,109 @@ impl Shell {
             workspace.tiling_layer.recalculate();
         }
 
-        if wants_focus {
-            Shell::set_focus(state, Some(&layer_surface.into()), &seat, None)
+        match interactivity {
+            // grab every seat on that output, the previous focus gets restored by `refresh_focus`
+            Some(KeyboardInteractivity::Exclusive) => {
+                let seats = state
+                    .common
+                    .seats()
+                    .filter(|s| *s == &seat || s.active_output() == output)
+                    .cloned()
+                    .collect::<Vec<_>>();
+                for seat in seats {
+                    Shell::set_focus(
+                        state,
+                        Some(&layer_surface.clone().into()),
+                        &seat,
+                        None,
+                        true,
+                    )
+                }
+            }
+            Some(KeyboardInteractivity::OnDemand) => {
+                if !fullscreen_keeps_focus(state, &layer_surface) {
+                    Shell::set_focus(state, Some(&layer_surface.into()), &seat, None, true)
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// Hides the focused window of `seat` in the scratchpad.
+    pub fn move_to_scratchpad(state: &mut State, seat: &Seat<State>) {
+        let output = seat.active_output();
+        let workspace = state.common.shell.active_space_mut(&output);
+        let Some(mapped) = workspace.focus_stack.get(seat).last().cloned() else {
+            return;
+        };
+        if workspace.unmap(&mapped).is_none() {
+            return;
+        }
+        let handle = workspace.handle;
+
+        let shell = &mut state.common.shell;
+        for (toplevel, _) in mapped.windows() {
+            shell
+                .toplevel_info_state
+                .toplevel_leave_workspace(&toplevel, &handle);
+            shell
+                .toplevel_info_state
+                .toplevel_leave_output(&toplevel, &output);
+        }
+        if shell.scratchpad_shown.as_ref() == Some(&mapped) {
+            shell.scratchpad_shown = None;
         }
+        shell.scratchpad.retain(|m| m != &mapped);
+        shell.scratchpad.push(mapped);
+    }
+
+    /// Shows the most recent scratchpad window floating on the active output of `seat`,
+    /// or hides the currently shown one again.
+    ///
+    /// The active workspace doesn't change, the window is mapped on top of it.
+    pub fn toggle_scratchpad(state: &mut State, seat: &Seat<State>) {
+        let output = seat.active_output();
+        let shell = &mut state.common.shell;
+
+        if let Some(mapped) = shell.scratchpad_shown.take() {
+            let on_active = shell.active_space(&output).mapped().any(|m| m == &mapped);
+            if let Some(workspace) = shell.space_for_mut(&mapped) {
+                let handle = workspace.handle;
+                let workspace_output = workspace.output().clone();
+                workspace.unmap(&mapped);
+                for (toplevel, _) in mapped.windows() {
+                    shell
+                        .toplevel_info_state
+                        .toplevel_leave_workspace(&toplevel, &handle);
+                    shell
+                        .toplevel_info_state
+                        .toplevel_leave_output(&toplevel, &workspace_output);
+                }
+                shell.scratchpad.push(mapped);
+                // shown somewhere else, move it over instead of just hiding it
+                if on_active {
+                    return;
+                }
+            }
+        }
+
+        let Some(mapped) = shell.scratchpad.pop() else {
+            return;
+        };
+        let workspace = shell.active_space_mut(&output);
+        workspace.floating_layer.map_centered(mapped.clone());
+        let handle = workspace.handle;
+        for (toplevel, _) in mapped.windows() {
+            shell
+                .toplevel_info_state
+                .toplevel_enter_output(&toplevel, &output);
+            shell
+                .toplevel_info_state
+                .toplevel_enter_workspace(&toplevel, &handle);
+        }
+        shell.scratchpad_shown = Some(mapped.clone());
+
+        Common::set_focus(state, Some(&KeyboardFocusTarget::from(mapped)), seat, None);
     }
 
     pub fn move_current_window(
@@ -1422,8 +2242,16 @@ impl Shell {
         let Some(window_state) = from_workspace.unmap(&mapped) else {
             return Ok(None);
         };
+        // transient windows (like dialogs) move along with their parent
+        let children = from_workspace.transient_children(&mapped);
+        for child in &children {
+            from_workspace.unmap(child);
+        }
 
-        for (toplevel, _) in mapped.windows() {
+        for (toplevel, _) in std::iter::once(&mapped)
+            .chain(children.iter())
+            .flat_map(|mapped| mapped.windows())
+        {
             state
                 .common
                 .shell
@@ -1463,6 +2291,13 @@ impl Shell {
                 .tiling_layer
                 .map(mapped.clone(), Some(focus_stack.iter()), direction);
         }
+        let parent_geometry = to_workspace.element_geometry(&mapped);
+        for child in children.iter().cloned() {
+            match parent_geometry {
+                Some(geometry) => to_workspace.floating_layer.map_transient(child, geometry),
+                None => to_workspace.floating_layer.map(child, None),
+            }
+        }
         let focus_target = if let Some(f) = window_state.was_fullscreen {
             if to_workspace.fullscreen.is_some() {
                 if let Some((mapped, layer, previous_workspace)) = to_workspace.remove_fullscreen()
@@ -1498,10 +2333,17 @@ impl Shell {
                 .map(|f| KeyboardFocusTarget::from(f.surface.clone()))
                 .unwrap_or_else(|| KeyboardFocusTarget::from(mapped.clone()))
         } else {
+            if window_state.was_maximized {
+                // maximize again against the usable area of the new output
+                to_workspace.maximize_request(&mapped.active_window());
+            }
             KeyboardFocusTarget::from(mapped.clone())
         };
 
-        for (toplevel, _) in mapped.windows() {
+        for (toplevel, _) in std::iter::once(&mapped)
+            .chain(children.iter())
+            .flat_map(|mapped| mapped.windows())
+        {
             if from_output != to_output {
                 state
                     .common
@@ -1724,3 +2566,73 @@ pub fn check_grab_preconditions(
 
     Some(start_data)
 }
+
+/// Focused window of `seat` on `output`, that `window` takes the place of, because it was
+/// launched from it, see `WindowRule::swallow`
+fn swallow_parent(
+    state: &State,
+    window: &CosmicSurface,
+    seat: &Seat<State>,
+    output: &Output,
+) -> Option<CosmicMapped> {
+    let workspace = state.common.shell.active_space(output);
+    if workspace.get_fullscreen().is_some() || workspace.transient_parent(window).is_some() {
+        return None;
+    }
+    let Some(KeyboardFocusTarget::Element(parent)) = seat.get_keyboard()?.current_focus() else {
+        return None;
+    };
+    if parent.is_stack()
+        || parent.maximized_state.lock().unwrap().is_some()
+        || !workspace.mapped().any(|m| m == &parent)
+    {
+        return None;
+    }
+
+    let parent_window = parent.active_window();
+    if !window_rule(
+        &state.common.config.static_conf.window_rules,
+        &parent_window,
+        |rule| rule.swallow,
+    )
+    .unwrap_or(false)
+    {
+        return None;
+    }
+
+    let pid = |surface: &CosmicSurface| {
+        surface
+            .wl_surface()?
+            .client()?
+            .get_credentials(&state.common.display_handle)
+            .ok()
+            .map(|credentials| credentials.pid)
+    };
+    is_descendant(pid(window)?, pid(&parent_window)?).then_some(parent)
+}
+
+/// Checks if process `pid` was (indirectly) started by process `ancestor`
+fn is_descendant(mut pid: i32, ancestor: i32) -> bool {
+    // bounded, in case the chain changes while walking it
+    for _ in 0..64 {
+        let Ok(stat) = std::fs::read_to_string(format!("/proc/{}/stat", pid)) else {
+            return false;
+        };
+        // the command name may contain spaces and parentheses, the parent pid follows the state
+        let Some(ppid) = stat
+            .rsplit_once(')')
+            .and_then(|(_, rest)| rest.split_whitespace().nth(1))
+            .and_then(|ppid| ppid.parse::<i32>().ok())
+        else {
+            return false;
+        };
+        if ppid == ancestor {
+            return true;
+        }
+        if ppid <= 1 {
+            return false;
+        }
+        pid = ppid;
+    }
+    false
+}
@@ -5,7 +5,10 @@ use crate::{
     },
     shell::{
         grabs::MoveGrab,
-        layout::{floating::FloatingLayout, tiling::TilingLayout},
+        layout::{
+            floating::FloatingLayout,
+            tiling::{TilingLayout, TilingOptions},
+        },
         OverviewMode, ANIMATION_DURATION,
     },
     state::State,
@@ -28,8 +31,11 @@ use keyframe::{ease, functions::EaseInOutCubic};
 use smithay::{
     backend::renderer::{
         element::{
-            surface::WaylandSurfaceRenderElement, texture::TextureRenderElement,
-            utils::RescaleRenderElement, AsRenderElements, Element, Id, RenderElement,
+            memory::{MemoryRenderBuffer, MemoryRenderBufferRenderElement},
+            surface::WaylandSurfaceRenderElement,
+            texture::TextureRenderElement,
+            utils::RescaleRenderElement,
+            AsRenderElements, Element, Id, Kind, RenderElement,
         },
         gles::{GlesError, GlesTexture},
         glow::{GlowFrame, GlowRenderer},
@@ -60,8 +66,8 @@ use wayland_backend::server::ClientId;
 use super::{
     element::{
         resize_indicator::ResizeIndicator, stack::CosmicStackRenderElement,
-        swap_indicator::SwapIndicator, window::CosmicWindowRenderElement, CosmicMapped,
-        MaximizedState,
+        surface::Responsiveness, swap_indicator::SwapIndicator, window::CosmicWindowRenderElement,
+        ConfigureReason, CosmicMapped, MaximizedState,
     },
     focus::{
         target::{KeyboardFocusTarget, PointerFocusTarget, WindowGroup},
@@ -73,6 +79,8 @@ use super::{
 };
 
 const FULLSCREEN_ANIMATION_DURATION: Duration = Duration::from_millis(200);
+/// Brightness of windows, whose client doesn't answer pings.
+const NOT_RESPONDING_BRIGHTNESS: f32 = 0.5;
 
 #[derive(Debug)]
 pub struct Workspace {
@@ -89,6 +97,16 @@ pub struct Workspace {
     pub output_stack: VecDeque<String>,
     pub(super) backdrop_id: Id,
     pub dirty: AtomicBool,
+    closing: Vec<ClosingWindow>,
+}
+
+/// Snapshot of a destroyed window, faded out where the window was.
+#[derive(Debug)]
+struct ClosingWindow {
+    buffer: MemoryRenderBuffer,
+    geometry: Rectangle<i32, Local>,
+    start: Instant,
+    duration: Duration,
 }
 
 #[derive(Debug, Clone)]
@@ -140,6 +158,7 @@ pub struct FocusStacks(HashMap<Seat<State>, IndexSet<CosmicMapped>>);
 pub struct ManagedState {
     pub layer: ManagedLayer,
     pub was_fullscreen: Option<FullscreenSurface>,
+    pub was_maximized: bool,
 }
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum ManagedLayer {
@@ -155,6 +174,14 @@ pub enum Direction {
     Down,
 }
 
+#[derive(Debug, serde::Deserialize, Clone, Copy, PartialEq, Eq)]
+pub enum Corner {
+    TopLeft,
+    TopRight,
+    BottomLeft,
+    BottomRight,
+}
+
 impl std::ops::Not for Direction {
     type Output = Self;
     fn not(self) -> Self::Output {
@@ -211,9 +238,10 @@ impl Workspace {
         handle: WorkspaceHandle,
         output: Output,
         tiling_enabled: bool,
+        tiling_options: TilingOptions,
         theme: cosmic::Theme,
     ) -> Workspace {
-        let tiling_layer = TilingLayout::new(theme, &output);
+        let tiling_layer = TilingLayout::new(theme, tiling_options, &output);
         let floating_layer = FloatingLayout::new(&output);
 
         Workspace {
@@ -229,11 +257,12 @@ impl Workspace {
             output_stack: VecDeque::new(),
             backdrop_id: Id::new(),
             dirty: AtomicBool::new(false),
+            closing: Vec::new(),
         }
     }
 
     pub fn refresh(&mut self) {
-        #[cfg(feature = "debug")]
+        #[cfg(feature = "puffin")]
         puffin::profile_function!();
 
         // TODO: `Option::take_if` once stabilitized
@@ -252,8 +281,66 @@ impl Workspace {
         }
     }
 
+    /// Sends throttled configures, that are due by now.
+    pub fn flush_throttled_configures(&self) {
+        for mapped in self.mapped() {
+            for (window, _) in mapped.windows() {
+                window.flush_throttled_configure();
+            }
+        }
+    }
+
+    /// Stops waiting for configures, that were not acked within `timeout`.
+    /// Returns true if the layout changed as a result.
+    pub fn check_configure_timeouts(&mut self, timeout: Duration) -> bool {
+        let mut changed = false;
+        for mapped in self.mapped().cloned().collect::<Vec<_>>() {
+            let Some(reason) = mapped.take_timed_out_configure(timeout) else {
+                continue;
+            };
+            warn!(
+                app_id = mapped.active_window().app_id(),
+                ?reason,
+                "Client did not acknowledge configure in time, using its current size."
+            );
+            match reason {
+                ConfigureReason::Maximize => {
+                    // center whatever size the client is using in the maximized area
+                    let area = layer_map_for_output(&self.output)
+                        .non_exclusive_zone()
+                        .as_local();
+                    let size = mapped.geometry().size;
+                    let location = area.loc
+                        + Point::from((
+                            (area.size.w - size.w).max(0) / 2,
+                            (area.size.h - size.h).max(0) / 2,
+                        ));
+                    if self.floating_layer.mapped().any(|m| m == &mapped) {
+                        self.floating_layer.space.map_element(
+                            mapped.clone(),
+                            location.as_logical(),
+                            false,
+                        );
+                    }
+                }
+                // fullscreen surfaces of the wrong size are already centered while rendering
+                ConfigureReason::Fullscreen => {}
+                ConfigureReason::Resize => {
+                    // don't keep adjusting the location for a size, that never arrives
+                    mapped.resize_state.lock().unwrap().take();
+                    mapped.set_resizing(false);
+                }
+            }
+            self.dirty.store(true, Ordering::SeqCst);
+            changed = true;
+        }
+        changed
+    }
+
     pub fn animations_going(&self) -> bool {
         self.tiling_layer.animations_going()
+            || self.mapped().any(|mapped| mapped.open_progress().is_some())
+            || !self.closing.is_empty()
             || self
                 .fullscreen
                 .as_ref()
@@ -303,10 +390,35 @@ impl Workspace {
             }
         }
 
+        let closing = self.closing.len();
+        self.closing
+            .retain(|closing| closing.start.elapsed() < closing.duration);
+        let opened = self.mapped().fold(false, |opened, mapped| {
+            mapped.update_open_animation() || opened
+        });
+        if opened || self.closing.len() != closing {
+            self.dirty.store(true, Ordering::SeqCst);
+        }
+
         clients.extend(self.tiling_layer.update_animation_state());
         clients
     }
 
+    /// Fades out `buffer` at `geometry`, standing in for a window that was just destroyed.
+    pub fn animate_close(
+        &mut self,
+        buffer: MemoryRenderBuffer,
+        geometry: Rectangle<i32, Local>,
+        duration: Duration,
+    ) {
+        self.closing.push(ClosingWindow {
+            buffer,
+            geometry,
+            start: Instant::now(),
+            duration,
+        });
+    }
+
     pub fn commit(&mut self, surface: &WlSurface) {
         if let Some(mapped) = self.element_for_wl_surface(surface) {
             mapped
@@ -347,7 +459,8 @@ impl Workspace {
             .unwrap_or(false)
             .then(|| self.fullscreen.take().unwrap());
 
-        if mapped.maximized_state.lock().unwrap().is_some() {
+        let was_maximized = mapped.maximized_state.lock().unwrap().is_some();
+        if was_maximized {
             // If surface is maximized then unmaximize it, so it is assigned to only one layer
             let _ = self.unmaximize_request(&mapped.active_window());
         }
@@ -366,11 +479,13 @@ impl Workspace {
             Some(ManagedState {
                 layer: ManagedLayer::Floating,
                 was_fullscreen,
+                was_maximized,
             })
         } else if was_tiling {
             Some(ManagedState {
                 layer: ManagedLayer::Tiling,
                 was_fullscreen,
+                was_maximized,
             })
         } else {
             None
@@ -417,6 +532,41 @@ impl Workspace {
     pub fn recalculate(&mut self) {
         self.tiling_layer.recalculate();
         self.floating_layer.refresh();
+        self.update_output_geometry();
+    }
+
+    /// Fits fullscreen and maximized windows to the current geometry of the output,
+    /// after its mode, scale, transform or usable area changed.
+    fn update_output_geometry(&mut self) {
+        if let Some(f) = self.fullscreen.as_ref().filter(|f| f.ended_at.is_none()) {
+            let geo = self.output.geometry();
+            if f.surface.geometry().as_global() != geo {
+                f.surface.set_geometry(geo);
+                let serial = f.surface.send_configure();
+                if let Some(mapped) = self.element_for_surface(&f.surface) {
+                    mapped.expect_ack(serial, ConfigureReason::Fullscreen);
+                }
+            }
+        }
+
+        let geometry = layer_map_for_output(&self.output)
+            .non_exclusive_zone()
+            .as_local();
+        let maximized = self
+            .floating_layer
+            .mapped()
+            .filter(|mapped| mapped.maximized_state.lock().unwrap().is_some())
+            .filter(|mapped| self.floating_layer.element_geometry(mapped) != Some(geometry))
+            .cloned()
+            .collect::<Vec<_>>();
+        for mapped in maximized {
+            self.floating_layer.map_maximized(mapped);
+        }
+
+        for mapped in self.mapped() {
+            mapped.set_bounds(geometry.size.as_logical());
+            mapped.configure();
+        }
     }
 
     pub fn maximize_request(&mut self, window: &CosmicSurface) {
@@ -504,7 +654,10 @@ impl Workspace {
             None
         };
         window.set_geometry(geo);
-        window.send_configure();
+        let serial = window.send_configure();
+        if let Some(mapped) = self.element_for_surface(window) {
+            mapped.expect_ack(serial, ConfigureReason::Fullscreen);
+        }
 
         self.fullscreen = Some(FullscreenSurface {
             surface: window.clone(),
@@ -589,6 +742,25 @@ impl Workspace {
         }
     }
 
+    /// Where the fullscreen window is shown, centered on the output, if it is smaller.
+    pub fn fullscreen_geometry(&self) -> Option<Rectangle<i32, Local>> {
+        let fullscreen = self.fullscreen.as_ref()?;
+        let bbox = fullscreen.surface.bbox().as_local();
+        let mut full_geo =
+            Rectangle::from_loc_and_size((0, 0), self.output.geometry().size.as_local());
+        if bbox != full_geo {
+            if bbox.size.w < full_geo.size.w {
+                full_geo.loc.x += (full_geo.size.w - bbox.size.w) / 2;
+                full_geo.size.w = bbox.size.w;
+            }
+            if bbox.size.h < full_geo.size.h {
+                full_geo.loc.y += (full_geo.size.h - bbox.size.h) / 2;
+                full_geo.size.h = bbox.size.h;
+            }
+        }
+        Some(full_geo)
+    }
+
     pub fn get_fullscreen(&self) -> Option<&CosmicSurface> {
         self.fullscreen
             .as_ref()
@@ -686,6 +858,7 @@ impl Workspace {
             start_data,
             mapped,
             seat,
+            self.handle,
             pos,
             initial_window_location,
             indicator_thickness,
@@ -739,6 +912,24 @@ impl Workspace {
         }
     }
 
+    pub fn tile_half(&mut self, mapped: &CosmicMapped, side: Direction) -> bool {
+        self.prepare_snap(mapped) && self.floating_layer.tile_half(mapped, side)
+    }
+
+    pub fn tile_quarter(&mut self, mapped: &CosmicMapped, corner: Corner) -> bool {
+        self.prepare_snap(mapped) && self.floating_layer.tile_quarter(mapped, corner)
+    }
+
+    fn prepare_snap(&mut self, mapped: &CosmicMapped) -> bool {
+        if self.is_fullscreen(mapped) {
+            return false;
+        }
+        if mapped.maximized_state.lock().unwrap().is_some() {
+            let _ = self.unmaximize_request(&mapped.active_window());
+        }
+        self.floating_layer.mapped().any(|m| m == mapped)
+    }
+
     pub fn mapped(&self) -> impl Iterator<Item = &CosmicMapped> {
         self.floating_layer
             .mapped()
@@ -749,6 +940,30 @@ impl Workspace {
         self.floating_layer.space.outputs()
     }
 
+    /// The mapped element `window` is a transient window (like a dialog) of, if any.
+    pub fn transient_parent(&self, window: &CosmicSurface) -> Option<CosmicMapped> {
+        self.mapped()
+            .find(|mapped| window.is_child_of(&mapped.active_window()))
+            .cloned()
+    }
+
+    /// Floating transient windows of `parent`, including the transient windows of those.
+    pub fn transient_children(&self, parent: &CosmicMapped) -> Vec<CosmicMapped> {
+        let mut children = Vec::new();
+        let mut parents = vec![parent.clone()];
+        while let Some(parent) = parents.pop() {
+            for child in self.floating_layer.mapped().filter(|elem| {
+                *elem != parent
+                    && !children.contains(*elem)
+                    && elem.active_window().is_child_of(&parent.active_window())
+            }) {
+                children.push(child.clone());
+                parents.push(child.clone());
+            }
+        }
+        children
+    }
+
     pub fn windows(&self) -> impl Iterator<Item = CosmicSurface> + '_ {
         self.floating_layer
             .windows()
@@ -848,6 +1063,7 @@ impl Workspace {
         overview: (OverviewMode, Option<(SwapIndicator, Option<&Tree<Data>>)>),
         resize_indicator: Option<(ResizeMode, ResizeIndicator)>,
         indicator_thickness: u8,
+        dim_unfocused: Option<f32>,
         theme: &CosmicTheme,
     ) -> Result<
         (
@@ -864,7 +1080,7 @@ impl Workspace {
         CosmicStackRenderElement<R>: RenderElement<R>,
         WorkspaceRenderElement<R>: RenderElement<R>,
     {
-        #[cfg(feature = "debug")]
+        #[cfg(feature = "puffin")]
         puffin::profile_function!();
 
         let mut window_elements = Vec::new();
@@ -876,6 +1092,22 @@ impl Workspace {
             layer_map.non_exclusive_zone().as_local()
         };
 
+        // windows focused by any seat on this output stay bright
+        let focused = self
+            .focus_stack
+            .0
+            .iter()
+            .filter(|(seat, _)| seat.active_output() == self.output)
+            .filter_map(|(_, stack)| stack.iter().rev().find(|w| w.alive()))
+            .collect::<Vec<_>>();
+        for mapped in self.mapped() {
+            if mapped.active_window().responsiveness() != Responsiveness::Responsive {
+                mapped.set_dimmed(Some(NOT_RESPONDING_BRIGHTNESS));
+            } else {
+                mapped.set_dimmed(dim_unfocused.filter(|_| !focused.contains(&mapped)));
+            }
+        }
+
         // OR windows above all
         popup_elements.extend(
             override_redirect_windows
@@ -918,20 +1150,11 @@ impl Workspace {
                 fullscreen.original_geometry.size.as_local(),
             );
 
-            let mut full_geo =
-                Rectangle::from_loc_and_size((0, 0), self.output.geometry().size.as_local());
-            if fullscreen.start_at.is_none() {
-                if bbox != full_geo {
-                    if bbox.size.w < full_geo.size.w {
-                        full_geo.loc.x += (full_geo.size.w - bbox.size.w) / 2;
-                        full_geo.size.w = bbox.size.w;
-                    }
-                    if bbox.size.h < full_geo.size.h {
-                        full_geo.loc.y += (full_geo.size.h - bbox.size.h) / 2;
-                        full_geo.size.h = bbox.size.h;
-                    }
-                }
-            }
+            let full_geo = if fullscreen.start_at.is_none() {
+                self.fullscreen_geometry().unwrap()
+            } else {
+                Rectangle::from_loc_and_size((0, 0), self.output.geometry().size.as_local())
+            };
 
             let (target_geo, alpha) = match (fullscreen.start_at, fullscreen.ended_at) {
                 (Some(started), _) => {
@@ -1019,6 +1242,40 @@ impl Workspace {
                 OverviewMode::None => 1.0,
             };
 
+            // closing windows above the remaining ones
+            for closing in &self.closing {
+                let progress = ease(
+                    EaseInOutCubic,
+                    0.0,
+                    1.0,
+                    closing.start.elapsed().as_secs_f64() / closing.duration.as_secs_f64(),
+                );
+                let geometry = closing.geometry.as_logical();
+                let center = Point::<i32, Logical>::from((
+                    geometry.loc.x + geometry.size.w / 2,
+                    geometry.loc.y + geometry.size.h / 2,
+                ));
+                match MemoryRenderBufferRenderElement::from_buffer(
+                    renderer,
+                    geometry.loc.to_f64().to_physical(output_scale),
+                    &closing.buffer,
+                    Some(alpha * (1.0 - progress) as f32),
+                    None,
+                    Some(geometry.size),
+                    Kind::Unspecified,
+                ) {
+                    Ok(elem) => window_elements.push(
+                        CosmicMappedRenderElement::Snapshot(RescaleRenderElement::from_element(
+                            elem,
+                            center.to_physical_precise_round(output_scale),
+                            1.0 - 0.1 * progress,
+                        ))
+                        .into(),
+                    ),
+                    Err(err) => warn!(?err, "Failed to render closing window."),
+                }
+            }
+
             let (w_elements, p_elements) = self.floating_layer.render::<R>(
                 renderer,
                 focused.as_ref(),
@@ -3,18 +3,30 @@
 use crate::{
     backend::{
         kms::{source_node_for_surface, KmsState},
+        render::background::Backgrounds,
         winit::WinitState,
         x11::X11State,
     },
-    config::{Config, OutputConfig},
-    input::Devices,
-    shell::{grabs::SeatMoveGrabState, Shell},
-    utils::prelude::*,
+    config::{window_rule, Config, OutputConfig},
+    recovery::Recovery,
+    session::{OutputEvents, WindowEvents},
+    shell::{element::surface::IdentityUpdate, grabs::SeatMoveGrabState, CosmicSurface, Shell},
+    utils::{
+        commit_stats::{commit_rate, THROTTLED_FRAME_INTERVAL},
+        prelude::*,
+        stalled_clients::ClientSockets,
+    },
+    wayland::handlers::{screencopy::ScreencopySessions, xdg_shell::ping::Pings},
     wayland::protocols::{
+        content_type::ContentTypeState,
         drm::WlDrmState,
+        gamma_control::GammaControlState,
+        idle_inhibit::IdleInhibitState,
+        idle_notify::IdleNotifierState,
         output_configuration::OutputConfigurationState,
         screencopy::{BufferParams, ScreencopyState, Session as ScreencopySession},
         workspace::WorkspaceClientState,
+        xdg_foreign::XdgForeignState,
     },
     xwayland::XWaylandState,
 };
@@ -49,7 +61,10 @@ use smithay::{
     input::{pointer::CursorImageStatus, Seat, SeatState},
     output::{Mode as OutputMode, Output, Scale},
     reexports::{
-        calloop::{LoopHandle, LoopSignal},
+        calloop::{
+            timer::{TimeoutAction, Timer},
+            LoopHandle, LoopSignal, RegistrationToken,
+        },
         wayland_protocols_misc::server_decoration::server::org_kde_kwin_server_decoration_manager::Mode,
         wayland_server::{
             backend::{ClientData, ClientId, DisconnectReason},
@@ -62,6 +77,7 @@ use smithay::{
         compositor::{CompositorClientState, CompositorState},
         dmabuf::{DmabufFeedback, DmabufState},
         fractional_scale::{with_fractional_scale, FractionalScaleManagerState},
+        input_method::InputMethodManagerState,
         keyboard_shortcuts_inhibit::KeyboardShortcutsInhibitState,
         output::OutputManagerState,
         pointer_constraints::PointerConstraintsState,
@@ -73,11 +89,13 @@ use smithay::{
         session_lock::{LockSurface, SessionLockManagerState},
         shell::{kde::decoration::KdeDecorationState, xdg::decoration::XdgDecorationState},
         shm::ShmState,
+        single_pixel_buffer::SinglePixelBufferState,
+        text_input::TextInputManagerState,
         viewporter::ViewporterState,
         xwayland_keyboard_grab::XWaylandKeyboardGrabState,
     },
 };
-use tracing::error;
+use tracing::{error, info, warn};
 
 use std::{cell::RefCell, ffi::OsString, time::Duration};
 use std::{
@@ -85,6 +103,11 @@ use std::{
     time::Instant,
 };
 
+/// Time clients get to close their windows on shutdown.
+const SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(5);
+/// Title and app_id changes of a window are announced at most this often.
+const IDENTITY_UPDATE_INTERVAL: Duration = Duration::from_millis(250);
+
 #[derive(RustEmbed)]
 #[folder = "resources/i18n"]
 struct Localizations;
@@ -136,12 +159,23 @@ pub struct Common {
     pub shell: Shell,
 
     seats: Vec<Seat<State>>,
+    /// Index into `seats` for the id of every input device
+    device_seats: HashMap<String, usize>,
     last_active_seat: Option<Seat<State>>,
 
     pub clock: Clock<Monotonic>,
     pub should_stop: bool,
+    shutdown_deadline: Option<Instant>,
+    /// Compositor shortcuts are handled, besides toggling them and terminating
+    pub shortcuts_enabled: bool,
 
     pub theme: cosmic::Theme,
+    pub recovery: Recovery,
+    pub backgrounds: Backgrounds,
+    pub window_events: WindowEvents,
+    pub output_events: OutputEvents,
+    pub pings: Pings,
+    pub client_sockets: ClientSockets,
 
     #[cfg(feature = "debug")]
     pub egui: Egui,
@@ -151,6 +185,11 @@ pub struct Common {
     pub data_device_state: DataDeviceState,
     pub dmabuf_state: DmabufState,
     pub fractional_scale_state: FractionalScaleManagerState,
+    pub gamma_control_state: GammaControlState,
+    pub idle_inhibit_state: IdleInhibitState,
+    pub idle_notifier_state: IdleNotifierState,
+    /// Timer sending out the next idle notification, see `State::notify_activity`
+    pub idle_timer: Option<RegistrationToken>,
     pub keyboard_shortcuts_inhibit_state: KeyboardShortcutsInhibitState,
     pub output_state: OutputManagerState,
     pub output_configuration_state: OutputConfigurationState<State>,
@@ -164,6 +203,7 @@ pub struct Common {
     pub viewporter_state: ViewporterState,
     pub kde_decoration_state: KdeDecorationState,
     pub xdg_decoration_state: XdgDecorationState,
+    pub xdg_foreign_state: XdgForeignState,
 
     pub session_lock: Option<SessionLock>,
 
@@ -238,26 +278,73 @@ impl BackendData {
                 .get::<RefCell<OutputConfig>>()
                 .unwrap()
                 .borrow();
-            let mode = Some(OutputMode {
-                size: final_config.mode_size(),
-                refresh: final_config.mode_refresh() as i32,
-            })
-            .filter(|m| match output.current_mode() {
-                None => true,
-                Some(c_m) => m.size != c_m.size || m.refresh != c_m.refresh,
-            });
+            // kms might end up using a different mode, if the configured one got rejected
+            let applied_mode = match self {
+                BackendData::Kms(ref state) => state.applied_mode(output),
+                _ => None,
+            };
+            let mode = applied_mode
+                .or(Some(OutputMode {
+                    size: final_config.mode_size(),
+                    refresh: final_config.mode_refresh() as i32,
+                }))
+                .filter(|m| match output.current_mode() {
+                    None => true,
+                    Some(c_m) => m.size != c_m.size || m.refresh != c_m.refresh,
+                });
             let transform =
                 Some(final_config.transform.into()).filter(|x| *x != output.current_transform());
             let scale = Some(final_config.scale)
                 .filter(|x| *x != output.current_scale().fractional_scale());
             let location =
                 Some(final_config.position.into()).filter(|x| *x != output.current_location());
+            let geometry_changed = mode.is_some() || transform.is_some() || scale.is_some();
+            std::mem::drop(final_config);
             output.change_current_state(mode, transform, scale.map(Scale::Fractional), location);
+
+            if geometry_changed {
+                // fullscreen and maximized windows need to follow the new output size
+                smithay::desktop::layer_map_for_output(output).arrange();
+                for workspace in shell.workspaces.spaces_mut() {
+                    if workspace.output() == output {
+                        workspace.recalculate();
+                    }
+                }
+            }
         }
 
         result
     }
 
+    /// Turns the display of `output` off or back on, without unmapping anything.
+    pub fn set_output_power(
+        &mut self,
+        output: &Output,
+        on: bool,
+        loop_handle: &LoopHandle<'_, State>,
+    ) -> Result<(), anyhow::Error> {
+        match self {
+            BackendData::Kms(ref mut state) => state.set_output_power(output, on, loop_handle),
+            _ => Err(anyhow::anyhow!(
+                "Output power is only supported on the kms backend"
+            )),
+        }
+    }
+
+    /// Toggles the night light on all outputs and returns, whether it is now enabled.
+    pub fn toggle_night_light(&mut self) -> Result<bool, anyhow::Error> {
+        match self {
+            BackendData::Kms(ref mut state) => {
+                let enabled = !state.night_light();
+                state.set_night_light(enabled);
+                Ok(enabled)
+            }
+            _ => Err(anyhow::anyhow!(
+                "Night light is only supported on the kms backend"
+            )),
+        }
+    }
+
     pub fn schedule_render(
         &mut self,
         loop_handle: &LoopHandle<'_, State>,
@@ -303,6 +390,10 @@ impl State {
         let data_device_state = DataDeviceState::new::<Self>(dh);
         let dmabuf_state = DmabufState::new();
         let fractional_scale_state = FractionalScaleManagerState::new::<State>(dh);
+        let gamma_control_state =
+            GammaControlState::new::<Self, _>(dh, client_has_security_context);
+        let idle_inhibit_state = IdleInhibitState::new::<Self>(dh);
+        let idle_notifier_state = IdleNotifierState::new::<Self>(dh);
         let keyboard_shortcuts_inhibit_state = KeyboardShortcutsInhibitState::new::<Self>(dh);
         let output_state = OutputManagerState::new_with_xdg_output::<Self>(dh);
         let output_configuration_state =
@@ -321,14 +412,20 @@ impl State {
         let wl_drm_state = WlDrmState;
         let kde_decoration_state = KdeDecorationState::new::<Self>(&dh, Mode::Client);
         let xdg_decoration_state = XdgDecorationState::new::<Self>(&dh);
+        let xdg_foreign_state = XdgForeignState::new::<Self>(&dh);
         let session_lock_manager_state =
             SessionLockManagerState::new::<Self, _>(&dh, client_has_security_context);
         XWaylandKeyboardGrabState::new::<Self>(&dh);
+        SinglePixelBufferState::new::<Self>(&dh);
+        ContentTypeState::new::<Self>(&dh);
         PointerConstraintsState::new::<Self>(&dh);
         PointerGesturesState::new::<Self>(&dh);
         SecurityContextState::new::<Self, _>(&dh, client_has_security_context);
+        TextInputManagerState::new::<Self>(&dh);
+        InputMethodManagerState::new::<Self, _>(&dh, client_has_security_context);
 
         let shell = Shell::new(&config, dh);
+        let backgrounds = Backgrounds::load(&config.static_conf);
 
         State {
             common: Common {
@@ -341,12 +438,21 @@ impl State {
                 shell,
 
                 seats: Vec::new(),
+                device_seats: HashMap::new(),
                 last_active_seat: None,
 
                 clock,
                 should_stop: false,
+                shutdown_deadline: None,
+                shortcuts_enabled: true,
 
                 theme: cosmic::theme::system_preference(),
+                recovery: Recovery::default(),
+                backgrounds,
+                window_events: WindowEvents::default(),
+                output_events: OutputEvents::default(),
+                pings: Pings::default(),
+                client_sockets: ClientSockets::default(),
 
                 #[cfg(feature = "debug")]
                 egui: Egui {
@@ -355,12 +461,19 @@ impl State {
                         (0, 0),
                         (800, 600),
                     )),
+                    highlighted: None,
+                    logs: Default::default(),
+                    toast: None,
                 },
 
                 compositor_state,
                 data_device_state,
                 dmabuf_state,
                 fractional_scale_state,
+                gamma_control_state,
+                idle_inhibit_state,
+                idle_notifier_state,
+                idle_timer: None,
                 screencopy_state,
                 shm_state,
                 seat_state,
@@ -374,6 +487,7 @@ impl State {
                 wl_drm_state,
                 kde_decoration_state,
                 xdg_decoration_state,
+                xdg_foreign_state,
 
                 session_lock: None,
 
@@ -383,6 +497,50 @@ impl State {
         }
     }
 
+    /// Drives a requested shutdown and returns if the event loop may stop now.
+    ///
+    /// Asks every toplevel to close on the first call and then waits
+    /// for them to go away, but no longer than `SHUTDOWN_TIMEOUT`.
+    pub fn ready_to_stop(&mut self) -> bool {
+        let windows = self
+            .common
+            .shell
+            .workspaces
+            .spaces()
+            .flat_map(|w| w.mapped())
+            .flat_map(|mapped| mapped.windows().map(|(window, _)| window))
+            .collect::<Vec<_>>();
+
+        match self.common.shutdown_deadline {
+            Some(deadline) => windows.is_empty() || Instant::now() >= deadline,
+            None => {
+                info!(windows = windows.len(), "Closing windows for shutdown");
+                #[cfg(feature = "systemd")]
+                if let BackendData::Kms(_) = &self.backend {
+                    crate::systemd::stopping();
+                }
+
+                let deadline = Instant::now() + SHUTDOWN_TIMEOUT;
+                self.common.shutdown_deadline = Some(deadline);
+                for window in &windows {
+                    window.close();
+                }
+                // wake up the loop once the time is up, even if no client reacts
+                if let Err(err) = self
+                    .common
+                    .event_loop_handle
+                    .insert_source(Timer::from_deadline(deadline), |_, _, _| {
+                        TimeoutAction::Drop
+                    })
+                {
+                    warn!(?err, "Failed to schedule shutdown timeout");
+                    return true;
+                }
+                windows.is_empty()
+            }
+        }
+    }
+
     pub fn new_client_state(&self) -> ClientState {
         ClientState {
             compositor_client_state: CompositorClientState::default(),
@@ -443,7 +601,13 @@ impl Common {
     }
 
     pub fn remove_seat(&mut self, seat: &Seat<State>) {
-        self.seats.retain(|s| s != seat);
+        if let Some(idx) = self.seats.iter().position(|s| s == seat) {
+            self.seats.remove(idx);
+            self.device_seats.retain(|_, i| *i != idx);
+            for i in self.device_seats.values_mut().filter(|i| **i > idx) {
+                *i -= 1;
+            }
+        }
         if self.seats.is_empty() {
             self.last_active_seat = None;
         } else if self.last_active_seat() == seat {
@@ -456,17 +620,118 @@ impl Common {
     }
 
     pub fn seat_with_device<D: Device>(&self, device: &D) -> Option<&Seat<State>> {
-        self.seats().find(|seat| {
-            let userdata = seat.user_data();
-            let devices = userdata.get::<Devices>().unwrap();
-            devices.has_device(device)
-        })
+        self.device_seats
+            .get(&device.id())
+            .and_then(|idx| self.seats.get(*idx))
+    }
+
+    /// Remembers `seat` as the seat of `device` for `seat_with_device`.
+    pub fn add_device_seat<D: Device>(&mut self, device: &D, seat: &Seat<State>) {
+        if let Some(idx) = self.seats.iter().position(|s| s == seat) {
+            self.device_seats.insert(device.id(), idx);
+        }
+    }
+
+    /// Forgets the seat of `device` and returns it.
+    pub fn remove_device_seat<D: Device>(&mut self, device: &D) -> Option<Seat<State>> {
+        let idx = self.device_seats.remove(&device.id())?;
+        self.seats.get(idx).cloned()
     }
 
     pub fn last_active_seat(&self) -> &Seat<State> {
         self.last_active_seat.as_ref().expect("No seat?")
     }
 
+    /// Announces title and app_id changes of `window` to IPC subscribers and re-applies
+    /// title-based window rules, once the window got its first title.
+    pub fn update_window_identity(&mut self, window: &CosmicSurface) {
+        self.publish_window_identity(window, IDENTITY_UPDATE_INTERVAL)
+    }
+
+    fn publish_window_identity(&mut self, window: &CosmicSurface, interval: Duration) {
+        match window.publish_identity(interval) {
+            IdentityUpdate::Unchanged => {}
+            IdentityUpdate::Throttled(delay) => {
+                let window = window.clone();
+                if let Err(err) = self.event_loop_handle.insert_source(
+                    Timer::from_duration(delay),
+                    move |_, _, state| {
+                        if window.alive() {
+                            state
+                                .common
+                                .publish_window_identity(&window, Duration::ZERO);
+                        }
+                        TimeoutAction::Drop
+                    },
+                ) {
+                    warn!(?err, "Failed to schedule window title update");
+                }
+            }
+            IdentityUpdate::Published(identity) => {
+                if window.take_first_title() {
+                    if let Some(opacity) =
+                        window_rule(&self.config.static_conf.window_rules, window, |rule| {
+                            rule.opacity
+                        })
+                    {
+                        window.set_opacity(opacity);
+                    }
+                }
+                self.window_events.send(window, identity);
+            }
+        }
+    }
+
+    /// Makes `output` the primary output and persists it.
+    pub fn set_primary_output(&mut self, output: &Output) {
+        self.shell.set_primary_output(output);
+        self.config
+            .write_outputs(self.output_configuration_state.outputs());
+        self.publish_primary_output();
+    }
+
+    /// Announces a changed primary output to IPC subscribers.
+    pub fn publish_primary_output(&mut self) {
+        if self.shell.take_primary_output_changed() {
+            self.output_events.send_primary(self.shell.primary_output());
+        }
+    }
+
+    /// Whether key bindings are forwarded to the focused window of `seat`,
+    /// either by a window rule or by `Action::ToggleKeybindingsInhibit`.
+    pub fn keybindings_inhibited(&self, seat: &Seat<State>) -> bool {
+        let output = seat.active_output();
+        self.shell
+            .active_space(&output)
+            .focus_stack
+            .get(seat)
+            .last()
+            .map(|mapped| {
+                let window = mapped.active_window();
+                window
+                    .inhibit_keybindings_override()
+                    .or_else(|| {
+                        window_rule(&self.config.static_conf.window_rules, &window, |rule| {
+                            rule.inhibit_keybindings
+                        })
+                    })
+                    .unwrap_or(false)
+            })
+            .unwrap_or(false)
+    }
+
+    /// The debug overlay follows the output of the last active seat
+    #[cfg(feature = "debug")]
+    pub fn egui_output(&self) -> Output {
+        self.last_active_seat().active_output()
+    }
+
+    /// Whether input of the given seat should be routed into the debug overlay
+    #[cfg(feature = "debug")]
+    pub fn seat_uses_egui(&self, seat: &Seat<State>) -> bool {
+        self.egui.active && seat.active_output() == self.egui_output()
+    }
+
     pub fn send_frames(
         &self,
         output: &Output,
@@ -575,7 +840,10 @@ impl Common {
                     output,
                     states,
                     render_element_states,
-                    |_current_output, _current_state, next_output, _next_state| next_output,
+                    // prefer the output with the higher refresh rate, so windows spanning
+                    // multiple outputs are paced by the fastest one instead of whichever
+                    // output happened to render last
+                    default_primary_scanout_output_compare,
                 );
                 if let Some(output) = primary_scanout_output {
                     with_fractional_scale(states, |fraction_scale| {
@@ -599,15 +867,52 @@ impl Common {
             }
         });
 
+        // windows of hidden workspaces only get frame callbacks, if something still looks at them
+        // or they might wait for one to ack a configure
+        let overview = self.shell.is_overview_active();
+        let previous = self
+            .shell
+            .workspaces
+            .active(output)
+            .0
+            .map(|(w, _)| &w.handle);
         for space in self
             .shell
             .workspaces
             .spaces()
-            .filter(|w| w.handle != active.handle)
+            // only pace workspaces of this output, others follow their own output's refresh rate
+            .filter(|w| w.output() == output && w.handle != active.handle)
         {
+            let captured = overview
+                || Some(&space.handle) == previous
+                || !space.screencopy_sessions.is_empty();
             space.mapped().for_each(|mapped| {
                 let window = mapped.active_window();
-                window.send_frame(space.output(), time, throttle, |_, _| None);
+                let window_captured = window
+                    .user_data()
+                    .get::<ScreencopySessions>()
+                    .is_some_and(|sessions| !sessions.0.borrow().is_empty());
+                // suspended windows are captured from their last buffer instead of waking them up
+                let window_captured = window_captured && !window.is_suspended();
+                if captured || window_captured || mapped.awaits_ack() || window.awaits_suspend_ack()
+                {
+                    // clients committing way too often get fewer chances to do so
+                    let throttled = self
+                        .config
+                        .static_conf
+                        .commit_throttle
+                        .is_some_and(|limit| {
+                            window.wl_surface().is_some_and(|surface| {
+                                commit_rate(&surface).commits_per_sec > limit as f64
+                            })
+                        });
+                    let throttle = if throttled {
+                        Some(THROTTLED_FRAME_INTERVAL)
+                    } else {
+                        throttle
+                    };
+                    window.send_frame(space.output(), time, throttle, |_, _| None);
+                }
             });
         }
 
@@ -747,6 +1052,10 @@ impl Common {
 pub struct Egui {
     pub active: bool,
     pub state: smithay_egui::EguiState,
+    pub highlighted: Option<crate::shell::CosmicMapped>,
+    pub logs: crate::debug::LogView,
+    /// Short notification and when it was shown
+    pub toast: Option<(Instant, &'static str)>,
 }
 
 #[derive(Debug)]
@@ -757,6 +1066,8 @@ pub struct Fps {
     pub state: smithay_egui::EguiState,
     pending_frame: Option<PendingFrame>,
     pub frames: VecDeque<Frame>,
+    pub estimated_rendertime: Duration,
+    pub missed_deadlines: usize,
 }
 
 #[derive(Debug)]
@@ -782,7 +1093,7 @@ impl Frame {
         self.duration_elements + self.duration_render
     }
 
-    fn frame_time(&self) -> Duration {
+    pub fn frame_time(&self) -> Duration {
         self.duration_elements
             + self.duration_render
             + self.duration_screencopy.clone().unwrap_or(Duration::ZERO)
@@ -902,14 +1213,33 @@ impl Fps {
     }
 
     pub fn avg_rendertime(&self, window: usize) -> Duration {
+        let window = window.min(self.frames.len());
+        if window == 0 {
+            return Duration::ZERO;
+        }
         self.frames
             .iter()
+            .rev()
             .take(window)
             .map(|f| f.render_time())
             .sum::<Duration>()
             / window as u32
     }
 
+    pub fn max_rendertime(&self, window: usize) -> Duration {
+        self.frames
+            .iter()
+            .rev()
+            .take(window)
+            .map(|f| f.render_time())
+            .max()
+            .unwrap_or(Duration::ZERO)
+    }
+
+    pub fn missed_deadline(&mut self) {
+        self.missed_deadlines += 1;
+    }
+
     pub fn avg_fps(&self) -> f64 {
         if self.frames.is_empty() {
             return 0.0;
@@ -962,6 +1292,8 @@ impl Fps {
             rd: renderdoc::RenderDoc::new().ok(),
             pending_frame: None,
             frames: VecDeque::with_capacity(Fps::WINDOW_SIZE + 1),
+            estimated_rendertime: Duration::ZERO,
+            missed_deadlines: 0,
         }
     }
 }
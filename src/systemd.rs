@@ -1,36 +1,42 @@
 // SPDX-License-Identifier: GPL-3.0-only
 
 use crate::state::State;
-use libsystemd::daemon::{booted, notify, NotifyState};
-use std::process::Command;
-use tracing::{error, warn};
+use calloop::{
+    timer::{TimeoutAction, Timer},
+    LoopHandle,
+};
+use libsystemd::daemon::{booted, notify, watchdog_enabled, NotifyState};
+use tracing::{error, info, warn};
 
-pub fn ready(state: &State) {
+pub fn ready() {
     if booted() {
-        match Command::new("systemctl")
-            .args(["--user", "import-environment", "WAYLAND_DISPLAY", "DISPLAY"])
-            .env("WAYLAND_DISPLAY", &state.common.socket)
-            .env(
-                "DISPLAY",
-                &state
-                    .common
-                    .xwayland_state
-                    .as_ref()
-                    .map(|s| format!(":{}", s.display))
-                    .unwrap_or(String::new()),
-            )
-            .status()
-        {
-            Ok(x) if x.success() => {}
-            Ok(x) => warn!(
-                exit_code = ?x.code(),
-                "Failed to import WAYLAND_DISPLAY/DISPLAY into systemd",
-            ),
-            Err(err) => error!(?err, "Failed to run systemctl although booted with systemd",),
-        };
-
         if let Err(err) = notify(false, &[NotifyState::Ready]) {
             error!(?err, "Failed to notify systemd");
         }
     }
 }
+
+/// Pings the systemd watchdog from the event loop, so a hung compositor gets restarted.
+pub fn watchdog(handle: &LoopHandle<'static, State>) {
+    let Some(timeout) = watchdog_enabled(false) else {
+        return;
+    };
+    let interval = timeout / 2;
+    info!(?interval, "Enabling systemd watchdog");
+    if let Err(err) = handle.insert_source(Timer::from_duration(interval), move |_, _, _| {
+        if let Err(err) = notify(false, &[NotifyState::Watchdog]) {
+            warn!(?err, "Failed to ping systemd watchdog");
+        }
+        TimeoutAction::ToDuration(interval)
+    }) {
+        error!(?err, "Failed to setup systemd watchdog");
+    }
+}
+
+pub fn stopping() {
+    if booted() {
+        if let Err(err) = notify(false, &[NotifyState::Stopping]) {
+            error!(?err, "Failed to notify systemd");
+        }
+    }
+}
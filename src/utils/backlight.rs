@@ -0,0 +1,83 @@
+// SPDX-License-Identifier: GPL-3.0-only
+
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    process::Command,
+};
+use tracing::warn;
+
+const BACKLIGHT_PATH: &str = "/sys/class/backlight";
+
+/// Changes the brightness of the first backlight by `step` percent of its range,
+/// returns the new brightness in percent.
+///
+/// The new value is written through logind's `SetBrightness`, which is allowed
+/// for the active session without any further permissions.
+pub fn adjust_brightness(step: i32) -> Option<u32> {
+    let Some(device) = backlight_device() else {
+        warn!("No backlight device found to adjust brightness");
+        return None;
+    };
+    let (Some(current), Some(max)) = (
+        read_value(&device.join("brightness")),
+        read_value(&device.join("max_brightness")),
+    ) else {
+        warn!(?device, "Failed to read backlight brightness");
+        return None;
+    };
+    let name = device
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_default();
+
+    let delta = (max as i64 * step as i64 / 100).max(1) * step.signum() as i64;
+    let value = (current as i64 + delta).clamp(0, max as i64) as u32;
+
+    std::thread::spawn(move || {
+        let result = Command::new("busctl")
+            .args([
+                "call",
+                "--system",
+                "org.freedesktop.login1",
+                "/org/freedesktop/login1/session/auto",
+                "org.freedesktop.login1.Session",
+                "SetBrightness",
+                "ssu",
+                "backlight",
+                &name,
+                &value.to_string(),
+            ])
+            .status();
+        match result {
+            Ok(status) if status.success() => {}
+            Ok(status) => warn!(?status, "logind refused to set brightness"),
+            Err(err) => warn!(?err, "Failed to call logind to set brightness"),
+        }
+    });
+
+    Some((value as u64 * 100 / max.max(1) as u64) as u32)
+}
+
+// firmware and platform interfaces control the panel directly, prefer them over raw ones
+fn backlight_device() -> Option<PathBuf> {
+    let mut devices = fs::read_dir(BACKLIGHT_PATH)
+        .ok()?
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .collect::<Vec<_>>();
+    devices.sort_by_key(|device| {
+        match fs::read_to_string(device.join("type"))
+            .as_deref()
+            .map(str::trim)
+        {
+            Ok("firmware") => 0,
+            Ok("platform") => 1,
+            _ => 2,
+        }
+    });
+    devices.into_iter().next()
+}
+
+fn read_value(path: &Path) -> Option<u32> {
+    fs::read_to_string(path).ok()?.trim().parse().ok()
+}
@@ -0,0 +1,168 @@
+// SPDX-License-Identifier: GPL-3.0-only
+
+use std::{
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use calloop::{
+    timer::{TimeoutAction, Timer},
+    LoopHandle,
+};
+use serde::{Deserialize, Serialize};
+use smithay::{
+    backend::renderer::{buffer_dimensions, utils::with_renderer_surface_state},
+    reexports::wayland_server::{protocol::wl_surface::WlSurface, Resource},
+    wayland::{
+        compositor::{get_parent, with_states},
+        seat::WaylandFocus,
+    },
+};
+use tracing::warn;
+
+use crate::{shell::CosmicSurface, utils::prelude::*};
+
+/// How often the commit counters are turned into rates
+const STATS_INTERVAL: Duration = Duration::from_secs(1);
+/// Throttled windows on hidden workspaces get a frame callback at most this often
+pub const THROTTLED_FRAME_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Commits of a surface tree since the last aggregation, counted on the root surface
+#[derive(Debug)]
+struct CommitCounter(Mutex<CommitCounterInner>);
+
+#[derive(Debug)]
+struct CommitCounterInner {
+    commits: u64,
+    buffer_bytes: u64,
+    since: Instant,
+    rate: CommitRate,
+}
+
+/// Rates of a surface tree, as of the last aggregation
+#[derive(Debug, Default, Clone, Copy, Serialize, Deserialize)]
+pub struct CommitRate {
+    pub commits_per_sec: f64,
+    pub buffer_bytes_per_sec: u64,
+}
+
+/// Commit rates of a window, see `top_offenders`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WindowStats {
+    pub pid: Option<i32>,
+    pub app_id: String,
+    #[serde(flatten)]
+    pub rate: CommitRate,
+}
+
+fn root_surface(surface: &WlSurface) -> WlSurface {
+    let mut root = surface.clone();
+    while let Some(parent) = get_parent(&root) {
+        root = parent;
+    }
+    root
+}
+
+/// Counts a commit of `surface` towards its root surface.
+///
+/// Has to be called after the buffer was handled by `on_commit_buffer_handler`.
+pub fn record_commit(surface: &WlSurface, new_buffer: bool) {
+    // estimated as 4 bytes per pixel, which is what most clients use
+    let bytes = new_buffer
+        .then(|| with_renderer_surface_state(surface, |state| state.buffer().cloned()))
+        .flatten()
+        .and_then(|buffer| buffer_dimensions(&buffer))
+        .map(|size| size.w as u64 * size.h as u64 * 4)
+        .unwrap_or(0);
+
+    with_states(&root_surface(surface), |states| {
+        states.data_map.insert_if_missing_threadsafe(|| {
+            CommitCounter(Mutex::new(CommitCounterInner {
+                commits: 0,
+                buffer_bytes: 0,
+                since: Instant::now(),
+                rate: CommitRate::default(),
+            }))
+        });
+        let mut counter = states
+            .data_map
+            .get::<CommitCounter>()
+            .unwrap()
+            .0
+            .lock()
+            .unwrap();
+        counter.commits += 1;
+        counter.buffer_bytes += bytes;
+    })
+}
+
+/// Returns the commit rate of the surface tree of `surface`.
+pub fn commit_rate(surface: &WlSurface) -> CommitRate {
+    with_states(surface, |states| {
+        states
+            .data_map
+            .get::<CommitCounter>()
+            .map(|counter| counter.0.lock().unwrap().rate)
+            .unwrap_or_default()
+    })
+}
+
+fn aggregate(surface: &WlSurface) {
+    with_states(surface, |states| {
+        if let Some(counter) = states.data_map.get::<CommitCounter>() {
+            let mut counter = counter.0.lock().unwrap();
+            let elapsed = counter.since.elapsed().as_secs_f64().max(f64::EPSILON);
+            counter.rate = CommitRate {
+                commits_per_sec: counter.commits as f64 / elapsed,
+                buffer_bytes_per_sec: (counter.buffer_bytes as f64 / elapsed) as u64,
+            };
+            counter.commits = 0;
+            counter.buffer_bytes = 0;
+            counter.since = Instant::now();
+        }
+    })
+}
+
+fn windows(common: &Common) -> impl Iterator<Item = CosmicSurface> + '_ {
+    common
+        .shell
+        .workspaces
+        .spaces()
+        .flat_map(|workspace| workspace.mapped())
+        .flat_map(|mapped| mapped.windows().map(|(window, _)| window))
+}
+
+/// Periodically turns the commit counters of all windows into rates.
+pub fn init(handle: &LoopHandle<'static, State>) {
+    if let Err(err) = handle.insert_source(Timer::from_duration(STATS_INTERVAL), |_, _, state| {
+        for window in windows(&state.common) {
+            if let Some(surface) = window.wl_surface() {
+                aggregate(&surface);
+            }
+        }
+        TimeoutAction::ToDuration(STATS_INTERVAL)
+    }) {
+        warn!(?err, "Failed to setup commit statistics");
+    }
+}
+
+/// Returns the windows committing most often, at most `limit` of them.
+pub fn top_offenders(common: &Common, limit: usize) -> Vec<WindowStats> {
+    let mut stats = windows(common)
+        .filter_map(|window| {
+            let surface = window.wl_surface()?;
+            let pid = surface
+                .client()
+                .and_then(|client| client.get_credentials(&common.display_handle).ok())
+                .map(|credentials| credentials.pid);
+            Some(WindowStats {
+                pid,
+                app_id: window.app_id(),
+                rate: commit_rate(&surface),
+            })
+        })
+        .collect::<Vec<_>>();
+    stats.sort_by(|a, b| b.rate.commits_per_sec.total_cmp(&a.rate.commits_per_sec));
+    stats.truncate(limit);
+    stats
+}
@@ -2,7 +2,12 @@
 
 mod ids;
 pub(crate) use self::ids::id_gen;
+pub mod backlight;
+pub mod commit_stats;
 pub mod geometry;
 pub mod iced;
 pub mod prelude;
+pub mod stalled_clients;
+#[cfg(test)]
+pub mod testing;
 pub mod tween;
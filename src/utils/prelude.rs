@@ -1,12 +1,17 @@
-use std::{cell::RefCell, sync::Mutex, time::Duration};
+use std::{
+    cell::{Cell, RefCell},
+    sync::Mutex,
+    time::Duration,
+};
 
 use crate::{
     backend::render::cursor::{CursorShape, CursorState},
-    input::{ActiveOutput, SeatId},
+    input::{ActiveOutput, CurrentModifiers, SeatId},
 };
 use smithay::{
     desktop::utils::bbox_from_surface_tree,
     input::{
+        keyboard::ModifiersState,
         pointer::{CursorIcon, CursorImageAttributes, CursorImageStatus},
         Seat,
     },
@@ -22,8 +27,13 @@ pub use crate::wayland::handlers::xdg_shell::popup::update_reactive_popups;
 
 pub trait OutputExt {
     fn geometry(&self) -> Rectangle<i32, Global>;
+    /// Whether the output was blanked by `Action::ToggleOutputPower`
+    fn is_powered_off(&self) -> bool;
+    fn set_powered_off(&self, off: bool);
 }
 
+struct OutputPoweredOff(Cell<bool>);
+
 impl OutputExt for Output {
     fn geometry(&self) -> Rectangle<i32, Global> {
         Rectangle::from_loc_and_size(self.current_location(), {
@@ -39,6 +49,22 @@ impl OutputExt for Output {
         })
         .as_global()
     }
+
+    fn is_powered_off(&self) -> bool {
+        self.user_data()
+            .get::<OutputPoweredOff>()
+            .map_or(false, |off| off.0.get())
+    }
+
+    fn set_powered_off(&self, off: bool) {
+        self.user_data()
+            .insert_if_missing(|| OutputPoweredOff(Cell::new(false)));
+        self.user_data()
+            .get::<OutputPoweredOff>()
+            .unwrap()
+            .0
+            .set(off);
+    }
 }
 
 pub trait SeatExt {
@@ -46,6 +72,8 @@ pub trait SeatExt {
 
     fn active_output(&self) -> Output;
     fn set_active_output(&self, output: &Output);
+    /// Current keyboard modifiers, usable outside of keyboard events
+    fn modifiers(&self) -> ModifiersState;
     fn cursor_geometry(
         &self,
         loc: impl Into<Point<f64, Buffer>>,
@@ -74,6 +102,13 @@ impl SeatExt for Seat<State> {
             .borrow_mut() = output.clone();
     }
 
+    fn modifiers(&self) -> ModifiersState {
+        self.user_data()
+            .get::<CurrentModifiers>()
+            .map(|x| x.0.borrow().clone())
+            .unwrap_or_default()
+    }
+
     fn cursor_geometry(
         &self,
         loc: impl Into<Point<f64, Buffer>>,
@@ -139,3 +174,84 @@ impl SeatExt for Seat<State> {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::OutputExt;
+    use smithay::{
+        output::{Mode, Output, PhysicalProperties, Scale, Subpixel},
+        utils::{Rectangle, Transform},
+    };
+
+    fn output() -> Output {
+        let output = Output::new(
+            String::from("DP-1"),
+            PhysicalProperties {
+                size: (600, 340).into(),
+                subpixel: Subpixel::Unknown,
+                make: String::from("Make"),
+                model: String::from("Model"),
+            },
+        );
+        let mode = Mode {
+            size: (3840, 2160).into(),
+            refresh: 60_000,
+        };
+        output.add_mode(mode);
+        output.set_preferred(mode);
+        output.change_current_state(Some(mode), None, None, Some((0, 0).into()));
+        output
+    }
+
+    #[test]
+    fn geometry_is_the_mode_size_without_scale() {
+        assert_eq!(
+            output().geometry(),
+            Rectangle::from_loc_and_size((0, 0), (3840, 2160))
+        );
+    }
+
+    #[test]
+    fn geometry_is_logical() {
+        let output = output();
+        output.change_current_state(None, None, Some(Scale::Integer(2)), None);
+        assert_eq!(
+            output.geometry(),
+            Rectangle::from_loc_and_size((0, 0), (1920, 1080))
+        );
+        output.change_current_state(None, None, Some(Scale::Fractional(1.5)), None);
+        assert_eq!(
+            output.geometry(),
+            Rectangle::from_loc_and_size((0, 0), (2560, 1440))
+        );
+    }
+
+    #[test]
+    fn geometry_follows_transform_and_position() {
+        let output = output();
+        output.change_current_state(
+            None,
+            Some(Transform::_90),
+            Some(Scale::Integer(2)),
+            Some((1920, 0).into()),
+        );
+        assert_eq!(
+            output.geometry(),
+            Rectangle::from_loc_and_size((1920, 0), (1080, 1920))
+        );
+    }
+
+    #[test]
+    fn geometry_without_mode_is_empty() {
+        let output = Output::new(
+            String::from("HDMI-A-1"),
+            PhysicalProperties {
+                size: (0, 0).into(),
+                subpixel: Subpixel::Unknown,
+                make: String::from("Unknown"),
+                model: String::from("Unknown"),
+            },
+        );
+        assert_eq!(output.geometry().size, (0, 0).into());
+    }
+}
@@ -0,0 +1,259 @@
+// SPDX-License-Identifier: GPL-3.0-only
+
+//! Detection of clients, that stopped reading their socket.
+//!
+//! Events for such a client first fill up the kernel socket buffer and afterwards the
+//! outgoing buffer of the wayland backend, which every following flush has to deal with.
+//! Clients, that don't read any of their data for `STALL_TIMEOUT`, get disconnected instead.
+//!
+//! Data is only written to the sockets while flushing, so whatever disappeared from a socket
+//! between two flushes was read by the client, even if the flush in between refilled it.
+
+use crate::state::{ClientState, State};
+use smithay::reexports::wayland_server::{
+    backend::{ClientId, DisconnectReason},
+    Client,
+};
+use std::{
+    collections::HashMap,
+    io,
+    os::unix::{
+        io::{AsFd, AsRawFd, OwnedFd},
+        net::UnixStream,
+    },
+    sync::Arc,
+    time::{Duration, Instant},
+};
+use tracing::{debug, warn};
+
+/// Time between two checks of the client sockets.
+const CHECK_INTERVAL: Duration = Duration::from_secs(1);
+/// Time the unread data of a client may stay without shrinking, before it is disconnected.
+const STALL_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Sockets of all connected clients.
+#[derive(Debug, Default)]
+pub struct ClientSockets {
+    sockets: HashMap<ClientId, Socket>,
+    last_check: Option<Instant>,
+}
+
+#[derive(Debug)]
+struct Socket {
+    fd: OwnedFd,
+    /// Bytes not yet read by the client after the last flush
+    queued: usize,
+    /// Bytes read by the client since the last check
+    drained: usize,
+    /// Last time the client was seen reading or had nothing left to read
+    progress: Instant,
+}
+
+impl Socket {
+    fn new(fd: OwnedFd) -> Socket {
+        Socket {
+            fd,
+            queued: 0,
+            drained: 0,
+            progress: Instant::now(),
+        }
+    }
+
+    /// Counts the bytes read by the client since the last flush, has to be called before flushing.
+    fn count_drained(&mut self) -> io::Result<()> {
+        let queued = queued_bytes(&self.fd)?;
+        self.drained += self.queued.saturating_sub(queued);
+        self.queued = queued;
+        Ok(())
+    }
+
+    /// Records the bytes left unread after a flush.
+    fn flushed(&mut self) -> io::Result<()> {
+        self.queued = queued_bytes(&self.fd)?;
+        Ok(())
+    }
+
+    /// Returns whether the client didn't read anything for `STALL_TIMEOUT` at `now`.
+    fn check(&mut self, now: Instant) -> io::Result<bool> {
+        self.count_drained()?;
+        if self.queued == 0 || self.drained > 0 {
+            self.progress = now;
+        }
+        self.drained = 0;
+        Ok(now.saturating_duration_since(self.progress) >= STALL_TIMEOUT)
+    }
+}
+
+/// Bytes written to `fd`, that the other side didn't read yet.
+fn queued_bytes(fd: &OwnedFd) -> io::Result<usize> {
+    let mut queued: libc::c_int = 0;
+    // SAFETY: TIOCOUTQ (aka SIOCOUTQ) writes a single int
+    if unsafe { libc::ioctl(fd.as_raw_fd(), libc::TIOCOUTQ, &mut queued) } == -1 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(queued.max(0) as usize)
+}
+
+impl State {
+    /// Adds a new client to the display and keeps track of its socket.
+    ///
+    /// Every client connection should go through here instead of `DisplayHandle::insert_client`.
+    pub fn insert_client(&mut self, stream: UnixStream, data: ClientState) -> io::Result<Client> {
+        let fd = stream.as_fd().try_clone_to_owned()?;
+        let client = self
+            .common
+            .display_handle
+            .insert_client(stream, Arc::new(data))?;
+        self.common
+            .client_sockets
+            .sockets
+            .insert(client.id(), Socket::new(fd));
+        Ok(client)
+    }
+
+    /// Sends out pending events to all clients, counting how much data each of them read.
+    pub fn flush_clients(&mut self) {
+        let common = &mut self.common;
+        for socket in common.client_sockets.sockets.values_mut() {
+            let _ = socket.count_drained();
+        }
+        let _ = common.display_handle.flush_clients();
+        for socket in common.client_sockets.sockets.values_mut() {
+            let _ = socket.flushed();
+        }
+    }
+
+    /// Disconnects clients, that haven't read any of their pending events within `STALL_TIMEOUT`.
+    pub fn disconnect_stalled_clients(&mut self) {
+        let client_sockets = &mut self.common.client_sockets;
+        if client_sockets
+            .last_check
+            .is_some_and(|last_check| last_check.elapsed() < CHECK_INTERVAL)
+        {
+            return;
+        }
+        client_sockets.last_check = Some(Instant::now());
+
+        let dh = self.common.display_handle.clone();
+        let now = Instant::now();
+        let mut stalled = Vec::new();
+        client_sockets.sockets.retain(|id, socket| {
+            if dh.backend_handle().get_client_data(id.clone()).is_err() {
+                return false;
+            }
+
+            match socket.check(now) {
+                Ok(true) => {
+                    stalled.push(id.clone());
+                    false
+                }
+                Ok(false) => true,
+                Err(err) => {
+                    debug!(?err, client = ?id, "Failed to query client socket");
+                    true
+                }
+            }
+        });
+
+        for id in stalled {
+            warn!(client = ?id, "Client stopped reading its socket, disconnecting");
+            dh.backend_handle()
+                .kill_client(id, DisconnectReason::ConnectionClosed);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{queued_bytes, Socket, STALL_TIMEOUT};
+    use std::{
+        io::{ErrorKind, Read, Write},
+        os::unix::{io::AsFd, net::UnixStream},
+        time::{Duration, Instant},
+    };
+
+    /// Connected compositor and client sockets, with the compositor side tracked.
+    fn pair() -> (UnixStream, UnixStream, Socket) {
+        let (compositor, client) = UnixStream::pair().unwrap();
+        compositor.set_nonblocking(true).unwrap();
+        let socket = Socket::new(compositor.as_fd().try_clone_to_owned().unwrap());
+        (compositor, client, socket)
+    }
+
+    /// Writes to the client until its socket is full, like a flush with plenty of pending events.
+    fn flush(compositor: &mut UnixStream, socket: &mut Socket) {
+        socket.count_drained().unwrap();
+        loop {
+            match compositor.write(&[0; 4096]) {
+                Ok(_) => {}
+                Err(err) if err.kind() == ErrorKind::WouldBlock => break,
+                Err(err) => panic!("write failed: {}", err),
+            }
+        }
+        socket.flushed().unwrap();
+    }
+
+    #[test]
+    fn unread_data_is_queued() {
+        let (mut compositor, mut client, _) = pair();
+        let fd = compositor.as_fd().try_clone_to_owned().unwrap();
+        assert_eq!(queued_bytes(&fd).unwrap(), 0);
+
+        compositor.write_all(&[0; 1024]).unwrap();
+        assert!(queued_bytes(&fd).unwrap() > 0);
+
+        client.read_exact(&mut [0; 1024]).unwrap();
+        assert_eq!(queued_bytes(&fd).unwrap(), 0);
+    }
+
+    #[test]
+    fn stalled_client_is_detected() {
+        let (mut compositor, _client, mut socket) = pair();
+        let start = Instant::now();
+
+        // the client never reads, so every flush finds the socket full
+        for secs in 1..10 {
+            flush(&mut compositor, &mut socket);
+            assert!(!socket.check(start + Duration::from_secs(secs)).unwrap());
+        }
+        flush(&mut compositor, &mut socket);
+        assert!(socket.check(start + STALL_TIMEOUT).unwrap());
+    }
+
+    #[test]
+    fn reading_client_with_full_socket_is_not_stalled() {
+        let (mut compositor, mut client, mut socket) = pair();
+        let start = Instant::now();
+        flush(&mut compositor, &mut socket);
+        let full = queued_bytes(&socket.fd).unwrap();
+
+        // the client keeps reading, but every flush fills the socket up again
+        for secs in 1..=30 {
+            client.read_exact(&mut [0; 4096]).unwrap();
+            flush(&mut compositor, &mut socket);
+            assert_eq!(queued_bytes(&socket.fd).unwrap(), full);
+            assert!(!socket.check(start + Duration::from_secs(secs)).unwrap());
+        }
+    }
+
+    #[test]
+    fn reads_between_checks_are_counted() {
+        let (mut compositor, mut client, mut socket) = pair();
+        let start = Instant::now();
+        flush(&mut compositor, &mut socket);
+
+        assert!(!socket.check(start + Duration::from_secs(9)).unwrap());
+        // read before a flush, which isn't followed by a check
+        client.read_exact(&mut [0; 4096]).unwrap();
+        flush(&mut compositor, &mut socket);
+        assert!(!socket.check(start + STALL_TIMEOUT).unwrap());
+        assert!(socket.check(start + STALL_TIMEOUT + STALL_TIMEOUT).unwrap());
+    }
+
+    #[test]
+    fn idle_clients_are_not_stalled() {
+        let (_compositor, _client, mut socket) = pair();
+        let start = Instant::now();
+        assert!(!socket.check(start + STALL_TIMEOUT * 2).unwrap());
+    }
+}
@@ -0,0 +1,524 @@
+// SPDX-License-Identifier: GPL-3.0-only
+
+//! A compositor without backend and real wayland clients talking to it over a socket pair,
+//! for testing protocol handling end to end.
+
+use std::{
+    cell::RefCell,
+    ffi::OsString,
+    io::Write,
+    os::{
+        fd::{AsFd, FromRawFd, OwnedFd},
+        unix::net::UnixStream,
+    },
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+
+use calloop::EventLoop;
+use smithay::{
+    input::Seat,
+    output::{Mode, Output, PhysicalProperties, Scale, Subpixel},
+    reexports::wayland_server::{Client, Display, Resource},
+    utils::Transform,
+};
+use wayland_client::{
+    delegate_noop,
+    protocol::{
+        wl_buffer::WlBuffer,
+        wl_callback::{self, WlCallback},
+        wl_compositor::WlCompositor,
+        wl_output::{self, WlOutput},
+        wl_pointer::{self, WlPointer},
+        wl_region::WlRegion,
+        wl_registry::{self, WlRegistry},
+        wl_seat::WlSeat,
+        wl_shm::{self, WlShm},
+        wl_shm_pool::WlShmPool,
+        wl_subcompositor::WlSubcompositor,
+        wl_subsurface::WlSubsurface,
+        wl_surface::WlSurface,
+    },
+    Connection, Dispatch, EventQueue, Proxy, QueueHandle,
+};
+use wayland_protocols::xdg::{
+    foreign::zv2::client::{
+        zxdg_exported_v2::{self, ZxdgExportedV2},
+        zxdg_exporter_v2::ZxdgExporterV2,
+        zxdg_imported_v2::{self, ZxdgImportedV2},
+        zxdg_importer_v2::ZxdgImporterV2,
+    },
+    shell::client::{
+        xdg_popup::XdgPopup,
+        xdg_positioner::XdgPositioner,
+        xdg_surface::{self, XdgSurface},
+        xdg_toplevel::XdgToplevel,
+        xdg_wm_base::{self, XdgWmBase},
+    },
+    xdg_output::zv1::client::{
+        zxdg_output_manager_v1::ZxdgOutputManagerV1,
+        zxdg_output_v1::{self, ZxdgOutputV1},
+    },
+};
+
+use wayland_protocols_wlr::layer_shell::v1::client::{
+    zwlr_layer_shell_v1::ZwlrLayerShellV1,
+    zwlr_layer_surface_v1::{self, ZwlrLayerSurfaceV1},
+};
+
+use crate::{config::OutputConfig, state::State};
+
+/// The compositor state with a display, but without backend.
+///
+/// Nothing is rendered, so surfaces with buffers should be kept out of the shell.
+pub struct TestServer {
+    pub state: State,
+    display: Display<State>,
+    event_loop: EventLoop<'static, State>,
+}
+
+impl TestServer {
+    /// Creates a compositor with one 1920x1080 output and one seat.
+    pub fn new() -> TestServer {
+        let event_loop = EventLoop::try_new().unwrap();
+        let display = Display::new().unwrap();
+        let state = State::new(
+            &display.handle(),
+            OsString::from("wayland-test"),
+            event_loop.handle(),
+            event_loop.get_signal(),
+        );
+        let mut server = TestServer {
+            state,
+            display,
+            event_loop,
+        };
+        server.add_output("TEST-1", (0, 0));
+        server.add_seat("seat-0");
+        server
+    }
+
+    /// Adds a 1920x1080 output at `location`, like a backend would on hotplug.
+    pub fn add_output(&mut self, name: &str, location: (i32, i32)) -> Output {
+        let output = create_output(name, location);
+        let common = &mut self.state.common;
+        common
+            .output_configuration_state
+            .add_heads(std::iter::once(&output));
+        common.output_configuration_state.update();
+        common.shell.add_output(&output);
+        output
+    }
+
+    /// Adds a seat, which starts out on the first output.
+    pub fn add_seat(&mut self, name: &str) -> Seat<State> {
+        let common = &mut self.state.common;
+        let output = common.shell.outputs().next().unwrap().clone();
+        let seat = crate::input::add_seat(
+            &common.display_handle,
+            &mut common.seat_state,
+            &output,
+            &common.config,
+            name.to_string(),
+        );
+        common.add_seat(seat.clone());
+        seat
+    }
+
+    /// Connects a new client, that already received the globals.
+    pub fn connect(&mut self) -> TestClient {
+        let (server_stream, client_stream) = UnixStream::pair().unwrap();
+        let client_state = self.state.new_client_state();
+        let client = self
+            .state
+            .insert_client(server_stream, client_state)
+            .unwrap();
+        let mut client = TestClient::new(client, client_stream);
+        self.roundtrip(&mut client);
+        client
+    }
+
+    /// Handles all pending requests of all clients and sends the resulting events.
+    pub fn dispatch(&mut self) {
+        self.display.dispatch_clients(&mut self.state).unwrap();
+        self.event_loop
+            .dispatch(Duration::ZERO, &mut self.state)
+            .unwrap();
+        self.state.flush_clients();
+    }
+
+    /// Lets `client` and the server exchange messages, until the server handled all requests
+    /// `client` sent so far and the client handled all events in response.
+    pub fn roundtrip(&mut self, client: &mut TestClient) {
+        let done = client.sync();
+        for _ in 0..16 {
+            self.dispatch();
+            client.dispatch();
+            if done.load(Ordering::SeqCst) {
+                return;
+            }
+        }
+        panic!("The client didn't get a response from the server");
+    }
+
+    /// The object `proxy` of `client` refers to on the server side.
+    pub fn object<I: Resource + 'static>(&self, client: &TestClient, proxy: &impl Proxy) -> I {
+        client
+            .client
+            .object_from_protocol_id(&self.display.handle(), proxy.id().protocol_id())
+            .unwrap()
+    }
+}
+
+/// Creates a 1920x1080 output at `location`, that isn't known to the compositor.
+///
+/// Surfaces can be mapped on it, e.g. with `layer_map_for_output`, without being rendered.
+pub fn create_output(name: &str, location: (i32, i32)) -> Output {
+    let output = Output::new(
+        name.to_string(),
+        PhysicalProperties {
+            size: (600, 340).into(),
+            subpixel: Subpixel::Unknown,
+            make: String::from("COSMIC"),
+            model: name.to_string(),
+        },
+    );
+    let mode = Mode {
+        size: (1920, 1080).into(),
+        refresh: 60_000,
+    };
+    output.add_mode(mode);
+    output.set_preferred(mode);
+    output.change_current_state(
+        Some(mode),
+        Some(Transform::Normal),
+        Some(Scale::Integer(1)),
+        Some(location.into()),
+    );
+    output.user_data().insert_if_missing(|| {
+        RefCell::new(OutputConfig {
+            mode: ((1920, 1080), Some(60_000)),
+            position: location,
+            ..Default::default()
+        })
+    });
+    output
+}
+
+/// Events of a `TestClient`, that tests are interested in
+#[derive(Debug)]
+pub enum ClientEvent {
+    Output(wl_output::Event),
+    XdgOutput(zxdg_output_v1::Event),
+    Exported(String),
+    ImportedDestroyed,
+    Pointer(wl_pointer::Event),
+}
+
+#[derive(Debug, Default)]
+pub struct ClientData {
+    globals: Vec<(u32, String, u32)>,
+    events: Vec<ClientEvent>,
+}
+
+/// A wayland client connected to a `TestServer`
+pub struct TestClient {
+    /// The client as seen by the server
+    client: Client,
+    conn: Connection,
+    queue: EventQueue<ClientData>,
+    registry: WlRegistry,
+    data: ClientData,
+}
+
+impl TestClient {
+    fn new(client: Client, stream: UnixStream) -> TestClient {
+        let conn = Connection::from_socket(stream).unwrap();
+        let queue = conn.new_event_queue();
+        let registry = conn.display().get_registry(&queue.handle(), ());
+        TestClient {
+            client,
+            conn,
+            queue,
+            registry,
+            data: ClientData::default(),
+        }
+    }
+
+    pub fn handle(&self) -> QueueHandle<ClientData> {
+        self.queue.handle()
+    }
+
+    /// Binds the global of `I` in at most `version`.
+    pub fn bind<I>(&self, version: u32) -> I
+    where
+        I: Proxy + 'static,
+        ClientData: Dispatch<I, ()>,
+    {
+        self.bind_all(version)
+            .into_iter()
+            .next()
+            .unwrap_or_else(|| panic!("{} is not advertised", I::interface().name))
+    }
+
+    /// Binds every global of `I` (e.g. one per seat) in at most `version`,
+    /// in the order they were advertised.
+    pub fn bind_all<I>(&self, version: u32) -> Vec<I>
+    where
+        I: Proxy + 'static,
+        ClientData: Dispatch<I, ()>,
+    {
+        self.data
+            .globals
+            .iter()
+            .filter(|(_, interface, _)| interface == I::interface().name)
+            .map(|(name, _, advertised)| {
+                self.registry
+                    .bind(*name, version.min(*advertised), &self.handle(), ())
+            })
+            .collect()
+    }
+
+    /// Creates a toplevel, that still needs its initial commit.
+    pub fn create_toplevel(&self) -> (WlSurface, XdgToplevel) {
+        let qh = self.handle();
+        let surface = self.bind::<WlCompositor>(5).create_surface(&qh, ());
+        let xdg_surface = self.bind::<XdgWmBase>(2).get_xdg_surface(&surface, &qh, ());
+        (surface, xdg_surface.get_toplevel(&qh, ()))
+    }
+
+    /// Creates an opaque shm buffer of the given size.
+    pub fn create_buffer(&self, width: i32, height: i32) -> WlBuffer {
+        let size = width * height * 4;
+        // SAFETY: memfd_create returns a new fd, which nobody else owns, or -1
+        let fd = unsafe {
+            libc::memfd_create(
+                b"cosmic-comp-test\0".as_ptr() as *const libc::c_char,
+                libc::MFD_CLOEXEC,
+            )
+        };
+        assert!(fd >= 0, "Failed to create shm file");
+        let mut file = std::fs::File::from(unsafe { OwnedFd::from_raw_fd(fd) });
+        file.write_all(&vec![0xff; size as usize]).unwrap();
+
+        let qh = self.handle();
+        let pool = self
+            .bind::<WlShm>(1)
+            .create_pool(file.as_fd(), size, &qh, ());
+        let buffer = pool.create_buffer(
+            0,
+            width,
+            height,
+            width * 4,
+            wl_shm::Format::Argb8888,
+            &qh,
+            (),
+        );
+        pool.destroy();
+        buffer
+    }
+
+    /// Creates a region made of the given rectangles.
+    pub fn create_region(&self, rects: &[(i32, i32, i32, i32)]) -> WlRegion {
+        let region = self
+            .bind::<WlCompositor>(5)
+            .create_region(&self.handle(), ());
+        for (x, y, width, height) in rects {
+            region.add(*x, *y, *width, *height);
+        }
+        region
+    }
+
+    /// Removes and returns the events received so far.
+    pub fn take_events(&mut self) -> Vec<ClientEvent> {
+        std::mem::take(&mut self.data.events)
+    }
+
+    fn sync(&self) -> Arc<AtomicBool> {
+        let done = Arc::new(AtomicBool::new(false));
+        self.conn.display().sync(&self.handle(), done.clone());
+        let _ = self.conn.flush();
+        done
+    }
+
+    fn dispatch(&mut self) {
+        if let Some(guard) = self.conn.prepare_read() {
+            let _ = guard.read();
+        }
+        self.queue.dispatch_pending(&mut self.data).unwrap();
+        // send replies, like acks of configures
+        let _ = self.conn.flush();
+    }
+}
+
+impl Dispatch<WlRegistry, ()> for ClientData {
+    fn event(
+        data: &mut Self,
+        _registry: &WlRegistry,
+        event: wl_registry::Event,
+        _: &(),
+        _conn: &Connection,
+        _qh: &QueueHandle<Self>,
+    ) {
+        match event {
+            wl_registry::Event::Global {
+                name,
+                interface,
+                version,
+            } => data.globals.push((name, interface, version)),
+            wl_registry::Event::GlobalRemove { name } => {
+                data.globals.retain(|(global, _, _)| *global != name)
+            }
+            _ => {}
+        }
+    }
+}
+
+impl Dispatch<WlCallback, Arc<AtomicBool>> for ClientData {
+    fn event(
+        _data: &mut Self,
+        _callback: &WlCallback,
+        event: wl_callback::Event,
+        done: &Arc<AtomicBool>,
+        _conn: &Connection,
+        _qh: &QueueHandle<Self>,
+    ) {
+        if let wl_callback::Event::Done { .. } = event {
+            done.store(true, Ordering::SeqCst);
+        }
+    }
+}
+
+impl Dispatch<WlOutput, ()> for ClientData {
+    fn event(
+        data: &mut Self,
+        _output: &WlOutput,
+        event: wl_output::Event,
+        _: &(),
+        _conn: &Connection,
+        _qh: &QueueHandle<Self>,
+    ) {
+        data.events.push(ClientEvent::Output(event));
+    }
+}
+
+impl Dispatch<ZxdgOutputV1, ()> for ClientData {
+    fn event(
+        data: &mut Self,
+        _xdg_output: &ZxdgOutputV1,
+        event: zxdg_output_v1::Event,
+        _: &(),
+        _conn: &Connection,
+        _qh: &QueueHandle<Self>,
+    ) {
+        data.events.push(ClientEvent::XdgOutput(event));
+    }
+}
+
+impl Dispatch<ZxdgExportedV2, ()> for ClientData {
+    fn event(
+        data: &mut Self,
+        _exported: &ZxdgExportedV2,
+        event: zxdg_exported_v2::Event,
+        _: &(),
+        _conn: &Connection,
+        _qh: &QueueHandle<Self>,
+    ) {
+        if let zxdg_exported_v2::Event::Handle { handle } = event {
+            data.events.push(ClientEvent::Exported(handle));
+        }
+    }
+}
+
+impl Dispatch<ZxdgImportedV2, ()> for ClientData {
+    fn event(
+        data: &mut Self,
+        _imported: &ZxdgImportedV2,
+        event: zxdg_imported_v2::Event,
+        _: &(),
+        _conn: &Connection,
+        _qh: &QueueHandle<Self>,
+    ) {
+        if let zxdg_imported_v2::Event::Destroyed = event {
+            data.events.push(ClientEvent::ImportedDestroyed);
+        }
+    }
+}
+
+impl Dispatch<WlPointer, ()> for ClientData {
+    fn event(
+        data: &mut Self,
+        _pointer: &WlPointer,
+        event: wl_pointer::Event,
+        _: &(),
+        _conn: &Connection,
+        _qh: &QueueHandle<Self>,
+    ) {
+        data.events.push(ClientEvent::Pointer(event));
+    }
+}
+
+impl Dispatch<XdgWmBase, ()> for ClientData {
+    fn event(
+        _data: &mut Self,
+        wm_base: &XdgWmBase,
+        event: xdg_wm_base::Event,
+        _: &(),
+        _conn: &Connection,
+        _qh: &QueueHandle<Self>,
+    ) {
+        if let xdg_wm_base::Event::Ping { serial } = event {
+            wm_base.pong(serial);
+        }
+    }
+}
+
+impl Dispatch<XdgSurface, ()> for ClientData {
+    fn event(
+        _data: &mut Self,
+        xdg_surface: &XdgSurface,
+        event: xdg_surface::Event,
+        _: &(),
+        _conn: &Connection,
+        _qh: &QueueHandle<Self>,
+    ) {
+        if let xdg_surface::Event::Configure { serial } = event {
+            xdg_surface.ack_configure(serial);
+        }
+    }
+}
+
+impl Dispatch<ZwlrLayerSurfaceV1, ()> for ClientData {
+    fn event(
+        _data: &mut Self,
+        layer_surface: &ZwlrLayerSurfaceV1,
+        event: zwlr_layer_surface_v1::Event,
+        _: &(),
+        _conn: &Connection,
+        _qh: &QueueHandle<Self>,
+    ) {
+        if let zwlr_layer_surface_v1::Event::Configure { serial, .. } = event {
+            layer_surface.ack_configure(serial);
+        }
+    }
+}
+
+delegate_noop!(ClientData: WlCompositor);
+delegate_noop!(ClientData: WlSubcompositor);
+delegate_noop!(ClientData: WlSubsurface);
+delegate_noop!(ClientData: WlRegion);
+delegate_noop!(ClientData: WlShmPool);
+delegate_noop!(ClientData: XdgPositioner);
+delegate_noop!(ClientData: ZxdgOutputManagerV1);
+delegate_noop!(ClientData: ZxdgExporterV2);
+delegate_noop!(ClientData: ZxdgImporterV2);
+delegate_noop!(ClientData: ZwlrLayerShellV1);
+delegate_noop!(ClientData: ignore WlSeat);
+delegate_noop!(ClientData: ignore WlShm);
+delegate_noop!(ClientData: ignore WlBuffer);
+delegate_noop!(ClientData: ignore WlSurface);
+delegate_noop!(ClientData: ignore XdgToplevel);
+delegate_noop!(ClientData: ignore XdgPopup);
@@ -3,7 +3,7 @@
 use crate::{
     shell::CosmicSurface,
     state::{BackendData, ClientState},
-    utils::prelude::*,
+    utils::{commit_stats, prelude::*},
     wayland::protocols::screencopy::SessionType,
 };
 use calloop::Interest;
@@ -30,7 +30,10 @@ use smithay::{
 };
 use std::sync::Mutex;
 
-use super::screencopy::PendingScreencopyBuffers;
+use super::{
+    screencopy::PendingScreencopyBuffers,
+    single_pixel_buffer::{is_solid_color, update_solid_color},
+};
 
 impl State {
     fn early_import_surface(&mut self, surface: &WlSurface) {
@@ -166,9 +169,23 @@ impl CompositorHandler for State {
     }
 
     fn commit(&mut self, surface: &WlSurface) {
+        #[cfg(feature = "puffin")]
+        puffin::profile_function!();
+        let _span = tracing::trace_span!("commit", surface = ?surface.id()).entered();
+
         X11Wm::commit_hook::<State>(surface);
+        let new_buffer = with_states(surface, |states| {
+            matches!(
+                states.cached_state.current::<SurfaceAttributes>().buffer,
+                Some(BufferAssignment::NewBuffer(_))
+            )
+        });
         // first load the buffer for various smithay helper functions
         on_commit_buffer_handler::<Self>(surface);
+        if new_buffer {
+            update_solid_color(surface);
+        }
+        commit_stats::record_commit(surface, new_buffer);
 
         // then handle initial configure events and map windows if necessary
         if let Some((window, _, _)) = self
@@ -226,6 +243,12 @@ impl CompositorHandler for State {
                 );
                 workspace.commit(surface);
             }
+            if let Some((window, _)) = element
+                .windows()
+                .find(|(window, _)| window.wl_surface().as_ref() == Some(surface))
+            {
+                self.common.update_window_identity(&window);
+            }
         }
 
         //handle window screencopy sessions
@@ -233,7 +256,10 @@ impl CompositorHandler for State {
 
         // We need to know every potential output for importing to the right gpu and scheduling a render,
         // so call this only after every potential surface map operation has been done.
-        self.early_import_surface(surface);
+        // Solid colors are drawn without their buffer, so there is nothing to import.
+        if !is_solid_color(surface) {
+            self.early_import_surface(surface);
+        }
 
         // and refresh smithays internal state
         self.common.shell.popups.commit(surface);
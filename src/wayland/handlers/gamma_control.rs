@@ -0,0 +1,39 @@
+// SPDX-License-Identifier: GPL-3.0-only
+
+use smithay::output::Output;
+use tracing::warn;
+
+use crate::{
+    state::{BackendData, State},
+    wayland::protocols::gamma_control::{
+        delegate_gamma_control, GammaControlHandler, GammaControlState,
+    },
+};
+
+impl GammaControlHandler for State {
+    fn gamma_control_state(&mut self) -> &mut GammaControlState {
+        &mut self.common.gamma_control_state
+    }
+
+    fn gamma_size(&mut self, output: &Output) -> Option<u32> {
+        match &mut self.backend {
+            BackendData::Kms(state) => state.gamma_size(output).ok(),
+            _ => None,
+        }
+    }
+
+    fn set_gamma(&mut self, output: &Output, ramp: Option<Vec<u16>>) -> bool {
+        match &mut self.backend {
+            BackendData::Kms(state) => match state.set_gamma(output, ramp) {
+                Ok(()) => true,
+                Err(err) => {
+                    warn!(?err, output = output.name(), "Failed to set gamma ramp.");
+                    false
+                }
+            },
+            _ => false,
+        }
+    }
+}
+
+delegate_gamma_control!(State);
@@ -0,0 +1,77 @@
+// SPDX-License-Identifier: GPL-3.0-only
+
+use calloop::timer::{TimeoutAction, Timer};
+use smithay::wayland::compositor::get_parent;
+
+use crate::{
+    state::State,
+    wayland::protocols::idle_notify::{delegate_idle_notify, IdleNotifierState, IdleNotifyHandler},
+};
+
+impl IdleNotifyHandler for State {
+    fn idle_notifier_state(&mut self) -> &mut IdleNotifierState {
+        &mut self.common.idle_notifier_state
+    }
+
+    fn idle_notification_created(&mut self) {
+        self.arm_idle_timer();
+    }
+}
+
+impl State {
+    /// Restarts the idle timeouts, called for every input event.
+    pub fn notify_activity(&mut self) {
+        self.common.idle_notifier_state.notify_activity();
+        // a running timer just picks up the later deadline
+        if self.common.idle_timer.is_none() {
+            self.arm_idle_timer();
+        }
+    }
+
+    /// Inhibits idling, while a surface with an idle inhibitor is visible.
+    pub fn refresh_idle_inhibit(&mut self) {
+        let inhibited = self
+            .common
+            .idle_inhibit_state
+            .surfaces()
+            .any(|mut surface| {
+                while let Some(parent) = get_parent(&surface) {
+                    surface = parent;
+                }
+                self.common
+                    .shell
+                    .visible_outputs_for_surface(&surface)
+                    .next()
+                    .is_some()
+            });
+        if self.common.idle_notifier_state.set_inhibited(inhibited) {
+            self.arm_idle_timer();
+        }
+    }
+
+    /// (Re-)starts the timer sending out idle notifications, once the next one is due.
+    fn arm_idle_timer(&mut self) {
+        if let Some(token) = self.common.idle_timer.take() {
+            self.common.event_loop_handle.remove(token);
+        }
+        let Some(deadline) = self.common.idle_notifier_state.next_deadline() else {
+            return;
+        };
+        self.common.idle_timer = self
+            .common
+            .event_loop_handle
+            .insert_source(Timer::from_deadline(deadline), |_, _, state| {
+                state.common.idle_notifier_state.refresh();
+                match state.common.idle_notifier_state.next_deadline() {
+                    Some(deadline) => TimeoutAction::ToInstant(deadline),
+                    None => {
+                        state.common.idle_timer = None;
+                        TimeoutAction::Drop
+                    }
+                }
+            })
+            .ok();
+    }
+}
+
+delegate_idle_notify!(State);
@@ -0,0 +1,49 @@
+// SPDX-License-Identifier: GPL-3.0-only
+
+use crate::state::State;
+use smithay::{
+    delegate_input_method_manager, delegate_text_input_manager,
+    desktop::{space::SpaceElement, PopupKind, PopupManager},
+    reexports::wayland_server::protocol::wl_surface::WlSurface,
+    utils::{Logical, Rectangle},
+    wayland::{
+        input_method::{InputMethodHandler, PopupSurface},
+        seat::WaylandFocus,
+    },
+};
+use tracing::warn;
+
+impl InputMethodHandler for State {
+    fn new_popup(&mut self, surface: PopupSurface) {
+        if let Err(err) = self
+            .common
+            .shell
+            .popups
+            .track_popup(PopupKind::from(surface))
+        {
+            warn!(?err, "Failed to track input method popup");
+        }
+    }
+
+    fn dismiss_popup(&mut self, surface: PopupSurface) {
+        if let Some(parent) = surface.get_parent().map(|parent| parent.surface.clone()) {
+            let _ = PopupManager::dismiss_popup(&parent, &PopupKind::from(surface));
+        }
+    }
+
+    fn parent_geometry(&self, parent: &WlSurface) -> Rectangle<i32, Logical> {
+        self.common
+            .shell
+            .element_for_wl_surface(parent)
+            .and_then(|mapped| {
+                mapped
+                    .windows()
+                    .find(|(window, _)| window.wl_surface().as_ref() == Some(parent))
+            })
+            .map(|(window, _)| SpaceElement::geometry(&window))
+            .unwrap_or_default()
+    }
+}
+
+delegate_input_method_manager!(State);
+delegate_text_input_manager!(State);
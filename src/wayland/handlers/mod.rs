@@ -2,11 +2,16 @@
 
 pub mod buffer;
 pub mod compositor;
+pub mod content_type;
 pub mod data_device;
 pub mod decoration;
 pub mod dmabuf;
 pub mod drm_lease;
 pub mod fractional_scale;
+pub mod gamma_control;
+pub mod idle_inhibit;
+pub mod idle_notify;
+pub mod input_method;
 pub mod keyboard_shortcuts_inhibit;
 pub mod layer_shell;
 pub mod output;
@@ -22,10 +27,12 @@ pub mod security_context;
 pub mod selection;
 pub mod session_lock;
 pub mod shm;
+pub mod single_pixel_buffer;
 pub mod toplevel_info;
 pub mod toplevel_management;
 pub mod viewporter;
 pub mod wl_drm;
 pub mod workspace;
+pub mod xdg_foreign;
 pub mod xdg_shell;
 pub mod xwayland_keyboard_grab;
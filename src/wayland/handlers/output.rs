@@ -4,3 +4,143 @@ use crate::state::State;
 use smithay::delegate_output;
 
 delegate_output!(State);
+
+#[cfg(test)]
+mod tests {
+    use smithay::output::Scale;
+    use wayland_client::{
+        protocol::wl_output::{self, WlOutput},
+        WEnum,
+    };
+    use wayland_protocols::xdg::xdg_output::zv1::client::{
+        zxdg_output_manager_v1::ZxdgOutputManagerV1, zxdg_output_v1,
+    };
+
+    use crate::utils::testing::{ClientEvent, TestClient, TestServer};
+
+    /// Takes the events of one update and checks, that they are concluded by exactly one `done`.
+    fn take_update(client: &mut TestClient) -> Vec<ClientEvent> {
+        let mut events = client.take_events();
+        assert!(
+            matches!(
+                events.pop(),
+                Some(ClientEvent::Output(wl_output::Event::Done))
+            ),
+            "update isn't concluded by done: {:?}",
+            events
+        );
+        assert!(
+            !events
+                .iter()
+                .any(|event| matches!(event, ClientEvent::Output(wl_output::Event::Done))),
+            "update is split by done: {:?}",
+            events
+        );
+        events
+    }
+
+    #[test]
+    fn wl_output_describes_the_output() {
+        let mut server = TestServer::new();
+        let mut client = server.connect();
+        client.bind::<WlOutput>(4);
+        server.roundtrip(&mut client);
+
+        let events = take_update(&mut client);
+        assert!(events.iter().any(|event| matches!(
+            event,
+            ClientEvent::Output(wl_output::Event::Geometry { x: 0, y: 0, make, model, .. })
+                if make == "COSMIC" && model == "TEST-1"
+        )));
+        assert!(events.iter().any(|event| matches!(
+            event,
+            ClientEvent::Output(wl_output::Event::Mode {
+                flags: WEnum::Value(flags),
+                width: 1920,
+                height: 1080,
+                refresh: 60_000,
+            }) if flags.contains(wl_output::Mode::Current)
+        )));
+        assert!(events.iter().any(|event| matches!(
+            event,
+            ClientEvent::Output(wl_output::Event::Scale { factor: 1 })
+        )));
+        assert!(events.iter().any(|event| matches!(
+            event,
+            ClientEvent::Output(wl_output::Event::Name { name }) if name == "TEST-1"
+        )));
+        assert!(events.iter().any(|event| matches!(
+            event,
+            ClientEvent::Output(wl_output::Event::Description { description })
+                if description.contains("COSMIC") && description.contains("TEST-1")
+        )));
+    }
+
+    #[test]
+    fn xdg_output_has_the_logical_geometry() {
+        let mut server = TestServer::new();
+        let mut client = server.connect();
+        let wl_output = client.bind::<WlOutput>(4);
+        server.roundtrip(&mut client);
+        take_update(&mut client);
+
+        client
+            .bind::<ZxdgOutputManagerV1>(3)
+            .get_xdg_output(&wl_output, &client.handle(), ());
+        server.roundtrip(&mut client);
+
+        let events = take_update(&mut client);
+        assert!(events.iter().any(|event| matches!(
+            event,
+            ClientEvent::XdgOutput(zxdg_output_v1::Event::LogicalPosition { x: 0, y: 0 })
+        )));
+        assert!(events.iter().any(|event| matches!(
+            event,
+            ClientEvent::XdgOutput(zxdg_output_v1::Event::LogicalSize {
+                width: 1920,
+                height: 1080,
+            })
+        )));
+        assert!(events.iter().any(|event| matches!(
+            event,
+            ClientEvent::XdgOutput(zxdg_output_v1::Event::Name { name }) if name == "TEST-1"
+        )));
+    }
+
+    #[test]
+    fn reconfiguration_is_sent_atomically() {
+        let mut server = TestServer::new();
+        let output = server.state.common.shell.outputs().next().unwrap().clone();
+        let mut client = server.connect();
+        let wl_output = client.bind::<WlOutput>(4);
+        client
+            .bind::<ZxdgOutputManagerV1>(3)
+            .get_xdg_output(&wl_output, &client.handle(), ());
+        server.roundtrip(&mut client);
+        client.take_events();
+
+        output.change_current_state(None, None, Some(Scale::Integer(2)), Some((100, 0).into()));
+        server.roundtrip(&mut client);
+
+        let events = take_update(&mut client);
+        assert!(events.iter().any(|event| matches!(
+            event,
+            ClientEvent::Output(wl_output::Event::Geometry { x: 100, y: 0, .. })
+        )));
+        assert!(events.iter().any(|event| matches!(
+            event,
+            ClientEvent::Output(wl_output::Event::Scale { factor: 2 })
+        )));
+        assert!(events.iter().any(|event| matches!(
+            event,
+            ClientEvent::XdgOutput(zxdg_output_v1::Event::LogicalPosition { x: 100, y: 0 })
+        )));
+        assert!(events.iter().any(|event| matches!(
+            event,
+            ClientEvent::XdgOutput(zxdg_output_v1::Event::LogicalSize {
+                width: 960,
+                height: 540,
+            })
+        )));
+    }
+}
@@ -28,7 +28,8 @@ impl OutputConfigurationHandler for State {
 }
 
 impl State {
-    fn output_configuration(
+    /// Applies and persists `conf`, restoring the previous configuration on failure.
+    pub fn output_configuration(
         &mut self,
         test_only: bool,
         conf: Vec<(Output, OutputConfiguration)>,
@@ -61,9 +62,11 @@ impl State {
                         Some(ModeConfiguration::Mode(mode)) => {
                             current_config.mode =
                                 ((mode.size.w, mode.size.h), Some(mode.refresh as u32));
+                            current_config.modeline = None;
                         }
                         Some(ModeConfiguration::Custom { size, refresh }) => {
                             current_config.mode = ((size.w, size.h), refresh.map(|x| x as u32));
+                            current_config.modeline = None;
                         }
                         _ => {}
                     }
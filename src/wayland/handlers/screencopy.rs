@@ -1,12 +1,16 @@
 use std::{
     borrow::Borrow,
-    cell::RefCell,
+    cell::{Cell, RefCell},
     collections::HashSet,
     ops::{Deref, DerefMut},
+    time::{Duration, Instant},
 };
 
 use anyhow::anyhow;
-use calloop::LoopHandle;
+use calloop::{
+    timer::{TimeoutAction, Timer},
+    LoopHandle,
+};
 use cosmic_protocols::screencopy::v1::server::zcosmic_screencopy_session_v1::{
     FailureReason, InputType,
 };
@@ -63,6 +67,10 @@ pub const WORKSPACE_OVERVIEW_NAMESPACE: &str = "cosmic-workspace-overview";
 
 pub type PendingScreencopyBuffers = RefCell<Vec<(Session, BufferParams)>>;
 
+/// Time the last capture of a toplevel was scheduled, see `StaticConfig::toplevel_capture_fps`
+#[derive(Debug, Default)]
+struct LastToplevelCapture(Cell<Option<Instant>>);
+
 #[derive(Debug, Default)]
 pub struct ScreencopySessions(pub RefCell<Vec<DropableSession>>);
 
@@ -164,6 +172,15 @@ impl ScreencopyHandler for State {
     }
 
     fn capture_toplevel(&mut self, toplevel: CosmicSurface, session: Session) -> Vec<BufferInfo> {
+        // contents of individual windows are only handed to clients started by the session
+        if !session
+            .client()
+            .and_then(|client| client.get_data::<ClientState>().map(|data| data.privileged))
+            .unwrap_or(false)
+        {
+            session.failed(FailureReason::InvalidToplevel);
+            return Vec::new();
+        }
         let Some(surface) = toplevel.wl_surface() else {
             return Vec::new();
         };
@@ -607,7 +624,7 @@ where
         usize,
     ) -> Result<RenderOutputResult, DTError<R>>,
 {
-    #[cfg(feature = "debug")]
+    #[cfg(feature = "puffin")]
     puffin::profile_function!();
 
     let mut dt = session.user_data().get::<SessionDT>().unwrap().borrow_mut();
@@ -918,7 +935,7 @@ pub fn render_window_to_buffer(
     params: BufferParams,
     window: &CosmicSurface,
 ) -> Result<bool, (FailureReason, anyhow::Error)> {
-    #[cfg(feature = "debug")]
+    #[cfg(feature = "puffin")]
     puffin::profile_function!();
 
     let geometry = window.geometry();
@@ -1176,9 +1193,13 @@ impl State {
         {
             let active = element.active_window();
             if active.wl_surface().as_ref() == Some(surface) {
+                let delay = toplevel_capture_delay(
+                    &active,
+                    self.common.config.static_conf.toplevel_capture_fps,
+                );
                 for (session, params) in active.pending_buffers() {
                     let window = active.clone();
-                    self.common.event_loop_handle.insert_idle(move |state| {
+                    let render = move |state: &mut State| {
                         if !session.alive() {
                             return;
                         }
@@ -1192,7 +1213,24 @@ impl State {
                                 session.failed(reason);
                             }
                         }
-                    });
+                    };
+                    match delay {
+                        Some(delay) => {
+                            let mut render = Some(render);
+                            let _ = self.common.event_loop_handle.insert_source(
+                                Timer::from_duration(delay),
+                                move |_, _, state| {
+                                    if let Some(render) = render.take() {
+                                        render(state);
+                                    }
+                                    TimeoutAction::Drop
+                                },
+                            );
+                        }
+                        None => {
+                            self.common.event_loop_handle.insert_idle(render);
+                        }
+                    }
                 }
             }
         }
@@ -1325,3 +1363,20 @@ pub fn schedule_offscreen_workspace_session(
 }
 
 delegate_screencopy!(State);
+
+/// Time to wait before capturing `window` again, to stay below `fps` captures per second.
+fn toplevel_capture_delay(window: &CosmicSurface, fps: Option<u32>) -> Option<Duration> {
+    let interval = Duration::from_secs(1) / fps.filter(|fps| *fps > 0)?;
+    window
+        .user_data()
+        .insert_if_missing(LastToplevelCapture::default);
+    let last = window.user_data().get::<LastToplevelCapture>().unwrap();
+    let now = Instant::now();
+    let next = last
+        .0
+        .get()
+        .map(|at| at + interval)
+        .filter(|next| *next > now);
+    last.0.set(Some(next.unwrap_or(now)));
+    next.map(|next| next - now)
+}
@@ -1,13 +1,14 @@
 // SPDX-License-Identifier: GPL-3.0-only
 
 use crate::{
+    config::DataDeviceFocus,
     shell::focus::target::{KeyboardFocusTarget, PointerFocusTarget},
     state::State,
 };
 use smithay::{
     delegate_seat,
-    input::{pointer::CursorImageStatus, SeatHandler, SeatState},
-    reexports::wayland_server::Resource,
+    input::{pointer::CursorImageStatus, Seat, SeatHandler, SeatState},
+    reexports::wayland_server::{backend::ClientId, Resource},
     wayland::{
         seat::WaylandFocus, selection::data_device::set_data_device_focus,
         selection::primary_selection::set_primary_focus,
@@ -40,6 +41,9 @@ impl SeatHandler for State {
         seat: &smithay::input::Seat<Self>,
         focused: Option<&Self::KeyboardFocus>,
     ) {
+        if self.common.config.static_conf.data_device_focus == DataDeviceFocus::Pointer {
+            return;
+        }
         let dh = &self.common.display_handle;
         if let Some(client) = focused
             .and_then(|t| t.wl_surface())
@@ -52,3 +56,35 @@ impl SeatHandler for State {
 }
 
 delegate_seat!(State);
+
+/// Client that last got the selection focus from the pointer of a seat
+#[derive(Default, Debug)]
+pub struct PointerSelectionFocus(RefCell<Option<ClientId>>);
+
+impl State {
+    /// Gives the clipboard and primary selection focus of `seat` to the client below its pointer,
+    /// if they are configured to follow the pointer.
+    pub fn update_pointer_selection_focus(&mut self, seat: &Seat<State>) {
+        if self.common.config.static_conf.data_device_focus == DataDeviceFocus::Keyboard {
+            return;
+        }
+        let dh = &self.common.display_handle;
+        let Some(client) = seat
+            .get_pointer()
+            .unwrap()
+            .current_focus()
+            .and_then(|t| t.wl_surface())
+            .and_then(|s| dh.get_client(s.id()).ok())
+        else {
+            return;
+        };
+
+        let last = &seat.user_data().get::<PointerSelectionFocus>().unwrap().0;
+        if last.borrow().as_ref() == Some(&client.id()) {
+            return;
+        }
+        *last.borrow_mut() = Some(client.id());
+        set_data_device_focus(dh, seat, Some(client.clone()));
+        set_primary_focus(dh, seat, Some(client))
+    }
+}
@@ -5,7 +5,6 @@ use smithay::{
         SecurityContext, SecurityContextHandler, SecurityContextListenerSource,
     },
 };
-use std::sync::Arc;
 use tracing::warn;
 
 impl SecurityContextHandler for State {
@@ -17,13 +16,11 @@ impl SecurityContextHandler for State {
         self.common
             .event_loop_handle
             .insert_source(source, move |client_stream, _, state| {
-                if let Err(err) = state.common.display_handle.insert_client(
-                    client_stream,
-                    Arc::new(ClientState {
-                        security_context: Some(security_context.clone()),
-                        ..state.new_client_state()
-                    }),
-                ) {
+                let client_state = ClientState {
+                    security_context: Some(security_context.clone()),
+                    ..state.new_client_state()
+                };
+                if let Err(err) = state.insert_client(client_stream, client_state) {
                     warn!(?err, "Error adding wayland client");
                 };
             })
@@ -3,20 +3,101 @@
 use crate::state::State;
 use smithay::{
     input::Seat,
-    wayland::selection::{SelectionHandler, SelectionSource, SelectionTarget},
+    reexports::calloop::{generic::Generic, Interest, Mode, PostAction},
+    wayland::selection::{
+        data_device::{request_data_device_client_selection, set_data_device_selection},
+        primary_selection::{request_primary_client_selection, set_primary_selection},
+        SelectionHandler, SelectionSource, SelectionTarget,
+    },
     xwayland::xwm::XwmId,
 };
-use std::os::unix::io::OwnedFd;
-use tracing::warn;
+use std::{
+    cell::RefCell,
+    collections::HashMap,
+    fs::File,
+    io::{ErrorKind, Read, Write},
+    os::unix::io::{FromRawFd, OwnedFd},
+    sync::Arc,
+};
+use tracing::{debug, warn};
+
+/// Who is answering requests for a selection not owned by a wayland client.
+#[derive(Debug, Clone, PartialEq)]
+pub enum SelectionOwner {
+    Xwayland(XwmId),
+    /// Data copied by a client, that we cached to outlive it
+    Persisted,
+}
+
+/// Per-seat cache of the last copied selections.
+#[derive(Debug, Default)]
+pub struct ClipboardCache(RefCell<ClipboardCacheInner>);
+
+#[derive(Debug, Default)]
+struct ClipboardCacheInner {
+    generation: usize,
+    clipboard: Option<CachedSelection>,
+    primary: Option<CachedSelection>,
+}
+
+#[derive(Debug)]
+struct CachedSelection {
+    generation: usize,
+    pending: usize,
+    data: HashMap<String, Arc<[u8]>>,
+}
+
+impl ClipboardCacheInner {
+    fn slot(&mut self, target: SelectionTarget) -> &mut Option<CachedSelection> {
+        match target {
+            SelectionTarget::Clipboard => &mut self.clipboard,
+            SelectionTarget::Primary => &mut self.primary,
+        }
+    }
+}
+
+impl ClipboardCache {
+    fn get(seat: &Seat<State>) -> &ClipboardCache {
+        seat.user_data().insert_if_missing(ClipboardCache::default);
+        seat.user_data().get::<ClipboardCache>().unwrap()
+    }
+
+    /// Drops the cached data of `target` and returns the generation of its new selection.
+    fn reset(seat: &Seat<State>, target: SelectionTarget) -> usize {
+        let mut cache = ClipboardCache::get(seat).0.borrow_mut();
+        cache.generation = cache.generation.wrapping_add(1);
+        let generation = cache.generation;
+        *cache.slot(target) = Some(CachedSelection {
+            generation,
+            pending: 0,
+            data: HashMap::new(),
+        });
+        generation
+    }
+
+    fn clear(seat: &Seat<State>, target: SelectionTarget) {
+        ClipboardCache::get(seat).0.borrow_mut().slot(target).take();
+    }
+
+    fn data(seat: &Seat<State>, target: SelectionTarget, mime_type: &str) -> Option<Arc<[u8]>> {
+        ClipboardCache::get(seat)
+            .0
+            .borrow_mut()
+            .slot(target)
+            .as_ref()
+            .filter(|cached| cached.pending == 0)
+            .and_then(|cached| cached.data.get(mime_type).cloned())
+    }
+}
 
 impl SelectionHandler for State {
-    type SelectionUserData = XwmId;
+    type SelectionUserData = SelectionOwner;
 
     fn new_selection(
         &mut self,
         target: SelectionTarget,
         source: Option<SelectionSource>,
-        _seat: Seat<State>,
+        seat: Seat<State>,
     ) {
         if let Some(xwm) = self
             .common
@@ -32,6 +113,18 @@ impl SelectionHandler for State {
                 warn!(?err, "Failed to clear Xwayland selection.");
             }
         }
+
+        match source {
+            Some(source) => {
+                let generation = ClipboardCache::reset(&seat, target);
+                let mime_types = source.mime_types();
+                // smithay only stores the new source once we return, request its data afterwards
+                self.common.event_loop_handle.insert_idle(move |state| {
+                    state.cache_selection(&seat, target, generation, mime_types)
+                });
+            }
+            None => ClipboardCache::clear(&seat, target),
+        }
     }
 
     fn send_selection(
@@ -39,20 +132,260 @@ impl SelectionHandler for State {
         target: SelectionTarget,
         mime_type: String,
         fd: OwnedFd,
-        _seat: Seat<State>,
-        _user_data: &Self::SelectionUserData,
+        seat: Seat<State>,
+        user_data: &Self::SelectionUserData,
+    ) {
+        match user_data {
+            SelectionOwner::Xwayland(_) => {
+                if let Some(xwm) = self
+                    .common
+                    .xwayland_state
+                    .as_mut()
+                    .and_then(|xstate| xstate.xwm.as_mut())
+                {
+                    if let Err(err) = xwm.send_selection(
+                        target,
+                        mime_type,
+                        fd,
+                        self.common.event_loop_handle.clone(),
+                    ) {
+                        warn!(?err, "Failed to send selection (X11 -> Wayland).");
+                    }
+                }
+            }
+            SelectionOwner::Persisted => self.send_cached_selection(&seat, target, &mime_type, fd),
+        }
+    }
+}
+
+impl State {
+    /// Reads all cacheable mime types of a freshly set client selection and takes
+    /// ownership of the selection once done, so pasting keeps working after the client exits.
+    fn cache_selection(
+        &mut self,
+        seat: &Seat<State>,
+        target: SelectionTarget,
+        generation: usize,
+        mime_types: Vec<String>,
+    ) {
+        let config = &self.common.config.static_conf.clipboard;
+        let enabled = match target {
+            SelectionTarget::Clipboard => config.persist,
+            SelectionTarget::Primary => config.persist && config.persist_primary,
+        };
+        if !enabled {
+            ClipboardCache::clear(seat, target);
+            return;
+        }
+        let max_size = config.max_size;
+        let mime_types = mime_types
+            .into_iter()
+            .filter(|mime_type| config.caches(mime_type))
+            .collect::<Vec<_>>();
+
+        {
+            let mut cache = ClipboardCache::get(seat).0.borrow_mut();
+            let slot = cache.slot(target);
+            let Some(cached) = slot.as_mut().filter(|cached| cached.generation == generation)
+            else {
+                // the selection changed in the meantime
+                return;
+            };
+            if mime_types.is_empty() {
+                slot.take();
+                return;
+            }
+            cached.pending = mime_types.len();
+        }
+
+        for mime_type in mime_types {
+            let reader = match pipe(seat, target, mime_type.clone()) {
+                Ok(reader) => reader,
+                Err(err) => {
+                    warn!(?err, ?mime_type, "Failed to request selection for caching.");
+                    self.finish_cached_mime_type(seat, target, generation, mime_type, None);
+                    continue;
+                }
+            };
+
+            let source_seat = seat.clone();
+            let source_mime_type = mime_type.clone();
+            let mut buffer = Vec::new();
+            if let Err(err) = self.common.event_loop_handle.insert_source(
+                Generic::new(reader, Interest::READ, Mode::Level),
+                move |_, reader, state| {
+                    let mut chunk = [0u8; 4096];
+                    loop {
+                        match (&**reader).read(&mut chunk) {
+                            Ok(0) => {
+                                let data = std::mem::take(&mut buffer);
+                                state.finish_cached_mime_type(
+                                    &source_seat,
+                                    target,
+                                    generation,
+                                    mime_type.clone(),
+                                    Some(data),
+                                );
+                                return Ok(PostAction::Remove);
+                            }
+                            Ok(len) if buffer.len() + len <= max_size => {
+                                buffer.extend_from_slice(&chunk[..len]);
+                            }
+                            Ok(_) => {
+                                debug!(?mime_type, "Selection exceeds clipboard cache size.");
+                                state.finish_cached_mime_type(
+                                    &source_seat,
+                                    target,
+                                    generation,
+                                    mime_type.clone(),
+                                    None,
+                                );
+                                return Ok(PostAction::Remove);
+                            }
+                            Err(err) if err.kind() == ErrorKind::WouldBlock => {
+                                return Ok(PostAction::Continue)
+                            }
+                            Err(err) if err.kind() == ErrorKind::Interrupted => continue,
+                            Err(err) => {
+                                warn!(?err, ?mime_type, "Failed to read selection for caching.");
+                                state.finish_cached_mime_type(
+                                    &source_seat,
+                                    target,
+                                    generation,
+                                    mime_type.clone(),
+                                    None,
+                                );
+                                return Ok(PostAction::Remove);
+                            }
+                        }
+                    }
+                },
+            ) {
+                warn!(?err, "Failed to read selection for caching.");
+                self.finish_cached_mime_type(seat, target, generation, source_mime_type, None);
+            }
+        }
+    }
+
+    fn finish_cached_mime_type(
+        &mut self,
+        seat: &Seat<State>,
+        target: SelectionTarget,
+        generation: usize,
+        mime_type: String,
+        data: Option<Vec<u8>>,
     ) {
+        let mime_types = {
+            let mut cache = ClipboardCache::get(seat).0.borrow_mut();
+            let slot = cache.slot(target);
+            let Some(cached) = slot.as_mut().filter(|cached| cached.generation == generation)
+            else {
+                // the selection changed in the meantime
+                return;
+            };
+            if let Some(data) = data {
+                cached.data.insert(mime_type, data.into());
+            }
+            cached.pending -= 1;
+            if cached.pending > 0 {
+                return;
+            }
+            if cached.data.is_empty() {
+                slot.take();
+                return;
+            }
+            cached.data.keys().cloned().collect::<Vec<_>>()
+        };
+
         if let Some(xwm) = self
             .common
             .xwayland_state
             .as_mut()
             .and_then(|xstate| xstate.xwm.as_mut())
         {
-            if let Err(err) =
-                xwm.send_selection(target, mime_type, fd, self.common.event_loop_handle.clone())
-            {
-                warn!(?err, "Failed to send selection (X11 -> Wayland).");
+            if let Err(err) = xwm.new_selection(target, Some(mime_types.clone())) {
+                warn!(?err, "Failed to set Xwayland clipboard selection.");
+            }
+        }
+
+        let dh = &self.common.display_handle;
+        match target {
+            SelectionTarget::Clipboard => {
+                set_data_device_selection(dh, seat, mime_types, SelectionOwner::Persisted)
+            }
+            SelectionTarget::Primary => {
+                set_primary_selection(dh, seat, mime_types, SelectionOwner::Persisted)
             }
         }
     }
+
+    /// Answers a paste request for a persisted selection from the cache.
+    pub fn send_cached_selection(
+        &mut self,
+        seat: &Seat<State>,
+        target: SelectionTarget,
+        mime_type: &str,
+        fd: OwnedFd,
+    ) {
+        let Some(data) = ClipboardCache::data(seat, target, mime_type) else {
+            warn!(?mime_type, "Requested mime type is not cached.");
+            return;
+        };
+
+        let writer = File::from(fd);
+        if let Err(err) = set_nonblocking(&writer) {
+            warn!(?err, "Failed to send cached selection.");
+            return;
+        }
+        let mut offset = 0;
+        if let Err(err) = self.common.event_loop_handle.insert_source(
+            Generic::new(writer, Interest::WRITE, Mode::Level),
+            move |_, writer, _| loop {
+                if offset >= data.len() {
+                    return Ok(PostAction::Remove);
+                }
+                match (&**writer).write(&data[offset..]) {
+                    Ok(len) => offset += len,
+                    Err(err) if err.kind() == ErrorKind::WouldBlock => {
+                        return Ok(PostAction::Continue)
+                    }
+                    Err(err) if err.kind() == ErrorKind::Interrupted => continue,
+                    Err(err) => {
+                        debug!(?err, "Failed to send cached selection.");
+                        return Ok(PostAction::Remove);
+                    }
+                }
+            },
+        ) {
+            warn!(?err, "Failed to send cached selection.");
+        }
+    }
+}
+
+fn pipe(seat: &Seat<State>, target: SelectionTarget, mime_type: String) -> std::io::Result<File> {
+    let mut fds = [0; 2];
+    if unsafe { libc::pipe2(fds.as_mut_ptr(), libc::O_CLOEXEC) } != 0 {
+        return Err(std::io::Error::last_os_error());
+    }
+    let (reader, writer) = unsafe { (File::from_raw_fd(fds[0]), OwnedFd::from_raw_fd(fds[1])) };
+    set_nonblocking(&reader)?;
+
+    match target {
+        SelectionTarget::Clipboard => request_data_device_client_selection(seat, mime_type, writer),
+        SelectionTarget::Primary => request_primary_client_selection(seat, mime_type, writer),
+    }
+    .map_err(|err| std::io::Error::new(ErrorKind::Other, format!("{:?}", err)))?;
+
+    Ok(reader)
+}
+
+fn set_nonblocking(file: &File) -> std::io::Result<()> {
+    use std::os::unix::io::AsRawFd;
+
+    let fd = file.as_raw_fd();
+    let flags = unsafe { libc::fcntl(fd, libc::F_GETFL) };
+    if flags < 0 || unsafe { libc::fcntl(fd, libc::F_SETFL, flags | libc::O_NONBLOCK) } < 0 {
+        return Err(std::io::Error::last_os_error());
+    }
+    Ok(())
 }
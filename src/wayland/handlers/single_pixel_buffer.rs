@@ -0,0 +1,152 @@
+// SPDX-License-Identifier: GPL-3.0-only
+
+use std::sync::{
+    atomic::{AtomicU64, Ordering},
+    Mutex,
+};
+
+use crate::state::State;
+use smithay::{
+    backend::renderer::{
+        element::{solid::SolidColorRenderElement, Id, Kind},
+        utils::{with_renderer_surface_state, CommitCounter},
+    },
+    delegate_single_pixel_buffer,
+    reexports::wayland_server::protocol::{wl_shm, wl_surface::WlSurface},
+    utils::{Physical, Point, Rectangle, Scale},
+    wayland::{
+        compositor::{get_children, with_states},
+        shm::{with_buffer_contents, BufferData},
+        single_pixel_buffer::get_single_pixel_buffer,
+    },
+};
+
+delegate_single_pixel_buffer!(State);
+
+/// Bytes of shm buffers, that didn't need to be uploaded, because they were drawn as solid colors
+pub static SAVED_UPLOAD_BYTES: AtomicU64 = AtomicU64::new(0);
+
+/// Color of a surface, whose buffer is a single pixel buffer or a single opaque color
+struct SolidColor(Mutex<SolidColorState>);
+
+struct SolidColorState {
+    id: Id,
+    /// Bumped whenever the color changes, so the whole surface gets damaged
+    commit: CommitCounter,
+    color: Option<[f32; 4]>,
+}
+
+/// Checks if the newly attached buffer of `surface` is a single color.
+///
+/// Has to be called after the buffer was handled by `on_commit_buffer_handler`.
+pub fn update_solid_color(surface: &WlSurface) {
+    let Some(buffer) = with_renderer_surface_state(surface, |state| state.buffer().cloned()) else {
+        return;
+    };
+
+    let color = if let Ok(pixel) = get_single_pixel_buffer(&buffer) {
+        // values are already premultiplied
+        Some([pixel.r, pixel.g, pixel.b, pixel.a].map(|x| (x as f64 / u32::MAX as f64) as f32))
+    } else {
+        with_buffer_contents(&buffer, |ptr, len, data| {
+            let color = unsafe { shm_solid_color(ptr, len, &data) };
+            if color.is_some() {
+                SAVED_UPLOAD_BYTES.fetch_add(
+                    data.width as u64 * data.height as u64 * 4,
+                    Ordering::Relaxed,
+                );
+            }
+            color
+        })
+        .ok()
+        .flatten()
+    };
+
+    with_states(surface, |states| {
+        states.data_map.insert_if_missing_threadsafe(|| {
+            SolidColor(Mutex::new(SolidColorState {
+                id: Id::new(),
+                commit: CommitCounter::default(),
+                color: None,
+            }))
+        });
+        let mut state = states
+            .data_map
+            .get::<SolidColor>()
+            .unwrap()
+            .0
+            .lock()
+            .unwrap();
+        if state.color != color {
+            state.color = color;
+            state.commit.increment();
+        }
+    })
+}
+
+/// Returns the color of an opaque shm buffer, if all of its pixels are the same.
+unsafe fn shm_solid_color(ptr: *const u8, len: usize, data: &BufferData) -> Option<[f32; 4]> {
+    let mask = match data.format {
+        wl_shm::Format::Xrgb8888 => 0x00ff_ffff,
+        wl_shm::Format::Argb8888 => 0xffff_ffff,
+        _ => return None,
+    };
+    let (width, height) = (data.width as usize, data.height as usize);
+    let (offset, stride) = (data.offset as usize, data.stride as usize);
+    if width == 0 || height == 0 || offset + (height - 1) * stride + width * 4 > len {
+        return None;
+    }
+
+    let first = (ptr.add(offset) as *const u32).read_unaligned() & mask;
+    if mask == 0xffff_ffff && first >> 24 != 0xff {
+        return None;
+    }
+    for y in 0..height {
+        let row = ptr.add(offset + y * stride);
+        for x in 0..width {
+            if (row.add(x * 4) as *const u32).read_unaligned() & mask != first {
+                return None;
+            }
+        }
+    }
+
+    let channel = |shift: u32| ((first >> shift) & 0xff) as f32 / 255.0;
+    Some([channel(16), channel(8), channel(0), 1.0])
+}
+
+/// Whether `surface` can be drawn with `solid_color_element`.
+pub fn is_solid_color(surface: &WlSurface) -> bool {
+    get_children(surface).is_empty()
+        && with_states(surface, |states| {
+            states
+                .data_map
+                .get::<SolidColor>()
+                .is_some_and(|solid| solid.0.lock().unwrap().color.is_some())
+        })
+}
+
+/// Draws `surface` as a solid color instead of a texture,
+/// if its buffer is a single color and it has no subsurfaces.
+pub fn solid_color_element(
+    surface: &WlSurface,
+    location: Point<i32, Physical>,
+    scale: Scale<f64>,
+    alpha: f32,
+) -> Option<SolidColorRenderElement> {
+    if !get_children(surface).is_empty() {
+        return None;
+    }
+    let (id, commit, color) = with_states(surface, |states| {
+        let state = states.data_map.get::<SolidColor>()?.0.lock().unwrap();
+        Some((state.id.clone(), state.commit, state.color?))
+    })?;
+    let size = with_renderer_surface_state(surface, |state| state.surface_size())?;
+
+    Some(SolidColorRenderElement::new(
+        id,
+        Rectangle::from_loc_and_size(location, size.to_physical_precise_round(scale)),
+        commit,
+        color.map(|x| x * alpha),
+        Kind::Unspecified,
+    ))
+}
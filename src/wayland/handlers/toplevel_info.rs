@@ -23,11 +23,11 @@ impl ToplevelInfoHandler for State {
 
 impl Window for CosmicSurface {
     fn title(&self) -> String {
-        CosmicSurface::title(self)
+        self.published_identity().title
     }
 
     fn app_id(&self) -> String {
-        CosmicSurface::app_id(self)
+        self.published_identity().app_id
     }
 
     fn is_activated(&self) -> bool {
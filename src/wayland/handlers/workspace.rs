@@ -1,7 +1,7 @@
 // SPDX-License-Identifier: GPL-3.0-only
 
 use crate::{
-    state::ClientState,
+    state::{ClientState, Common},
     utils::prelude::*,
     wayland::protocols::workspace::{
         delegate_workspace, Request, WorkspaceClientHandler, WorkspaceClientState,
@@ -37,7 +37,13 @@ impl WorkspaceHandler for State {
                     });
 
                     if let Some((output, idx)) = maybe {
-                        let _ = self.common.shell.activate(&output, idx); // TODO: move cursor?
+                        if self.common.shell.activate(&output, idx).is_ok() {
+                            // TODO: move cursor?
+                            let seat = self.common.last_active_seat().clone();
+                            if seat.active_output() == output {
+                                Common::set_workspace_focus(self, &seat, &output);
+                            }
+                        }
                     }
                 }
                 _ => {}
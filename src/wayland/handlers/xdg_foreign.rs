@@ -0,0 +1,14 @@
+// SPDX-License-Identifier: GPL-3.0-only
+
+use crate::{
+    state::State,
+    wayland::protocols::xdg_foreign::{delegate_xdg_foreign, XdgForeignHandler, XdgForeignState},
+};
+
+impl XdgForeignHandler for State {
+    fn xdg_foreign_state(&mut self) -> &mut XdgForeignState {
+        &mut self.common.xdg_foreign_state
+    }
+}
+
+delegate_xdg_foreign!(State);
@@ -1,15 +1,18 @@
 // SPDX-License-Identifier: GPL-3.0-only
 
 use crate::{
-    shell::{element::CosmicWindow, CosmicMapped, CosmicSurface, ManagedLayer},
+    shell::{
+        element::CosmicWindow, focus::target::KeyboardFocusTarget, CosmicMapped, CosmicSurface,
+        ManagedLayer,
+    },
     utils::prelude::*,
     wayland::protocols::screencopy::SessionType,
 };
 use smithay::{
     delegate_xdg_shell,
     desktop::{
-        find_popup_root_surface, PopupGrab, PopupKeyboardGrab, PopupKind, PopupPointerGrab,
-        PopupUngrabStrategy, Window,
+        find_popup_root_surface, layer_map_for_output, PopupGrab, PopupKeyboardGrab, PopupKind,
+        PopupPointerGrab, PopupUngrabStrategy, Window, WindowSurfaceType,
     },
     input::{pointer::Focus, Seat},
     output::Output,
@@ -21,7 +24,8 @@ use smithay::{
     wayland::{
         seat::WaylandFocus,
         shell::xdg::{
-            PopupSurface, PositionerState, ToplevelSurface, XdgShellHandler, XdgShellState,
+            PopupSurface, PositionerState, ShellClient, ToplevelSurface, XdgShellHandler,
+            XdgShellState,
         },
     },
 };
@@ -30,6 +34,7 @@ use tracing::warn;
 
 use super::{compositor::client_compositor_state, screencopy::PendingScreencopyBuffers};
 
+pub mod ping;
 pub mod popup;
 
 pub type PopupGrabData = Cell<Option<PopupGrab<State>>>;
@@ -41,11 +46,20 @@ impl XdgShellHandler for State {
 
     fn new_toplevel(&mut self, surface: ToplevelSurface) {
         let seat = self.common.last_active_seat().clone();
+        // let the client know how much space it has, before it picks its initial size
+        let bounds = layer_map_for_output(&seat.active_output())
+            .non_exclusive_zone()
+            .size;
+        surface.with_pending_state(|state| state.bounds = Some(bounds));
         let window = CosmicSurface::Wayland(Window::new(surface));
         self.common.shell.pending_windows.push((window, seat, None));
         // We will position the window after the first commit, when we know its size hints
     }
 
+    fn client_pong(&mut self, client: ShellClient) {
+        self.client_answered_ping(&client);
+    }
+
     fn new_popup(&mut self, surface: PopupSurface, positioner: PositionerState) {
         surface.with_pending_state(|state| {
             state.geometry = positioner.get_geometry();
@@ -69,11 +83,23 @@ impl XdgShellHandler for State {
     fn grab(&mut self, surface: PopupSurface, seat: WlSeat, serial: Serial) {
         let seat = Seat::from_resource(&seat).unwrap();
         let kind = PopupKind::Xdg(surface);
-        if let Some(root) = find_popup_root_surface(&kind)
-            .ok()
-            .and_then(|root| self.common.shell.element_for_wl_surface(&root))
-        {
-            let target = root.clone().into();
+        let target = find_popup_root_surface(&kind).ok().and_then(|root| {
+            self.common
+                .shell
+                .element_for_wl_surface(&root)
+                .cloned()
+                .map(KeyboardFocusTarget::from)
+                .or_else(|| {
+                    // popups of layer surfaces, e.g. panel menus
+                    self.common.shell.outputs().find_map(|output| {
+                        layer_map_for_output(output)
+                            .layer_for_surface(&root, WindowSurfaceType::TOPLEVEL)
+                            .cloned()
+                            .map(KeyboardFocusTarget::LayerSurface)
+                    })
+                })
+        });
+        if let Some(target) = target {
             let ret = self
                 .common
                 .shell
@@ -306,6 +332,12 @@ impl XdgShellHandler for State {
     }
 
     fn toplevel_destroyed(&mut self, surface: ToplevelSurface) {
+        Shell::animate_close(self, surface.wl_surface());
+        Shell::unswallow(self, surface.wl_surface());
+        self.common
+            .xdg_foreign_state
+            .toplevel_destroyed(surface.wl_surface());
+
         let outputs = self
             .common
             .shell
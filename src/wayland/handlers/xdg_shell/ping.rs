@@ -0,0 +1,184 @@
+// SPDX-License-Identifier: GPL-3.0-only
+
+use crate::{shell::CosmicSurface, utils::prelude::*};
+use calloop::{
+    timer::{TimeoutAction, Timer},
+    LoopHandle, RegistrationToken,
+};
+use smithay::{
+    reexports::wayland_server::{backend::ClientId, Resource},
+    utils::SERIAL_COUNTER,
+    wayland::{seat::WaylandFocus, shell::xdg::ShellClient},
+};
+use std::{
+    collections::HashMap,
+    time::{Duration, Instant},
+};
+use tracing::warn;
+
+/// Time a client has to answer a ping or a window has to ack a configure,
+/// before it is marked as not responding.
+const PING_TIMEOUT: Duration = Duration::from_secs(5);
+/// Time between two pings of the same client.
+const PING_INTERVAL: Duration = Duration::from_secs(5);
+/// How often clients due for a ping and overdue configures are looked for.
+const PING_CHECK_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Last ping of every client with mapped windows.
+#[derive(Debug, Default)]
+pub struct Pings(HashMap<ClientId, Ping>);
+
+#[derive(Debug)]
+struct Ping {
+    client: ShellClient,
+    sent: Instant,
+    /// Marks the client as not responding, unless it answers first
+    timeout: Option<RegistrationToken>,
+}
+
+/// Pings clients periodically, see `State::ping_clients`.
+pub fn init(handle: &LoopHandle<'static, State>) {
+    if let Err(err) =
+        handle.insert_source(Timer::from_duration(PING_CHECK_INTERVAL), |_, _, state| {
+            state.ping_clients();
+            TimeoutAction::ToDuration(PING_CHECK_INTERVAL)
+        })
+    {
+        warn!(?err, "Failed to setup pinging clients");
+    }
+}
+
+impl State {
+    /// Pings the clients of mapped windows, that weren't pinged within `PING_INTERVAL`,
+    /// and marks windows not acking their configures as not responding.
+    pub fn ping_clients(&mut self) {
+        let dh = self.common.display_handle.clone();
+        self.common
+            .pings
+            .0
+            .retain(|id, _| dh.backend_handle().get_client_data(id.clone()).is_ok());
+
+        let overdue = self
+            .common
+            .shell
+            .workspaces
+            .spaces()
+            .flat_map(|workspace| workspace.mapped())
+            .flat_map(|mapped| mapped.windows().map(|(window, _)| window))
+            .filter(|window| window.configure_overdue(PING_TIMEOUT))
+            .collect::<Vec<_>>();
+        for window in overdue {
+            self.update_responsiveness(&window, false);
+        }
+
+        let toplevels = self
+            .common
+            .shell
+            .workspaces
+            .spaces()
+            .flat_map(|workspace| workspace.mapped())
+            .flat_map(|mapped| mapped.windows().map(|(window, _)| window))
+            .filter_map(|window| match window {
+                CosmicSurface::Wayland(window) => Some(window.toplevel().clone()),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        for toplevel in toplevels {
+            let Some(id) = toplevel.wl_surface().client().map(|client| client.id()) else {
+                continue;
+            };
+            if self
+                .common
+                .pings
+                .0
+                .get(&id)
+                .is_some_and(|ping| ping.timeout.is_some() || ping.sent.elapsed() < PING_INTERVAL)
+            {
+                continue;
+            }
+
+            let client = toplevel.client();
+            if client.send_ping(SERIAL_COUNTER.next_serial()).is_err() {
+                continue;
+            }
+            let timeout_id = id.clone();
+            let timeout = self
+                .common
+                .event_loop_handle
+                .insert_source(Timer::from_duration(PING_TIMEOUT), move |_, _, state| {
+                    if let Some(ping) = state.common.pings.0.get_mut(&timeout_id) {
+                        ping.timeout = None;
+                    }
+                    state.set_client_responsiveness(&timeout_id, false);
+                    TimeoutAction::Drop
+                })
+                .ok();
+            self.common.pings.0.insert(
+                id,
+                Ping {
+                    client,
+                    sent: Instant::now(),
+                    timeout,
+                },
+            );
+        }
+    }
+
+    pub fn client_answered_ping(&mut self, client: &ShellClient) {
+        let Some((id, ping)) = self
+            .common
+            .pings
+            .0
+            .iter_mut()
+            .find(|(_, ping)| &ping.client == client)
+        else {
+            return;
+        };
+        if let Some(token) = ping.timeout.take() {
+            self.common.event_loop_handle.remove(token);
+        }
+        let id = id.clone();
+        self.set_client_responsiveness(&id, true);
+    }
+
+    fn set_client_responsiveness(&mut self, id: &ClientId, answered: bool) {
+        let windows = self
+            .common
+            .shell
+            .workspaces
+            .spaces()
+            .flat_map(|workspace| workspace.mapped())
+            .flat_map(|mapped| mapped.windows().map(|(window, _)| window))
+            .filter(|window| {
+                window
+                    .wl_surface()
+                    .and_then(|surface| surface.client())
+                    .is_some_and(|client| &client.id() == id)
+            })
+            .collect::<Vec<_>>();
+
+        for window in windows {
+            // answering pings doesn't help a window, that still doesn't ack its configures
+            let responding = answered && !window.configure_overdue(PING_TIMEOUT);
+            self.update_responsiveness(&window, responding);
+        }
+    }
+
+    fn update_responsiveness(&mut self, window: &CosmicSurface, responding: bool) {
+        if !window.set_responsiveness(window.responsiveness().update(responding)) {
+            return;
+        }
+        let Some(surface) = window.wl_surface() else {
+            return;
+        };
+        for output in self
+            .common
+            .shell
+            .visible_outputs_for_surface(&surface)
+            .collect::<Vec<_>>()
+        {
+            self.backend
+                .schedule_render(&self.common.event_loop_handle, &output, None);
+        }
+    }
+}
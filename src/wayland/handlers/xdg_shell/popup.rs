@@ -42,13 +42,14 @@ impl Shell {
                 let window_geo_offset = window.geometry().loc;
                 let window_loc: Point<i32, Global> =
                     element_geo.loc + offset.as_global() + window_geo_offset.as_global();
+                let usable_area = usable_area(workspace.output());
                 if workspace.is_tiled(elem) {
                     element_geo.loc = (0, 0).into();
                     if !unconstrain_xdg_popup_tile(surface, element_geo.as_logical()) {
-                        unconstrain_xdg_popup(surface, window_loc, workspace.output().geometry());
+                        unconstrain_xdg_popup(surface, window_loc, usable_area);
                     }
                 } else {
-                    unconstrain_xdg_popup(surface, window_loc, workspace.output().geometry());
+                    unconstrain_xdg_popup(surface, window_loc, usable_area);
                 }
             } else if let Some((output, layer_surface)) = self.outputs().find_map(|o| {
                 let map = layer_map_for_output(o);
@@ -66,7 +67,9 @@ pub fn update_reactive_popups<'a>(
     loc: Point<i32, Global>,
     outputs: impl Iterator<Item = &'a Output>,
 ) {
-    let output_geo = outputs.map(|o| o.geometry()).collect::<Vec<_>>();
+    let output_geo = outputs
+        .map(|o| (o.geometry(), usable_area(o)))
+        .collect::<Vec<_>>();
     for (popup, _) in PopupManager::popups_for_surface(window.toplevel().wl_surface()) {
         match popup {
             PopupKind::Xdg(surface) => {
@@ -83,8 +86,8 @@ pub fn update_reactive_popups<'a>(
                     let anchor_point = loc + get_anchor_point(&positioner).as_global();
                     if let Some(rect) = output_geo
                         .iter()
-                        .find(|geo| geo.contains(anchor_point))
-                        .copied()
+                        .find(|(geo, _)| geo.contains(anchor_point))
+                        .map(|(_, usable_area)| *usable_area)
                     {
                         unconstrain_xdg_popup(&surface, loc, rect);
                         if let Err(err) = surface.send_configure() {
@@ -164,13 +167,42 @@ fn unconstrain_layer_popup(surface: &PopupSurface, output: &Output, layer_surfac
 fn unconstrain_flip(popup: &PopupSurface, toplevel_box: Rectangle<i32, Logical>) -> bool {
     let toplevel_offset = get_popup_toplevel_coords(popup);
     let positioner = popup.with_pending_state(|state| state.positioner.clone());
-    let mut geometry = positioner.get_geometry();
-    geometry.loc += toplevel_offset;
-    let offset = check_constrained(geometry, toplevel_box);
+    let offset = positioner_offset(&positioner, toplevel_offset, toplevel_box);
     if offset.x == 0 && offset.y == 0 {
         return true;
     }
 
+    let positioner = flip_positioner(&positioner, toplevel_offset, toplevel_box);
+    let new_offset = positioner_offset(&positioner, toplevel_offset, toplevel_box);
+    if new_offset.x.abs() < offset.x.abs() || new_offset.y.abs() < offset.y.abs() {
+        popup.with_pending_state(|state| {
+            state.geometry = positioner.get_geometry();
+            state.positioner = positioner;
+        });
+    }
+
+    new_offset.x == 0 && new_offset.y == 0
+}
+
+/// How far the popup placed by `positioner` has to move to fit into `toplevel_box`
+fn positioner_offset(
+    positioner: &PositionerState,
+    toplevel_offset: Point<i32, Logical>,
+    toplevel_box: Rectangle<i32, Logical>,
+) -> Point<i32, Logical> {
+    let mut geometry = positioner.get_geometry();
+    geometry.loc += toplevel_offset;
+    check_constrained(geometry, toplevel_box)
+}
+
+/// `positioner` flipped on every axis it is constrained on and allowed to flip,
+/// as long as that moves the popup further into `toplevel_box`.
+fn flip_positioner(
+    positioner: &PositionerState,
+    toplevel_offset: Point<i32, Logical>,
+    toplevel_box: Rectangle<i32, Logical>,
+) -> PositionerState {
+    let offset = positioner_offset(positioner, toplevel_offset, toplevel_box);
     let mut positioner = positioner.clone();
 
     let flip_x = offset.x != 0
@@ -186,9 +218,7 @@ fn unconstrain_flip(popup: &PopupSurface, toplevel_box: Rectangle<i32, Logical>)
         let old_positioner = positioner.clone();
         positioner.anchor_edges = invert_anchor_x(positioner.anchor_edges);
         positioner.gravity = invert_gravity_x(positioner.gravity);
-        geometry = positioner.get_geometry();
-        geometry.loc += toplevel_offset;
-        let new_offset = check_constrained(geometry, toplevel_box);
+        let new_offset = positioner_offset(&positioner, toplevel_offset, toplevel_box);
         if !(new_offset.x.abs() < offset.x.abs()) {
             positioner = old_positioner;
         }
@@ -197,25 +227,13 @@ fn unconstrain_flip(popup: &PopupSurface, toplevel_box: Rectangle<i32, Logical>)
         let old_positioner = positioner.clone();
         positioner.anchor_edges = invert_anchor_y(positioner.anchor_edges);
         positioner.gravity = invert_gravity_y(positioner.gravity);
-        geometry = positioner.get_geometry();
-        geometry.loc += toplevel_offset;
-        let new_offset = check_constrained(geometry, toplevel_box);
+        let new_offset = positioner_offset(&positioner, toplevel_offset, toplevel_box);
         if !(new_offset.y.abs() < offset.y.abs()) {
             positioner = old_positioner;
         }
     }
 
-    geometry = positioner.get_geometry();
-    geometry.loc += toplevel_offset;
-    let new_offset = check_constrained(geometry, toplevel_box);
-    if new_offset.x.abs() < offset.x.abs() || new_offset.y.abs() < offset.y.abs() {
-        popup.with_pending_state(|state| {
-            state.geometry = positioner.get_geometry();
-            state.positioner = positioner;
-        });
-    }
-
-    new_offset.x == 0 && new_offset.y == 0
+    positioner
 }
 
 fn unconstrain_slide(popup: &PopupSurface, toplevel_box: Rectangle<i32, Logical>) -> bool {
@@ -267,8 +285,9 @@ fn unconstrain_slide(popup: &PopupSurface, toplevel_box: Rectangle<i32, Logical>
 
 fn unconstrain_resize(popup: &PopupSurface, toplevel_box: Rectangle<i32, Logical>) -> bool {
     let toplevel_offset = get_popup_toplevel_coords(popup);
-    let positioner = popup.with_pending_state(|state| state.positioner.clone());
-    let mut geometry = positioner.get_geometry();
+    // start from the already flipped and slid geometry
+    let (positioner, mut geometry) =
+        popup.with_pending_state(|state| (state.positioner.clone(), state.geometry));
     geometry.loc += toplevel_offset;
     let offset = check_constrained(geometry, toplevel_box);
     if offset.x == 0 && offset.y == 0 {
@@ -284,17 +303,14 @@ fn unconstrain_resize(popup: &PopupSurface, toplevel_box: Rectangle<i32, Logical
             .constraint_adjustment
             .contains(ConstraintAdjustment::ResizeY);
 
-    let mut geometry = positioner.get_geometry();
-    if resize_x {
-        geometry.size.w -= offset.x;
-    }
-    if resize_y {
-        geometry.size.h -= offset.y;
-    }
+    let geometry = popup.with_pending_state(|state| state.geometry);
+    let Some(geometry) = resize_geometry(geometry, offset, resize_x, resize_y) else {
+        return false;
+    };
 
     let mut check_geometry = geometry.clone();
     check_geometry.loc += toplevel_offset;
-    let offset = check_constrained(geometry, toplevel_box);
+    let offset = check_constrained(check_geometry, toplevel_box);
     if offset.x == 0 && offset.y == 0 {
         // no longer constrained
         popup.with_pending_state(|state| {
@@ -306,6 +322,39 @@ fn unconstrain_resize(popup: &PopupSurface, toplevel_box: Rectangle<i32, Logical
     }
 }
 
+/// `geometry` shrunk by `offset` on the axes it may be resized on,
+/// `None` if nothing would be left of it.
+fn resize_geometry(
+    mut geometry: Rectangle<i32, Logical>,
+    offset: Point<i32, Logical>,
+    resize_x: bool,
+    resize_y: bool,
+) -> Option<Rectangle<i32, Logical>> {
+    // a positive offset means the popup sticks out at the left or top, so the origin moves too
+    if resize_x {
+        if offset.x > 0 {
+            geometry.loc.x += offset.x;
+        }
+        geometry.size.w -= offset.x.abs();
+    }
+    if resize_y {
+        if offset.y > 0 {
+            geometry.loc.y += offset.y;
+        }
+        geometry.size.h -= offset.y.abs();
+    }
+    (geometry.size.w > 0 && geometry.size.h > 0).then_some(geometry)
+}
+
+/// Area of `output`, that isn't covered by exclusive zones of layer surfaces
+fn usable_area(output: &Output) -> Rectangle<i32, Global> {
+    let mut area = layer_map_for_output(output)
+        .non_exclusive_zone()
+        .as_global();
+    area.loc += output.geometry().loc;
+    area
+}
+
 fn check_constrained(
     geometry: Rectangle<i32, Logical>,
     toplevel_box: Rectangle<i32, Logical>,
@@ -456,3 +505,108 @@ fn invert_gravity_y(gravity: Gravity) -> Gravity {
         x => x,
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{check_constrained, flip_positioner, positioner_offset, resize_geometry};
+    use smithay::{
+        reexports::wayland_protocols::xdg::shell::server::xdg_positioner::{
+            Anchor, ConstraintAdjustment, Gravity,
+        },
+        utils::{Logical, Point, Rectangle},
+        wayland::shell::xdg::PositionerState,
+    };
+
+    fn output() -> Rectangle<i32, Logical> {
+        Rectangle::from_loc_and_size((0, 0), (1920, 1080))
+    }
+
+    /// A 200x300 context menu opening to the bottom right of a click at `(x, y)`
+    fn menu_at(x: i32, y: i32) -> PositionerState {
+        PositionerState {
+            rect_size: (200, 300).into(),
+            anchor_rect: Rectangle::from_loc_and_size((x, y), (1, 1)),
+            anchor_edges: Anchor::BottomRight,
+            gravity: Gravity::BottomRight,
+            constraint_adjustment: ConstraintAdjustment::FlipX | ConstraintAdjustment::FlipY,
+            ..Default::default()
+        }
+    }
+
+    fn flipped_geometry(positioner: &PositionerState) -> Rectangle<i32, Logical> {
+        flip_positioner(positioner, (0, 0).into(), output()).get_geometry()
+    }
+
+    #[test]
+    fn top_left_corner_needs_no_flip() {
+        let menu = menu_at(10, 10);
+        assert_eq!(
+            positioner_offset(&menu, (0, 0).into(), output()),
+            Point::from((0, 0))
+        );
+        assert_eq!(flipped_geometry(&menu), menu.get_geometry());
+    }
+
+    #[test]
+    fn top_right_corner_flips_left() {
+        let geometry = flipped_geometry(&menu_at(1910, 10));
+        assert!(output().contains_rect(geometry));
+        assert_eq!(geometry.loc, Point::from((1710, 11)));
+    }
+
+    #[test]
+    fn bottom_left_corner_flips_up() {
+        let geometry = flipped_geometry(&menu_at(10, 1070));
+        assert!(output().contains_rect(geometry));
+        assert_eq!(geometry.loc, Point::from((11, 770)));
+    }
+
+    #[test]
+    fn bottom_right_corner_flips_both() {
+        let geometry = flipped_geometry(&menu_at(1910, 1070));
+        assert!(output().contains_rect(geometry));
+        assert_eq!(geometry.loc, Point::from((1710, 770)));
+    }
+
+    #[test]
+    fn flips_only_allowed_axes() {
+        let mut menu = menu_at(1910, 1070);
+        menu.constraint_adjustment = ConstraintAdjustment::FlipY;
+        let geometry = flipped_geometry(&menu);
+        assert_eq!(geometry.loc, Point::from((1911, 770)));
+        assert_ne!(check_constrained(geometry, output()).x, 0);
+    }
+
+    #[test]
+    fn resizing_shrinks_from_the_constrained_edge() {
+        // sticking out at the top
+        let geometry = Rectangle::from_loc_and_size((100, -50), (200, 300));
+        let offset = check_constrained(geometry, output());
+        assert_eq!(
+            resize_geometry(geometry, offset, true, true),
+            Some(Rectangle::from_loc_and_size((100, 0), (200, 250)))
+        );
+
+        // sticking out at the right
+        let geometry = Rectangle::from_loc_and_size((1820, 100), (200, 300));
+        let offset = check_constrained(geometry, output());
+        assert_eq!(
+            resize_geometry(geometry, offset, true, true),
+            Some(Rectangle::from_loc_and_size((1820, 100), (100, 300)))
+        );
+    }
+
+    #[test]
+    fn resizing_respects_allowed_axes_and_empty_results() {
+        let geometry = Rectangle::from_loc_and_size((1820, 100), (200, 300));
+        let offset = check_constrained(geometry, output());
+        assert_eq!(
+            resize_geometry(geometry, offset, false, true),
+            Some(geometry)
+        );
+
+        let geometry = Rectangle::from_loc_and_size((1920, 100), (200, 300));
+        let offset = check_constrained(geometry, output());
+        assert_eq!(resize_geometry(geometry, offset, true, false), None);
+    }
+}
@@ -0,0 +1,201 @@
+// SPDX-License-Identifier: GPL-3.0-only
+
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use smithay::{
+    reexports::{
+        wayland_protocols::wp::content_type::v1::server::{
+            wp_content_type_manager_v1::{self, WpContentTypeManagerV1},
+            wp_content_type_v1::{self, WpContentTypeV1},
+        },
+        wayland_server::{
+            backend::{ClientId, GlobalId},
+            protocol::wl_surface::WlSurface,
+            Client, DataInit, Dispatch, DisplayHandle, GlobalDispatch, New, Resource, Weak,
+        },
+    },
+    wayland::compositor::{with_states, Cacheable},
+};
+
+pub use smithay::reexports::wayland_protocols::wp::content_type::v1::server::wp_content_type_v1::Type as ContentType;
+
+/// State of the `wp_content_type_manager_v1` global
+#[derive(Debug)]
+pub struct ContentTypeState {
+    global: GlobalId,
+}
+
+/// Double-buffered content type of a surface, applied on commit
+#[derive(Debug, Default, Clone, Copy)]
+pub struct ContentTypeSurfaceCachedState {
+    content_type: Option<ContentType>,
+}
+
+impl Cacheable for ContentTypeSurfaceCachedState {
+    fn commit(&mut self, _dh: &DisplayHandle) -> Self {
+        *self
+    }
+    fn merge_into(self, into: &mut Self, _dh: &DisplayHandle) {
+        *into = self;
+    }
+}
+
+/// Set while a `wp_content_type_v1` object exists for the surface
+#[derive(Debug, Default)]
+struct ContentTypeConstructed(AtomicBool);
+
+/// Returns the content type hinted by the client for the current state of `surface`.
+pub fn content_type(surface: &WlSurface) -> ContentType {
+    with_states(surface, |states| {
+        states
+            .cached_state
+            .current::<ContentTypeSurfaceCachedState>()
+            .content_type
+            .unwrap_or(ContentType::None)
+    })
+}
+
+impl ContentTypeState {
+    pub fn new<D>(dh: &DisplayHandle) -> ContentTypeState
+    where
+        D: GlobalDispatch<WpContentTypeManagerV1, ()>
+            + Dispatch<WpContentTypeManagerV1, ()>
+            + Dispatch<WpContentTypeV1, Weak<WlSurface>>
+            + 'static,
+    {
+        let global = dh.create_global::<D, WpContentTypeManagerV1, _>(1, ());
+        ContentTypeState { global }
+    }
+
+    pub fn global_id(&self) -> GlobalId {
+        self.global.clone()
+    }
+}
+
+impl<D> GlobalDispatch<WpContentTypeManagerV1, (), D> for ContentTypeState
+where
+    D: GlobalDispatch<WpContentTypeManagerV1, ()>
+        + Dispatch<WpContentTypeManagerV1, ()>
+        + Dispatch<WpContentTypeV1, Weak<WlSurface>>
+        + 'static,
+{
+    fn bind(
+        _state: &mut D,
+        _dh: &DisplayHandle,
+        _client: &Client,
+        resource: New<WpContentTypeManagerV1>,
+        _global_data: &(),
+        data_init: &mut DataInit<'_, D>,
+    ) {
+        data_init.init(resource, ());
+    }
+}
+
+impl<D> Dispatch<WpContentTypeManagerV1, (), D> for ContentTypeState
+where
+    D: Dispatch<WpContentTypeManagerV1, ()> + Dispatch<WpContentTypeV1, Weak<WlSurface>> + 'static,
+{
+    fn request(
+        _state: &mut D,
+        _client: &Client,
+        obj: &WpContentTypeManagerV1,
+        request: wp_content_type_manager_v1::Request,
+        _data: &(),
+        _dh: &DisplayHandle,
+        data_init: &mut DataInit<'_, D>,
+    ) {
+        match request {
+            wp_content_type_manager_v1::Request::GetSurfaceContentType { id, surface } => {
+                let already_constructed = with_states(&surface, |states| {
+                    states
+                        .data_map
+                        .insert_if_missing_threadsafe(ContentTypeConstructed::default);
+                    states
+                        .data_map
+                        .get::<ContentTypeConstructed>()
+                        .unwrap()
+                        .0
+                        .swap(true, Ordering::SeqCst)
+                });
+                if already_constructed {
+                    obj.post_error(
+                        wp_content_type_manager_v1::Error::AlreadyConstructed,
+                        "wl_surface already has a content type object",
+                    );
+                    return;
+                }
+                data_init.init(id, surface.downgrade());
+            }
+            wp_content_type_manager_v1::Request::Destroy => {}
+            _ => unreachable!(),
+        }
+    }
+}
+
+impl<D> Dispatch<WpContentTypeV1, Weak<WlSurface>, D> for ContentTypeState
+where
+    D: Dispatch<WpContentTypeV1, Weak<WlSurface>> + 'static,
+{
+    fn request(
+        _state: &mut D,
+        _client: &Client,
+        _obj: &WpContentTypeV1,
+        request: wp_content_type_v1::Request,
+        surface: &Weak<WlSurface>,
+        _dh: &DisplayHandle,
+        _data_init: &mut DataInit<'_, D>,
+    ) {
+        match request {
+            wp_content_type_v1::Request::SetContentType { content_type } => {
+                let Ok(surface) = surface.upgrade() else {
+                    return;
+                };
+                let content_type = content_type.into_result().unwrap_or(ContentType::None);
+                with_states(&surface, |states| {
+                    states
+                        .cached_state
+                        .pending::<ContentTypeSurfaceCachedState>()
+                        .content_type = Some(content_type);
+                });
+            }
+            wp_content_type_v1::Request::Destroy => {}
+            _ => unreachable!(),
+        }
+    }
+
+    fn destroyed(
+        _state: &mut D,
+        _client: ClientId,
+        _resource: &WpContentTypeV1,
+        surface: &Weak<WlSurface>,
+    ) {
+        // the content type is reset with the next commit of the surface
+        let Ok(surface) = surface.upgrade() else {
+            return;
+        };
+        with_states(&surface, |states| {
+            if let Some(constructed) = states.data_map.get::<ContentTypeConstructed>() {
+                constructed.0.store(false, Ordering::SeqCst);
+            }
+            states
+                .cached_state
+                .pending::<ContentTypeSurfaceCachedState>()
+                .content_type = None;
+        });
+    }
+}
+
+macro_rules! delegate_content_type {
+    ($(@<$( $lt:tt $( : $clt:tt $(+ $dlt:tt )* )? ),+>)? $ty: ty) => {
+        smithay::reexports::wayland_server::delegate_global_dispatch!($(@< $( $lt $( : $clt $(+ $dlt )* )? ),+ >)? $ty: [
+            smithay::reexports::wayland_protocols::wp::content_type::v1::server::wp_content_type_manager_v1::WpContentTypeManagerV1: ()
+        ] => $crate::wayland::protocols::content_type::ContentTypeState);
+        smithay::reexports::wayland_server::delegate_dispatch!($(@< $( $lt $( : $clt $(+ $dlt )* )? ),+ >)? $ty: [
+            smithay::reexports::wayland_protocols::wp::content_type::v1::server::wp_content_type_manager_v1::WpContentTypeManagerV1: ()
+        ] => $crate::wayland::protocols::content_type::ContentTypeState);
+        smithay::reexports::wayland_server::delegate_dispatch!($(@< $( $lt $( : $clt $(+ $dlt )* )? ),+ >)? $ty: [
+            smithay::reexports::wayland_protocols::wp::content_type::v1::server::wp_content_type_v1::WpContentTypeV1: smithay::reexports::wayland_server::Weak<smithay::reexports::wayland_server::protocol::wl_surface::WlSurface>
+        ] => $crate::wayland::protocols::content_type::ContentTypeState);
+    };
+}
+pub(crate) use delegate_content_type;
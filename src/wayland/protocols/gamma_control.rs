@@ -0,0 +1,282 @@
+// SPDX-License-Identifier: GPL-3.0-only
+
+use std::{collections::HashMap, fs::File, os::unix::fs::FileExt};
+
+use smithay::{
+    output::Output,
+    reexports::{
+        wayland_protocols_wlr::gamma_control::v1::server::{
+            zwlr_gamma_control_manager_v1::{self, ZwlrGammaControlManagerV1},
+            zwlr_gamma_control_v1::{self, ZwlrGammaControlV1},
+        },
+        wayland_server::{
+            backend::{ClientId, GlobalId},
+            Client, DataInit, Dispatch, DisplayHandle, GlobalDispatch, New,
+        },
+    },
+};
+use tracing::warn;
+
+/// State of the `zwlr_gamma_control_manager_v1` global
+#[derive(Debug)]
+pub struct GammaControlState {
+    /// The active gamma control of every output, there can only be one at a time
+    controls: HashMap<Output, ZwlrGammaControlV1>,
+    global: GlobalId,
+}
+
+pub struct GammaControlGlobalData {
+    filter: Box<dyn for<'a> Fn(&'a Client) -> bool + Send + Sync>,
+}
+
+/// Data of a `zwlr_gamma_control_v1` object
+#[derive(Debug)]
+pub struct GammaControlData {
+    /// `None` if the output was already gone, when the control was created
+    output: Option<Output>,
+    /// Number of entries per color channel
+    size: u32,
+}
+
+pub trait GammaControlHandler {
+    fn gamma_control_state(&mut self) -> &mut GammaControlState;
+    /// Number of entries per color channel of the gamma ramp of `output`,
+    /// or `None` if it can't be changed.
+    fn gamma_size(&mut self, output: &Output) -> Option<u32>;
+    /// Sets the red, green and blue ramps of `output` back to back,
+    /// `None` restores the default ramp. Returns `false` on failure.
+    fn set_gamma(&mut self, output: &Output, ramp: Option<Vec<u16>>) -> bool;
+}
+
+impl GammaControlState {
+    pub fn new<D, F>(dh: &DisplayHandle, client_filter: F) -> GammaControlState
+    where
+        D: GlobalDispatch<ZwlrGammaControlManagerV1, GammaControlGlobalData>
+            + Dispatch<ZwlrGammaControlManagerV1, ()>
+            + Dispatch<ZwlrGammaControlV1, GammaControlData>
+            + GammaControlHandler
+            + 'static,
+        F: for<'a> Fn(&'a Client) -> bool + Send + Sync + 'static,
+    {
+        let global = dh.create_global::<D, ZwlrGammaControlManagerV1, _>(
+            1,
+            GammaControlGlobalData {
+                filter: Box::new(client_filter),
+            },
+        );
+        GammaControlState {
+            controls: HashMap::new(),
+            global,
+        }
+    }
+
+    /// Fails the gamma control of a removed output.
+    pub fn output_removed(&mut self, output: &Output) {
+        if let Some(control) = self.controls.remove(output) {
+            control.failed();
+        }
+    }
+
+    fn is_active(&self, output: &Output, control: &ZwlrGammaControlV1) -> bool {
+        self.controls.get(output) == Some(control)
+    }
+}
+
+/// Size in bytes of a ramp with `size` entries per color channel
+fn ramp_bytes(size: u32) -> usize {
+    size as usize * 3 * std::mem::size_of::<u16>()
+}
+
+/// Parses the red, green and blue ramps of `size` entries each from the contents of
+/// a `set_gamma` fd, `None` if they don't have the expected length.
+fn parse_gamma_ramp(bytes: &[u8], size: u32) -> Option<Vec<u16>> {
+    if size == 0 || bytes.len() != ramp_bytes(size) {
+        return None;
+    }
+    Some(
+        bytes
+            .chunks_exact(2)
+            .map(|x| u16::from_ne_bytes([x[0], x[1]]))
+            .collect(),
+    )
+}
+
+impl<D> GlobalDispatch<ZwlrGammaControlManagerV1, GammaControlGlobalData, D> for GammaControlState
+where
+    D: GlobalDispatch<ZwlrGammaControlManagerV1, GammaControlGlobalData>
+        + Dispatch<ZwlrGammaControlManagerV1, ()>
+        + Dispatch<ZwlrGammaControlV1, GammaControlData>
+        + GammaControlHandler
+        + 'static,
+{
+    fn bind(
+        _state: &mut D,
+        _dh: &DisplayHandle,
+        _client: &Client,
+        resource: New<ZwlrGammaControlManagerV1>,
+        _global_data: &GammaControlGlobalData,
+        data_init: &mut DataInit<'_, D>,
+    ) {
+        data_init.init(resource, ());
+    }
+
+    fn can_view(client: Client, global_data: &GammaControlGlobalData) -> bool {
+        (global_data.filter)(&client)
+    }
+}
+
+impl<D> Dispatch<ZwlrGammaControlManagerV1, (), D> for GammaControlState
+where
+    D: GlobalDispatch<ZwlrGammaControlManagerV1, GammaControlGlobalData>
+        + Dispatch<ZwlrGammaControlManagerV1, ()>
+        + Dispatch<ZwlrGammaControlV1, GammaControlData>
+        + GammaControlHandler
+        + 'static,
+{
+    fn request(
+        state: &mut D,
+        _client: &Client,
+        _obj: &ZwlrGammaControlManagerV1,
+        request: zwlr_gamma_control_manager_v1::Request,
+        _data: &(),
+        _dh: &DisplayHandle,
+        data_init: &mut DataInit<'_, D>,
+    ) {
+        match request {
+            zwlr_gamma_control_manager_v1::Request::GetGammaControl { id, output } => {
+                let output = Output::from_resource(&output);
+                let size = output
+                    .as_ref()
+                    .filter(|output| !state.gamma_control_state().controls.contains_key(output))
+                    .and_then(|output| state.gamma_size(output))
+                    .filter(|size| *size > 0);
+
+                let control = data_init.init(
+                    id,
+                    GammaControlData {
+                        output: output.clone(),
+                        size: size.unwrap_or(0),
+                    },
+                );
+                match (output, size) {
+                    (Some(output), Some(size)) => {
+                        control.gamma_size(size);
+                        state.gamma_control_state().controls.insert(output, control);
+                    }
+                    _ => control.failed(),
+                }
+            }
+            zwlr_gamma_control_manager_v1::Request::Destroy => {}
+            _ => {}
+        }
+    }
+}
+
+impl<D> Dispatch<ZwlrGammaControlV1, GammaControlData, D> for GammaControlState
+where
+    D: GlobalDispatch<ZwlrGammaControlManagerV1, GammaControlGlobalData>
+        + Dispatch<ZwlrGammaControlManagerV1, ()>
+        + Dispatch<ZwlrGammaControlV1, GammaControlData>
+        + GammaControlHandler
+        + 'static,
+{
+    fn request(
+        state: &mut D,
+        _client: &Client,
+        obj: &ZwlrGammaControlV1,
+        request: zwlr_gamma_control_v1::Request,
+        data: &GammaControlData,
+        _dh: &DisplayHandle,
+        _data_init: &mut DataInit<'_, D>,
+    ) {
+        match request {
+            zwlr_gamma_control_v1::Request::SetGamma { fd } => {
+                let Some(output) = data.output.as_ref() else {
+                    return;
+                };
+                if !state.gamma_control_state().is_active(output, obj) {
+                    return;
+                }
+
+                let mut bytes = vec![0u8; ramp_bytes(data.size)];
+                let ramp = match File::from(fd).read_exact_at(&mut bytes, 0) {
+                    Ok(()) => parse_gamma_ramp(&bytes, data.size),
+                    Err(err) => {
+                        warn!(?err, output = output.name(), "Failed to read gamma ramp.");
+                        None
+                    }
+                };
+
+                if !ramp.is_some_and(|ramp| state.set_gamma(output, Some(ramp))) {
+                    state.gamma_control_state().controls.remove(output);
+                    state.set_gamma(output, None);
+                    obj.failed();
+                }
+            }
+            zwlr_gamma_control_v1::Request::Destroy => {}
+            _ => {}
+        }
+    }
+
+    fn destroyed(
+        state: &mut D,
+        _client: ClientId,
+        resource: &ZwlrGammaControlV1,
+        data: &GammaControlData,
+    ) {
+        let Some(output) = data.output.as_ref() else {
+            return;
+        };
+        if state.gamma_control_state().is_active(output, resource) {
+            state.gamma_control_state().controls.remove(output);
+            state.set_gamma(output, None);
+        }
+    }
+}
+
+macro_rules! delegate_gamma_control {
+    ($(@<$( $lt:tt $( : $clt:tt $(+ $dlt:tt )* )? ),+>)? $ty: ty) => {
+        smithay::reexports::wayland_server::delegate_global_dispatch!($(@< $( $lt $( : $clt $(+ $dlt )* )? ),+ >)? $ty: [
+            smithay::reexports::wayland_protocols_wlr::gamma_control::v1::server::zwlr_gamma_control_manager_v1::ZwlrGammaControlManagerV1: $crate::wayland::protocols::gamma_control::GammaControlGlobalData
+        ] => $crate::wayland::protocols::gamma_control::GammaControlState);
+        smithay::reexports::wayland_server::delegate_dispatch!($(@< $( $lt $( : $clt $(+ $dlt )* )? ),+ >)? $ty: [
+            smithay::reexports::wayland_protocols_wlr::gamma_control::v1::server::zwlr_gamma_control_manager_v1::ZwlrGammaControlManagerV1: ()
+        ] => $crate::wayland::protocols::gamma_control::GammaControlState);
+        smithay::reexports::wayland_server::delegate_dispatch!($(@< $( $lt $( : $clt $(+ $dlt )* )? ),+ >)? $ty: [
+            smithay::reexports::wayland_protocols_wlr::gamma_control::v1::server::zwlr_gamma_control_v1::ZwlrGammaControlV1: $crate::wayland::protocols::gamma_control::GammaControlData
+        ] => $crate::wayland::protocols::gamma_control::GammaControlState);
+    };
+}
+pub(crate) use delegate_gamma_control;
+
+#[cfg(test)]
+mod tests {
+    use super::{parse_gamma_ramp, ramp_bytes};
+
+    #[test]
+    fn ramp_has_three_channels_of_u16() {
+        assert_eq!(ramp_bytes(256), 256 * 3 * 2);
+    }
+
+    #[test]
+    fn parses_native_endian_entries() {
+        let entries = [0u16, 1, 0x1234, u16::MAX, 42, 7];
+        let bytes = entries
+            .iter()
+            .flat_map(|entry| entry.to_ne_bytes())
+            .collect::<Vec<_>>();
+        assert_eq!(parse_gamma_ramp(&bytes, 2), Some(entries.to_vec()));
+    }
+
+    #[test]
+    fn rejects_ramps_of_wrong_length() {
+        assert_eq!(parse_gamma_ramp(&[0; 11], 2), None);
+        assert_eq!(parse_gamma_ramp(&[0; 13], 2), None);
+        assert_eq!(parse_gamma_ramp(&[0; 6], 2), None);
+    }
+
+    #[test]
+    fn rejects_empty_ramps() {
+        assert_eq!(parse_gamma_ramp(&[], 0), None);
+    }
+}
@@ -0,0 +1,148 @@
+// SPDX-License-Identifier: GPL-3.0-only
+
+use smithay::reexports::{
+    wayland_protocols::wp::idle_inhibit::zv1::server::{
+        zwp_idle_inhibit_manager_v1::{self, ZwpIdleInhibitManagerV1},
+        zwp_idle_inhibitor_v1::{self, ZwpIdleInhibitorV1},
+    },
+    wayland_server::{
+        backend::{ClientId, GlobalId},
+        protocol::wl_surface::WlSurface,
+        Client, DataInit, Dispatch, DisplayHandle, GlobalDispatch, New, Resource, Weak,
+    },
+};
+
+/// State of the `zwp_idle_inhibit_manager_v1` global
+#[derive(Debug)]
+pub struct IdleInhibitState {
+    inhibitors: Vec<ZwpIdleInhibitorV1>,
+    global: GlobalId,
+}
+
+pub trait IdleInhibitHandler {
+    fn idle_inhibit_state(&mut self) -> &mut IdleInhibitState;
+}
+
+impl IdleInhibitState {
+    pub fn new<D>(dh: &DisplayHandle) -> IdleInhibitState
+    where
+        D: GlobalDispatch<ZwpIdleInhibitManagerV1, ()>
+            + Dispatch<ZwpIdleInhibitManagerV1, ()>
+            + Dispatch<ZwpIdleInhibitorV1, Weak<WlSurface>>
+            + IdleInhibitHandler
+            + 'static,
+    {
+        let global = dh.create_global::<D, ZwpIdleInhibitManagerV1, _>(1, ());
+        IdleInhibitState {
+            inhibitors: Vec::new(),
+            global,
+        }
+    }
+
+    pub fn global_id(&self) -> GlobalId {
+        self.global.clone()
+    }
+
+    /// Surfaces with an inhibitor, which only takes effect while the surface is visible.
+    pub fn surfaces(&self) -> impl Iterator<Item = WlSurface> + '_ {
+        self.inhibitors.iter().filter_map(|inhibitor| {
+            inhibitor
+                .data::<Weak<WlSurface>>()
+                .and_then(|surface| surface.upgrade().ok())
+        })
+    }
+}
+
+impl<D> GlobalDispatch<ZwpIdleInhibitManagerV1, (), D> for IdleInhibitState
+where
+    D: GlobalDispatch<ZwpIdleInhibitManagerV1, ()>
+        + Dispatch<ZwpIdleInhibitManagerV1, ()>
+        + Dispatch<ZwpIdleInhibitorV1, Weak<WlSurface>>
+        + IdleInhibitHandler
+        + 'static,
+{
+    fn bind(
+        _state: &mut D,
+        _dh: &DisplayHandle,
+        _client: &Client,
+        resource: New<ZwpIdleInhibitManagerV1>,
+        _global_data: &(),
+        data_init: &mut DataInit<'_, D>,
+    ) {
+        data_init.init(resource, ());
+    }
+}
+
+impl<D> Dispatch<ZwpIdleInhibitManagerV1, (), D> for IdleInhibitState
+where
+    D: Dispatch<ZwpIdleInhibitManagerV1, ()>
+        + Dispatch<ZwpIdleInhibitorV1, Weak<WlSurface>>
+        + IdleInhibitHandler
+        + 'static,
+{
+    fn request(
+        state: &mut D,
+        _client: &Client,
+        _obj: &ZwpIdleInhibitManagerV1,
+        request: zwp_idle_inhibit_manager_v1::Request,
+        _data: &(),
+        _dh: &DisplayHandle,
+        data_init: &mut DataInit<'_, D>,
+    ) {
+        match request {
+            zwp_idle_inhibit_manager_v1::Request::CreateInhibitor { id, surface } => {
+                let inhibitor = data_init.init(id, surface.downgrade());
+                state.idle_inhibit_state().inhibitors.push(inhibitor);
+            }
+            zwp_idle_inhibit_manager_v1::Request::Destroy => {}
+            _ => unreachable!(),
+        }
+    }
+}
+
+impl<D> Dispatch<ZwpIdleInhibitorV1, Weak<WlSurface>, D> for IdleInhibitState
+where
+    D: Dispatch<ZwpIdleInhibitorV1, Weak<WlSurface>> + IdleInhibitHandler + 'static,
+{
+    fn request(
+        _state: &mut D,
+        _client: &Client,
+        _obj: &ZwpIdleInhibitorV1,
+        request: zwp_idle_inhibitor_v1::Request,
+        _data: &Weak<WlSurface>,
+        _dh: &DisplayHandle,
+        _data_init: &mut DataInit<'_, D>,
+    ) {
+        match request {
+            zwp_idle_inhibitor_v1::Request::Destroy => {}
+            _ => unreachable!(),
+        }
+    }
+
+    fn destroyed(
+        state: &mut D,
+        _client: ClientId,
+        resource: &ZwpIdleInhibitorV1,
+        _data: &Weak<WlSurface>,
+    ) {
+        state
+            .idle_inhibit_state()
+            .inhibitors
+            .retain(|inhibitor| inhibitor != resource);
+    }
+}
+
+macro_rules! delegate_idle_inhibit {
+    ($(@<$( $lt:tt $( : $clt:tt $(+ $dlt:tt )* )? ),+>)? $ty: ty) => {
+        smithay::reexports::wayland_server::delegate_global_dispatch!($(@< $( $lt $( : $clt $(+ $dlt )* )? ),+ >)? $ty: [
+            smithay::reexports::wayland_protocols::wp::idle_inhibit::zv1::server::zwp_idle_inhibit_manager_v1::ZwpIdleInhibitManagerV1: ()
+        ] => $crate::wayland::protocols::idle_inhibit::IdleInhibitState);
+        smithay::reexports::wayland_server::delegate_dispatch!($(@< $( $lt $( : $clt $(+ $dlt )* )? ),+ >)? $ty: [
+            smithay::reexports::wayland_protocols::wp::idle_inhibit::zv1::server::zwp_idle_inhibit_manager_v1::ZwpIdleInhibitManagerV1: ()
+        ] => $crate::wayland::protocols::idle_inhibit::IdleInhibitState);
+        smithay::reexports::wayland_server::delegate_dispatch!($(@< $( $lt $( : $clt $(+ $dlt )* )? ),+ >)? $ty: [
+            smithay::reexports::wayland_protocols::wp::idle_inhibit::zv1::server::zwp_idle_inhibitor_v1::ZwpIdleInhibitorV1: smithay::reexports::wayland_server::Weak<smithay::reexports::wayland_server::protocol::wl_surface::WlSurface>
+        ] => $crate::wayland::protocols::idle_inhibit::IdleInhibitState);
+    };
+}
+pub(crate) use delegate_idle_inhibit;
@@ -0,0 +1,212 @@
+// SPDX-License-Identifier: GPL-3.0-only
+
+use std::time::{Duration, Instant};
+
+use smithay::reexports::{
+    wayland_protocols::ext::idle_notify::v1::server::{
+        ext_idle_notification_v1::{self, ExtIdleNotificationV1},
+        ext_idle_notifier_v1::{self, ExtIdleNotifierV1},
+    },
+    wayland_server::{
+        backend::{ClientId, GlobalId},
+        Client, DataInit, Dispatch, DisplayHandle, GlobalDispatch, New,
+    },
+};
+
+/// State of the `ext_idle_notifier_v1` global
+#[derive(Debug)]
+pub struct IdleNotifierState {
+    notifications: Vec<IdleNotification>,
+    /// Time of the last user input
+    last_activity: Instant,
+    /// Set while an idle inhibitor is active, which keeps notifications from becoming idle
+    inhibited: bool,
+    global: GlobalId,
+}
+
+#[derive(Debug)]
+struct IdleNotification {
+    obj: ExtIdleNotificationV1,
+    timeout: Duration,
+    /// Whether `idled` was sent, without a `resumed` following it
+    idle: bool,
+}
+
+pub trait IdleNotifyHandler {
+    fn idle_notifier_state(&mut self) -> &mut IdleNotifierState;
+    /// Called after a client created a notification, which might be due before all others.
+    fn idle_notification_created(&mut self);
+}
+
+impl IdleNotifierState {
+    pub fn new<D>(dh: &DisplayHandle) -> IdleNotifierState
+    where
+        D: GlobalDispatch<ExtIdleNotifierV1, ()>
+            + Dispatch<ExtIdleNotifierV1, ()>
+            + Dispatch<ExtIdleNotificationV1, ()>
+            + IdleNotifyHandler
+            + 'static,
+    {
+        let global = dh.create_global::<D, ExtIdleNotifierV1, _>(1, ());
+        IdleNotifierState {
+            notifications: Vec::new(),
+            last_activity: Instant::now(),
+            inhibited: false,
+            global,
+        }
+    }
+
+    pub fn global_id(&self) -> GlobalId {
+        self.global.clone()
+    }
+
+    /// Records user input, which resumes all idle notifications.
+    pub fn notify_activity(&mut self) {
+        self.last_activity = Instant::now();
+        for notification in self.notifications.iter_mut().filter(|n| n.idle) {
+            notification.obj.resumed();
+            notification.idle = false;
+        }
+    }
+
+    /// Sets whether idling is inhibited. The timeouts start over once the inhibition ends.
+    ///
+    /// Returns `true` if the state changed.
+    pub fn set_inhibited(&mut self, inhibited: bool) -> bool {
+        if self.inhibited == inhibited {
+            return false;
+        }
+        self.inhibited = inhibited;
+        if !inhibited {
+            self.last_activity = Instant::now();
+        }
+        true
+    }
+
+    /// Sends `idled` for every notification, whose timeout has passed.
+    pub fn refresh(&mut self) {
+        if self.inhibited {
+            return;
+        }
+        let idle_time = self.last_activity.elapsed();
+        for notification in self
+            .notifications
+            .iter_mut()
+            .filter(|n| !n.idle && n.timeout <= idle_time)
+        {
+            notification.obj.idled();
+            notification.idle = true;
+        }
+    }
+
+    /// Point in time, at which the next notification becomes idle without further input.
+    pub fn next_deadline(&self) -> Option<Instant> {
+        if self.inhibited {
+            return None;
+        }
+        self.notifications
+            .iter()
+            .filter(|n| !n.idle)
+            .map(|n| self.last_activity + n.timeout)
+            .min()
+    }
+}
+
+impl<D> GlobalDispatch<ExtIdleNotifierV1, (), D> for IdleNotifierState
+where
+    D: GlobalDispatch<ExtIdleNotifierV1, ()>
+        + Dispatch<ExtIdleNotifierV1, ()>
+        + Dispatch<ExtIdleNotificationV1, ()>
+        + IdleNotifyHandler
+        + 'static,
+{
+    fn bind(
+        _state: &mut D,
+        _dh: &DisplayHandle,
+        _client: &Client,
+        resource: New<ExtIdleNotifierV1>,
+        _global_data: &(),
+        data_init: &mut DataInit<'_, D>,
+    ) {
+        data_init.init(resource, ());
+    }
+}
+
+impl<D> Dispatch<ExtIdleNotifierV1, (), D> for IdleNotifierState
+where
+    D: GlobalDispatch<ExtIdleNotifierV1, ()>
+        + Dispatch<ExtIdleNotifierV1, ()>
+        + Dispatch<ExtIdleNotificationV1, ()>
+        + IdleNotifyHandler
+        + 'static,
+{
+    fn request(
+        state: &mut D,
+        _client: &Client,
+        _obj: &ExtIdleNotifierV1,
+        request: ext_idle_notifier_v1::Request,
+        _data: &(),
+        _dh: &DisplayHandle,
+        data_init: &mut DataInit<'_, D>,
+    ) {
+        match request {
+            // user input is tracked across all seats, so the seat isn't needed
+            ext_idle_notifier_v1::Request::GetIdleNotification { id, timeout, .. } => {
+                let obj = data_init.init(id, ());
+                state
+                    .idle_notifier_state()
+                    .notifications
+                    .push(IdleNotification {
+                        obj,
+                        timeout: Duration::from_millis(timeout as u64),
+                        idle: false,
+                    });
+                state.idle_notification_created();
+            }
+            ext_idle_notifier_v1::Request::Destroy => {}
+            _ => unreachable!(),
+        }
+    }
+}
+
+impl<D> Dispatch<ExtIdleNotificationV1, (), D> for IdleNotifierState
+where
+    D: Dispatch<ExtIdleNotificationV1, ()> + IdleNotifyHandler + 'static,
+{
+    fn request(
+        _state: &mut D,
+        _client: &Client,
+        _obj: &ExtIdleNotificationV1,
+        request: ext_idle_notification_v1::Request,
+        _data: &(),
+        _dh: &DisplayHandle,
+        _data_init: &mut DataInit<'_, D>,
+    ) {
+        match request {
+            ext_idle_notification_v1::Request::Destroy => {}
+            _ => unreachable!(),
+        }
+    }
+
+    fn destroyed(state: &mut D, _client: ClientId, resource: &ExtIdleNotificationV1, _data: &()) {
+        state
+            .idle_notifier_state()
+            .notifications
+            .retain(|n| &n.obj != resource);
+    }
+}
+
+macro_rules! delegate_idle_notify {
+    ($(@<$( $lt:tt $( : $clt:tt $(+ $dlt:tt )* )? ),+>)? $ty: ty) => {
+        smithay::reexports::wayland_server::delegate_global_dispatch!($(@< $( $lt $( : $clt $(+ $dlt )* )? ),+ >)? $ty: [
+            smithay::reexports::wayland_protocols::ext::idle_notify::v1::server::ext_idle_notifier_v1::ExtIdleNotifierV1: ()
+        ] => $crate::wayland::protocols::idle_notify::IdleNotifierState);
+        smithay::reexports::wayland_server::delegate_dispatch!($(@< $( $lt $( : $clt $(+ $dlt )* )? ),+ >)? $ty: [
+            smithay::reexports::wayland_protocols::ext::idle_notify::v1::server::ext_idle_notifier_v1::ExtIdleNotifierV1: ()
+        ] => $crate::wayland::protocols::idle_notify::IdleNotifierState);
+        smithay::reexports::wayland_server::delegate_dispatch!($(@< $( $lt $( : $clt $(+ $dlt )* )? ),+ >)? $ty: [
+            smithay::reexports::wayland_protocols::ext::idle_notify::v1::server::ext_idle_notification_v1::ExtIdleNotificationV1: ()
+        ] => $crate::wayland::protocols::idle_notify::IdleNotifierState);
+    };
+}
+pub(crate) use delegate_idle_notify;
@@ -1,9 +1,14 @@
 // SPDX-License-Identifier: GPL-3.0-only
 
+pub mod content_type;
 pub mod drm;
+pub mod gamma_control;
+pub mod idle_inhibit;
+pub mod idle_notify;
 //pub mod export_dmabuf;
 pub mod output_configuration;
 pub mod screencopy;
 pub mod toplevel_info;
 pub mod toplevel_management;
 pub mod workspace;
+pub mod xdg_foreign;
@@ -413,6 +413,10 @@ impl Session {
     pub fn cursor_mode(&self) -> CursorMode {
         self.data.inner.lock().unwrap().aux.cursor().clone()
     }
+
+    pub fn client(&self) -> Option<Client> {
+        self.obj.client()
+    }
 }
 
 impl IsAlive for Session {
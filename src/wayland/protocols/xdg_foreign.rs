@@ -0,0 +1,553 @@
+// SPDX-License-Identifier: GPL-3.0-only
+
+use std::{collections::HashMap, io, sync::Mutex};
+
+use smithay::{
+    reexports::{
+        wayland_protocols::xdg::foreign::zv2::server::{
+            zxdg_exported_v2::{self, ZxdgExportedV2},
+            zxdg_exporter_v2::{self, ZxdgExporterV2},
+            zxdg_imported_v2::{self, ZxdgImportedV2},
+            zxdg_importer_v2::{self, ZxdgImporterV2},
+        },
+        wayland_server::{
+            backend::{protocol::ProtocolError, ClientId, GlobalId},
+            protocol::wl_surface::WlSurface,
+            Client, DataInit, Dispatch, DisplayHandle, GlobalDispatch, New, Resource,
+        },
+    },
+    wayland::{
+        compositor::{get_role, with_states},
+        shell::xdg::XDG_TOPLEVEL_ROLE,
+    },
+};
+use tracing::error;
+
+/// State of the `zxdg_exporter_v2` and `zxdg_importer_v2` globals
+#[derive(Debug)]
+pub struct XdgForeignState {
+    exported: HashMap<String, ExportedToplevel>,
+    exporter_global: GlobalId,
+    importer_global: GlobalId,
+}
+
+#[derive(Debug)]
+struct ExportedToplevel {
+    surface: WlSurface,
+    exported: ZxdgExportedV2,
+    imported: Vec<ZxdgImportedV2>,
+}
+
+/// Data of a `zxdg_imported_v2` object
+#[derive(Debug)]
+pub struct ImportedData {
+    handle: String,
+    /// Surfaces that were made children of the imported toplevel
+    children: Mutex<Vec<WlSurface>>,
+}
+
+/// Parent of a surface set through `zxdg_imported_v2.set_parent_of`
+#[derive(Debug, Default)]
+struct ForeignParent(Mutex<Option<WlSurface>>);
+
+pub trait XdgForeignHandler {
+    fn xdg_foreign_state(&mut self) -> &mut XdgForeignState;
+}
+
+/// Returns the toplevel a surface was made a child of through xdg-foreign, if any.
+pub fn foreign_parent(surface: &WlSurface) -> Option<WlSurface> {
+    with_states(surface, |states| {
+        states
+            .data_map
+            .get::<ForeignParent>()
+            .and_then(|parent| parent.0.lock().unwrap().clone())
+    })
+}
+
+fn set_foreign_parent(surface: &WlSurface, parent: Option<WlSurface>) {
+    with_states(surface, |states| {
+        states
+            .data_map
+            .insert_if_missing_threadsafe(ForeignParent::default);
+        *states
+            .data_map
+            .get::<ForeignParent>()
+            .unwrap()
+            .0
+            .lock()
+            .unwrap() = parent;
+    })
+}
+
+/// Random handle, that can't be guessed by other clients
+fn new_handle() -> io::Result<String> {
+    let mut bytes = [0u8; 16];
+    let mut filled = 0;
+    while filled < bytes.len() {
+        let res = unsafe {
+            libc::getrandom(
+                bytes[filled..].as_mut_ptr() as *mut libc::c_void,
+                bytes.len() - filled,
+                0,
+            )
+        };
+        if res > 0 {
+            filled += res as usize;
+        } else {
+            let err = io::Error::last_os_error();
+            if err.kind() != io::ErrorKind::Interrupted {
+                return Err(err);
+            }
+        }
+    }
+    Ok(bytes.iter().map(|b| format!("{:02x}", b)).collect())
+}
+
+/// Invalidates an imported toplevel and removes the parent of all its children.
+fn invalidate_imported(imported: &ZxdgImportedV2) {
+    if let Some(data) = imported.data::<ImportedData>() {
+        for child in data.children.lock().unwrap().drain(..) {
+            if child.is_alive() {
+                set_foreign_parent(&child, None);
+            }
+        }
+    }
+}
+
+impl XdgForeignState {
+    pub fn new<D>(dh: &DisplayHandle) -> XdgForeignState
+    where
+        D: GlobalDispatch<ZxdgExporterV2, ()>
+            + GlobalDispatch<ZxdgImporterV2, ()>
+            + Dispatch<ZxdgExporterV2, ()>
+            + Dispatch<ZxdgExportedV2, String>
+            + Dispatch<ZxdgImporterV2, ()>
+            + Dispatch<ZxdgImportedV2, ImportedData>
+            + XdgForeignHandler
+            + 'static,
+    {
+        let exporter_global = dh.create_global::<D, ZxdgExporterV2, _>(1, ());
+        let importer_global = dh.create_global::<D, ZxdgImporterV2, _>(1, ());
+        XdgForeignState {
+            exported: HashMap::new(),
+            exporter_global,
+            importer_global,
+        }
+    }
+
+    /// Invalidates all exports of a destroyed toplevel.
+    pub fn toplevel_destroyed(&mut self, surface: &WlSurface) {
+        let handles = self
+            .exported
+            .iter()
+            .filter(|(_, exported)| &exported.surface == surface)
+            .map(|(handle, _)| handle.clone())
+            .collect::<Vec<_>>();
+        for handle in handles {
+            self.unexport(&handle);
+        }
+    }
+
+    fn unexport(&mut self, handle: &str) {
+        if let Some(exported) = self.exported.remove(handle) {
+            for imported in exported.imported {
+                invalidate_imported(&imported);
+                imported.destroyed();
+            }
+        }
+    }
+
+    pub fn exporter_global_id(&self) -> GlobalId {
+        self.exporter_global.clone()
+    }
+
+    pub fn importer_global_id(&self) -> GlobalId {
+        self.importer_global.clone()
+    }
+}
+
+impl<D> GlobalDispatch<ZxdgExporterV2, (), D> for XdgForeignState
+where
+    D: GlobalDispatch<ZxdgExporterV2, ()>
+        + Dispatch<ZxdgExporterV2, ()>
+        + Dispatch<ZxdgExportedV2, String>
+        + XdgForeignHandler
+        + 'static,
+{
+    fn bind(
+        _state: &mut D,
+        _dh: &DisplayHandle,
+        _client: &Client,
+        resource: New<ZxdgExporterV2>,
+        _global_data: &(),
+        data_init: &mut DataInit<'_, D>,
+    ) {
+        data_init.init(resource, ());
+    }
+}
+
+impl<D> GlobalDispatch<ZxdgImporterV2, (), D> for XdgForeignState
+where
+    D: GlobalDispatch<ZxdgImporterV2, ()>
+        + Dispatch<ZxdgImporterV2, ()>
+        + Dispatch<ZxdgImportedV2, ImportedData>
+        + XdgForeignHandler
+        + 'static,
+{
+    fn bind(
+        _state: &mut D,
+        _dh: &DisplayHandle,
+        _client: &Client,
+        resource: New<ZxdgImporterV2>,
+        _global_data: &(),
+        data_init: &mut DataInit<'_, D>,
+    ) {
+        data_init.init(resource, ());
+    }
+}
+
+impl<D> Dispatch<ZxdgExporterV2, (), D> for XdgForeignState
+where
+    D: Dispatch<ZxdgExporterV2, ()>
+        + Dispatch<ZxdgExportedV2, String>
+        + XdgForeignHandler
+        + 'static,
+{
+    fn request(
+        state: &mut D,
+        client: &Client,
+        obj: &ZxdgExporterV2,
+        request: zxdg_exporter_v2::Request,
+        _data: &(),
+        dh: &DisplayHandle,
+        data_init: &mut DataInit<'_, D>,
+    ) {
+        match request {
+            zxdg_exporter_v2::Request::ExportToplevel { id, surface } => {
+                let handle = match new_handle() {
+                    Ok(handle) => handle,
+                    Err(err) => {
+                        error!(?err, "Failed to generate xdg-foreign handle");
+                        data_init.init(id, String::new());
+                        client.kill(
+                            dh,
+                            ProtocolError {
+                                // wl_display.error.implementation
+                                code: 3,
+                                object_id: 1,
+                                object_interface: String::from("wl_display"),
+                                message: format!("Failed to generate handle: {}", err),
+                            },
+                        );
+                        return;
+                    }
+                };
+                let exported = data_init.init(id, handle.clone());
+                if get_role(&surface) != Some(XDG_TOPLEVEL_ROLE) {
+                    obj.post_error(
+                        zxdg_exporter_v2::Error::InvalidSurface,
+                        "Only xdg_toplevel surfaces can be exported",
+                    );
+                    return;
+                }
+
+                exported.handle(handle.clone());
+                state.xdg_foreign_state().exported.insert(
+                    handle,
+                    ExportedToplevel {
+                        surface,
+                        exported,
+                        imported: Vec::new(),
+                    },
+                );
+            }
+            zxdg_exporter_v2::Request::Destroy => {}
+            _ => unreachable!(),
+        }
+    }
+}
+
+impl<D> Dispatch<ZxdgExportedV2, String, D> for XdgForeignState
+where
+    D: Dispatch<ZxdgExportedV2, String> + XdgForeignHandler + 'static,
+{
+    fn request(
+        _state: &mut D,
+        _client: &Client,
+        _obj: &ZxdgExportedV2,
+        request: zxdg_exported_v2::Request,
+        _data: &String,
+        _dh: &DisplayHandle,
+        _data_init: &mut DataInit<'_, D>,
+    ) {
+        match request {
+            zxdg_exported_v2::Request::Destroy => {}
+            _ => unreachable!(),
+        }
+    }
+
+    fn destroyed(state: &mut D, _client: ClientId, resource: &ZxdgExportedV2, handle: &String) {
+        let foreign_state = state.xdg_foreign_state();
+        // the handle might already belong to a destroyed toplevel
+        if foreign_state
+            .exported
+            .get(handle)
+            .is_some_and(|exported| &exported.exported == resource)
+        {
+            foreign_state.unexport(handle);
+        }
+    }
+}
+
+impl<D> Dispatch<ZxdgImporterV2, (), D> for XdgForeignState
+where
+    D: Dispatch<ZxdgImporterV2, ()>
+        + Dispatch<ZxdgImportedV2, ImportedData>
+        + XdgForeignHandler
+        + 'static,
+{
+    fn request(
+        state: &mut D,
+        _client: &Client,
+        _obj: &ZxdgImporterV2,
+        request: zxdg_importer_v2::Request,
+        _data: &(),
+        _dh: &DisplayHandle,
+        data_init: &mut DataInit<'_, D>,
+    ) {
+        match request {
+            zxdg_importer_v2::Request::ImportToplevel { id, handle } => {
+                let imported = data_init.init(
+                    id,
+                    ImportedData {
+                        handle: handle.clone(),
+                        children: Mutex::new(Vec::new()),
+                    },
+                );
+                match state.xdg_foreign_state().exported.get_mut(&handle) {
+                    Some(exported) => exported.imported.push(imported),
+                    None => imported.destroyed(),
+                }
+            }
+            zxdg_importer_v2::Request::Destroy => {}
+            _ => unreachable!(),
+        }
+    }
+}
+
+impl<D> Dispatch<ZxdgImportedV2, ImportedData, D> for XdgForeignState
+where
+    D: Dispatch<ZxdgImportedV2, ImportedData> + XdgForeignHandler + 'static,
+{
+    fn request(
+        state: &mut D,
+        _client: &Client,
+        obj: &ZxdgImportedV2,
+        request: zxdg_imported_v2::Request,
+        data: &ImportedData,
+        _dh: &DisplayHandle,
+        _data_init: &mut DataInit<'_, D>,
+    ) {
+        match request {
+            zxdg_imported_v2::Request::SetParentOf { surface } => {
+                if get_role(&surface) != Some(XDG_TOPLEVEL_ROLE) {
+                    obj.post_error(
+                        zxdg_imported_v2::Error::InvalidSurface,
+                        "Only xdg_toplevel surfaces can be made children",
+                    );
+                    return;
+                }
+
+                // the exported toplevel is gone, `destroyed` was already sent
+                let Some(exported) = state
+                    .xdg_foreign_state()
+                    .exported
+                    .get(&data.handle)
+                    .filter(|exported| exported.imported.contains(obj))
+                else {
+                    return;
+                };
+                set_foreign_parent(&surface, Some(exported.surface.clone()));
+                data.children.lock().unwrap().push(surface);
+            }
+            zxdg_imported_v2::Request::Destroy => {}
+            _ => unreachable!(),
+        }
+    }
+
+    fn destroyed(state: &mut D, _client: ClientId, resource: &ZxdgImportedV2, data: &ImportedData) {
+        if let Some(exported) = state.xdg_foreign_state().exported.get_mut(&data.handle) {
+            exported.imported.retain(|imported| imported != resource);
+        }
+        for child in data.children.lock().unwrap().drain(..) {
+            if child.is_alive() {
+                set_foreign_parent(&child, None);
+            }
+        }
+    }
+}
+
+macro_rules! delegate_xdg_foreign {
+    ($(@<$( $lt:tt $( : $clt:tt $(+ $dlt:tt )* )? ),+>)? $ty: ty) => {
+        smithay::reexports::wayland_server::delegate_global_dispatch!($(@< $( $lt $( : $clt $(+ $dlt )* )? ),+ >)? $ty: [
+            smithay::reexports::wayland_protocols::xdg::foreign::zv2::server::zxdg_exporter_v2::ZxdgExporterV2: ()
+        ] => $crate::wayland::protocols::xdg_foreign::XdgForeignState);
+        smithay::reexports::wayland_server::delegate_global_dispatch!($(@< $( $lt $( : $clt $(+ $dlt )* )? ),+ >)? $ty: [
+            smithay::reexports::wayland_protocols::xdg::foreign::zv2::server::zxdg_importer_v2::ZxdgImporterV2: ()
+        ] => $crate::wayland::protocols::xdg_foreign::XdgForeignState);
+        smithay::reexports::wayland_server::delegate_dispatch!($(@< $( $lt $( : $clt $(+ $dlt )* )? ),+ >)? $ty: [
+            smithay::reexports::wayland_protocols::xdg::foreign::zv2::server::zxdg_exporter_v2::ZxdgExporterV2: ()
+        ] => $crate::wayland::protocols::xdg_foreign::XdgForeignState);
+        smithay::reexports::wayland_server::delegate_dispatch!($(@< $( $lt $( : $clt $(+ $dlt )* )? ),+ >)? $ty: [
+            smithay::reexports::wayland_protocols::xdg::foreign::zv2::server::zxdg_exported_v2::ZxdgExportedV2: String
+        ] => $crate::wayland::protocols::xdg_foreign::XdgForeignState);
+        smithay::reexports::wayland_server::delegate_dispatch!($(@< $( $lt $( : $clt $(+ $dlt )* )? ),+ >)? $ty: [
+            smithay::reexports::wayland_protocols::xdg::foreign::zv2::server::zxdg_importer_v2::ZxdgImporterV2: ()
+        ] => $crate::wayland::protocols::xdg_foreign::XdgForeignState);
+        smithay::reexports::wayland_server::delegate_dispatch!($(@< $( $lt $( : $clt $(+ $dlt )* )? ),+ >)? $ty: [
+            smithay::reexports::wayland_protocols::xdg::foreign::zv2::server::zxdg_imported_v2::ZxdgImportedV2: $crate::wayland::protocols::xdg_foreign::ImportedData
+        ] => $crate::wayland::protocols::xdg_foreign::XdgForeignState);
+    };
+}
+pub(crate) use delegate_xdg_foreign;
+
+#[cfg(test)]
+mod tests {
+    use super::{foreign_parent, new_handle};
+    use crate::utils::testing::{ClientEvent, TestClient, TestServer};
+    use smithay::reexports::wayland_server::protocol::wl_surface::WlSurface;
+    use wayland_protocols::xdg::{
+        foreign::zv2::client::{
+            zxdg_exporter_v2::ZxdgExporterV2, zxdg_importer_v2::ZxdgImporterV2,
+        },
+        shell::client::xdg_toplevel::XdgToplevel,
+    };
+
+    /// Exports a new toplevel of `client`, returns it with its handle.
+    fn export_toplevel(
+        server: &mut TestServer,
+        client: &mut TestClient,
+    ) -> (
+        wayland_client::protocol::wl_surface::WlSurface,
+        XdgToplevel,
+        String,
+    ) {
+        let (surface, toplevel) = client.create_toplevel();
+        surface.commit();
+        client
+            .bind::<ZxdgExporterV2>(1)
+            .export_toplevel(&surface, &client.handle(), ());
+        server.roundtrip(client);
+        match &client.take_events()[..] {
+            [ClientEvent::Exported(handle)] => (surface, toplevel, handle.clone()),
+            events => panic!("Expected a handle, got {:?}", events),
+        }
+    }
+
+    #[test]
+    fn handles_are_unguessable() {
+        let handle = new_handle().unwrap();
+        assert_eq!(handle.len(), 32);
+        assert!(handle.chars().all(|c| c.is_ascii_hexdigit()));
+        assert_ne!(handle, new_handle().unwrap());
+    }
+
+    #[test]
+    fn imported_toplevel_becomes_the_parent() {
+        let mut server = TestServer::new();
+        let mut a = server.connect();
+        let mut b = server.connect();
+        let (a_surface, _a_toplevel, handle) = export_toplevel(&mut server, &mut a);
+
+        let (b_surface, _b_toplevel) = b.create_toplevel();
+        b_surface.commit();
+        let imported = b
+            .bind::<ZxdgImporterV2>(1)
+            .import_toplevel(handle, &b.handle(), ());
+        imported.set_parent_of(&b_surface);
+        server.roundtrip(&mut b);
+
+        assert!(b.take_events().is_empty());
+        let parent = server.object::<WlSurface>(&a, &a_surface);
+        let child = server.object::<WlSurface>(&b, &b_surface);
+        assert_eq!(foreign_parent(&child), Some(parent));
+    }
+
+    #[test]
+    fn unknown_handles_cant_be_imported() {
+        let mut server = TestServer::new();
+        let mut a = server.connect();
+        let mut b = server.connect();
+        export_toplevel(&mut server, &mut a);
+
+        let (b_surface, _b_toplevel) = b.create_toplevel();
+        b_surface.commit();
+        let imported =
+            b.bind::<ZxdgImporterV2>(1)
+                .import_toplevel(new_handle().unwrap(), &b.handle(), ());
+        imported.set_parent_of(&b_surface);
+        server.roundtrip(&mut b);
+
+        assert!(matches!(
+            &b.take_events()[..],
+            [ClientEvent::ImportedDestroyed]
+        ));
+        let child = server.object::<WlSurface>(&b, &b_surface);
+        assert_eq!(foreign_parent(&child), None);
+    }
+
+    #[test]
+    fn destroying_the_toplevel_invalidates_imports() {
+        let mut server = TestServer::new();
+        let mut a = server.connect();
+        let mut b = server.connect();
+        let (_a_surface, a_toplevel, handle) = export_toplevel(&mut server, &mut a);
+
+        let (b_surface, _b_toplevel) = b.create_toplevel();
+        b_surface.commit();
+        let imported = b
+            .bind::<ZxdgImporterV2>(1)
+            .import_toplevel(handle.clone(), &b.handle(), ());
+        imported.set_parent_of(&b_surface);
+        server.roundtrip(&mut b);
+
+        a_toplevel.destroy();
+        server.roundtrip(&mut a);
+        server.roundtrip(&mut b);
+        assert!(matches!(
+            &b.take_events()[..],
+            [ClientEvent::ImportedDestroyed]
+        ));
+        let child = server.object::<WlSurface>(&b, &b_surface);
+        assert_eq!(foreign_parent(&child), None);
+
+        // the handle stays invalid
+        b.bind::<ZxdgImporterV2>(1)
+            .import_toplevel(handle, &b.handle(), ());
+        server.roundtrip(&mut b);
+        assert!(matches!(
+            &b.take_events()[..],
+            [ClientEvent::ImportedDestroyed]
+        ));
+    }
+
+    #[test]
+    fn destroying_the_import_removes_the_parent() {
+        let mut server = TestServer::new();
+        let mut a = server.connect();
+        let mut b = server.connect();
+        let (_a_surface, _a_toplevel, handle) = export_toplevel(&mut server, &mut a);
+
+        let (b_surface, _b_toplevel) = b.create_toplevel();
+        b_surface.commit();
+        let imported = b
+            .bind::<ZxdgImporterV2>(1)
+            .import_toplevel(handle, &b.handle(), ());
+        imported.set_parent_of(&b_surface);
+        imported.destroy();
+        server.roundtrip(&mut b);
+
+        let child = server.object::<WlSurface>(&b, &b_surface);
+        assert_eq!(foreign_parent(&child), None);
+    }
+}
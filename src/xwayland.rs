@@ -5,12 +5,18 @@ use crate::{
     shell::{focus::target::KeyboardFocusTarget, CosmicSurface, Shell},
     state::State,
     utils::prelude::*,
-    wayland::{handlers::screencopy::PendingScreencopyBuffers, protocols::screencopy::SessionType},
+    wayland::{
+        handlers::{screencopy::PendingScreencopyBuffers, selection::SelectionOwner},
+        protocols::screencopy::SessionType,
+    },
 };
 use smithay::{
     backend::drm::DrmNode,
     desktop::space::SpaceElement,
-    reexports::x11rb::protocol::xproto::Window as X11Window,
+    reexports::x11rb::{
+        protocol::xproto::{ConnectionExt as _, Window as X11Window},
+        rust_connection::RustConnection,
+    },
     utils::{Logical, Point, Rectangle, Size},
     wayland::selection::{
         data_device::{
@@ -38,6 +44,18 @@ pub struct XWaylandState {
     xwayland: XWayland,
 }
 
+impl XWaylandState {
+    /// Disconnects the X11 client owning `window` from the X server (`XKillClient`).
+    ///
+    /// The window manager connection isn't accessible, so this goes through a short-lived
+    /// connection of its own.
+    pub fn kill_client(&self, window: X11Window) -> anyhow::Result<()> {
+        let (conn, _) = RustConnection::connect(Some(&format!(":{}", self.display)))?;
+        conn.kill_client(window)?.check()?;
+        Ok(())
+    }
+}
+
 impl State {
     pub fn launch_xwayland(&mut self, render_node: Option<DrmNode>) {
         if self.common.xwayland_state.is_some() {
@@ -411,10 +429,21 @@ impl XwmHandler for State {
         mime_type: String,
         fd: OwnedFd,
     ) {
-        let seat = self.common.last_active_seat();
+        let seat = self.common.last_active_seat().clone();
+        let persisted = match selection {
+            SelectionTarget::Clipboard => current_data_device_selection_userdata(&seat),
+            SelectionTarget::Primary => current_primary_selection_userdata(&seat),
+        }
+        .as_deref()
+            == Some(&SelectionOwner::Persisted);
+        if persisted {
+            self.send_cached_selection(&seat, selection, &mime_type, fd);
+            return;
+        }
+
         match selection {
             SelectionTarget::Clipboard => {
-                if let Err(err) = request_data_device_client_selection(seat, mime_type, fd) {
+                if let Err(err) = request_data_device_client_selection(&seat, mime_type, fd) {
                     error!(
                         ?err,
                         "Failed to request current wayland clipboard for Xwayland.",
@@ -422,7 +451,7 @@ impl XwmHandler for State {
                 }
             }
             SelectionTarget::Primary => {
-                if let Err(err) = request_primary_client_selection(seat, mime_type, fd) {
+                if let Err(err) = request_primary_client_selection(&seat, mime_type, fd) {
                     error!(
                         ?err,
                         "Failed to request current wayland primary selection for Xwayland.",
@@ -442,12 +471,18 @@ impl XwmHandler for State {
         if self.common.is_x_focused(xwm) {
             let seat = self.common.last_active_seat();
             match selection {
-                SelectionTarget::Clipboard => {
-                    set_data_device_selection(&self.common.display_handle, &seat, mime_types, xwm)
-                }
-                SelectionTarget::Primary => {
-                    set_primary_selection(&self.common.display_handle, &seat, mime_types, xwm)
-                }
+                SelectionTarget::Clipboard => set_data_device_selection(
+                    &self.common.display_handle,
+                    &seat,
+                    mime_types,
+                    SelectionOwner::Xwayland(xwm),
+                ),
+                SelectionTarget::Primary => set_primary_selection(
+                    &self.common.display_handle,
+                    &seat,
+                    mime_types,
+                    SelectionOwner::Xwayland(xwm),
+                ),
             }
         }
     }
@@ -456,12 +491,16 @@ impl XwmHandler for State {
         for seat in self.common.seats() {
             match selection {
                 SelectionTarget::Clipboard => {
-                    if current_data_device_selection_userdata(seat).as_deref() == Some(&xwm) {
+                    if current_data_device_selection_userdata(seat).as_deref()
+                        == Some(&SelectionOwner::Xwayland(xwm))
+                    {
                         clear_data_device_selection(&self.common.display_handle, seat)
                     }
                 }
                 SelectionTarget::Primary => {
-                    if current_primary_selection_userdata(seat).as_deref() == Some(&xwm) {
+                    if current_primary_selection_userdata(seat).as_deref()
+                        == Some(&SelectionOwner::Xwayland(xwm))
+                    {
                         clear_primary_selection(&self.common.display_handle, seat)
                     }
                 }